@@ -25,7 +25,6 @@
 use core::{mem, ptr};
 use alloc::vec::Vec;
 
-use blake3;
 use crate::log::logger::{log_info, log_warn};
 use crate::verify::{verify_capsule, CapsuleVerification, CapsuleMetadata};
 
@@ -36,6 +35,7 @@ pub const CAPSULE_VERSION: u8 = 1;
 /// Flags
 pub const FLAG_ZK_REQUIRED: u8   = 1 << 0;
 pub const FLAG_COMPRESSED: u8    = 1 << 1; // payload is compressed (decompress before exec)
+pub const FLAG_PQ_SIGNED: u8     = 1 << 2; // ML-DSA-65 signature follows the signature region
 
 /// On-wire header. Keep repr(C) and read it with `read_unaligned` from the blob.
 #[repr(C)]
@@ -124,12 +124,17 @@ impl<'a> Capsule<'a> {
         &self.blob[s..e]
     }
 
-    /// BLAKE3 commitment of payload (domain-separated).
+    /// Canonical capsule identity of the payload (BLAKE3, domain-separated).
+    /// Same digest `nonosctl capsule verify` computes and the kernel quotes.
     #[inline]
     pub fn commitment(&self) -> [u8; 32] {
-        let mut h = blake3::Hasher::new_derive_key("NONOS:CAPSULE:COMMITMENT:v1");
-        h.update(self.payload());
-        *h.finalize().as_bytes()
+        self.identity().bytes
+    }
+
+    /// Algorithm-tagged capsule identity.
+    #[inline]
+    pub fn identity(&self) -> crate::digest::Digest {
+        crate::digest::capsule_identity(self.payload())
     }
 
     /// Run full verification pipeline (ZK or static sig).
@@ -23,18 +23,27 @@
 //   0x40  u64 memory_start  (first usable RAM after firmware)
 //   0x48  u64 memory_size   (total RAM in bytes)
 //   0x50  u8  entropy[32]            // seed material (truncate if you have 64)
-//   0x70  u8  rtc_utc[8]             // optional RTC snapshot (YYMMDDhh or raw)
-//   0x78  u8  reserved[8]            // kept zero; future use
+//   0x70  u8  rtc_utc[8]             // UTC at handoff, u64 LE UNIX seconds (0 = unknown)
+//   0x78  u64 kaslr_slide            // image slide applied by boot (0 = unslid)
 // Total: 128 bytes
+//
+// Extensions (BootModeFlags::MEMMAP): versioned records start `hdr_size` bytes
+// after the header, in the same allocation. See shared/bootinfo.rs; memmap.rs
+// builds them from the final UEFI memory map.
 
 #![allow(dead_code)]
 
 use core::{mem, ptr};
 
+use crate::digest::{Digest, HashAlgo};
+
 /// Magic tag "NONOSB00" in LE (for human-readable hex dumps).
 pub const ZS_MAGIC: u64 = 0x30424F534F4E4F4E; // "NONOSB0" + "0"
 pub const ZS_ABI_VERSION: u16 = 1;
 pub const ZS_HDR_SIZE: u16 = 128;
+/// Algorithm of `capsule_hash`; fixed by the ABI version, a new algorithm
+/// means a new `ZS_ABI_VERSION`
+pub const CAPSULE_HASH_ALGO: HashAlgo = HashAlgo::Blake3;
 
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy)]
@@ -45,12 +54,12 @@ pub struct ZeroStateBootInfo {
     pub boot_flags: u32,       // BootModeFlags
     pub capsule_base: u64,     // physical base address of .mod blob
     pub capsule_size: u64,     // size in bytes
-    pub capsule_hash: [u8; 32],// BLAKE3/SHA-256 commitment of payload
+    pub capsule_hash: [u8; 32],// digest::capsule_identity of payload, CAPSULE_HASH_ALGO
     pub memory_start: u64,     // first usable RAM (post-firmware)
     pub memory_size: u64,      // total RAM bytes
     pub entropy: [u8; 32],     // seed (truncate collector to 32 here)
-    pub rtc_utc: [u8; 8],      // optional RTC snapshot (format up to you)
-    pub reserved: [u8; 8],     // future fields (keep zero)
+    pub rtc_utc: [u8; 8],      // UTC at handoff as u64 LE UNIX seconds, 0 = unknown
+    pub kaslr_slide: u64,      // slide applied to a PIE kernel (BootModeFlags::KASLR)
 }
 
 // Compile-time guard: assert exact size = 128
@@ -74,7 +83,7 @@ impl ZeroStateBootInfo {
             memory_size: 0,
             entropy: [0u8; 32],
             rtc_utc: [0u8; 8],
-            reserved: [0u8; 8],
+            kaslr_slide: 0,
         }
     }
 
@@ -103,6 +112,8 @@ impl BootModeFlags {
     pub const COLD_START:  u32 = 1 << 3;
     pub const SECURE_BOOT: u32 = 1 << 4;
     pub const ZK_ATTESTED: u32 = 1 << 5;
+    pub const KASLR:       u32 = 1 << 6;
+    pub const MEMMAP:      u32 = 1 << 7;
 }
 
 /* -------------------------- Builder helpers (boot side) -------------------------- */
@@ -131,9 +142,38 @@ pub fn build_bootinfo(
     info
 }
 
+/// Firmware calendar time → `rtc_utc` bytes. `tz_minutes` is the UEFI
+/// TimeZone field (local = UTC - TimeZone); None for unspecified/local.
+pub fn rtc_utc_from_calendar(
+    year: u16, month: u8, day: u8,
+    hour: u8, minute: u8, second: u8,
+    tz_minutes: Option<i16>,
+) -> [u8; 8] {
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return [0u8; 8];
+    }
+    // days_from_civil (proleptic Gregorian), shifted so March is month 0
+    let y = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let mut secs = days * 86_400 + hour as i64 * 3600 + minute as i64 * 60 + second as i64;
+    if let Some(tz) = tz_minutes {
+        secs += tz as i64 * 60;
+    }
+    if secs <= 0 { [0u8; 8] } else { (secs as u64).to_le_bytes() }
+}
+
 /* -------------------------- Kernel-side convenience -------------------------- */
 
 impl ZeroStateBootInfo {
+    /// Algorithm-tagged capsule identity committed in `capsule_hash`
+    #[inline] pub fn capsule_digest(&self) -> Digest { Digest::new(CAPSULE_HASH_ALGO, self.capsule_hash) }
+
     /// Kernel helper: return (base, len) of capsule; safe to map/check.
     #[inline] pub fn capsule_span(&self) -> (u64, u64) { (self.capsule_base, self.capsule_size) }
 
@@ -146,3 +186,25 @@ impl ZeroStateBootInfo {
         self.memory_size > (16 * 1024 * 1024) // >16MiB sanity
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(b: [u8; 8]) -> u64 { u64::from_le_bytes(b) }
+
+    #[test]
+    fn rtc_epoch_and_known_dates() {
+        assert_eq!(secs(rtc_utc_from_calendar(1970, 1, 2, 0, 0, 0, None)), 86_400);
+        assert_eq!(secs(rtc_utc_from_calendar(2000, 3, 1, 0, 0, 0, None)), 951_868_800);
+        assert_eq!(secs(rtc_utc_from_calendar(2024, 2, 29, 12, 30, 15, None)), 1_709_209_815);
+    }
+
+    #[test]
+    fn rtc_applies_timezone_and_rejects_garbage() {
+        // 01:00 local at UTC+1 (TimeZone = -60) is midnight UTC
+        assert_eq!(secs(rtc_utc_from_calendar(1970, 1, 2, 1, 0, 0, Some(-60))), 86_400);
+        assert_eq!(secs(rtc_utc_from_calendar(1969, 12, 31, 0, 0, 0, None)), 0);
+        assert_eq!(secs(rtc_utc_from_calendar(2024, 13, 1, 0, 0, 0, None)), 0);
+    }
+}
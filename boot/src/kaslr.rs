@@ -0,0 +1,456 @@
+//! kaslr.rs — NØNOS boot-stage KASLR (load address + slide choice, PIE relocation)
+//! eK@nonos-tech.xyz
+//
+// The boot stage owns the kernel image placement: it is the last code that
+// sees the image before it runs, so loading and relocation happen here, once,
+// after the capsule has been verified and its commitment taken (the
+// commitment is over the file as signed, which is never modified).
+//
+// - Load address: 2 MiB-aligned physical slot, uniform over LOAD_WINDOW above
+//   LOAD_MIN, derived from the 64-byte boot entropy under its own BLAKE3
+//   domain (never reused as seed material); the loader retries other slots
+//   if firmware already owns one
+// - Slide: 2 MiB-aligned, uniform over (0, SLIDE_WINDOW), from the same
+//   entropy under a separate domain. The image runs at link address + slide
+//   in the higher half (paging.rs maps it there); the physical and virtual
+//   choices are independent
+// - Loading: ELF64 ET_DYN (kernel built with `KASLR=1`, see kernel/makefile);
+//   PT_LOAD segments are copied to the physical slot (bss zeroed) and every
+//   R_X86_64_RELATIVE relocation is applied for the run address. Any other
+//   relocation type fails the boot
+// - The slide goes to the kernel in `ZeroStateBootInfo::kaslr_slide`
+// - Non-PIE (ET_EXEC) images and flat payloads run unslid from the capsule
+//   buffer; the handoff then carries slide 0 without the KASLR flag
+//
+// The slide is secret-ish: it is never logged, only whether it was applied.
+
+#![allow(dead_code)]
+
+use core::{mem, ptr};
+
+/// Load address granularity (large-page aligned so the kernel can map with 2 MiB pages)
+pub const SLIDE_ALIGN: u64 = 2 * 1024 * 1024;
+/// Lowest physical load address (clear of legacy low memory)
+pub const LOAD_MIN: u64 = 16 * 1024 * 1024;
+/// Physical window the load address is drawn from
+pub const LOAD_WINDOW: u64 = 1024 * 1024 * 1024;
+/// Virtual window above the link address the slide is drawn from; must match
+/// kernel/src/memory/kaslr.rs
+pub const SLIDE_WINDOW: u64 = 1024 * 1024 * 1024;
+
+const DS_KASLR_LOAD: &str = "NONOS:BOOT:KASLR:LOAD";
+const DS_KASLR_SLIDE: &str = "NONOS:BOOT:KASLR:SLIDE";
+
+const ET_DYN: u16 = 3;
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const DT_NULL: u64 = 0;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_RELATIVE: u32 = 8;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PAGE: u64 = 4096;
+
+/// A PIE image placed and relocated by `load`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loaded {
+    /// Entry point at the run address
+    pub entry: u64,
+    /// Run address - link address
+    pub slide: u64,
+    pub relocations: usize,
+}
+
+/// One PT_LOAD segment, as an offset into the loaded image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub offset: u64,
+    pub len: u64,
+    pub write: bool,
+    pub exec: bool,
+}
+
+/// Pick candidate load address `attempt` from boot entropy (uniform: the slot
+/// count is a power of two). Candidates for different attempts are independent.
+pub fn choose_load_base(entropy64: &[u8; 64], attempt: u32) -> u64 {
+    let mut out = [0u8; 8];
+    blake3::Hasher::new_derive_key(DS_KASLR_LOAD)
+        .update(entropy64)
+        .update(&attempt.to_le_bytes())
+        .finalize_xof()
+        .fill(&mut out);
+    let slots = LOAD_WINDOW / SLIDE_ALIGN;
+    LOAD_MIN + (u64::from_le_bytes(out) % slots) * SLIDE_ALIGN
+}
+
+/// Pick the image slide from boot entropy: a non-zero SLIDE_ALIGN multiple
+/// below SLIDE_WINDOW, so a PIE boot always runs slid
+pub fn choose_slide(entropy64: &[u8; 64]) -> u64 {
+    let mut out = [0u8; 8];
+    blake3::Hasher::new_derive_key(DS_KASLR_SLIDE)
+        .update(entropy64)
+        .finalize_xof()
+        .fill(&mut out);
+    let slots = SLIDE_WINDOW / SLIDE_ALIGN;
+    (1 + u64::from_le_bytes(out) % (slots - 1)) * SLIDE_ALIGN
+}
+
+/// True if `image` is an ELF64 PIE that `load` can place
+pub fn is_pie(image: &[u8]) -> bool {
+    read_ehdr(image).map_or(false, |e| e.e_type == ET_DYN)
+}
+
+/// (link address, bytes) spanned by the PT_LOAD segments, page-rounded
+pub fn image_span(image: &[u8]) -> Result<(u64, usize), &'static str> {
+    let ehdr = read_ehdr(image).ok_or("kaslr: not an ELF64 image")?;
+    let phdrs = phdrs(image, &ehdr)?;
+    let (mut lo, mut hi) = (u64::MAX, 0u64);
+    for ph in (0..phdrs.count).map(|i| phdrs.get(image, i)).filter(|p| p.p_type == PT_LOAD) {
+        let end = ph.p_vaddr.checked_add(ph.p_memsz).ok_or("kaslr: segment overflow")?;
+        lo = lo.min(ph.p_vaddr);
+        hi = hi.max(end);
+    }
+    if lo >= hi {
+        return Err("kaslr: no PT_LOAD segment");
+    }
+    let lo = lo & !(PAGE - 1);
+    let span = (hi - lo + PAGE - 1) & !(PAGE - 1);
+    if span > LOAD_WINDOW {
+        return Err("kaslr: image too large");
+    }
+    Ok((lo, span as usize))
+}
+
+/// PT_LOAD segments of `image` with their access rights (offsets from the
+/// page-rounded link address, as laid out by `load`)
+pub fn segments(image: &[u8]) -> Result<impl Iterator<Item = Segment> + '_, &'static str> {
+    let ehdr = read_ehdr(image).ok_or("kaslr: not an ELF64 image")?;
+    let phdrs = phdrs(image, &ehdr)?;
+    let (link, _) = image_span(image)?;
+    Ok((0..phdrs.count)
+        .map(move |i| phdrs.get(image, i))
+        .filter(|p| p.p_type == PT_LOAD)
+        .map(move |p| Segment {
+            offset: p.p_vaddr - link,
+            len: p.p_memsz,
+            write: p.p_flags & PF_W != 0,
+            exec: p.p_flags & PF_X != 0,
+        }))
+}
+
+/// Copy the PT_LOAD segments of PIE `image` into `dest` (at least
+/// `image_span` bytes; the physical backing), zero the rest, and apply every
+/// R_X86_64_RELATIVE relocation for `run_base`, the address the image will
+/// be mapped and run at. `run_base` - link address must be a slide in policy.
+/// On error `dest` may be partially written and must not be run.
+pub fn load(image: &[u8], dest: &mut [u8], run_base: u64) -> Result<Loaded, &'static str> {
+    let ehdr = read_ehdr(image).ok_or("kaslr: not an ELF64 image")?;
+    if ehdr.e_type != ET_DYN {
+        return Err("kaslr: image is not PIE");
+    }
+    let (link, span) = image_span(image)?;
+    if dest.len() < span {
+        return Err("kaslr: load buffer too small");
+    }
+    let slide = run_base.wrapping_sub(link);
+    if run_base % SLIDE_ALIGN != 0 || slide % SLIDE_ALIGN != 0 || slide >= SLIDE_WINDOW {
+        return Err("kaslr: slide out of policy");
+    }
+    if run_base.checked_add(span as u64).is_none() {
+        return Err("kaslr: image past the top of the address space");
+    }
+
+    // Segments, in memory layout; bss and gaps stay zero
+    dest.fill(0);
+    let phdrs = phdrs(image, &ehdr)?;
+    for ph in (0..phdrs.count).map(|i| phdrs.get(image, i)).filter(|p| p.p_type == PT_LOAD) {
+        if ph.p_filesz > ph.p_memsz {
+            return Err("kaslr: segment filesz > memsz");
+        }
+        let src = ph.p_offset as usize;
+        let src_end = src.checked_add(ph.p_filesz as usize).ok_or("kaslr: segment overflow")?;
+        if src_end > image.len() {
+            return Err("kaslr: segment oob");
+        }
+        let at = (ph.p_vaddr - link) as usize;
+        dest[at..at + ph.p_filesz as usize].copy_from_slice(&image[src..src_end]);
+    }
+
+    let entry = ehdr.e_entry.checked_sub(link).filter(|&e| e < span as u64).ok_or("kaslr: entry outside image")?;
+    let relocations = relocate(&mut dest[..span], link, &phdrs, image, slide)?;
+    Ok(Loaded { entry: run_base + entry, slide, relocations })
+}
+
+/// Apply `slide` to every R_X86_64_RELATIVE relocation of a loaded image
+/// (`mem` = memory layout starting at link address `link`)
+fn relocate(mem: &mut [u8], link: u64, phdrs: &Phdrs, image: &[u8], slide: u64) -> Result<usize, &'static str> {
+    let Some(dynamic) = (0..phdrs.count)
+        .map(|i| phdrs.get(image, i))
+        .find(|p| p.p_type == PT_DYNAMIC)
+    else {
+        return Err("kaslr: PIE image without PT_DYNAMIC");
+    };
+
+    // Walk the dynamic section for the RELA table
+    let (mut rela, mut relasz, mut relaent) = (None, 0u64, mem::size_of::<Elf64Rela>() as u64);
+    let dyn_off = mem_offset(mem, link, dynamic.p_vaddr, dynamic.p_filesz)?;
+    let dyn_end = dyn_off + dynamic.p_filesz as usize;
+    for off in (dyn_off..dyn_end).step_by(16) {
+        if off + 16 > dyn_end { break; }
+        let tag = read_u64(mem, off);
+        let val = read_u64(mem, off + 8);
+        match tag {
+            DT_NULL => break,
+            DT_RELA => rela = Some(val),
+            DT_RELASZ => relasz = val,
+            DT_RELAENT => relaent = val,
+            _ => {}
+        }
+    }
+    let Some(rela_va) = rela else { return Ok(0) };
+    if relaent != mem::size_of::<Elf64Rela>() as u64 {
+        return Err("kaslr: bad relaent");
+    }
+
+    let rela_off = mem_offset(mem, link, rela_va, relasz)?;
+    let count = (relasz / relaent) as usize;
+    let mut applied = 0;
+    for i in 0..count {
+        let off = rela_off + i * relaent as usize;
+        // SAFETY: mem_offset bounds-checked the whole table
+        let r: Elf64Rela = unsafe { ptr::read_unaligned(mem[off..].as_ptr() as *const _) };
+        match (r.r_info & 0xffff_ffff) as u32 {
+            R_X86_64_NONE => {}
+            R_X86_64_RELATIVE => {
+                let at = mem_offset(mem, link, r.r_offset, 8)?;
+                let value = (r.r_addend as u64).wrapping_add(slide);
+                mem[at..at + 8].copy_from_slice(&value.to_le_bytes());
+                applied += 1;
+            }
+            _ => return Err("kaslr: unsupported relocation type"),
+        }
+    }
+    Ok(applied)
+}
+
+/* ---------- ELF helpers (bounds-checked, unaligned reads) ---------- */
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Ehdr {
+    e_ident: [u8; 16],
+    e_type: u16,
+    e_machine: u16,
+    e_version: u32,
+    e_entry: u64,
+    e_phoff: u64,
+    e_shoff: u64,
+    e_flags: u32,
+    e_ehsize: u16,
+    e_phentsize: u16,
+    e_phnum: u16,
+    e_shentsize: u16,
+    e_shnum: u16,
+    e_shstrndx: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Phdr {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Rela {
+    r_offset: u64,
+    r_info: u64,
+    r_addend: i64,
+}
+
+/// Program header table location (validated)
+struct Phdrs {
+    off: usize,
+    count: usize,
+}
+
+impl Phdrs {
+    fn get(&self, image: &[u8], i: usize) -> Elf64Phdr {
+        let at = self.off + i * mem::size_of::<Elf64Phdr>();
+        // SAFETY: `phdrs` checked the table lies inside the image
+        unsafe { ptr::read_unaligned(image[at..].as_ptr() as *const _) }
+    }
+}
+
+fn read_ehdr(image: &[u8]) -> Option<Elf64Ehdr> {
+    if image.len() < mem::size_of::<Elf64Ehdr>() || &image[0..4] != b"\x7FELF" || image[4] != 2 || image[5] != 1 {
+        return None;
+    }
+    // SAFETY: length checked above; header may be unaligned
+    Some(unsafe { ptr::read_unaligned(image.as_ptr() as *const _) })
+}
+
+fn phdrs(image: &[u8], ehdr: &Elf64Ehdr) -> Result<Phdrs, &'static str> {
+    if ehdr.e_phentsize as usize != mem::size_of::<Elf64Phdr>() {
+        return Err("kaslr: bad phentsize");
+    }
+    let off = ehdr.e_phoff as usize;
+    let count = ehdr.e_phnum as usize;
+    let end = count
+        .checked_mul(mem::size_of::<Elf64Phdr>())
+        .and_then(|n| n.checked_add(off))
+        .ok_or("kaslr: ph table overflow")?;
+    if end > image.len() {
+        return Err("kaslr: ph table oob");
+    }
+    Ok(Phdrs { off, count })
+}
+
+/// Offset of [va, va+len) in a loaded image that starts at link address `link`
+fn mem_offset(mem: &[u8], link: u64, va: u64, len: u64) -> Result<usize, &'static str> {
+    let off = va.checked_sub(link).ok_or("kaslr: address below image")?;
+    let end = off.checked_add(len).ok_or("kaslr: va overflow")?;
+    if end > mem.len() as u64 {
+        return Err("kaslr: address outside image");
+    }
+    Ok(off as usize)
+}
+
+#[inline]
+fn read_u64(b: &[u8], off: usize) -> u64 {
+    let mut w = [0u8; 8];
+    w.copy_from_slice(&b[off..off + 8]);
+    u64::from_le_bytes(w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u64 = 0xffff_ffff_8020_0000;
+
+    fn put(img: &mut [u8], off: usize, v: u64) {
+        img[off..off + 8].copy_from_slice(&v.to_le_bytes());
+    }
+
+    /// Minimal PIE: one PT_LOAD over the whole file plus 0x200 bytes of bss,
+    /// a PT_DYNAMIC pointing at one relocation that targets 0x180
+    fn pie(reloc_type: u64) -> [u8; 0x200] {
+        let mut img = [0u8; 0x200];
+        img[0..4].copy_from_slice(b"\x7FELF");
+        img[4] = 2;
+        img[5] = 1;
+        img[16..18].copy_from_slice(&ET_DYN.to_le_bytes());
+        img[18..20].copy_from_slice(&62u16.to_le_bytes());
+        put(&mut img, 24, BASE + 0x1c0); // e_entry
+        put(&mut img, 32, 0x40); // e_phoff
+        img[54..56].copy_from_slice(&56u16.to_le_bytes());
+        img[56..58].copy_from_slice(&2u16.to_le_bytes());
+        // PT_LOAD
+        img[0x40..0x44].copy_from_slice(&PT_LOAD.to_le_bytes());
+        img[0x44..0x48].copy_from_slice(&(PF_X | PF_W).to_le_bytes());
+        put(&mut img, 0x40 + 16, BASE);
+        put(&mut img, 0x40 + 32, 0x200);
+        put(&mut img, 0x40 + 40, 0x400);
+        // PT_DYNAMIC
+        img[0x78..0x7c].copy_from_slice(&PT_DYNAMIC.to_le_bytes());
+        put(&mut img, 0x78 + 8, 0x100);
+        put(&mut img, 0x78 + 16, BASE + 0x100);
+        put(&mut img, 0x78 + 32, 0x40);
+        // dynamic
+        put(&mut img, 0x100, DT_RELA);
+        put(&mut img, 0x108, BASE + 0x140);
+        put(&mut img, 0x110, DT_RELASZ);
+        put(&mut img, 0x118, 24);
+        put(&mut img, 0x120, DT_RELAENT);
+        put(&mut img, 0x128, 24);
+        // rela
+        put(&mut img, 0x140, BASE + 0x180);
+        put(&mut img, 0x148, reloc_type);
+        put(&mut img, 0x150, BASE + 0x1000);
+        img
+    }
+
+    #[test]
+    fn load_base_aligned_in_window() {
+        for seed in 0..64u8 {
+            for attempt in 0..4 {
+                let b = choose_load_base(&[seed; 64], attempt);
+                assert_eq!(b % SLIDE_ALIGN, 0);
+                assert!((LOAD_MIN..LOAD_MIN + LOAD_WINDOW).contains(&b));
+            }
+        }
+        let e = [7u8; 64];
+        assert!((1..8).any(|a| choose_load_base(&e, a) != choose_load_base(&e, 0)));
+    }
+
+    #[test]
+    fn slide_aligned_nonzero_in_window() {
+        for seed in 0..64u8 {
+            let s = choose_slide(&[seed; 64]);
+            assert_eq!(s % SLIDE_ALIGN, 0);
+            assert!(s > 0 && s < SLIDE_WINDOW);
+        }
+        assert_ne!(choose_slide(&[1u8; 64]), choose_slide(&[2u8; 64]));
+    }
+
+    #[test]
+    fn span_covers_bss() {
+        assert_eq!(image_span(&pie(R_X86_64_RELATIVE as u64)), Ok((BASE, 0x1000)));
+    }
+
+    #[test]
+    fn segments_carry_offsets_and_rights() {
+        let img = pie(R_X86_64_RELATIVE as u64);
+        let mut segs = segments(&img).unwrap();
+        assert_eq!(segs.next(), Some(Segment { offset: 0, len: 0x400, write: true, exec: true }));
+        assert_eq!(segs.next(), None);
+    }
+
+    #[test]
+    fn loaded_image_relocated_for_run_address() {
+        let img = pie(R_X86_64_RELATIVE as u64);
+        assert!(is_pie(&img));
+        let mut dest = [0xAAu8; 0x1000];
+        let slide = 4 * SLIDE_ALIGN;
+        let at = BASE + slide;
+        let l = load(&img, &mut dest, at).unwrap();
+        assert_eq!(l, Loaded { entry: at + 0x1c0, slide, relocations: 1 });
+        // pointer targets the run address, not the link address
+        assert_eq!(read_u64(&dest, 0x180), at + 0x1000);
+        // bss zeroed, file bytes copied, source (signed bytes) untouched
+        assert!(dest[0x200..].iter().all(|&b| b == 0));
+        assert_eq!(&dest[..0x100], &img[..0x100]);
+        assert_eq!(read_u64(&img, 0x180), 0);
+    }
+
+    #[test]
+    fn unknown_relocation_rejected() {
+        let mut dest = [0u8; 0x1000];
+        let img = pie(1); // R_X86_64_64 needs a symbol table
+        assert!(load(&img, &mut dest, BASE + SLIDE_ALIGN).is_err());
+    }
+
+    #[test]
+    fn slide_outside_policy_or_short_load_rejected() {
+        let img = pie(R_X86_64_RELATIVE as u64);
+        let mut dest = [0u8; 0x1000];
+        assert!(load(&img, &mut dest, BASE + SLIDE_ALIGN + 4096).is_err());
+        assert!(load(&img, &mut dest, BASE + SLIDE_WINDOW).is_err());
+        // below the link address wraps to a huge slide
+        assert!(load(&img, &mut dest, BASE - SLIDE_ALIGN).is_err());
+        // a physical address is not a run address
+        assert!(load(&img, &mut dest, LOAD_MIN).is_err());
+        assert!(load(&img, &mut dest[..0x200], BASE + SLIDE_ALIGN).is_err());
+    }
+}
@@ -16,6 +16,10 @@
 // - Early fail if header/magic invalid
 // - Handoff populated using `build_bootinfo()` with truncated entropy
 // - Entry point must be page-aligned inside payload span
+// - PIE kernels are loaded at a random 2 MiB-aligned physical address,
+//   relocated for a random higher-half run address (kaslr.rs) and mapped
+//   there in loader-built page tables (paging.rs); the signed capsule buffer
+//   is left as read. The slide travels in the handoff, never in the log
 
 use core::slice;
 
@@ -26,7 +30,9 @@ use uefi::CStr16;
 use uefi::table::boot::{AllocateType, MemoryType};
 
 use crate::capsule::Capsule;
-use crate::handoff::{ZeroStateBootInfo, build_bootinfo};
+use crate::handoff::{BootModeFlags, ZeroStateBootInfo, build_bootinfo, rtc_utc_from_calendar};
+use crate::kaslr;
+use crate::paging;
 use crate::log::logger::{log_info, log_warn};
 use crate::entropy::collect_boot_entropy;
 
@@ -34,10 +40,14 @@ pub struct KernelCapsule {
     pub entry_point: usize,
     pub base: *mut u8,
     pub size: usize,
+    /// PML4 to switch to before the jump (PIE kernels; None = firmware tables)
+    pub page_table: Option<u64>,
     pub handoff: ZeroStateBootInfo,
 }
 
 const MAX_CAPSULE_SIZE: usize = 32 * 1024 * 1024; // 32 MiB cap for sanity
+/// Random load slots tried before letting firmware pick the address
+const LOAD_ATTEMPTS: u32 = 16;
 
 pub fn load_kernel_capsule(st: &SystemTable<Boot>) -> Result<KernelCapsule, &'static str> {
     let bs = st.boot_services();
@@ -101,14 +111,14 @@ pub fn load_kernel_capsule(st: &SystemTable<Boot>) -> Result<KernelCapsule, &'st
 
     // Build ZeroStateBootInfo
     let entropy64 = collect_boot_entropy(bs);
-    let handoff = build_bootinfo(
+    let mut handoff = build_bootinfo(
         capsule_base_phys(buffer),
         bytes_read as u64,
         capsule.commitment(),
         /* memory_start */ 0,    // TODO: fill with usable RAM base
         /* memory_size */ 0,     // TODO: fill with total RAM size
         &entropy64,
-        [0u8; 8],                 // TODO: fill with RTC snapshot if needed
+        rtc_snapshot(st),
         0,                        // boot_flags
     );
 
@@ -122,16 +132,96 @@ pub fn load_kernel_capsule(st: &SystemTable<Boot>) -> Result<KernelCapsule, &'st
         log_warn("loader", "entry point not page-aligned");
     }
 
+    // KASLR: load a PIE payload at a random address and relocate it there;
+    // the capsule buffer keeps the bytes the commitment above was taken over
+    let (mut entry_point, mut base, mut size) = (entry_point, buffer as *mut u8, bytes_read);
+    let mut page_table = None;
+    if kaslr::is_pie(capsule.payload()) {
+        let loaded = match load_pie(bs, capsule.payload(), &entropy64) {
+            Ok(l) => l,
+            Err(e) => {
+                log_warn("loader", e);
+                zero_buf(capsule_slice);
+                return Err("[x] Kernel relocation failed");
+            }
+        };
+        entry_point = loaded.entry as usize;
+        base = loaded.base as *mut u8;
+        size = loaded.span;
+        page_table = Some(loaded.page_table);
+        handoff.kaslr_slide = loaded.slide;
+        handoff.boot_flags |= BootModeFlags::KASLR;
+        log_info("loader", "[✓] KASLR: kernel loaded and relocated");
+    } else {
+        log_warn("loader", "kernel image is not PIE; KASLR slide not applied");
+    }
+
     log_info("loader", "[✓] Capsule verification complete. Launch ready.");
 
     Ok(KernelCapsule {
         entry_point,
-        base: buffer as *mut u8,
-        size: bytes_read,
+        base,
+        size,
+        page_table,
         handoff,
     })
 }
 
+struct LoadedPie {
+    /// Entry and base at the run address
+    entry: u64,
+    base: u64,
+    span: usize,
+    slide: u64,
+    page_table: u64,
+}
+
+/// Place a PIE kernel at a random 2 MiB-aligned physical address (identity
+/// mapped under UEFI), relocate it for link address + a random slide, and
+/// build the page tables that map it there. Falls back to a firmware-chosen,
+/// 2 MiB-aligned physical address if every random slot is taken.
+fn load_pie(bs: &BootServices, image: &[u8], entropy64: &[u8; 64]) -> Result<LoadedPie, &'static str> {
+    let (link, span) = kaslr::image_span(image)?;
+    let pages = span.div_ceil(4096);
+
+    let mut at = None;
+    for attempt in 0..LOAD_ATTEMPTS {
+        let want = kaslr::choose_load_base(entropy64, attempt);
+        if bs.allocate_pages(AllocateType::Address(want), MemoryType::LOADER_CODE, pages).is_ok() {
+            at = Some(want);
+            break;
+        }
+    }
+    let base = match at {
+        Some(b) => b,
+        None => {
+            log_warn("loader", "no random load slot free; using a firmware-chosen address");
+            let slack = (kaslr::SLIDE_ALIGN / 4096) as usize;
+            let raw = bs
+                .allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_CODE, pages + slack)
+                .map_err(|_| "[x] Failed to allocate kernel image memory")?;
+            (raw + kaslr::SLIDE_ALIGN - 1) & !(kaslr::SLIDE_ALIGN - 1)
+        }
+    };
+
+    // SAFETY: `pages` pages at `base` were just allocated to us and are identity mapped
+    let dest = unsafe { slice::from_raw_parts_mut(base as *mut u8, pages * 4096) };
+    let run_base = link.wrapping_add(kaslr::choose_slide(entropy64));
+    let mapped = kaslr::load(image, dest, run_base).and_then(|l| {
+        let mut tables = paging::Tables::new(|| {
+            bs.allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, 1).ok()
+        })?;
+        // SAFETY: CR3 holds the firmware's live, identity-mapped PML4
+        unsafe { tables.adopt_lower_half(paging::current_root()) };
+        tables.map_image(image, base, run_base, span)?;
+        Ok(LoadedPie { entry: l.entry, base: run_base, span, slide: l.slide, page_table: tables.root() })
+    });
+    if mapped.is_err() {
+        zero_buf(dest);
+    }
+    mapped
+}
+
 #[inline]
 fn zero_buf(buf: &mut [u8]) {
     for b in buf.iter_mut() {
@@ -143,3 +233,13 @@ fn zero_buf(buf: &mut [u8]) {
 fn capsule_base_phys(ptr: u64) -> u64 {
     ptr
 }
+
+/// Firmware wall clock as handoff `rtc_utc` (zeros if the RTC is unreadable)
+fn rtc_snapshot(st: &SystemTable<Boot>) -> [u8; 8] {
+    match st.runtime_services().get_time() {
+        Ok(t) => rtc_utc_from_calendar(
+            t.year(), t.month(), t.day(), t.hour(), t.minute(), t.second(), t.time_zone(),
+        ),
+        Err(_) => [0u8; 8],
+    }
+}
@@ -10,6 +10,10 @@
 // - No undefined behaviour from entry pointer: strict `usize` sanity check.
 // - Explicit non-returning reset on failure (no dangling boot state).
 // - Minimal unsafe: only for transmute+call into verified capsule.
+// - ExitBootServices before the jump; the final memory map rides along with
+//   the handoff (memmap.rs), so the kernel never sees firmware-owned RAM as free.
+// - PIE kernels run in the higher half: CR3 switches to the loader's page
+//   tables (paging.rs) right before the jump; the slid entry is never logged.
 
 use uefi::prelude::*;
 use uefi::table::runtime::ResetType;
@@ -18,6 +22,16 @@ use uefi_services::init;
 use crate::loader::load_kernel_capsule;
 use crate::log::logger::{log_info, log_warn, log_critical};
 use crate::handoff::ZeroStateBootInfo;
+use crate::memmap;
+use crate::paging;
+
+/// Hash algorithm ids, domain tags and capsule identity shared with kernel + nonosctl
+#[path = "../../shared/digest.rs"]
+pub mod digest;
+
+/// Handoff extension records (physical memory map) shared with the kernel
+#[path = "../../shared/bootinfo.rs"]
+pub mod bootinfo;
 
 /// External capsule entry signature
 type KernelEntry = extern "C" fn(*const ZeroStateBootInfo) -> !;
@@ -44,13 +58,17 @@ fn efi_main(_handle: Handle, system_table: SystemTable<Boot>) -> Status {
     };
 
     log_info("capsule", "✓ Capsule validated, ready for execution");
-    log_info("capsule", &format!(
-        "⤴ Jumping to verified entry: 0x{:X} ({} bytes)",
-        kernel_capsule.entry_point,
-        kernel_capsule.size
-    ));
+    if kernel_capsule.page_table.is_some() {
+        log_info("capsule", &format!("⤴ Jumping to verified entry ({} bytes)", kernel_capsule.size));
+    } else {
+        log_info("capsule", &format!(
+            "⤴ Jumping to verified entry: 0x{:X} ({} bytes)",
+            kernel_capsule.entry_point,
+            kernel_capsule.size
+        ));
+    }
 
-    // 3. Guard: basic bounds check on entry_point inside capsule payload
+    // 3. Guard: basic bounds check on entry_point inside the image (run addresses)
     let base_addr = kernel_capsule.base as usize;
     let end_addr  = base_addr + kernel_capsule.size;
     if kernel_capsule.entry_point < base_addr || kernel_capsule.entry_point >= end_addr {
@@ -62,11 +80,28 @@ fn efi_main(_handle: Handle, system_table: SystemTable<Boot>) -> Status {
         core::mem::transmute(kernel_capsule.entry_point)
     };
 
-    // 5. Prepare handoff pointer (ZeroStateBootInfo telemetry)
-    let handoff_ptr = &kernel_capsule.handoff as *const _;
+    // 5. Stage handoff + memory map block while allocation is still possible
+    let staged = match memmap::stage(system_table.boot_services(), &kernel_capsule.handoff) {
+        Ok(s) => s,
+        Err(e) => {
+            log_warn("handoff", e);
+            fatal_reset(&system_table, "Handoff staging failed");
+        }
+    };
+    log_info("handoff", "✓ Handoff staged, exiting boot services");
+
+    // 6. Leave firmware; no logging or boot services past this point
+    let (_runtime, mmap) = system_table.exit_boot_services();
+    let handoff_ptr = staged.finish(
+        mmap.entries().map(|d| (d.ty.0, d.phys_start, d.page_count, d.att.bits())),
+    );
 
-    // 6. Transfer control — does not return
+    // 7. Transfer control — does not return. The loader's tables share the
+    //    firmware lower half, so this code, the stack and the handoff stay mapped
     unsafe {
+        if let Some(root) = kernel_capsule.page_table {
+            paging::activate(root);
+        }
         kernel_entry(handoff_ptr);
     }
 }
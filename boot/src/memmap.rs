@@ -0,0 +1,237 @@
+//! memmap.rs — NØNOS physical memory map handoff (UEFI map → handoff extension)
+//! eK@nonos-tech.xyz
+//
+// Flow:
+// - `stage()` runs while boot services are alive: it allocates one LOADER_DATA
+//   block sized for the header plus a full extension, and copies the
+//   ZeroStateBootInfo header into it. No allocation is possible later.
+// - `ExitBootServices` hands us the final map (main.rs).
+// - `Staged::finish()` classifies every descriptor (shared/bootinfo.rs), sorts
+//   and coalesces adjacent records of the same type/attributes, fills
+//   memory_start/memory_size and sets BootModeFlags::MEMMAP.
+//
+// Rules:
+// - finish() is infallible and never logs: firmware services are gone. A map
+//   larger than MEMMAP_MAX_ENTRIES is truncated and flagged, not dropped.
+// - The staging block itself is LOADER_DATA, so it shows up as BootLoaded and
+//   the kernel keeps it reserved until it has copied what it needs.
+// - Boot services code/data is BootLoaded too, tagged ATTR_BOOT_SERVICES: the
+//   kernel enters on the firmware stack and page tables that live there, and
+//   releases it itself once it has switched both.
+
+use core::{mem, ptr};
+
+use uefi::prelude::*;
+use uefi::table::boot::{AllocateType, MemoryType};
+
+use crate::bootinfo::{
+    attr_for_efi, memmap_len, MemoryRegion, MemoryRegionType, ZsExtHeader,
+    MEMMAP_MAX_ENTRIES, MEMMAP_SORTED, MEMMAP_TRUNCATED,
+};
+use crate::handoff::{BootModeFlags, ZeroStateBootInfo, ZS_HDR_SIZE};
+
+const PAGE: usize = 4096;
+const STAGE_BYTES: usize = ZS_HDR_SIZE as usize + memmap_len(MEMMAP_MAX_ENTRIES);
+
+/// Handoff header + extension block, allocated before ExitBootServices
+pub struct Staged {
+    base: *mut u8,
+}
+
+/// Allocate the staging block and copy the handoff header into it
+pub fn stage(bs: &BootServices, handoff: &ZeroStateBootInfo) -> Result<Staged, &'static str> {
+    let pages = (STAGE_BYTES + PAGE - 1) / PAGE;
+    let addr = bs
+        .allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, pages)
+        .map_err(|_| "[x] Failed to allocate handoff block")?;
+    let base = addr as *mut u8;
+    unsafe {
+        ptr::write_bytes(base, 0, pages * PAGE);
+        handoff.copy_to(base as *mut ZeroStateBootInfo);
+    }
+    Ok(Staged { base })
+}
+
+impl Staged {
+    /// Write the memory map extension after the header and return the handoff
+    /// pointer for the kernel. `entries` yields raw UEFI descriptors as
+    /// (type, phys_start, page_count, attribute).
+    pub fn finish<I>(self, entries: I) -> *const ZeroStateBootInfo
+    where
+        I: Iterator<Item = (u32, u64, u64, u64)>,
+    {
+        let ext = unsafe { self.base.add(ZS_HDR_SIZE as usize) };
+        let recs = unsafe {
+            core::slice::from_raw_parts_mut(
+                ext.add(mem::size_of::<ZsExtHeader>()) as *mut MemoryRegion,
+                MEMMAP_MAX_ENTRIES,
+            )
+        };
+
+        let mut n = 0usize;
+        let mut flags = 0u16;
+        for (ty, start, pages, attr) in entries {
+            if pages == 0 { continue; }
+            if n == recs.len() {
+                flags |= MEMMAP_TRUNCATED;
+                break;
+            }
+            recs[n] = MemoryRegion {
+                start,
+                len: pages.saturating_mul(PAGE as u64),
+                ty: MemoryRegionType::from_efi(ty) as u32,
+                attr: attr_for_efi(ty, attr),
+            };
+            n += 1;
+        }
+        let n = normalize(&mut recs[..n]);
+        flags |= MEMMAP_SORTED;
+
+        let (start, size) = summarize(&recs[..n]);
+        unsafe {
+            ptr::write_unaligned(ext as *mut ZsExtHeader, ZsExtHeader::memmap(n as u16, flags));
+            let info = self.base as *mut ZeroStateBootInfo;
+            let mut hdr = ptr::read_unaligned(info);
+            hdr.memory_start = start.unwrap_or(0);
+            hdr.memory_size = size;
+            hdr.boot_flags |= BootModeFlags::MEMMAP;
+            ptr::write_unaligned(info, hdr);
+        }
+        self.base as *const ZeroStateBootInfo
+    }
+}
+
+/// Sort by start and merge touching records of identical type and attributes.
+/// Returns the new record count. Overlaps are left for the kernel to resolve
+/// (it keeps the stricter type), so nothing is silently widened here.
+pub fn normalize(recs: &mut [MemoryRegion]) -> usize {
+    // insertion sort: no alloc, and firmware maps are nearly sorted already
+    for i in 1..recs.len() {
+        let mut j = i;
+        while j > 0 && recs[j - 1].start > recs[j].start {
+            recs.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+
+    let mut out = 0usize;
+    for i in 0..recs.len() {
+        let r = recs[i];
+        if out > 0 {
+            let prev = &mut recs[out - 1];
+            if prev.end() == r.start && prev.ty == r.ty && prev.attr == r.attr {
+                prev.len += r.len;
+                continue;
+            }
+        }
+        recs[out] = r;
+        out += 1;
+    }
+    out
+}
+
+/// (first usable byte, total RAM bytes) for the legacy header fields.
+/// MMIO and reserved firmware ranges are not RAM and are not counted.
+/// `None` when nothing is usable; usable RAM may start at address 0.
+pub fn summarize(recs: &[MemoryRegion]) -> (Option<u64>, u64) {
+    let mut first = None;
+    let mut total = 0u64;
+    for r in recs {
+        match r.kind() {
+            MemoryRegionType::Usable => {
+                if first.is_none() { first = Some(r.start); }
+                total = total.saturating_add(r.len);
+            }
+            MemoryRegionType::BootLoaded
+            | MemoryRegionType::AcpiReclaimable
+            | MemoryRegionType::AcpiNvs
+            | MemoryRegionType::BadMemory => total = total.saturating_add(r.len),
+            MemoryRegionType::Reserved | MemoryRegionType::Mmio => {}
+        }
+    }
+    (first, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bootinfo::{ATTR_BOOT_SERVICES, ATTR_WB};
+
+    fn rec(start: u64, pages: u64, ty: MemoryRegionType) -> MemoryRegion {
+        MemoryRegion { start, len: pages * PAGE as u64, ty: ty as u32, attr: 0 }
+    }
+
+    #[test]
+    fn normalize_sorts_and_merges_adjacent() {
+        let mut v = [
+            rec(0x20_0000, 16, MemoryRegionType::Usable),
+            rec(0x0, 16, MemoryRegionType::Usable),
+            rec(0x1_0000, 16, MemoryRegionType::Usable),
+            rec(0x10_0000, 256, MemoryRegionType::Usable),
+        ];
+        let n = normalize(&mut v);
+        assert_eq!(n, 2);
+        let (s0, l0, s1, l1) = (v[0].start, v[0].len, v[1].start, v[1].len);
+        assert_eq!((s0, l0), (0x0, 0x2_0000));
+        assert_eq!((s1, l1), (0x10_0000, 0x11_0000));
+    }
+
+    #[test]
+    fn normalize_keeps_type_boundaries() {
+        let mut v = [
+            rec(0x0, 16, MemoryRegionType::Usable),
+            rec(0x1_0000, 16, MemoryRegionType::AcpiNvs),
+            rec(0x2_0000, 16, MemoryRegionType::Usable),
+        ];
+        assert_eq!(normalize(&mut v), 3);
+    }
+
+    #[test]
+    fn efi_types_classify_conservatively() {
+        assert_eq!(MemoryRegionType::from_efi(7), MemoryRegionType::Usable);
+        assert_eq!(MemoryRegionType::from_efi(2), MemoryRegionType::BootLoaded);
+        // boot services memory holds the kernel's first stack and page tables
+        assert_eq!(MemoryRegionType::from_efi(3), MemoryRegionType::BootLoaded);
+        assert_eq!(MemoryRegionType::from_efi(4), MemoryRegionType::BootLoaded);
+        assert_eq!(attr_for_efi(4, 0x8), ATTR_WB | ATTR_BOOT_SERVICES);
+        assert_eq!(attr_for_efi(2, 0x8), ATTR_WB);
+        let bs = MemoryRegion { start: 0, len: PAGE as u64, ty: MemoryRegionType::from_efi(3) as u32, attr: attr_for_efi(3, 0) };
+        let loader = MemoryRegion { attr: attr_for_efi(2, 0), ..bs };
+        assert!(bs.is_boot_services() && !loader.is_boot_services());
+        assert_eq!(MemoryRegionType::from_efi(5), MemoryRegionType::Reserved);
+        assert_eq!(MemoryRegionType::from_efi(8), MemoryRegionType::BadMemory);
+        assert_eq!(MemoryRegionType::from_efi(11), MemoryRegionType::Mmio);
+        assert_eq!(MemoryRegionType::from_efi(0x7000_0000), MemoryRegionType::Reserved);
+        assert_eq!(MemoryRegionType::from_raw(42), MemoryRegionType::Reserved);
+    }
+
+    #[test]
+    fn summarize_skips_mmio_and_reserved() {
+        let v = [
+            rec(0x0, 1, MemoryRegionType::Reserved),
+            rec(0x1000, 15, MemoryRegionType::Usable),
+            rec(0x10_0000, 16, MemoryRegionType::BootLoaded),
+            rec(0xFEC0_0000, 1, MemoryRegionType::Mmio),
+        ];
+        assert_eq!(summarize(&v), (Some(0x1000), 31 * PAGE as u64));
+
+        // usable RAM at 0 is still the first usable byte, not "unset"
+        let v = [
+            rec(0x0, 1, MemoryRegionType::Usable),
+            rec(0x1000, 15, MemoryRegionType::Reserved),
+            rec(0x10_0000, 16, MemoryRegionType::Usable),
+        ];
+        assert_eq!(summarize(&v), (Some(0x0), 17 * PAGE as u64));
+        assert_eq!(summarize(&[rec(0x0, 1, MemoryRegionType::Mmio)]), (None, 0));
+    }
+
+    #[test]
+    fn extension_header_validates() {
+        let h = ZsExtHeader::memmap(3, MEMMAP_SORTED);
+        assert!(h.is_memmap());
+        let mut bad = h;
+        bad.len += 1;
+        assert!(!bad.is_memmap());
+        assert!(!ZsExtHeader::memmap(MEMMAP_MAX_ENTRIES as u16 + 1, 0).is_memmap());
+    }
+}
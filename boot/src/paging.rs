@@ -0,0 +1,253 @@
+//! paging.rs — NØNOS boot page tables (higher-half kernel mapping)
+//! eK@nonos-tech.xyz
+//
+// The kernel is linked at KERNEL_BASE and runs at KERNEL_BASE + slide
+// (kaslr.rs); UEFI only provides an identity map. Before the jump the loader
+// switches to a PML4 of its own:
+// - Lower half: the firmware's PML4 entries, shared as-is, so the loader, its
+//   stack, the handoff block and the firmware stack the kernel starts on stay
+//   mapped across the CR3 switch
+// - PHYS_OFFSET: the same firmware entries again, as the kernel's early
+//   phys-offset window (kernel/src/boot/mod.rs `init_memory`)
+// - The image: 4 KiB pages at its run address → its physical slot, with the
+//   rights of the PT_LOAD segments covering each page (W^X where the segments
+//   allow it; NX needs EFER.NXE, set by `activate`)
+//
+// Rules:
+// - Tables are LOADER_DATA pages (BootLoaded in the handoff), identity mapped
+//   while we build them; the kernel keeps them reserved.
+// - Shared firmware tables are never written: `map_4k` refuses the PML4 slots
+//   that alias them.
+// - The firmware tables live in boot-services memory, which the kernel may
+//   only reclaim once it runs on page tables and a stack of its own.
+
+#![allow(dead_code)]
+
+use core::arch::asm;
+
+use crate::kaslr;
+
+/// Higher-half alias of physical memory the kernel finds at entry
+pub const PHYS_OFFSET: u64 = 0xFFFF_8000_0000_0000;
+/// Lower-half PML4 slots aliased at PHYS_OFFSET (512 GiB each)
+pub const PHYS_SLOTS: usize = 64;
+
+const PRESENT: u64 = 1 << 0;
+const WRITABLE: u64 = 1 << 1;
+const HUGE: u64 = 1 << 7;
+const NX: u64 = 1 << 63;
+const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+const ENTRIES: usize = 512;
+const PAGE: u64 = 4096;
+const HALF: usize = ENTRIES / 2;
+
+const IA32_EFER: u32 = 0xC000_0080;
+const EFER_NXE: u64 = 1 << 11;
+
+/// 4-level page tables built from identity-mapped frames handed out by `alloc`
+pub struct Tables<A: FnMut() -> Option<u64>> {
+    root: u64,
+    alloc: A,
+}
+
+impl<A: FnMut() -> Option<u64>> Tables<A> {
+    pub fn new(mut alloc: A) -> Result<Self, &'static str> {
+        let root = new_table(&mut alloc)?;
+        Ok(Self { root, alloc })
+    }
+
+    /// Physical address of the PML4 (the value for CR3)
+    pub fn root(&self) -> u64 {
+        self.root
+    }
+
+    /// Share the lower half of PML4 `src` (the firmware identity map) and
+    /// alias its first PHYS_SLOTS entries at PHYS_OFFSET.
+    ///
+    /// # Safety
+    /// `src` must be a live, identity-mapped PML4.
+    pub unsafe fn adopt_lower_half(&mut self, src: u64) {
+        let (src, dst) = (table(src & ADDR_MASK), table(self.root));
+        dst[..HALF].copy_from_slice(&src[..HALF]);
+        dst[HALF..HALF + PHYS_SLOTS].copy_from_slice(&src[..PHYS_SLOTS]);
+    }
+
+    /// Map the 4 KiB page at `va` to `pa`
+    pub fn map_4k(&mut self, va: u64, pa: u64, write: bool, exec: bool) -> Result<(), &'static str> {
+        if (va | pa) & (PAGE - 1) != 0 {
+            return Err("paging: unaligned mapping");
+        }
+        if index(va, 3) < HALF + PHYS_SLOTS {
+            return Err("paging: slot shared with firmware tables");
+        }
+        let mut t = self.root;
+        for level in [3, 2, 1] {
+            // SAFETY: every table on the walk was allocated by `new_table`
+            let e = unsafe { &mut table(t)[index(va, level)] };
+            if *e & PRESENT == 0 {
+                *e = new_table(&mut self.alloc)? | PRESENT | WRITABLE;
+            } else if *e & HUGE != 0 {
+                return Err("paging: huge page in the way");
+            }
+            t = *e & ADDR_MASK;
+        }
+        // SAFETY: as above
+        let e = unsafe { &mut table(t)[index(va, 0)] };
+        if *e & PRESENT != 0 {
+            return Err("paging: page already mapped");
+        }
+        *e = pa | PRESENT | if write { WRITABLE } else { 0 } | if exec { 0 } else { NX };
+        Ok(())
+    }
+
+    /// Map PIE `image`, loaded at physical `phys` (`span` bytes, kaslr::load),
+    /// at `run_base`; each page takes the union of the rights of the segments
+    /// covering it, pages outside every segment are read-only NX
+    pub fn map_image(&mut self, image: &[u8], phys: u64, run_base: u64, span: usize) -> Result<(), &'static str> {
+        for off in (0..span as u64).step_by(PAGE as usize) {
+            let (mut write, mut exec) = (false, false);
+            for s in kaslr::segments(image)?.filter(|s| s.offset < off + PAGE && off < s.offset + s.len) {
+                write |= s.write;
+                exec |= s.exec;
+            }
+            self.map_4k(run_base + off, phys + off, write, exec)?;
+        }
+        Ok(())
+    }
+
+    /// Physical address behind `va` (4 KiB leaves built here only)
+    pub fn translate(&self, va: u64) -> Option<u64> {
+        let mut t = self.root;
+        for level in [3, 2, 1] {
+            // SAFETY: tables reachable from our root are identity mapped
+            let e = unsafe { table(t)[index(va, level)] };
+            if e & PRESENT == 0 || e & HUGE != 0 {
+                return None;
+            }
+            t = e & ADDR_MASK;
+        }
+        // SAFETY: as above
+        let e = unsafe { table(t)[index(va, 0)] };
+        (e & PRESENT != 0).then_some((e & ADDR_MASK) | (va & (PAGE - 1)))
+    }
+}
+
+/// PML4 the CPU is running on
+pub fn current_root() -> u64 {
+    let cr3: u64;
+    // SAFETY: reading CR3 has no side effects
+    unsafe { asm!("mov {}, cr3", out(reg) cr3, options(nomem, nostack, preserves_flags)) };
+    cr3 & ADDR_MASK
+}
+
+/// Turn on EFER.NXE and switch to `root`.
+///
+/// # Safety
+/// `root` must map everything the caller touches afterwards (code, stack,
+/// handoff) at the same addresses as before.
+pub unsafe fn activate(root: u64) {
+    let (lo, hi): (u32, u32);
+    asm!("rdmsr", in("ecx") IA32_EFER, out("eax") lo, out("edx") hi, options(nomem, nostack, preserves_flags));
+    let efer = ((hi as u64) << 32 | lo as u64) | EFER_NXE;
+    asm!("wrmsr", in("ecx") IA32_EFER, in("eax") efer as u32, in("edx") (efer >> 32) as u32, options(nostack, preserves_flags));
+    asm!("mov cr3, {}", in(reg) root, options(nostack, preserves_flags));
+}
+
+fn new_table<A: FnMut() -> Option<u64>>(alloc: &mut A) -> Result<u64, &'static str> {
+    let pa = alloc().ok_or("paging: out of table pages")?;
+    if pa & (PAGE - 1) != 0 {
+        return Err("paging: unaligned table page");
+    }
+    // SAFETY: freshly allocated, identity-mapped page
+    unsafe { table(pa).fill(0) };
+    Ok(pa)
+}
+
+/// # Safety
+/// `pa` must be an identity-mapped page-table page.
+unsafe fn table(pa: u64) -> &'static mut [u64; ENTRIES] {
+    &mut *(pa as *mut [u64; ENTRIES])
+}
+
+#[inline]
+fn index(va: u64, level: u32) -> usize {
+    ((va >> (12 + 9 * level)) & (ENTRIES as u64 - 1)) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUN: u64 = 0xffff_ffff_8060_0000;
+
+    #[repr(align(4096))]
+    struct Pool([[u64; ENTRIES]; 8]);
+
+    /// Host "physical" pages: identity is the pool's own addresses
+    fn pool_alloc(pool: &mut Pool) -> impl FnMut() -> Option<u64> + '_ {
+        let mut next = 0;
+        move || {
+            let p = pool.0.get_mut(next)?.as_mut_ptr() as u64;
+            next += 1;
+            Some(p)
+        }
+    }
+
+    #[test]
+    fn maps_and_translates_higher_half() {
+        let mut pool = Pool([[0xAA; ENTRIES]; 8]);
+        let mut t = Tables::new(pool_alloc(&mut pool)).unwrap();
+        t.map_4k(RUN, 0x0123_4000, false, true).unwrap();
+        t.map_4k(RUN + PAGE, 0x0123_5000, true, false).unwrap();
+        assert_eq!(t.translate(RUN + 0x10), Some(0x0123_4010));
+        assert_eq!(t.translate(RUN + PAGE), Some(0x0123_5000));
+        assert_eq!(t.translate(RUN + 2 * PAGE), None);
+        // one table per level, shared by both pages
+        assert!(t.map_4k(RUN, 0x0999_0000, true, false).is_err());
+    }
+
+    #[test]
+    fn leaf_rights_follow_request() {
+        let mut pool = Pool([[0; ENTRIES]; 8]);
+        let mut t = Tables::new(pool_alloc(&mut pool)).unwrap();
+        t.map_4k(RUN, 0x1000, false, true).unwrap();
+        t.map_4k(RUN + PAGE, 0x2000, true, false).unwrap();
+        let leaf = |t: &Tables<_>, va: u64| {
+            let mut at = t.root();
+            for level in [3, 2, 1] {
+                at = unsafe { table(at)[index(va, level)] } & ADDR_MASK;
+            }
+            unsafe { table(at)[index(va, 0)] }
+        };
+        let text = leaf(&t, RUN);
+        let data = leaf(&t, RUN + PAGE);
+        assert_eq!((text & WRITABLE, text & NX), (0, 0));
+        assert_eq!((data & WRITABLE, data & NX), (WRITABLE, NX));
+    }
+
+    #[test]
+    fn firmware_slots_shared_not_written() {
+        let mut pool = Pool([[0; ENTRIES]; 8]);
+        let mut fw = Pool([[0; ENTRIES]; 8]);
+        fw.0[0][0] = 0x7000 | PRESENT | WRITABLE;
+        let src = fw.0[0].as_ptr() as u64;
+        let mut t = Tables::new(pool_alloc(&mut pool)).unwrap();
+        unsafe { t.adopt_lower_half(src) };
+        let root = unsafe { table(t.root()) };
+        assert_eq!(root[0], fw.0[0][0]);
+        assert_eq!(root[index(PHYS_OFFSET, 3)], fw.0[0][0]);
+        assert!(t.map_4k(0x20_0000, 0x20_0000, true, false).is_err());
+        assert!(t.map_4k(PHYS_OFFSET, 0, true, false).is_err());
+        assert!(t.map_4k(RUN + 1, 0x1000, true, false).is_err());
+    }
+
+    #[test]
+    fn runs_out_of_tables_cleanly() {
+        let mut pool = Pool([[0; ENTRIES]; 8]);
+        let mut t = Tables::new(pool_alloc(&mut pool)).unwrap();
+        // 1 root + 3 per distinct PML4 slot: the third slot needs a 9th page
+        t.map_4k(RUN, 0x1000, true, false).unwrap();
+        t.map_4k(RUN - (1 << 39), 0x1000, true, false).unwrap();
+        assert!(t.map_4k(RUN - (2 << 39), 0x1000, true, false).is_err());
+    }
+}
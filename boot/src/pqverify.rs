@@ -0,0 +1,57 @@
+//! pqverify.rs — NØNOS post-quantum capsule signature check (ML-DSA-65)
+// eK@nonos-tech.xyz
+//
+// Capsules flagged FLAG_PQ_SIGNED carry an ML-DSA-65 signature immediately
+// after the classical signature region:
+//
+//   offset_sig .. offset_sig+len_sig            classical signature / proof
+//   offset_sig+len_sig .. +MLDSA65_SIG_LEN      ML-DSA-65 over the payload
+//
+// Both must verify (hybrid). The verifier only exists with the `pq-mldsa`
+// feature; the verifying key is embedded at build time from the file named
+// by $NONOS_PQ_PUBKEY. A `pq-mldsa` loader refuses any capsule without the
+// PQ signature (verify.rs); without the feature a flagged capsule is refused.
+
+#![allow(dead_code)]
+
+pub const MLDSA65_PUBKEY_LEN: usize = 1952;
+pub const MLDSA65_SIG_LEN: usize = 3309;
+
+/// Domain tag prepended to the payload before PQ signing/verification
+const DS_CAPSULE_PQ: &[u8] = b"NONOS:CAPSULE:MLDSA65:v1";
+
+#[cfg(feature = "pq-mldsa")]
+static PQ_ROOT_PUBKEY: &[u8] = include_bytes!(env!("NONOS_PQ_PUBKEY"));
+
+/// Verify the capsule's PQ signature over `payload` against the embedded root
+#[cfg(feature = "pq-mldsa")]
+pub fn verify_capsule_pq(payload: &[u8], signature: &[u8]) -> Result<(), &'static str> {
+    use ml_dsa::signature::Verifier;
+    use ml_dsa::{EncodedSignature, EncodedVerifyingKey, MlDsa65, Signature, VerifyingKey};
+
+    if PQ_ROOT_PUBKEY.len() != MLDSA65_PUBKEY_LEN {
+        return Err("embedded ML-DSA-65 key has wrong length");
+    }
+    if signature.len() != MLDSA65_SIG_LEN {
+        return Err("ML-DSA-65 signature has wrong length");
+    }
+
+    let key = EncodedVerifyingKey::<MlDsa65>::try_from(PQ_ROOT_PUBKEY)
+        .map(|enc| VerifyingKey::<MlDsa65>::decode(&enc))
+        .map_err(|_| "malformed ML-DSA-65 key")?;
+    let sig = EncodedSignature::<MlDsa65>::try_from(signature)
+        .ok()
+        .and_then(|enc| Signature::<MlDsa65>::decode(&enc))
+        .ok_or("malformed ML-DSA-65 signature")?;
+
+    let mut msg = alloc::vec::Vec::with_capacity(DS_CAPSULE_PQ.len() + payload.len());
+    msg.extend_from_slice(DS_CAPSULE_PQ);
+    msg.extend_from_slice(payload);
+
+    key.verify(&msg, &sig).map_err(|_| "ML-DSA-65 signature invalid")
+}
+
+#[cfg(not(feature = "pq-mldsa"))]
+pub fn verify_capsule_pq(_payload: &[u8], _signature: &[u8]) -> Result<(), &'static str> {
+    Err("capsule requires ML-DSA-65 but loader built without pq-mldsa")
+}
@@ -7,15 +7,14 @@ use alloc::vec::Vec;
 
 use crate::capsule::zkmeta::requires_zk;
 use crate::crypto::sig::verify_signature;          // back with ed25519 (recommended)
+use crate::capsule::FLAG_PQ_SIGNED;
 use crate::log::logger::{log_info, log_warn};
+use crate::pqverify::{verify_capsule_pq, MLDSA65_SIG_LEN};
 use crate::zk::zkverify::{verify_proof, ZkProof, ZkVerifyResult};
 
-use blake3;
 use sha2::{Digest, Sha256}; // optional if you still need SHA-256 elsewhere
 
-/// Domain separation labels
-const DS_CAPSULE_COMMIT: &str = "NONOS:CAPSULE:COMMITMENT:v1";
-const DS_PROGRAM_HASH:   &str = "NONOS:ZK:PROGRAM:v1";
+use crate::digest::{blake3_tagged, capsule_identity, domain};
 
 pub enum CapsuleVerification {
     StaticVerified,
@@ -44,6 +43,10 @@ pub fn verify_capsule(blob: &[u8], meta: &CapsuleMetadata) -> CapsuleVerificatio
             Ok(proof) => match verify_proof(&proof) {
                 ZkVerifyResult::Valid => {
                     log_info("verify", "ZK proof accepted");
+                    if let Err(e) = verify_pq(blob, meta) {
+                        log_warn("verify", e);
+                        return CapsuleVerification::Failed(e);
+                    }
                     CapsuleVerification::ZkVerified
                 }
                 ZkVerifyResult::Invalid(e)
@@ -59,13 +62,48 @@ pub fn verify_capsule(blob: &[u8], meta: &CapsuleMetadata) -> CapsuleVerificatio
             }
         }
     } else {
-        if verify_signature(blob, meta) {
-            log_info("verify", "Static signature accepted");
-            CapsuleVerification::StaticVerified
-        } else {
-            CapsuleVerification::Failed("signature verification failed")
+        if !verify_signature(blob, meta) {
+            return CapsuleVerification::Failed("signature verification failed");
         }
+        if let Err(e) = verify_pq(blob, meta) {
+            log_warn("verify", e);
+            return CapsuleVerification::Failed(e);
+        }
+        log_info("verify", "Static signature accepted");
+        CapsuleVerification::StaticVerified
+    }
+}
+
+/// ML-DSA-65 leg of the hybrid check. A `pq-mldsa` loader requires it on
+/// every capsule, static or ZK, whatever the flags say; otherwise only
+/// capsules flagged `FLAG_PQ_SIGNED` carry (and must pass) it.
+fn verify_pq(blob: &[u8], meta: &CapsuleMetadata) -> Result<(), &'static str> {
+    let flagged = meta.flags & FLAG_PQ_SIGNED != 0;
+    if !flagged {
+        if cfg!(feature = "pq-mldsa") {
+            return Err("capsule lacks the ML-DSA-65 signature this loader requires");
+        }
+        return Ok(());
+    }
+    pq_slices(blob, meta).and_then(|(sig, payload)| verify_capsule_pq(payload, sig))?;
+    log_info("verify", "ML-DSA-65 signature accepted");
+    Ok(())
+}
+
+/// PQ signature region (right after the classical one) and payload
+#[inline]
+fn pq_slices<'a>(blob: &'a [u8], meta: &CapsuleMetadata) -> Result<(&'a [u8], &'a [u8]), &'static str> {
+    let (_, payload) = slices_for(blob, meta)?;
+    let start = meta.offset_sig.checked_add(meta.len_sig).ok_or("pq sig offset overflow")?;
+    let end = start.checked_add(MLDSA65_SIG_LEN).ok_or("pq sig len overflow")?;
+    if end > blob.len() {
+        return Err("pq signature out of bounds");
+    }
+    let pay_end = meta.offset_payload + meta.len_payload;
+    if ranges_overlap(start, end, meta.offset_payload, pay_end) {
+        return Err("pq sig/payload overlap");
     }
+    Ok((&blob[start..end], payload))
 }
 
 /// Construct ZkProof from metadata and blob
@@ -83,12 +121,10 @@ fn extract_zk_proof(blob: &[u8], meta: &CapsuleMetadata) -> Result<ZkProof, &'st
     })
 }
 
-/// Compute capsule commitment (BLAKE3, domain-separated)
+/// Compute capsule commitment: the canonical capsule identity (`digest::capsule_identity`)
 #[inline]
 pub fn blake3_commit(payload: &[u8]) -> [u8; 32] {
-    let mut h = blake3::Hasher::new_derive_key(DS_CAPSULE_COMMIT);
-    h.update(payload);
-    *h.finalize().as_bytes()
+    capsule_identity(payload).bytes
 }
 
 /// Decide if keep SHA-256 helper kept for compatibility
@@ -100,9 +136,7 @@ pub fn sha256(data: &[u8]) -> [u8; 32] {
 /// Stable program hash for dev boot zkVM (domain-separated BLAKE3).
 /// Replace with Halo2 circuit ID hash when ready.
 fn known_program_hash() -> [u8; 32] {
-    let mut h = blake3::Hasher::new_derive_key(DS_PROGRAM_HASH);
-    h.update(b"zkmod-attestation-program-v1");
-    *h.finalize().as_bytes()
+    blake3_tagged(domain::ZK_PROGRAM, b"zkmod-attestation-program-v1").bytes
 }
 
 /// Validate offsets and produce borrowed slices
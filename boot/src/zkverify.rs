@@ -5,16 +5,24 @@
 // - Consistent commitments: use BLAKE3 (domain-separated), not ad-hoc SHA-256.
 // - Strict size caps for proof/public inputs; reject absurd blobs early.
 // - Constant-time comparisons for commitments/program IDs.
-// - Feature-gated mock verifier for bring-up; defaults to *reject* without feature.
+// - Real backend: Ristretto sigma protocols (`zk-sigma`, shared/zk_sigma.rs);
+//   the attestation program is a ring-membership proof over the commitment.
+// - Feature-gated mock verifier for bring-up; defaults to *reject* without a backend.
 // - No panics; precise error strings for boot logs.
 
 #![allow(dead_code)]
 
 use alloc::vec::Vec;
 
-use blake3;
 use core::cmp::min;
 
+use crate::digest::{blake3_tagged, capsule_identity, domain};
+
+/// Sigma-protocol verifier shared with the kernel (`crypto::zk`)
+#[cfg(feature = "zk-sigma")]
+#[path = "../../shared/zk_sigma.rs"]
+pub mod zk_sigma;
+
 /// Abstract proof type for any zk backend (SNARK, STARK, zkVM)
 #[derive(Debug, Clone)]
 pub struct ZkProof {
@@ -39,9 +47,6 @@ pub enum ZkVerifyResult {
 
 /* -------------------- constants & helpers -------------------- */
 
-const DS_PROGRAM_HASH: &str = "NONOS:ZK:PROGRAM:v1";
-const DS_COMMITMENT:   &str = "NONOS:CAPSULE:COMMITMENT:v1";
-
 const MAX_PROOF_SIZE: usize = 2 * 1024 * 1024; // 2 MiB cap
 const MAX_INPUT_SIZE: usize = 256 * 1024;      // 256 KiB cap
 
@@ -54,16 +59,14 @@ fn ct_eq32(a: &[u8;32], b: &[u8;32]) -> bool {
 
 #[inline]
 fn blake3_commit(payload: &[u8]) -> [u8; 32] {
-    let mut h = blake3::Hasher::new_derive_key(DS_COMMITMENT);
-    h.update(payload);
-    *h.finalize().as_bytes()
+    capsule_identity(payload).bytes
 }
 
+/// Attestation program: anonymous membership of the capsule signer in the
+/// `zk_sigma::keys::ANON_AUTH` ring, bound to the capsule commitment
 #[inline]
 fn known_program_hash() -> [u8; 32] {
-    let mut h = blake3::Hasher::new_derive_key(DS_PROGRAM_HASH);
-    h.update(b"zkmod-attestation-program-v1");
-    *h.finalize().as_bytes()
+    blake3_tagged(domain::ZK_PROGRAM, b"zkmod-attestation-program-v1").bytes
 }
 
 /* -------------------- verifier entry -------------------- */
@@ -72,7 +75,7 @@ fn known_program_hash() -> [u8; 32] {
 /// Checks:
 /// 1) program identity (domain-separated hash),
 /// 2) commitment binding (recompute from public inputs),
-/// 3) backend proof verification or feature-gated mock.
+/// 3) backend proof verification (`zk-sigma`) or feature-gated mock.
 ///
 /// Returns `Unsupported` if sizes are absurd or no backend is compiled in.
 pub fn verify_proof(proof: &ZkProof) -> ZkVerifyResult {
@@ -97,9 +100,22 @@ pub fn verify_proof(proof: &ZkProof) -> ZkVerifyResult {
     }
 
     // 3) backend verify (feature-gated). By default, no backend.
+    // - `zk-sigma`: CDS ring proof over the commitment against the fixed ring.
     // - `mock-proof` feature: accept blobs that start with a fixed magic prefix.
-    // - Real backends (halo2, risc0, etc.) should replace this block.
-    #[cfg(feature = "mock-proof")]
+    #[cfg(feature = "zk-sigma")]
+    {
+        let vk = &zk_sigma::keys::ANON_AUTH;
+        if proof.proof_blob.len() != 64 * vk.keys.len() {
+            return ZkVerifyResult::Invalid("sigma proof has wrong length");
+        }
+        return if zk_sigma::verify(vk, &proof.capsule_commitment, &proof.proof_blob) {
+            ZkVerifyResult::Valid
+        } else {
+            ZkVerifyResult::Invalid("sigma ring proof rejected")
+        };
+    }
+
+    #[cfg(all(feature = "mock-proof", not(feature = "zk-sigma")))]
     {
         const MAGIC: &[u8] = &[0xAA, 0xBB, 0xCC, 0xDD];
         let ok = proof.proof_blob.len() >= MAGIC.len()
@@ -107,7 +123,7 @@ pub fn verify_proof(proof: &ZkProof) -> ZkVerifyResult {
         return if ok { ZkVerifyResult::Valid } else { ZkVerifyResult::Invalid("mock verifier: bad prefix") };
     }
 
-    #[cfg(not(feature = "mock-proof"))]
+    #[cfg(not(any(feature = "zk-sigma", feature = "mock-proof")))]
     {
         ZkVerifyResult::Unsupported("no zk backend compiled")
     }
@@ -131,3 +147,50 @@ pub fn load_test_proof() -> ZkProof {
         capsule_commitment: blake3_commit(&inputs),
     }
 }
+
+#[cfg(all(test, feature = "zk-sigma"))]
+mod tests {
+    use super::*;
+    use super::zk_sigma::vectors;
+
+    fn vector_proof() -> ZkProof {
+        let inputs = b"nonos test capsule v1".to_vec();
+        ZkProof {
+            proof_blob: vectors::ANON_AUTH_PROOF.to_vec(),
+            capsule_commitment: blake3_commit(&inputs),
+            public_inputs: inputs,
+            program_hash: known_program_hash(),
+        }
+    }
+
+    #[test]
+    fn vector_commitment_matches() {
+        assert_eq!(blake3_commit(b"nonos test capsule v1"), vectors::ANON_AUTH_STATEMENT);
+    }
+
+    #[test]
+    fn ring_vector_verifies() {
+        assert_eq!(verify_proof(&vector_proof()), ZkVerifyResult::Valid);
+    }
+
+    #[test]
+    fn ring_proof_bound_to_commitment() {
+        let mut p = vector_proof();
+        p.public_inputs = b"nonos test capsule v2".to_vec();
+        p.capsule_commitment = blake3_commit(&p.public_inputs);
+        assert_eq!(verify_proof(&p), ZkVerifyResult::Invalid("sigma ring proof rejected"));
+    }
+
+    #[test]
+    fn tampered_ring_proof_rejected() {
+        let mut p = vector_proof();
+        p.proof_blob[200] ^= 0x01;
+        assert!(matches!(verify_proof(&p), ZkVerifyResult::Invalid(_)));
+    }
+
+    #[test]
+    fn modsig_vector_verifies() {
+        assert!(zk_sigma::verify(&zk_sigma::keys::MODSIG, &vectors::MODSIG_STATEMENT, &vectors::MODSIG_PROOF));
+        assert!(!zk_sigma::verify(&zk_sigma::keys::MODSIG, &vectors::ANON_AUTH_STATEMENT, &vectors::MODSIG_PROOF));
+    }
+}
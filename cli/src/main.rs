@@ -3,12 +3,26 @@
 // As privacy is a fundamental human right
 // eK <3
 
+extern crate alloc;
+
 use clap::{Parser, Subcommand, Args};
 use std::fs;
 use std::path::Path;
 use serde_json::json;
 
 mod nonosctl;
+
+/// Hash algorithm ids, domain tags and capsule identity shared with the kernel and bootloader
+#[path = "../../shared/digest.rs"]
+mod digest;
+
+/// Merkle trees and proofs shared with the kernel (`crypto::merkle`)
+#[path = "../../shared/merkle.rs"]
+mod merkle;
+
+/// Kernel memory audit export format (`memory::proof`)
+#[path = "../../shared/memaudit.rs"]
+mod memaudit;
 use nonosctl::{users, logging, capsule, services, capsule_net};
 
 const CONFIG_PATH: &str = "/etc/nonos/config.toml";
@@ -67,6 +81,21 @@ enum Commands {
         #[command(subcommand)]
         action: DevAction,
     },
+    /// Verify kernel memory audit exports offline
+    MemAudit {
+        #[command(subcommand)]
+        action: MemAuditAction,
+    },
+    /// Runtime state snapshot inclusion proofs
+    State {
+        #[command(subcommand)]
+        action: StateAction,
+    },
+    /// Verify kernel ZeroState proofs offline
+    ZeroState {
+        #[command(subcommand)]
+        action: ZeroStateAction,
+    },
     Sysinfo,
 }
 
@@ -112,6 +141,43 @@ enum ConfigAction {
     Set { key: String, value: String },
 }
 
+#[derive(Subcommand)]
+enum MemAuditAction {
+    /// Recompute roots from a snapshot and exported batches
+    Verify { snapshot: String, batches: Vec<String> },
+    /// Print the inclusion proof of one event, checked against a snapshot
+    Prove {
+        #[arg(long)]
+        cpu: u32,
+        #[arg(long)]
+        seq: u64,
+        snapshot: String,
+        batches: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StateAction {
+    /// Print the inclusion proof of one runtime file (live state unless --snapshot)
+    Prove {
+        file: String,
+        #[arg(long)]
+        snapshot: Option<String>,
+    },
+    /// Check a proof printed by `state prove`
+    Verify { proof: String },
+}
+
+#[derive(Subcommand)]
+enum ZeroStateAction {
+    /// Check a `zs.prove` frame, optionally against an `attest.quote` frame
+    VerifySandbox {
+        proof: String,
+        #[arg(long)]
+        quote: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 enum DevAction {
     MockUser { name: String },
@@ -227,6 +293,24 @@ fn main() {
             }
         },
 
+        Commands::MemAudit { action } => match action {
+            MemAuditAction::Verify { snapshot, batches } => nonosctl::memaudit::verify_export(&snapshot, &batches, cli.json),
+            MemAuditAction::Prove { cpu, seq, snapshot, batches } => {
+                nonosctl::memaudit::prove_event(&snapshot, &batches, cpu, seq, cli.json)
+            }
+        },
+
+        Commands::State { action } => match action {
+            StateAction::Prove { file, snapshot } => nonosctl::beacon::state::prove_file(snapshot.as_deref(), &file),
+            StateAction::Verify { proof } => nonosctl::beacon::state::verify_proof_file(&proof, cli.json),
+        },
+
+        Commands::ZeroState { action } => match action {
+            ZeroStateAction::VerifySandbox { proof, quote } => {
+                nonosctl::zerostate::verify_sandbox_proof(&proof, quote.as_deref(), cli.json)
+            }
+        },
+
         Commands::Sysinfo => {
             let uptime = std::fs::read_to_string("/proc/uptime").unwrap_or_default();
             let mem = std::fs::read_to_string("/proc/meminfo").unwrap_or_default();
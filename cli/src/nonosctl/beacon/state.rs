@@ -14,9 +14,21 @@ use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use base58::ToBase58;
 
+use crate::digest::domain;
+use crate::merkle::{self, MerkleTree};
+
 const CAPSULE_STATE_PATH: &str = "/run/nonos/runtime";
 const SNAPSHOT_DIR: &str = "/var/nonos/snapshots";
 
+/// Snapshot root format. 1: SHA-256 over the concatenated entries;
+/// 2: `merkle` root over `domain::BEACON_STATE` leaves (proofs need 2)
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Snapshots written before the field existed are version 1
+fn legacy_version() -> u32 {
+    1
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct StateEntry {
     pub file: String,
@@ -28,6 +40,8 @@ pub struct StateEntry {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StateSnapshot {
+    #[serde(default = "legacy_version")]
+    pub version: u32,
     pub hash: String,
     pub entries: Vec<StateEntry>,
     pub timestamp: u64,
@@ -40,7 +54,9 @@ pub struct StateDiff {
     pub changed: Vec<(String, String)>, // filename -> new hash
 }
 
-/// Computes Merkle-style hash of sorted state + returns detailed entries
+/// Computes the Merkle root of the sorted state entries + returns detailed entries.
+/// The root is the hex of a `merkle` tree (same construction as the kernel's)
+/// with one `domain::BEACON_STATE` leaf per entry.
 pub fn hash_runtime_state_detailed() -> StateSnapshot {
     let mut entries = vec![];
 
@@ -70,20 +86,148 @@ pub fn hash_runtime_state_detailed() -> StateSnapshot {
     }
 
     entries.sort_by_key(|e| e.file.clone());
-    let mut full_hasher = Sha256::new();
-    for entry in &entries {
-        full_hasher.update(format!("{}:{}:{}:{}:{}|", entry.file, entry.size, entry.mtime, entry.ftype, entry.hash));
-    }
-
-    let root_hash = format!("{:x}", full_hasher.finalize());
+    let root_hash = hex(&state_tree(&entries).root());
 
     StateSnapshot {
+        version: SNAPSHOT_VERSION,
         hash: root_hash,
         entries,
         timestamp: now_epoch(),
     }
 }
 
+fn entry_leaf(entry: &StateEntry) -> String {
+    format!("{}:{}:{}:{}:{}", entry.file, entry.size, entry.mtime, entry.ftype, entry.hash)
+}
+
+fn state_tree(entries: &[StateEntry]) -> MerkleTree {
+    let mut tree = MerkleTree::new();
+    for entry in entries {
+        tree.append(domain::BEACON_STATE, entry_leaf(entry).as_bytes());
+    }
+    tree
+}
+
+/// Inclusion proof for one entry of a snapshot; verifiable without the other entries
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateProof {
+    pub version: u32,
+    pub root: String,
+    pub index: usize,
+    pub size: usize,
+    pub entry: StateEntry,
+    pub path: Vec<String>,
+}
+
+/// Prove `file` against a snapshot root (None for unknown files or v1 snapshots)
+pub fn prove_entry(snapshot: &StateSnapshot, file: &str) -> Option<StateProof> {
+    if snapshot.version != SNAPSHOT_VERSION {
+        return None;
+    }
+    let index = snapshot.entries.iter().position(|e| e.file == file)?;
+    let tree = state_tree(&snapshot.entries);
+    let path = tree.inclusion_proof(index, tree.len())?;
+    Some(StateProof {
+        version: snapshot.version,
+        root: snapshot.hash.clone(),
+        index,
+        size: tree.len(),
+        entry: snapshot.entries[index].clone(),
+        path: path.iter().map(|h| hex(h)).collect(),
+    })
+}
+
+/// Light verification: check one entry against a snapshot root without the other entries
+pub fn verify_entry(proof: &StateProof) -> bool {
+    if proof.version != SNAPSHOT_VERSION {
+        return false;
+    }
+    let path: Option<Vec<merkle::Hash>> = proof.path.iter().map(|h| unhex(h)).collect();
+    match (path, unhex(&proof.root)) {
+        (Some(path), Some(root)) => {
+            let leaf = merkle::leaf_hash(domain::BEACON_STATE, entry_leaf(&proof.entry).as_bytes());
+            merkle::verify_inclusion(&leaf, proof.index, proof.size, &path, &root)
+        }
+        _ => false,
+    }
+}
+
+/// Load a snapshot written by `export_state_snapshot`
+pub fn load_snapshot(path: &str) -> Result<StateSnapshot, String> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// `nonosctl state prove`: print the proof of `file` from a saved (or the live) snapshot
+pub fn prove_file(snapshot_path: Option<&str>, file: &str) {
+    let snapshot = match snapshot_path.map(load_snapshot) {
+        Some(Ok(s)) => s,
+        Some(Err(e)) => {
+            eprintln!("[state] {}", e);
+            std::process::exit(2);
+        }
+        None => hash_runtime_state_detailed(),
+    };
+    if snapshot.version != SNAPSHOT_VERSION {
+        eprintln!("[state] snapshot format v{} has no Merkle root (need v{})", snapshot.version, SNAPSHOT_VERSION);
+        std::process::exit(2);
+    }
+    match prove_entry(&snapshot, file) {
+        Some(proof) => println!("{}", serde_json::to_string_pretty(&proof).unwrap()),
+        None => {
+            eprintln!("[state] {} not in snapshot {}", file, snapshot.hash);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `nonosctl state verify`: check a proof printed by `prove_file`
+pub fn verify_proof_file(path: &str, json: bool) {
+    let proof: StateProof = match fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|raw| serde_json::from_str(&raw).map_err(|e| e.to_string()))
+    {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("[state] {}: {}", path, e);
+            std::process::exit(2);
+        }
+    };
+    let ok = verify_entry(&proof);
+    if json {
+        let v = serde_json::json!({
+            "file": proof.entry.file,
+            "root": proof.root,
+            "version": proof.version,
+            "index": proof.index,
+            "size": proof.size,
+            "valid": ok,
+        });
+        println!("{}", serde_json::to_string_pretty(&v).unwrap());
+    } else {
+        println!("[state] {} leaf {}/{} of v{} root {}", proof.entry.file, proof.index, proof.size, proof.version, proof.root);
+        println!("[state] {}", if ok { "✔ inclusion verified" } else { "✘ inclusion failed" });
+    }
+    if !ok {
+        std::process::exit(1);
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<merkle::Hash> {
+    if s.len() != 64 {
+        return None;
+    }
+    let mut out = [0u8; 32];
+    for (i, b) in out.iter_mut().enumerate() {
+        *b = u8::from_str_radix(s.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(out)
+}
+
 /// Save the current state snapshot to disk for auditing
 pub fn export_state_snapshot(snapshot: &StateSnapshot) {
     fs::create_dir_all(SNAPSHOT_DIR).ok();
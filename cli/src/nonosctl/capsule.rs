@@ -11,6 +11,7 @@ use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::digest;
 use crate::logging::log_event;
 
 const CAPSULE_DB: &str = "/var/nonos/capsules/index.json";
@@ -18,6 +19,11 @@ const CAPSULE_DIR: &str = "/var/nonos/capsules";
 const LOG_DIR: &str = "/var/nonos/capsules/logs";
 const TELEMETRY_DIR: &str = "/var/nonos/capsules/telemetry";
 
+/// N0N capsule container magic (see boot/src/capsule.rs)
+const CAPSULE_MAGIC: &[u8; 4] = b"N0N\0";
+/// `size_of::<CapsuleHeader>()` as read by the bootloader (repr(C): u32s start at 8)
+const CAPSULE_HEADER_LEN: usize = 24;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CapsuleInfo {
     pub api_version: String,
@@ -43,7 +49,7 @@ pub fn deploy_capsule(name: &str, source_path: &str) {
 
     fs::copy(source_path, &target_path).expect("[capsule] failed to copy binary");
 
-    let checksum = compute_sha256(&target_path).unwrap_or_else(|_| "<error>".into());
+    let checksum = compute_identity(&target_path).unwrap_or_else(|_| "<error>".into());
 
     let mut capsule = CapsuleInfo {
         api_version: "v1".into(),
@@ -121,9 +127,14 @@ pub fn run_capsule(name: &str) {
 pub fn verify_capsule(name: &str) {
     let db = read_index();
     if let Some(capsule) = db.get(name) {
-        let current = compute_sha256(&capsule.path).unwrap_or_default();
+        // Index entries written before hash agility hold a bare SHA-256 hex string
+        let current = if digest::Digest::parse(&capsule.checksum).is_some() {
+            compute_identity(&capsule.path).unwrap_or_default()
+        } else {
+            compute_sha256(&capsule.path).unwrap_or_default()
+        };
         if current == capsule.checksum {
-            println!("[verify] ✅ '{}' passed integrity check.", name);
+            println!("[verify] ✅ '{}' passed integrity check ({}).", name, current);
         } else {
             println!("[verify] ❌ '{}' checksum mismatch.", name);
         }
@@ -181,6 +192,24 @@ pub fn delete_capsule(name: &str) {
     }
 }
 
+/// Canonical capsule identity (`blake3:<hex>`), identical to the commitment the
+/// bootloader hands the kernel. N0N containers are hashed over their payload
+/// section; any other file is hashed whole.
+fn compute_identity(path: &str) -> Result<String, std::io::Error> {
+    let blob = fs::read(path)?;
+    let payload = capsule_payload(&blob).unwrap_or(&blob);
+    Ok(digest::capsule_identity(payload).to_string())
+}
+
+fn capsule_payload(blob: &[u8]) -> Option<&[u8]> {
+    if blob.len() < CAPSULE_HEADER_LEN || &blob[..4] != CAPSULE_MAGIC {
+        return None;
+    }
+    let field = |at: usize| u32::from_le_bytes([blob[at], blob[at + 1], blob[at + 2], blob[at + 3]]) as usize;
+    let (offset, len) = (field(12), field(20));
+    blob.get(offset..offset.checked_add(len)?)
+}
+
 fn compute_sha256(path: &str) -> Result<String, std::io::Error> {
     let mut file = fs::File::open(path)?;
     let mut hasher = Sha256::new();
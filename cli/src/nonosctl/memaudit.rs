@@ -0,0 +1,303 @@
+// cli/src/nonosctl/memaudit.rs — Offline verifier for kernel memory audit exports
+// Maintained by ek@nonos-tech.xyz | © 2025 NØN Technologies
+// Recomputes `memory::proof` commitments from exported events, without
+// trusting the running kernel.
+//
+// Inputs are what the kernel's `proof.export` emits: one snapshot record
+// (`SnapshotHeader` + CPU roots) and any number of batch records. Each file
+// may hold raw binary, a hex blob, or the GUI bridge JSON frames
+// (`{"type":"proof_snapshot"|"proof_batches",...,"data":"<hex>"}`, one per line).
+
+use std::collections::BTreeMap;
+use std::fs;
+use serde::Serialize;
+
+use crate::memaudit::{
+    BatchHeader, Event, EventProof, Kind, SnapshotHeader, BATCH_HDR_LEN, BATCH_MAGIC,
+    EVENT_ENCODED_LEN, SNAPSHOT_HDR_LEN, SNAPSHOT_MAGIC,
+};
+use crate::merkle::Hash;
+
+#[derive(Debug, Default, Serialize)]
+pub struct CpuReport {
+    pub cpu: u32,
+    pub batches: usize,
+    pub events: usize,
+    pub first_index: u64,
+    pub last_index: u64,
+    /// Chain starts at the zero root (batch 0), so nothing before it is missing
+    pub from_genesis: bool,
+    /// Last `root_after` equals this CPU's root in the snapshot
+    pub linked_to_snapshot: bool,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AuditReport {
+    pub epoch: u64,
+    pub boot_nonce: u64,
+    pub cpu_count: u32,
+    pub dropped_events: u64,
+    pub root: String,
+    pub snapshot_ok: bool,
+    pub cpus: Vec<CpuReport>,
+    pub ok: bool,
+}
+
+/// Decode an export file into raw record bytes
+fn load(path: &str) -> Result<Vec<u8>, String> {
+    let raw = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if raw.starts_with(&BATCH_MAGIC) || raw.starts_with(&SNAPSHOT_MAGIC) {
+        return Ok(raw);
+    }
+    let text = String::from_utf8(raw).map_err(|_| format!("{}: not a binary or text export", path))?;
+    let mut out = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let blob = if line.starts_with('{') {
+            let v: serde_json::Value =
+                serde_json::from_str(line).map_err(|e| format!("{}: {}", path, e))?;
+            v["data"].as_str().map(str::to_string).ok_or_else(|| format!("{}: frame without data", path))?
+        } else {
+            line.to_string()
+        };
+        out.extend(unhex(&blob).ok_or_else(|| format!("{}: bad hex", path))?);
+    }
+    Ok(out)
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_start_matches("0x");
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok()).collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_snapshot(raw: &[u8]) -> Result<(SnapshotHeader, Vec<Hash>), String> {
+    let hdr = SnapshotHeader::decode(raw).ok_or("snapshot: bad header")?;
+    let n = hdr.cpu_count as usize;
+    let body = &raw[SNAPSHOT_HDR_LEN..];
+    if body.len() < n * 32 {
+        return Err(format!("snapshot: {} cpu roots expected, file truncated", n));
+    }
+    let roots = body[..n * 32]
+        .chunks_exact(32)
+        .map(|c| {
+            let mut h = [0u8; 32];
+            h.copy_from_slice(c);
+            h
+        })
+        .collect();
+    Ok((hdr, roots))
+}
+
+fn parse_batches(mut raw: &[u8]) -> Result<Vec<(BatchHeader, Vec<Event>)>, String> {
+    let mut out = Vec::new();
+    while !raw.is_empty() {
+        let hdr = BatchHeader::decode(raw)
+            .ok_or_else(|| format!("batches: bad record header after {} batches", out.len()))?;
+        let len = hdr.record_len();
+        if raw.len() < len {
+            return Err(format!("batches: record {} of cpu {} truncated", hdr.index, hdr.cpu));
+        }
+        let events = raw[BATCH_HDR_LEN..len]
+            .chunks_exact(EVENT_ENCODED_LEN)
+            .map(|c| Event::decode(c).ok_or("batches: bad event"))
+            .collect::<Result<Vec<_>, _>>()?;
+        out.push((hdr, events));
+        raw = &raw[len..];
+    }
+    Ok(out)
+}
+
+fn verify_cpu(cpu: u32, batches: &[(BatchHeader, Vec<Event>)], snapshot_root: Option<&Hash>) -> CpuReport {
+    let mut r = CpuReport { cpu, batches: batches.len(), ..Default::default() };
+    let mut prev: Option<(&BatchHeader, u64)> = None; // (header, last seq)
+    for (hdr, events) in batches {
+        r.events += events.len();
+        if !hdr.verify(events) {
+            r.errors.push(format!("batch {}: events do not hash to root_after", hdr.index));
+        }
+        if events.windows(2).any(|w| w[1].seq <= w[0].seq) {
+            r.errors.push(format!("batch {}: sequence numbers not increasing", hdr.index));
+        }
+        match prev {
+            None => {
+                r.first_index = hdr.index;
+                r.from_genesis = hdr.index == 0 && hdr.root_before == [0u8; 32];
+            }
+            Some((p, last_seq)) => {
+                if hdr.index != p.index + 1 || hdr.root_before != p.root_after {
+                    r.errors.push(format!(
+                        "gap between batch {} and {} ({} batches missing or reordered)",
+                        p.index,
+                        hdr.index,
+                        hdr.index.saturating_sub(p.index + 1)
+                    ));
+                }
+                if events.first().map_or(false, |e| e.seq <= last_seq) {
+                    r.errors.push(format!("batch {}: sequence rewinds", hdr.index));
+                }
+            }
+        }
+        r.last_index = hdr.index;
+        prev = Some((hdr, events.last().map_or(0, |e| e.seq)));
+    }
+    r.linked_to_snapshot = match (prev, snapshot_root) {
+        (Some((last, _)), Some(root)) => last.root_after == *root,
+        _ => false,
+    };
+    match (prev, snapshot_root) {
+        (_, None) => r.errors.push(format!("cpu {} not in snapshot", cpu)),
+        (Some((last, _)), Some(_)) if !r.linked_to_snapshot => r.errors.push(format!(
+            "batch {} does not end at the snapshot root (later batches missing or export not from this snapshot)",
+            last.index
+        )),
+        _ => {}
+    }
+    r
+}
+
+/// Verify a snapshot and its batches; returns the report (never panics on bad input)
+pub fn audit(snapshot_path: &str, batch_paths: &[String]) -> Result<AuditReport, String> {
+    let (hdr, roots) = parse_snapshot(&load(snapshot_path)?)?;
+    let mut by_cpu: BTreeMap<u32, Vec<(BatchHeader, Vec<Event>)>> = BTreeMap::new();
+    for path in batch_paths {
+        for (h, ev) in parse_batches(&load(path)?)? {
+            by_cpu.entry(h.cpu).or_default().push((h, ev));
+        }
+    }
+
+    let mut report = AuditReport {
+        epoch: hdr.epoch,
+        boot_nonce: hdr.boot_nonce,
+        cpu_count: hdr.cpu_count,
+        dropped_events: hdr.drop_total,
+        root: hex(&hdr.root),
+        snapshot_ok: hdr.verify(&roots),
+        ..Default::default()
+    };
+    for (cpu, mut batches) in by_cpu {
+        batches.sort_by_key(|(h, _)| h.index);
+        report.cpus.push(verify_cpu(cpu, &batches, roots.get(cpu as usize)));
+    }
+    report.ok = report.snapshot_ok
+        && report.cpus.iter().all(|c| c.linked_to_snapshot && c.errors.is_empty());
+    Ok(report)
+}
+
+pub fn verify_export(snapshot_path: &str, batch_paths: &[String], json: bool) {
+    let report = match audit(snapshot_path, batch_paths) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("[memaudit] {}", e);
+            std::process::exit(2);
+        }
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        println!("[memaudit] epoch {} | boot nonce {:#x} | {} cpus | {} events dropped",
+            report.epoch, report.boot_nonce, report.cpu_count, report.dropped_events);
+        println!("[memaudit] root {} — {}", report.root,
+            if report.snapshot_ok { "matches cpu roots" } else { "MISMATCH" });
+        for c in &report.cpus {
+            println!("  cpu {}: batches {}..={} ({} events) genesis={} linked={}",
+                c.cpu, c.first_index, c.last_index, c.events, c.from_genesis, c.linked_to_snapshot);
+            for e in &c.errors {
+                println!("    ✘ {}", e);
+            }
+        }
+        println!("[memaudit] {}", if report.ok { "✔ export verified" } else { "✘ verification failed" });
+    }
+    if !report.ok {
+        std::process::exit(1);
+    }
+}
+
+/// Print the inclusion proof of event `seq` on `cpu` from an exported batch
+/// file, chained through the later exported batches to the CPU root of
+/// `snapshot_path`
+pub fn prove_event(snapshot_path: &str, batch_paths: &[String], cpu: u32, seq: u64, json: bool) {
+    let fail = |e: String| -> ! {
+        eprintln!("[memaudit] {}", e);
+        std::process::exit(2);
+    };
+    let (hdr, roots) = load(snapshot_path).and_then(|raw| parse_snapshot(&raw)).unwrap_or_else(|e| fail(e));
+    if !hdr.verify(&roots) {
+        fail(format!("{}: snapshot root does not match its cpu roots", snapshot_path));
+    }
+    let Some(cpu_root) = roots.get(cpu as usize) else {
+        fail(format!("cpu {} not in snapshot", cpu));
+    };
+    let mut batches = Vec::new();
+    for path in batch_paths {
+        let parsed = load(path).and_then(|raw| parse_batches(&raw)).unwrap_or_else(|e| fail(e));
+        batches.extend(parsed.into_iter().filter(|(h, _)| h.cpu == cpu));
+    }
+    batches.sort_by_key(|(h, _)| h.index);
+
+    let mut found = None;
+    for (hdr, events) in &batches {
+        match found.as_mut() {
+            None => {
+                if let Some(i) = events.iter().position(|e| e.seq == seq) {
+                    found = EventProof::build(hdr, events, i);
+                }
+            }
+            Some(p) => {
+                if !p.push_link(hdr, events) {
+                    fail(format!("cannot chain batch {} of cpu {} (gap, duplicate or more than {} later batches)",
+                        hdr.index, cpu, crate::memaudit::MAX_LINKS));
+                }
+            }
+        }
+    }
+    let Some(p) = found else {
+        eprintln!("[memaudit] event {} of cpu {} not found in export", seq, cpu);
+        std::process::exit(1);
+    };
+    let valid = p.verify(cpu_root);
+    let kind = Kind::from_u8(p.event.kind).map_or("Unknown".to_string(), |k| format!("{:?}", k));
+    let path: Vec<String> = p.path[..p.path_len as usize].iter().map(|h| hex(h)).collect();
+    if json {
+        let v = serde_json::json!({
+            "cpu": cpu,
+            "seq": seq,
+            "kind": kind,
+            "vaddr": p.event.vaddr,
+            "paddr": p.event.paddr,
+            "len": p.event.len,
+            "captag": p.event.captag,
+            "batch": p.batch.index,
+            "leaf_index": p.leaf_index,
+            "batch_size": p.batch.count,
+            "leaf": hex(&p.event.leaf()),
+            "path": path,
+            "root_before": hex(&p.batch.root_before),
+            "root_after": hex(&p.batch.root_after),
+            "later_batches": p.link_count,
+            "cpu_root": hex(cpu_root),
+            "snapshot_root": hex(&hdr.root),
+            "valid": valid,
+        });
+        println!("{}", serde_json::to_string_pretty(&v).unwrap());
+    } else {
+        println!("[memaudit] cpu {} seq {}: {} vaddr {:#x} paddr {:#x} len {:#x} captag {:#x}",
+            cpu, seq, kind, p.event.vaddr, p.event.paddr, p.event.len, p.event.captag);
+        println!("  leaf {}/{} of batch {}  {}", p.leaf_index, p.batch.count, p.batch.index, hex(&p.event.leaf()));
+        for (i, h) in path.iter().enumerate() {
+            println!("  path[{}] {}", i, h);
+        }
+        println!("  root_after {} + {} later batches", hex(&p.batch.root_after), p.link_count);
+        println!("  cpu root {} (snapshot {})", hex(cpu_root), hex(&hdr.root));
+        println!("[memaudit] {}", if valid { "✔ inclusion verified against snapshot" } else { "✘ inclusion failed" });
+    }
+    if !valid {
+        std::process::exit(1);
+    }
+}
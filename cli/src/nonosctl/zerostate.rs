@@ -0,0 +1,121 @@
+// cli/src/nonosctl/zerostate.rs — Offline verifier for ZeroState sandbox proofs
+// Maintained by ek@nonos-tech.xyz | © 2025 NØN Technologies
+// Checks a kernel `zs.prove` frame (`{"type":"sandbox_proof",...}`) against
+// the sandbox Merkle root, and optionally against the `sandboxes` root of an
+// `attest.quote` frame taken at the same time. The quote's signature is not
+// checked here; only the roots are compared.
+
+use std::fs;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::digest::{domain, Digest, HashAlgo};
+use crate::merkle::{self, Hash};
+
+#[derive(Debug, Default, Serialize)]
+pub struct SandboxProofReport {
+    pub exec_id: String,
+    pub epoch: u64,
+    pub index: usize,
+    pub size: usize,
+    pub root: String,
+    /// Audit path leads from the exec id leaf to `root`
+    pub included: bool,
+    /// `root` equals the quote's sandbox root (None when no quote was given)
+    pub matches_quote: Option<bool>,
+    pub ok: bool,
+}
+
+/// First JSON frame of `kind` in a file of GUI bridge frames (one per line)
+fn load_frame(path: &str, kind: &str) -> Result<Value, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    text.lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with('{'))
+        .filter_map(|l| serde_json::from_str::<Value>(l).ok())
+        .find(|v| v["type"] == kind)
+        .ok_or_else(|| format!("{}: no {} frame", path, kind))
+}
+
+fn unhex32(s: &str) -> Option<Hash> {
+    let s = s.trim_start_matches("0x");
+    if s.len() != 64 {
+        return None;
+    }
+    let mut out = [0u8; 32];
+    for (i, b) in out.iter_mut().enumerate() {
+        *b = u8::from_str_radix(s.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(out)
+}
+
+fn blake3_root(v: &Value, field: &str) -> Result<Hash, String> {
+    let d = v[field]
+        .as_str()
+        .and_then(Digest::parse)
+        .ok_or_else(|| format!("bad {} digest", field))?;
+    if d.algo != HashAlgo::Blake3 {
+        return Err(format!("{}: unsupported algorithm {}", field, d.algo.name()));
+    }
+    Ok(d.bytes)
+}
+
+/// Verify a `sandbox_proof` frame; `quote_path` optionally pins its root
+pub fn check_sandbox_proof(proof_path: &str, quote_path: Option<&str>) -> Result<SandboxProofReport, String> {
+    let v = load_frame(proof_path, "sandbox_proof")?;
+    let exec_id = v["exec_id"].as_str().and_then(unhex32).ok_or("bad exec_id")?;
+    let index = v["index"].as_u64().ok_or("missing index")? as usize;
+    let size = v["size"].as_u64().ok_or("missing size")? as usize;
+    let root = blake3_root(&v, "root")?;
+    let path = v["path"]
+        .as_array()
+        .ok_or("missing path")?
+        .iter()
+        .map(|h| h.as_str().and_then(unhex32))
+        .collect::<Option<Vec<Hash>>>()
+        .ok_or("bad path hash")?;
+
+    let leaf = merkle::leaf_hash(domain::ZEROSTATE_SANDBOX, &exec_id);
+    let included = merkle::verify_inclusion(&leaf, index, size, &path, &root);
+    let matches_quote = match quote_path {
+        Some(q) => Some(blake3_root(&load_frame(q, "quote")?, "sandboxes")? == root),
+        None => None,
+    };
+    Ok(SandboxProofReport {
+        exec_id: v["exec_id"].as_str().unwrap_or_default().to_string(),
+        epoch: v["epoch"].as_u64().unwrap_or(0),
+        index,
+        size,
+        root: Digest::new(HashAlgo::Blake3, root).to_string(),
+        included,
+        matches_quote,
+        ok: included && matches_quote != Some(false),
+    })
+}
+
+pub fn verify_sandbox_proof(proof_path: &str, quote_path: Option<&str>, json: bool) {
+    let report = match check_sandbox_proof(proof_path, quote_path) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("[zerostate] {}", e);
+            std::process::exit(2);
+        }
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        println!("[zerostate] sandbox {} | epoch {:#x} | leaf {}/{}",
+            report.exec_id, report.epoch, report.index, report.size);
+        println!("[zerostate] root {} — {}", report.root,
+            if report.included { "path verified" } else { "PATH MISMATCH" });
+        match report.matches_quote {
+            Some(true) => println!("[zerostate] root matches quote sandbox root"),
+            Some(false) => println!("[zerostate] root DIFFERS from quote sandbox root"),
+            None => {}
+        }
+        println!("[zerostate] {}", if report.ok { "✔ sandbox included" } else { "✘ verification failed" });
+    }
+    if !report.ok {
+        std::process::exit(1);
+    }
+}
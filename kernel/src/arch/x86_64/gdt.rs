@@ -187,9 +187,10 @@ unsafe fn init_cpu_common(apic_id: u32, alloc: &dyn IstAllocator, is_bsp: bool,
     // harden control regs
     harden_crs();
 
-    // PCID hint (off by default until ASIDs used)
+    // PCID tagging (capsule address spaces keep their TLB entries across switches)
+    #[cfg(feature = "nonos-pcid")]
     if has_pcid() {
-        // leave off; enable via enable_pcid() once scheduler sets ASIDs
+        enable_pcid(asid);
     }
 
     // EFER NX on
@@ -250,8 +251,12 @@ fn has_ecx_1(bit: u32) -> bool {
     (c & (1 << bit)) != 0
 }
 fn has_pcid() -> bool {
-    let (_a, _b, _c, d) = cpuid(0x1, 0);
-    (d & (1 << 17)) != 0 // PCID in CR4
+    let (_a, _b, c, _d) = cpuid(0x1, 0);
+    (c & (1 << 17)) != 0 // CPUID.1:ECX.PCID
+}
+fn has_invpcid() -> bool {
+    let (_a, b, _c, _d) = cpuid(0x7, 0);
+    (b & (1 << 10)) != 0 // CPUID.7:EBX.INVPCID
 }
 
 fn cpuid(leaf: u32, subleaf: u32) -> (u32, u32, u32, u32) {
@@ -292,18 +297,21 @@ fn ensure_nxe() {
     }
 }
 
-/// opt-in when scheduler assigns ASIDs
+/// Set CR4.PCIDE and hand PCID allocation to `memory::virt::pcid`
 pub unsafe fn enable_pcid(asid: u16) {
     if !has_pcid() { return; }
-    let mut cr4 = Cr4::read();
-    cr4.insert(Cr4Flags::PCID);
-    Cr4::write(cr4);
 
-    // install kernel CR3 with PCID=0; user will use PCID=asid
+    // CR4.PCIDE may only be set while CR3[11:0] == 0: install kernel CR3
+    // with PCID 0 first; capsule spaces get their PCIDs from virt::pcid
     let (level4, _flags) = Cr3::read();
-    let kcr3 = level4.start_address().as_u64() | 0u64; // PCID 0
+    let kcr3 = level4.start_address().as_u64(); // PCID 0
     asm!("mov cr3, {}", in(reg) kcr3, options(nostack, preserves_flags));
 
+    let mut cr4 = Cr4::read();
+    cr4.insert(Cr4Flags::PCID);
+    Cr4::write(cr4);
+
+    crate::memory::virt::pcid::online(has_invpcid());
     // store ASID where needed (registry already holds asid)
     let _ = asid;
 }
@@ -171,6 +171,14 @@ extern "x86-interrupt" fn gpf_handler(stack: InterruptStackFrame, code: u64) {
 
 extern "x86-interrupt" fn pf_handler(stack: InterruptStackFrame, err: PageFaultErrorCode) {
     let addr = Cr2::read();
+    // Demand-paged regions resolve first touch and COW writes here
+    if crate::memory::region::handle_page_fault(
+        addr.as_u64(),
+        err.contains(PageFaultErrorCode::CAUSED_BY_WRITE),
+        err.contains(PageFaultErrorCode::PROTECTION_VIOLATION),
+    ) {
+        return;
+    }
     trap!(log_err, 14, "Page Fault", stack, format!("Fault Addr={:?} Error={:?}", addr, err));
 }
 
@@ -164,6 +164,11 @@ pub unsafe fn init() {
     proof::audit_phys_alloc(0xA11C_AP1C, 0x1N17_u64, CapTag::KERNEL);
 }
 
+/// True once `init` has enabled the local APIC on this system
+pub fn ready() -> bool {
+    X2APIC.load(Ordering::Relaxed) || MMIO_BASE_LO.load(Ordering::Relaxed) != 0
+}
+
 /// Return local APIC ID.
 pub fn id() -> u32 {
     if X2APIC.load(Ordering::Relaxed) {
@@ -291,6 +291,18 @@ pub fn sleep_long_ns(ns: u64, cb: fn()) -> u64 {
     id
 }
 
+/// Blocking sleep for kernel tasks: yields to the scheduler and halts until
+/// the deadline instead of spinning. `sleep_long_ns` only arms a callback.
+pub fn sleep_ns(ns: u64) {
+    let deadline = now_ns().saturating_add(ns);
+    while now_ns() < deadline {
+        crate::sched::schedule_now();
+        if now_ns() < deadline && x86_64::instructions::interrupts::are_enabled() {
+            x86_64::instructions::hlt();
+        }
+    }
+}
+
 // —————————————————— IRQ glue ——————————————————
 
 pub fn on_timer_irq() -> bool {
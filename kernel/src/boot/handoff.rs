@@ -0,0 +1,169 @@
+// kernel/src/boot/handoff.rs
+//! Kernel-side view of the ZeroState Boot→Kernel handoff block.
+//!
+//! Must stay byte-for-byte identical to `boot/src/handoff.rs` (128 bytes,
+//! packed, little-endian). The kernel only reads it.
+//!
+//! With `BOOT_MEMMAP` the bootloader appends the physical memory map as a
+//! versioned extension right after the header (`shared/bootinfo.rs`). It is
+//! validated and copied out in `record`, so nothing later depends on loader
+//! memory staying intact.
+
+use core::{mem, ptr};
+use spin::Once;
+
+use crate::crypto::hash::{Digest, HashAlgo};
+
+/// Handoff extension records, shared verbatim with the bootloader
+#[path = "../../../shared/bootinfo.rs"]
+pub mod bootinfo;
+
+use bootinfo::{MemoryRegion, ZsExtHeader, MEMMAP_MAX_ENTRIES, MEMMAP_TRUNCATED};
+
+pub const ZS_MAGIC: u64 = 0x30424F534F4E4F4E;
+pub const ZS_ABI_VERSION: u16 = 1;
+pub const ZS_HDR_SIZE: u16 = 128;
+/// Algorithm of `capsule_hash`; fixed by the ABI version, a new algorithm
+/// means a new `ZS_ABI_VERSION`
+pub const CAPSULE_HASH_ALGO: HashAlgo = HashAlgo::Blake3;
+
+/// `BootModeFlags::KASLR`: the image was relocated by `kaslr_slide`
+pub const BOOT_KASLR: u32 = 1 << 6;
+/// `BootModeFlags::MEMMAP`: a memory map extension follows the header
+pub const BOOT_MEMMAP: u32 = 1 << 7;
+
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct ZeroStateBootInfo {
+    pub magic: u64,
+    pub abi_version: u16,
+    pub hdr_size: u16,
+    pub boot_flags: u32,
+    pub capsule_base: u64,
+    pub capsule_size: u64,
+    /// `digest::capsule_identity` of the kernel capsule payload, raw bytes
+    /// of a `CAPSULE_HASH_ALGO` digest; read it through `capsule_digest`
+    pub capsule_hash: [u8; 32],
+    pub memory_start: u64,
+    pub memory_size: u64,
+    pub entropy: [u8; 32],
+    /// UTC at handoff, u64 LE UNIX seconds (0 = firmware clock unknown)
+    pub rtc_utc: [u8; 8],
+    /// Slide the bootloader applied to the kernel image (valid with `BOOT_KASLR`)
+    pub kaslr_slide: u64,
+}
+
+const _: () = {
+    assert!(mem::size_of::<ZeroStateBootInfo>() == 128);
+};
+
+impl ZeroStateBootInfo {
+    /// Header invariants; nothing else in the block is trusted until this passes
+    pub fn basic_sanity(&self) -> bool {
+        let (magic, abi, size) = (self.magic, self.abi_version, self.hdr_size);
+        magic == ZS_MAGIC && abi == ZS_ABI_VERSION && size == ZS_HDR_SIZE
+    }
+
+    /// Bootloader-collected seed material (RDSEED/RDRAND/TSC/EFI RNG)
+    pub fn entropy(&self) -> [u8; 32] {
+        self.entropy
+    }
+
+    /// Algorithm-tagged capsule identity the bootloader committed to
+    pub fn capsule_digest(&self) -> Digest {
+        Digest::new(CAPSULE_HASH_ALGO, self.capsule_hash)
+    }
+
+    /// Wall-clock seconds the bootloader read from the firmware RTC
+    pub fn rtc_unix(&self) -> Option<u64> {
+        match u64::from_le_bytes(self.rtc_utc) {
+            0 => None,
+            secs => Some(secs),
+        }
+    }
+
+    /// Image slide, if the bootloader relocated the kernel
+    pub fn kaslr_slide(&self) -> Option<u64> {
+        let (flags, slide) = (self.boot_flags, self.kaslr_slide);
+        (flags & BOOT_KASLR != 0).then_some(slide)
+    }
+}
+
+/// Bootloader-reported physical memory map, sorted by start address
+pub struct MemoryMap {
+    regions: [MemoryRegion; MEMMAP_MAX_ENTRIES],
+    count: usize,
+    flags: u16,
+}
+
+impl MemoryMap {
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions[..self.count]
+    }
+
+    /// The bootloader dropped records it had no room for
+    pub fn truncated(&self) -> bool {
+        self.flags & MEMMAP_TRUNCATED != 0
+    }
+}
+
+/// Copy of the accepted handoff block, kept for attestation quotes
+static BOOT_INFO: Once<ZeroStateBootInfo> = Once::new();
+static MEMORY_MAP: Once<MemoryMap> = Once::new();
+
+/// Keep a sane handoff block for later use; the first accepted block wins.
+/// `info` must point at the bootloader's block, not a copy, since extensions
+/// follow it in memory.
+pub fn record(info: &ZeroStateBootInfo) {
+    if info.basic_sanity() {
+        BOOT_INFO.call_once(|| *info);
+        if let Some(map) = unsafe { read_memory_map(info) } {
+            MEMORY_MAP.call_once(|| map);
+        }
+    }
+}
+
+/// Validate and copy the memory map extension; any mismatch drops it whole
+unsafe fn read_memory_map(info: &ZeroStateBootInfo) -> Option<MemoryMap> {
+    let (flags, hdr_size) = (info.boot_flags, info.hdr_size);
+    if flags & BOOT_MEMMAP == 0 {
+        return None;
+    }
+    let ext = (info as *const ZeroStateBootInfo as *const u8).add(hdr_size as usize);
+    let hdr = ptr::read_unaligned(ext as *const ZsExtHeader);
+    if !hdr.is_memmap() {
+        return None;
+    }
+    let count = hdr.count as usize;
+    let src = ext.add(mem::size_of::<ZsExtHeader>()) as *const MemoryRegion;
+    let mut map = MemoryMap {
+        regions: [MemoryRegion { start: 0, len: 0, ty: 0, attr: 0 }; MEMMAP_MAX_ENTRIES],
+        count,
+        flags: hdr.flags,
+    };
+    for i in 0..count {
+        let r = ptr::read_unaligned(src.add(i));
+        if r.start.checked_add(r.len).is_none() {
+            return None;
+        }
+        map.regions[i] = r;
+    }
+    Some(map)
+}
+
+/// Current UTC (UNIX seconds): the handoff RTC snapshot plus uptime.
+/// None when the bootloader could not read the firmware clock.
+pub fn unix_time() -> Option<u64> {
+    let base = boot_info()?.rtc_unix()?;
+    Some(base + crate::arch::x86_64::time::timer::now_ns() / 1_000_000_000)
+}
+
+/// The validated memory map extension, if the bootloader passed one
+pub fn memory_map() -> Option<&'static MemoryMap> {
+    MEMORY_MAP.get()
+}
+
+/// The recorded handoff block, if the bootloader passed a sane one
+pub fn boot_info() -> Option<&'static ZeroStateBootInfo> {
+    BOOT_INFO.get()
+}
@@ -12,34 +12,10 @@ use core::panic::PanicInfo;
 use x86_64::VirtAddr;
 use x86_64::structures::paging::OffsetPageTable;
 
-/// UEFI Memory Map Entry
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct MemoryDescriptor {
-    pub ty: u32,
-    pub phys_start: u64,
-    pub virt_start: u64,
-    pub page_count: u64,
-    pub attribute: u64,
-}
-
-/// Boot Information passed from bootloader
-#[repr(C)]
-pub struct BootInfo {
-    pub memory_map: &'static [MemoryDescriptor],
-    pub framebuffer: Option<FramebufferInfo>,
-    pub rsdp_addr: Option<u64>,
-    pub kernel_image_offset: u64,
-}
+pub mod handoff;
 
-#[repr(C)]
-pub struct FramebufferInfo {
-    pub buffer_addr: u64,
-    pub buffer_size: usize,
-    pub width: u32,
-    pub height: u32,
-    pub stride: u32,
-}
+use handoff::ZeroStateBootInfo;
+use handoff::bootinfo::MemoryRegionType;
 
 /// Early allocator for IST stacks (before heap is ready)
 struct EarlyAllocator {
@@ -67,6 +43,12 @@ impl EarlyAllocator {
         
         addr
     }
+    
+    /// Physical range handed out so far (pages sit in the boot phys window)
+    fn phys_range(&self) -> (u64, u64) {
+        let base = crate::memory::layout::BOOT_PHYS_OFFSET;
+        (Self::new().next_page.as_u64() - base, self.next_page.as_u64() - base)
+    }
 }
 
 impl crate::arch::x86_64::gdt::IstAllocator for EarlyAllocator {
@@ -75,6 +57,9 @@ impl crate::arch::x86_64::gdt::IstAllocator for EarlyAllocator {
         let base = self.alloc_pages(pages + 1); // +1 for guard
         let usable = base + 4096u64; // Skip guard page
         let end = usable + (pages * 4096) as u64;
+        // Early pages come from the boot mapping, so the guard is only
+        // recorded if it really is unmapped (else GuardMapped would fire)
+        crate::memory::mapcheck::track_stack(Some(base), usable, pages);
         (usable, end)
     }
     
@@ -85,9 +70,11 @@ impl crate::arch::x86_64::gdt::IstAllocator for EarlyAllocator {
 
 static mut EARLY_ALLOC: EarlyAllocator = EarlyAllocator::new();
 
-/// Kernel entry point from bootloader
+/// Kernel entry point from bootloader. The ABI is the bootloader's
+/// `KernelEntry`: one pointer to the ZeroState handoff block, followed in the
+/// same allocation by its extensions (memory map).
 #[no_mangle]
-pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
+pub extern "C" fn _start(handoff_ptr: *const ZeroStateBootInfo) -> ! {
     // Stage 0: Absolute minimum initialization
     unsafe {
         // Clear BSS (should be done by bootloader, but be safe)
@@ -97,14 +84,37 @@ pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
         init_serial_early();
         serial_println!("[BOOT] NØNOS kernel starting...");
         
+        // Seed the DRBG before anything can ask for randomness
+        seed_entropy(handoff_ptr);
+        
         // Stage 1: Memory initialization
         serial_println!("[BOOT] Initializing memory subsystem...");
-        init_memory(boot_info);
+        init_memory();
         
+        // Leave the firmware's stack (boot services memory) for the image's
+        // own boot stack; the handoff has been recorded, nothing on the old
+        // stack is needed again
+        core::arch::asm!(
+            "mov rsp, {stack}",
+            "xor ebp, ebp",
+            "call {cont}",
+            stack = in(reg) &__boot_stack_top as *const u8 as u64,
+            cont = sym boot_continue,
+            options(noreturn)
+        );
+    }
+}
+
+/// Rest of the boot sequence, on `__boot_stack_top`
+extern "C" fn boot_continue() -> ! {
+    unsafe {
         // Stage 2: CPU structures (GDT, IDT, TSS)
         serial_println!("[BOOT] Setting up CPU structures...");
         init_cpu_structures();
         
+        // Stack, page tables' memory, GDT and IDT are now the kernel's own
+        release_boot_memory();
+        
         // Stage 3: Interrupts and APIC
         serial_println!("[BOOT] Initializing interrupts...");
         init_interrupts();
@@ -123,6 +133,24 @@ pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
     }
 }
 
+extern "C" {
+    static __boot_stack_top: u8;
+}
+
+/// Free firmware boot services memory: move any page table still in it,
+/// then hand its frames to the allocator, minus what the early allocator
+/// (phys bitmap, IST stacks) took from it
+unsafe fn release_boot_memory() {
+    let moved = crate::memory::virt::detach_tables(crate::memory::phys::in_boot_services)
+        .expect("Failed to move page tables out of boot services memory");
+    let (lo, hi) = EARLY_ALLOC.phys_range();
+    let freed = crate::memory::phys::release_boot_services(|pa| pa >= lo && pa < hi);
+    serial_println!(
+        "[BOOT] Boot services memory released: {} KiB ({} page tables moved)",
+        freed * 4, moved,
+    );
+}
+
 unsafe fn clear_bss() {
     extern "C" {
         static mut __bss_start: u8;
@@ -136,6 +164,21 @@ unsafe fn clear_bss() {
     core::ptr::write_bytes(bss_start as *mut u8, 0, bss_len);
 }
 
+fn seed_entropy(handoff_ptr: *const ZeroStateBootInfo) {
+    // Record straight from the bootloader's block: extensions follow it
+    let zerostate = unsafe { handoff_ptr.as_ref() };
+    match zerostate.filter(|zs| zs.basic_sanity()) {
+        Some(zs) => {
+            handoff::record(zs);
+            crate::crypto::entropy::seed_from_boot(&zs.entropy());
+        }
+        None => {
+            serial_println!("[BOOT] No valid handoff block, seeding DRBG from CPU only");
+            crate::crypto::entropy::seed_rng();
+        }
+    }
+}
+
 unsafe fn init_serial_early() {
     // Initialize COM1 for debugging
     use x86_64::instructions::port::Port;
@@ -166,40 +209,56 @@ unsafe fn init_serial_early() {
     ier.write(0x01);
 }
 
-unsafe fn init_memory(boot_info: &'static BootInfo) {
-    // Parse memory map
-    let mut usable_regions = heapless::Vec::<(u64, u64), 32>::new();
-    
-    for desc in boot_info.memory_map {
-        if desc.ty == 7 { // EFI_CONVENTIONAL_MEMORY
-            let _ = usable_regions.push((desc.phys_start, desc.page_count * 4096));
-        }
-    }
-    
-    // Initialize physical memory allocator
-    crate::memory::phys::init_from_regions(
-        &usable_regions[..],
+unsafe fn init_memory() {
+    // Physical zones come from the bootloader's full memory map (handoff
+    // extension), which carries every reserved/ACPI/MMIO/bad range
+    let from_handoff = crate::memory::phys::init_from_handoff(
         0, // node_id
         crate::memory::phys::ScrubPolicy::OnFree,
-        |words| {
-            // Allocate bitmap backing
-            let bytes = words * 8;
-            let pages = (bytes + 4095) / 4096;
-            let addr = EARLY_ALLOC.alloc_pages(pages);
-            unsafe {
-                core::slice::from_raw_parts_mut(
-                    addr.as_u64() as *mut core::sync::atomic::AtomicU64,
-                    words
-                )
-            }
-        },
+        carve_bitmap,
         None, // No audit sink yet
     );
+    match handoff::memory_map() {
+        Some(map) if from_handoff => serial_println!(
+            "[BOOT] Memory map from handoff: {} regions{}",
+            map.regions().len(),
+            if map.truncated() { " (truncated)" } else { "" },
+        ),
+        _ => {
+            // Guessing at RAM would hand firmware-owned frames to the allocator
+            serial_println!("[BOOT] No memory map in handoff, halting");
+            panic!("boot: handoff carries no physical memory map");
+        }
+    }
+    
+    // Initialize virtual memory on the bootloader's tables, reached through
+    // its physical alias (layout::BOOT_PHYS_OFFSET) until the direct map is up
+    let (l4_frame, _) = x86_64::registers::control::Cr3::read();
+    crate::memory::virt::init(l4_frame.start_address().as_u64())
+        .expect("Failed to initialize virtual memory");
+    
+    // KASLR: adopt the bootloader's image slide, randomize region bases
+    // (the heap reads its base on first use, so this must precede it)
+    let kaslr = crate::memory::kaslr::init(
+        crate::memory::kaslr::Policy::from_boot(handoff::boot_info()),
+    );
+    serial_println!(
+        "[BOOT] KASLR: image {}, regions {}",
+        if kaslr.slide != 0 { "slid" } else { "unslid" },
+        if crate::memory::kaslr::applied() & crate::memory::kaslr::APPLIED_REGIONS != 0 { "randomized" } else { "fixed" },
+    );
     
-    // Initialize virtual memory
-    let phys_offset = VirtAddr::new(0xFFFF_8000_0000_0000);
-    let l4_table = get_level_4_table(phys_offset);
-    crate::memory::virt::init(l4_table as u64)?;
+    // Direct map at its (randomized) base over all RAM in the handoff map;
+    // page-table access moves onto it
+    let ram_top = handoff::memory_map().map_or(0, |map| {
+        map.regions()
+            .iter()
+            .filter(|r| !matches!(r.kind(), MemoryRegionType::Mmio))
+            .map(|r| r.start + r.len)
+            .max()
+            .unwrap_or(0)
+    });
+    crate::memory::virt::map_directmap(ram_top).expect("Failed to map direct map");
     
     // Initialize kernel heap
     const HEAP_SIZE: usize = 8 * 1024 * 1024; // 8 MiB
@@ -218,8 +277,22 @@ unsafe fn init_memory(boot_info: &'static BootInfo) {
         ).expect("Failed to map heap page");
     }
     
-    // Initialize heap allocator
+    // Initialize heap allocator: early arena, then hand over to the slab heap
     crate::memory::heap::init_kernel_heap();
+    crate::memory::heap::init(crate::memory::heap::HeapPolicy::default());
+}
+
+/// Bitmap backing for phys zones, from the early allocator
+fn carve_bitmap(words: usize) -> &'static mut [core::sync::atomic::AtomicU64] {
+    let bytes = words * 8;
+    let pages = (bytes + 4095) / 4096;
+    unsafe {
+        let addr = EARLY_ALLOC.alloc_pages(pages);
+        core::slice::from_raw_parts_mut(
+            addr.as_u64() as *mut core::sync::atomic::AtomicU64,
+            words
+        )
+    }
 }
 
 unsafe fn init_cpu_structures() {
@@ -227,6 +300,10 @@ unsafe fn init_cpu_structures() {
     let apic_id = read_apic_id();
     crate::arch::x86_64::gdt::init_bsp(apic_id, &EARLY_ALLOC);
     
+    // Per-CPU area at its (randomized) base; GS points at it
+    let percpu = crate::memory::virt::map_percpu(0).expect("Failed to map per-CPU area");
+    crate::arch::x86_64::gdt::set_gs_base(percpu.as_u64());
+    
     // Initialize IDT
     crate::arch::x86_64::idt::init();
     
@@ -264,9 +341,14 @@ unsafe fn init_core_subsystems() {
     crate::log::logger::init();
     log_info!("[BOOT] Logger initialized");
     
-    // Initialize crypto vault
-    crate::crypto::vault::init_vault();
-    log_info!("[BOOT] Crypto vault initialized");
+    // Initialize crypto vault and run the power-on self-tests; capsule
+    // admission stays closed (restricted mode) until the POST has passed
+    crate::crypto::init_crypto();
+    if crate::crypto::selftest::restricted_mode() {
+        log_warn!("[BOOT] Crypto POST failed, restricted mode: capsule admission disabled");
+    } else {
+        log_info!("[BOOT] Crypto vault initialized, POST passed");
+    }
     
     // Initialize scheduler
     crate::sched::init();
@@ -282,11 +364,23 @@ unsafe fn init_core_subsystems() {
 }
 
 unsafe fn init_module_system() {
+    // Load the threshold signer policy from vault trust roots
+    crate::modules::auth::init_trusted_signers();
+
     // Initialize module loader
     crate::modules::mod_loader::init_module_loader();
     
     // Initialize capability system
     crate::syscall::capabilities::init_capabilities();
+
+    // Start capsule supervisor (restart backoff + heartbeat probes)
+    crate::modules::supervisor::init_supervisor();
+
+    // Start memory pressure monitor (reclaim + capsule OOM policy)
+    crate::memory::pressure::init();
+
+    // Start mapping invariant scanner (W^X, user/kernel, stacks, guards)
+    crate::memory::mapcheck::init();
     
     // Load initial modules if any
     load_initial_modules();
@@ -305,6 +399,7 @@ unsafe fn load_initial_modules() {
         entry_point_addr: Some(init_module_entry as u64),
         signature: [0; 64],
         signer: crate::crypto::vault::VaultPublicKey::default(),
+        cosignatures: &[],
         auth_chain_id: None,
         auth_method: crate::modules::manifest::AuthMethod::VaultSignature,
         zk_attestation: None,
@@ -314,11 +409,17 @@ unsafe fn load_initial_modules() {
         ],
         fault_policy: Some(crate::modules::runtime::FaultPolicy::Restart),
         memory_bytes: 64 * 1024, // 64 KiB
+        code_bytes: 0,
+        code: &[],
+        importance: crate::modules::manifest::IMPORTANCE_CRITICAL,
+        provides: &["init"],
+        dependencies: &[],
         timestamp: 0,
         expiry_seconds: None,
     };
     
     crate::modules::mod_loader::verify_and_queue(&test_manifest).ok();
+    crate::modules::mod_loader::admit_ready_modules();
 }
 
 extern "C" fn init_module_entry(_arg: usize) -> ! {
@@ -353,15 +454,6 @@ fn read_apic_id() -> u32 {
     result
 }
 
-unsafe fn get_level_4_table(phys_offset: VirtAddr) -> *mut PageTable {
-    use x86_64::registers::control::Cr3;
-    
-    let (level_4_table_frame, _) = Cr3::read();
-    let phys = level_4_table_frame.start_address();
-    let virt = phys_offset + phys.as_u64();
-    virt.as_mut_ptr()
-}
-
 /// Panic handler
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
@@ -0,0 +1,252 @@
+//! NØNOS Authenticated Encryption
+//!
+//! ChaCha20-Poly1305 (RFC 8439) on the in-tree ChaCha20 core, and AES-256-GCM
+//! (`nonos-crypto-aesgcm`, wire-compatible with the CLI's onion layers).
+//! Both work in place with a detached 16-byte tag; `open` authenticates
+//! before it decrypts and leaves the buffer untouched on failure.
+//!
+//! Nonces must never repeat under one key. Callers derive per-session keys
+//! from the vault or X25519 and use a counter nonce.
+
+use super::chacha20;
+use super::ct::ct_eq;
+use super::poly1305::Poly1305;
+
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+/// RFC 8439 limit: 2^32 - 1 keystream blocks after the Poly1305 key block
+const MAX_PLAINTEXT: u64 = (u32::MAX as u64 - 1) * chacha20::BLOCK_LEN as u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AeadAlgo {
+    ChaCha20Poly1305 = 0x01,
+    Aes256Gcm = 0x02,
+}
+
+impl AeadAlgo {
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0x01 => Some(AeadAlgo::ChaCha20Poly1305),
+            0x02 => Some(AeadAlgo::Aes256Gcm),
+            _ => None,
+        }
+    }
+
+    /// False when the backing implementation was not compiled in
+    pub fn available(&self) -> bool {
+        match self {
+            AeadAlgo::ChaCha20Poly1305 => true,
+            AeadAlgo::Aes256Gcm => cfg!(feature = "nonos-crypto-aesgcm"),
+        }
+    }
+}
+
+/// Encrypt `buf` in place and return the tag
+pub fn seal(
+    algo: AeadAlgo,
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    buf: &mut [u8],
+) -> Result<[u8; TAG_LEN], &'static str> {
+    match algo {
+        AeadAlgo::ChaCha20Poly1305 => chacha20poly1305_seal(key, nonce, aad, buf),
+        AeadAlgo::Aes256Gcm => aes256gcm_seal(key, nonce, aad, buf),
+    }
+}
+
+/// Verify `tag` and decrypt `buf` in place
+pub fn open(
+    algo: AeadAlgo,
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    buf: &mut [u8],
+    tag: &[u8; TAG_LEN],
+) -> Result<(), &'static str> {
+    match algo {
+        AeadAlgo::ChaCha20Poly1305 => chacha20poly1305_open(key, nonce, aad, buf, tag),
+        AeadAlgo::Aes256Gcm => aes256gcm_open(key, nonce, aad, buf, tag),
+    }
+}
+
+/* -------------------- ChaCha20-Poly1305 -------------------- */
+
+fn poly1305_key(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN]) -> [u8; 32] {
+    let mut block = chacha20::block(key, 0, nonce);
+    let mut otk = [0u8; 32];
+    otk.copy_from_slice(&block[..32]);
+    super::hkdf::scrub(&mut block);
+    otk
+}
+
+fn chacha20poly1305_tag(otk: &[u8; 32], aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+    let mut mac = Poly1305::new(otk);
+    mac.update(aad);
+    mac.pad_to_block();
+    mac.update(ciphertext);
+    mac.pad_to_block();
+    mac.update(&(aad.len() as u64).to_le_bytes());
+    mac.update(&(ciphertext.len() as u64).to_le_bytes());
+    mac.finalize()
+}
+
+pub fn chacha20poly1305_seal(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    buf: &mut [u8],
+) -> Result<[u8; TAG_LEN], &'static str> {
+    if buf.len() as u64 > MAX_PLAINTEXT {
+        return Err("AEAD message too long");
+    }
+    let mut otk = poly1305_key(key, nonce);
+    chacha20::apply_keystream(key, 1, nonce, buf);
+    let tag = chacha20poly1305_tag(&otk, aad, buf);
+    super::hkdf::scrub(&mut otk);
+    Ok(tag)
+}
+
+pub fn chacha20poly1305_open(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    buf: &mut [u8],
+    tag: &[u8; TAG_LEN],
+) -> Result<(), &'static str> {
+    if buf.len() as u64 > MAX_PLAINTEXT {
+        return Err("AEAD message too long");
+    }
+    let mut otk = poly1305_key(key, nonce);
+    let expected = chacha20poly1305_tag(&otk, aad, buf);
+    super::hkdf::scrub(&mut otk);
+    if !ct_eq(&expected, tag) {
+        return Err("AEAD tag mismatch");
+    }
+    chacha20::apply_keystream(key, 1, nonce, buf);
+    Ok(())
+}
+
+/* -------------------- AES-256-GCM -------------------- */
+
+#[cfg(feature = "nonos-crypto-aesgcm")]
+fn aes256gcm_seal(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    buf: &mut [u8],
+) -> Result<[u8; TAG_LEN], &'static str> {
+    use aes_gcm::aead::AeadInPlace;
+    use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+
+    let cipher = Aes256Gcm::new(key.into());
+    cipher
+        .encrypt_in_place_detached(Nonce::from_slice(nonce), aad, buf)
+        .map(|tag| tag.into())
+        .map_err(|_| "AES-GCM encryption failed")
+}
+
+#[cfg(feature = "nonos-crypto-aesgcm")]
+fn aes256gcm_open(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    buf: &mut [u8],
+    tag: &[u8; TAG_LEN],
+) -> Result<(), &'static str> {
+    use aes_gcm::aead::AeadInPlace;
+    use aes_gcm::{Aes256Gcm, KeyInit, Nonce, Tag};
+
+    let cipher = Aes256Gcm::new(key.into());
+    cipher
+        .decrypt_in_place_detached(Nonce::from_slice(nonce), aad, buf, Tag::from_slice(tag))
+        .map_err(|_| "AEAD tag mismatch")
+}
+
+#[cfg(not(feature = "nonos-crypto-aesgcm"))]
+fn aes256gcm_seal(
+    _key: &[u8; KEY_LEN],
+    _nonce: &[u8; NONCE_LEN],
+    _aad: &[u8],
+    _buf: &mut [u8],
+) -> Result<[u8; TAG_LEN], &'static str> {
+    Err("AES-GCM not built (nonos-crypto-aesgcm)")
+}
+
+#[cfg(not(feature = "nonos-crypto-aesgcm"))]
+fn aes256gcm_open(
+    _key: &[u8; KEY_LEN],
+    _nonce: &[u8; NONCE_LEN],
+    _aad: &[u8],
+    _buf: &mut [u8],
+    _tag: &[u8; TAG_LEN],
+) -> Result<(), &'static str> {
+    Err("AES-GCM not built (nonos-crypto-aesgcm)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::String, vec::Vec};
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    const PLAINTEXT: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+    /// key, nonce, aad, ciphertext, tag
+    type Vector = ([u8; KEY_LEN], [u8; NONCE_LEN], Vec<u8>, Vec<u8>, [u8; TAG_LEN]);
+
+    // RFC 8439 §2.8.2
+    fn rfc8439_2_8_2() -> Vector {
+        let key: [u8; KEY_LEN] = core::array::from_fn(|i| 0x80 + i as u8);
+        let nonce: [u8; NONCE_LEN] = hex("070000004041424344454647").try_into().unwrap();
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+        let ct = hex(
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6\
+             3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36\
+             92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+             3ff4def08e4b7a9de576d26586cec64b6116",
+        );
+        let tag = hex("1ae10b594f09e26a7e902ecbd0600691").try_into().unwrap();
+        (key, nonce, aad, ct, tag)
+    }
+
+    #[test]
+    fn chacha20poly1305_seal_matches_rfc8439() {
+        let (key, nonce, aad, ct, tag) = rfc8439_2_8_2();
+        let mut buf = PLAINTEXT.to_vec();
+        let got = seal(AeadAlgo::ChaCha20Poly1305, &key, &nonce, &aad, &mut buf).unwrap();
+        assert_eq!(buf, ct);
+        assert_eq!(got, tag);
+    }
+
+    #[test]
+    fn chacha20poly1305_open_matches_rfc8439() {
+        let (key, nonce, aad, ct, tag) = rfc8439_2_8_2();
+        let mut buf = ct.clone();
+        open(AeadAlgo::ChaCha20Poly1305, &key, &nonce, &aad, &mut buf, &tag).unwrap();
+        assert_eq!(&buf[..], PLAINTEXT);
+    }
+
+    #[test]
+    fn chacha20poly1305_open_rejects_tampering() {
+        let (key, nonce, aad, ct, tag) = rfc8439_2_8_2();
+
+        let mut buf = ct.clone();
+        buf[0] ^= 1;
+        assert!(open(AeadAlgo::ChaCha20Poly1305, &key, &nonce, &aad, &mut buf, &tag).is_err());
+        // Untouched on failure
+        assert_eq!(buf[1..], ct[1..]);
+
+        let mut buf = ct.clone();
+        let mut bad_aad = aad.clone();
+        bad_aad[0] ^= 1;
+        assert!(open(AeadAlgo::ChaCha20Poly1305, &key, &nonce, &bad_aad, &mut buf, &tag).is_err());
+    }
+}
@@ -1,18 +1,35 @@
 //! NØNOS Cryptography Subsystem Entrypoint
 //!
-//! Initializes and wires all cryptographic components: entropy, vault, hash, sig, zk.
+//! Initializes and wires all cryptographic components: entropy, vault, hash, sig,
+//! aead, x25519, zk.
 //! Ensures that the ZeroState environment has a hardened root-of-trust and entropy pool.
 
 pub mod vault;
 pub mod hash;
+pub mod sha3;
 pub mod sig;
 pub mod entropy;
+pub mod chacha20;
+pub mod hkdf;
+pub mod signer;
+pub mod pq;
 pub mod zk;
+pub mod ct;
+pub mod poly1305;
+pub mod aead;
+pub mod x25519;
+pub mod selftest;
 
-/// Initializes all cryptographic systems during kernel boot
+/// Merkle trees and proofs shared with `nonosctl`
+#[path = "../../../shared/merkle.rs"]
+pub mod merkle;
+
+/// Initializes all cryptographic systems during kernel boot, then runs the
+/// power-on self-tests (a failure leaves the kernel in restricted mode)
 pub fn init_crypto() {
     entropy::seed_rng();
     vault::init_vault();
+    selftest::run_post();
     audit("[crypto] subsystem online");
 }
 
@@ -25,7 +42,7 @@ fn audit(msg: &str) {
 
 /// For external cryptographic health/status checks
 pub fn crypto_ready() -> bool {
-    vault::is_vault_ready() && entropy::rand_u64() != 0
+    vault::is_vault_ready() && entropy::is_healthy()
 }
 
 /// Exposed for testing hash function correctness
@@ -33,10 +50,15 @@ pub fn test_hash(input: &[u8]) -> [u8; 32] {
     hash::blake3_hash(input)
 }
 
-/// Utility: sign+verify signature roundtrip (dev only)
+/// Utility: sign with the kernel vault key and verify the result (dev only)
 pub fn test_signature_roundtrip() -> bool {
-    let message = b"test-signature";
-    let keypair = vault::get_test_keypair();
-    let sig = keypair.sign(message);
-    sig.verify(&keypair.public, message).is_ok()
+    let message = b"NONOS:test-signature";
+    let public = match signer::kernel_handle().ok().and_then(signer::public_key) {
+        Some(pk) => pk.0,
+        None => return false,
+    };
+    match signer::kernel_sign(message) {
+        Ok(signature) => sig::verify_ed25519_signature(&public, message, &signature),
+        Err(_) => false,
+    }
 }
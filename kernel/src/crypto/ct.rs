@@ -0,0 +1,27 @@
+//! NØNOS Constant-Time Helpers
+//!
+//! Tag, MAC and key comparisons go through here. With `nonos-consttime` the
+//! comparison is `subtle::ConstantTimeEq`; otherwise an accumulate-then-test
+//! loop hidden from the optimizer with `black_box`.
+
+/// Constant-time equality of two byte strings (length is not secret)
+#[cfg(feature = "nonos-consttime")]
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    use subtle::ConstantTimeEq;
+    a.len() == b.len() && bool::from(a.ct_eq(b))
+}
+
+#[cfg(not(feature = "nonos-consttime"))]
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    core::hint::black_box(diff) == 0
+}
+
+/// True if every byte is zero, without early exit
+pub fn ct_is_zero(a: &[u8]) -> bool {
+    let acc = a.iter().fold(0u8, |acc, x| acc | x);
+    core::hint::black_box(acc) == 0
+}
@@ -1,80 +1,378 @@
-//! NØNOS Entropy & RNG Subsystem – Production-Grade
+//! NØNOS Entropy & RNG Subsystem – ChaCha20 DRBG
 //!
-//! Provides cryptographically secure entropy sources for kernel boot, module identity,
-//! protocol salts, and ZeroState randomness. Hybrid design with hardware fallback,
-//! CPU timestamp seeding, and deterministic failover.
+//! Provides cryptographically secure randomness for kernel boot, module identity,
+//! protocol salts, exec ids and key material.
+//!
+//! Design:
+//! - Master DRBG seeded from the bootloader handoff entropy plus RDSEED
+//!   (RDRAND, then TSC jitter as fallbacks)
+//! - Fast key erasure: every request rekeys the generator from its own
+//!   keystream, so a state compromise cannot recover earlier outputs
+//! - Periodic reseeding by bytes generated and by request count
+//! - One DRBG per CPU, forked from the master with the CPU id as
+//!   personalization; a global fork generation forces every CPU to re-fork
+//! - Continuous health tests on hardware samples (repetition count,
+//!   adaptive proportion) and on DRBG output (stuck block)
 
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use spin::Mutex;
 
-static ENTROPY_SEED: AtomicU64 = AtomicU64::new(0);
-static RNG: Mutex<ChaoticRng> = Mutex::new(ChaoticRng::new(0));
+use crate::crypto::chacha20;
+
+/// Reseed the master after this many output bytes
+const RESEED_INTERVAL_BYTES: u64 = 1 << 20;
+/// ...or after this many requests, whichever comes first
+const RESEED_INTERVAL_REQUESTS: u64 = 1 << 14;
+/// Hardware samples mixed into each reseed
+const RESEED_SAMPLES: usize = 8;
+/// Repetition count test cutoff (identical consecutive 64-bit samples)
+const RCT_CUTOFF: u32 = 3;
+/// Adaptive proportion test: window and cutoff for a repeated low byte
+const APT_WINDOW: u32 = 64;
+const APT_CUTOFF: u32 = 16;
+
+const MAX_CPUS: usize = 64;
 
-/// CSPRNG Engine (XOR-Shift-Rotate Scheme)
-pub struct ChaoticRng {
-    state: u64,
-    counter: u64,
+const DS_SEED: &str = "NONOS:DRBG:SEED:v1";
+const DS_RESEED: &str = "NONOS:DRBG:RESEED:v1";
+const DS_FORK: &str = "NONOS:DRBG:FORK:v1";
+
+/// ChaCha20-based deterministic random bit generator with fast key erasure
+pub struct ChaChaDrbg {
+    key: [u8; chacha20::KEY_LEN],
+    nonce: u64,
+    bytes_since_reseed: u64,
+    requests_since_reseed: u64,
+    last_block: [u8; 16],
+    generation: u64,
 }
 
-impl ChaoticRng {
-    pub const fn new(seed: u64) -> Self {
-        Self { state: seed, counter: 0 }
+impl ChaChaDrbg {
+    pub const fn unseeded() -> Self {
+        Self {
+            key: [0; chacha20::KEY_LEN],
+            nonce: 0,
+            bytes_since_reseed: 0,
+            requests_since_reseed: 0,
+            last_block: [0; 16],
+            generation: 0,
+        }
     }
 
-    pub fn reseed(&mut self, seed: u64) {
-        self.state ^= seed.rotate_left(11) ^ seed.rotate_right(5);
-        self.counter = 0;
+    /// Instantiate from seed material and a personalization string
+    pub fn instantiate(seed: &[u8], personalization: &[u8]) -> Self {
+        let mut h = blake3::Hasher::new_derive_key(DS_SEED);
+        h.update(seed);
+        h.update(personalization);
+        let mut drbg = Self::unseeded();
+        drbg.key = *h.finalize().as_bytes();
+        drbg
     }
 
-    pub fn next(&mut self) -> u64 {
-        self.counter += 1;
-        self.state ^= self.state << 13;
-        self.state ^= self.state >> 7;
-        self.state ^= self.state << 17;
-        self.state.wrapping_add(self.counter.rotate_left(19))
+    /// Mix fresh seed material into the key
+    pub fn reseed(&mut self, material: &[u8]) {
+        let mut h = blake3::Hasher::new_derive_key(DS_RESEED);
+        h.update(&self.key);
+        h.update(material);
+        self.key = *h.finalize().as_bytes();
+        self.nonce = 0;
+        self.bytes_since_reseed = 0;
+        self.requests_since_reseed = 0;
     }
 
-    pub fn next_byte(&mut self) -> u8 {
-        (self.next() & 0xFF) as u8
+    pub fn needs_reseed(&self) -> bool {
+        self.bytes_since_reseed >= RESEED_INTERVAL_BYTES
+            || self.requests_since_reseed >= RESEED_INTERVAL_REQUESTS
     }
 
-    pub fn next_u32(&mut self) -> u32 {
-        (self.next() & 0xFFFF_FFFF) as u32
+    /// Fill `out`, then rekey from the keystream so earlier output cannot be
+    /// reconstructed from the state left behind. Returns false if the output
+    /// health test tripped.
+    pub fn generate(&mut self, out: &mut [u8]) -> bool {
+        let nonce = self.next_nonce();
+        out.iter_mut().for_each(|b| *b = 0);
+        chacha20::apply_keystream(&self.key, 1, &nonce, out);
+
+        // Block 0 of the same stream becomes the next key (never emitted)
+        let mut next = chacha20::block(&self.key, 0, &nonce);
+        let healthy = next[32..48] != self.last_block;
+        self.last_block.copy_from_slice(&next[32..48]);
+        self.key.copy_from_slice(&next[..32]);
+        next.iter_mut().for_each(|b| unsafe { core::ptr::write_volatile(b, 0) });
+
+        self.bytes_since_reseed += out.len() as u64;
+        self.requests_since_reseed += 1;
+        healthy
     }
 
-    pub fn next_bytes(&mut self, out: &mut [u8]) {
-        for byte in out.iter_mut() {
-            *byte = self.next_byte();
+    /// Derive an independent child generator (per-CPU instances)
+    pub fn fork(&mut self, personalization: &[u8]) -> Self {
+        let mut seed = [0u8; 32];
+        if !self.generate(&mut seed) {
+            HEALTHY.store(false, Ordering::SeqCst);
         }
+        let mut h = blake3::Hasher::new_derive_key(DS_FORK);
+        h.update(&seed);
+        h.update(personalization);
+        seed.iter_mut().for_each(|b| unsafe { core::ptr::write_volatile(b, 0) });
+        let mut child = Self::unseeded();
+        child.key = *h.finalize().as_bytes();
+        child
+    }
+
+    fn next_nonce(&mut self) -> [u8; chacha20::NONCE_LEN] {
+        let mut n = [0u8; chacha20::NONCE_LEN];
+        n[..8].copy_from_slice(&self.nonce.to_le_bytes());
+        self.nonce = self.nonce.wrapping_add(1);
+        n
+    }
+}
+
+impl Drop for ChaChaDrbg {
+    fn drop(&mut self) {
+        self.key.iter_mut().for_each(|b| unsafe { core::ptr::write_volatile(b, 0) });
     }
 }
 
-/// Seeds the RNG using timestamp counter or fallback method
+/// Continuous health state for the hardware noise source
+struct NoiseHealth {
+    last: u64,
+    repeats: u32,
+    apt_ref: u8,
+    apt_count: u32,
+    apt_seen: u32,
+}
+
+impl NoiseHealth {
+    const fn new() -> Self {
+        Self { last: 0, repeats: 0, apt_ref: 0, apt_count: 0, apt_seen: 0 }
+    }
+
+    /// Returns false if the sample trips a test
+    fn check(&mut self, sample: u64) -> bool {
+        let mut ok = true;
+
+        if sample == self.last {
+            self.repeats += 1;
+            if self.repeats >= RCT_CUTOFF {
+                ok = false;
+            }
+        } else {
+            self.repeats = 0;
+            self.last = sample;
+        }
+
+        let low = sample as u8;
+        if self.apt_seen == 0 {
+            self.apt_ref = low;
+            self.apt_count = 0;
+        }
+        if low == self.apt_ref {
+            self.apt_count += 1;
+        }
+        self.apt_seen += 1;
+        if self.apt_count >= APT_CUTOFF {
+            ok = false;
+        }
+        if self.apt_seen >= APT_WINDOW {
+            self.apt_seen = 0;
+        }
+
+        ok
+    }
+}
+
+static MASTER: Mutex<ChaChaDrbg> = Mutex::new(ChaChaDrbg::unseeded());
+static PER_CPU: [Mutex<Option<ChaChaDrbg>>; MAX_CPUS] = {
+    const NONE: Mutex<Option<ChaChaDrbg>> = Mutex::new(None);
+    [NONE; MAX_CPUS]
+};
+static NOISE: Mutex<NoiseHealth> = Mutex::new(NoiseHealth::new());
+static SEEDED: AtomicBool = AtomicBool::new(false);
+static HEALTHY: AtomicBool = AtomicBool::new(true);
+static FORK_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Seed the master DRBG from the bootloader handoff entropy plus hardware noise.
+/// Call as early as possible; `seed_rng` covers boots without a handoff block.
+pub fn seed_from_boot(handoff_entropy: &[u8]) {
+    let mut material = [0u8; 64 + RESEED_SAMPLES * 8];
+    material[..handoff_entropy.len().min(64)].copy_from_slice(&handoff_entropy[..handoff_entropy.len().min(64)]);
+    gather_hw(&mut material[64..]);
+
+    *MASTER.lock() = ChaChaDrbg::instantiate(&material, b"master");
+    scrub(&mut material);
+
+    SEEDED.store(true, Ordering::SeqCst);
+    FORK_GENERATION.fetch_add(1, Ordering::SeqCst);
+    audit("[entropy] DRBG seeded from boot handoff + RDSEED");
+}
+
+/// Seed from hardware noise only (no handoff entropy available)
 pub fn seed_rng() {
-    let entropy = read_tsc() ^ read_fallback_timer();
-    ENTROPY_SEED.store(entropy, Ordering::SeqCst);
-    RNG.lock().reseed(entropy);
-    audit("[entropy] RNG seeded from TSC+fallback");
+    if SEEDED.load(Ordering::SeqCst) {
+        reseed();
+        return;
+    }
+    let mut material = [0u8; RESEED_SAMPLES * 8 * 2];
+    gather_hw(&mut material);
+    *MASTER.lock() = ChaChaDrbg::instantiate(&material, b"master:no-handoff");
+    scrub(&mut material);
+
+    SEEDED.store(true, Ordering::SeqCst);
+    FORK_GENERATION.fetch_add(1, Ordering::SeqCst);
+    audit("[entropy] DRBG seeded from RDSEED/RDRAND (no handoff entropy)");
+}
+
+/// Mix fresh hardware noise into the master and force every CPU to re-fork
+pub fn reseed() {
+    let mut material = [0u8; RESEED_SAMPLES * 8];
+    gather_hw(&mut material);
+    MASTER.lock().reseed(&material);
+    scrub(&mut material);
+    FORK_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// True once seeded and no health test has failed
+pub fn is_healthy() -> bool {
+    SEEDED.load(Ordering::SeqCst) && HEALTHY.load(Ordering::SeqCst)
+}
+
+/// Fill buffer with random data (backtracking resistant)
+pub fn fill_bytes(buffer: &mut [u8]) {
+    if !SEEDED.load(Ordering::Acquire) {
+        seed_rng();
+    }
+
+    let cpu = current_cpu() % MAX_CPUS;
+    let generation = FORK_GENERATION.load(Ordering::Acquire);
+
+    let mut slot = PER_CPU[cpu].lock();
+    let stale = slot.as_ref().map_or(true, |d| d.generation != generation || d.needs_reseed());
+    if stale {
+        let mut master = MASTER.lock();
+        if master.needs_reseed() {
+            drop(master);
+            reseed();
+            master = MASTER.lock();
+        }
+        let mut child = master.fork(&(cpu as u64).to_le_bytes());
+        child.generation = FORK_GENERATION.load(Ordering::Acquire);
+        *slot = Some(child);
+    }
+
+    if let Some(drbg) = slot.as_mut() {
+        if !drbg.generate(buffer) {
+            HEALTHY.store(false, Ordering::SeqCst);
+            audit("[entropy] DRBG output health test FAILED");
+        }
+    }
 }
 
 /// Return secure 64-bit random number
 pub fn rand_u64() -> u64 {
-    RNG.lock().next()
+    let mut b = [0u8; 8];
+    fill_bytes(&mut b);
+    u64::from_le_bytes(b)
 }
 
 /// Return secure 8-bit random value
 pub fn rand_byte() -> u8 {
-    RNG.lock().next_byte()
+    let mut b = [0u8; 1];
+    fill_bytes(&mut b);
+    b[0]
 }
 
 /// Return secure 32-bit random number
 pub fn rand_u32() -> u32 {
-    RNG.lock().next_u32()
+    let mut b = [0u8; 4];
+    fill_bytes(&mut b);
+    u32::from_le_bytes(b)
 }
 
-/// Fill buffer with random data
-pub fn fill_bytes(buffer: &mut [u8]) {
-    RNG.lock().next_bytes(buffer);
+// ===== Private Helpers =====
+
+/// Collect hardware samples into `out` (8 bytes each), health-testing each one
+fn gather_hw(out: &mut [u8]) {
+    let mut noise = NOISE.lock();
+    for chunk in out.chunks_mut(8) {
+        let sample = rdseed64().or_else(rdrand64).unwrap_or_else(|| read_tsc() ^ read_fallback_timer());
+        if !noise.check(sample) {
+            HEALTHY.store(false, Ordering::SeqCst);
+            audit("[entropy] noise source health test FAILED");
+        }
+        let bytes = sample.to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+/// CPUID feature probe, done once: `HW_PROBED | HW_RDRAND | HW_RDSEED`
+static HW_FEATURES: AtomicU8 = AtomicU8::new(0);
+const HW_PROBED: u8 = 1 << 0;
+const HW_RDRAND: u8 = 1 << 1;
+const HW_RDSEED: u8 = 1 << 2;
+
+fn hw_features() -> u8 {
+    let cached = HW_FEATURES.load(Ordering::Relaxed);
+    if cached & HW_PROBED != 0 {
+        return cached;
+    }
+    let mut f = HW_PROBED;
+    if cpuid(1, 0).2 & (1 << 30) != 0 { f |= HW_RDRAND; }
+    if cpuid(0, 0).0 >= 7 && cpuid(7, 0).1 & (1 << 18) != 0 { f |= HW_RDSEED; }
+    HW_FEATURES.store(f, Ordering::Relaxed);
+    f
+}
+
+#[inline] fn has_rdrand() -> bool { hw_features() & HW_RDRAND != 0 }
+#[inline] fn has_rdseed() -> bool { hw_features() & HW_RDSEED != 0 }
+
+fn cpuid(leaf: u32, sub: u32) -> (u32, u32, u32, u32) {
+    let r = unsafe { core::arch::x86_64::__cpuid_count(leaf, sub) };
+    (r.eax, r.ebx, r.ecx, r.edx)
+}
+
+fn rdseed64() -> Option<u64> {
+    if !has_rdseed() {
+        return None;
+    }
+    for _ in 0..16 {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!("rdseed {v}", "setc {ok}", v = out(reg) value, ok = out(reg_byte) ok, options(nomem, nostack));
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn rdrand64() -> Option<u64> {
+    if !has_rdrand() {
+        return None;
+    }
+    for _ in 0..16 {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!("rdrand {v}", "setc {ok}", v = out(reg) value, ok = out(reg_byte) ok, options(nomem, nostack));
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// DRBG slot of the running CPU: the local APIC id once the LAPIC is up
+/// (a register read, no CPUID exit); before that only the BSP runs
+fn current_cpu() -> usize {
+    if crate::arch::x86_64::interrupt::apic::ready() {
+        crate::arch::x86_64::interrupt::apic::id() as usize
+    } else {
+        0
+    }
 }
 
 /// Hardware entropy fallback: using a different instruction as backup
@@ -96,6 +394,10 @@ fn read_tsc() -> u64 {
     ((high as u64) << 32) | (low as u64)
 }
 
+fn scrub(buf: &mut [u8]) {
+    buf.iter_mut().for_each(|b| unsafe { core::ptr::write_volatile(b, 0) });
+}
+
 /// Emit entropy log for audit trails
 fn audit(msg: &str) {
     if let Some(logger) = crate::log::logger::try_get_logger() {
@@ -3,10 +3,22 @@
 //! Supports BLAKE3 hashing for module integrity, state snapshots,
 //! memory fingerprints, identity derivation, and secure IPC.
 //! All functions are deterministic and memory-safe.
+//!
+//! Hash agility lives in `digest` (shared with the bootloader and `nonosctl`):
+//! every exported commitment is an algorithm-tagged `Digest`, computed under a
+//! `digest::domain` tag. The boot capsule is identified by the bootloader's
+//! `capsule_identity`, which the kernel only carries (`handoff::capsule_digest`).
 
 use blake3::Hasher;
 use core::fmt::{self, Write};
 
+use crate::crypto::sha3::{Keccak256, Sha3_256};
+
+#[path = "../../../shared/digest.rs"]
+pub mod digest;
+
+pub use digest::{capsule_identity, domain, Digest, HashAlgo};
+
 /// Computes a 32-byte BLAKE3 hash of a given byte slice
 pub fn blake3_hash(data: &[u8]) -> [u8; 32] {
     let mut hasher = Hasher::new();
@@ -15,9 +27,48 @@ pub fn blake3_hash(data: &[u8]) -> [u8; 32] {
     *hash.as_bytes()
 }
 
+/// SHA3-256 of a byte slice (untagged)
+pub fn sha3_256(data: &[u8]) -> [u8; 32] {
+    let mut h = Sha3_256::new();
+    h.update(data);
+    h.finalize_bytes()
+}
+
+/// Keccak-256 of a byte slice (untagged)
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut h = Keccak256::new();
+    h.update(data);
+    h.finalize_bytes()
+}
+
+/// Domain-separated, algorithm-tagged digest.
+/// BLAKE3 uses `domain` as its derive-key context; the SHA3 family absorbs
+/// `len(domain) || domain` first. SHA-256 is not built into the kernel.
+pub fn hash_tagged(algo: HashAlgo, domain: &str, data: &[u8]) -> Option<Digest> {
+    let bytes = match algo {
+        HashAlgo::Blake3 => return Some(digest::blake3_tagged(domain, data)),
+        HashAlgo::Sha3_256 => {
+            let mut h = Sha3_256::new();
+            h.update(&[domain.len() as u8]);
+            h.update(domain.as_bytes());
+            h.update(data);
+            h.finalize_bytes()
+        }
+        HashAlgo::Keccak256 => {
+            let mut h = Keccak256::new();
+            h.update(&[domain.len() as u8]);
+            h.update(domain.as_bytes());
+            h.update(data);
+            h.finalize_bytes()
+        }
+        HashAlgo::Sha256 => return None,
+    };
+    Some(Digest::new(algo, bytes))
+}
+
 /// Performs constant-time comparison of two hash outputs
 pub fn verify_hash(a: &[u8; 32], b: &[u8; 32]) -> bool {
-    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+    super::ct::ct_eq(a, b)
 }
 
 /// Hashes a UTF-8 string into a fixed 32-byte digest
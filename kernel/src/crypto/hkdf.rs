@@ -0,0 +1,177 @@
+//! NØNOS HKDF (RFC 5869) over HMAC-SHA3-256
+//!
+//! The one key-derivation primitive for the kernel. `crypto::vault` builds its
+//! key tree on it; other subsystems may call it directly for labeled subkeys.
+
+use sha3::{Digest, Sha3_256};
+
+/// SHA3-256 rate in bytes (HMAC block size)
+const BLOCK: usize = 136;
+pub const HASH_LEN: usize = 32;
+/// RFC 5869 limit: 255 blocks of output
+pub const MAX_OUTPUT: usize = 255 * HASH_LEN;
+/// `info` may be passed as up to this many slices (concatenated)
+pub const MAX_INFO_PARTS: usize = 8;
+
+/// HMAC-SHA3-256 over the concatenation of `parts`
+pub fn hmac_sha3(key: &[u8], parts: &[&[u8]]) -> [u8; HASH_LEN] {
+    hmac::<Sha3_256, BLOCK>(key, parts)
+}
+
+/// HMAC over any 32-byte digest with block size `B`
+fn hmac<D: Digest, const B: usize>(key: &[u8], parts: &[&[u8]]) -> [u8; HASH_LEN] {
+    let mut k = [0u8; B];
+    if key.len() > B {
+        k[..HASH_LEN].copy_from_slice(&D::digest(key));
+    } else {
+        k[..key.len()].copy_from_slice(key);
+    }
+
+    let mut ipad = [0x36u8; B];
+    let mut opad = [0x5cu8; B];
+    for i in 0..B {
+        ipad[i] ^= k[i];
+        opad[i] ^= k[i];
+    }
+
+    let mut inner = D::new();
+    inner.update(&ipad[..]);
+    for part in parts {
+        inner.update(part);
+    }
+    let inner = inner.finalize();
+
+    let mut outer = D::new();
+    outer.update(&opad[..]);
+    outer.update(&inner);
+
+    scrub(&mut k);
+    scrub(&mut ipad);
+    scrub(&mut opad);
+
+    let mut out = [0u8; HASH_LEN];
+    out.copy_from_slice(&outer.finalize());
+    out
+}
+
+/// HKDF-Extract: PRK = HMAC(salt, IKM)
+pub fn extract(salt: &[u8], ikm: &[u8]) -> [u8; HASH_LEN] {
+    hmac_sha3(salt, &[ikm])
+}
+
+/// HKDF-Expand: fill `out` from `prk` under `info`
+pub fn expand(prk: &[u8; HASH_LEN], info: &[&[u8]], out: &mut [u8]) -> Result<(), &'static str> {
+    expand_with(hmac_sha3, prk, info, out)
+}
+
+fn expand_with(
+    prf: fn(&[u8], &[&[u8]]) -> [u8; HASH_LEN],
+    prk: &[u8; HASH_LEN],
+    info: &[&[u8]],
+    out: &mut [u8],
+) -> Result<(), &'static str> {
+    if out.len() > MAX_OUTPUT {
+        return Err("HKDF output too long");
+    }
+    if info.len() > MAX_INFO_PARTS {
+        return Err("HKDF info has too many parts");
+    }
+
+    let mut t = [0u8; HASH_LEN];
+    let mut t_len = 0;
+    for (i, chunk) in out.chunks_mut(HASH_LEN).enumerate() {
+        let counter = [(i + 1) as u8];
+        let mut parts: [&[u8]; MAX_INFO_PARTS + 2] = [&[]; MAX_INFO_PARTS + 2];
+        parts[0] = &t[..t_len];
+        let n = info.len();
+        parts[1..1 + n].copy_from_slice(info);
+        parts[1 + n] = &counter;
+        t = prf(prk, &parts[..2 + n]);
+        t_len = HASH_LEN;
+        chunk.copy_from_slice(&t[..chunk.len()]);
+    }
+    scrub(&mut t);
+    Ok(())
+}
+
+/// Extract-then-expand into a fixed 32-byte key
+pub fn derive(salt: &[u8], ikm: &[u8], info: &[&[u8]]) -> [u8; HASH_LEN] {
+    let mut prk = extract(salt, ikm);
+    let mut out = [0u8; HASH_LEN];
+    let _ = expand(&prk, info, &mut out);
+    scrub(&mut prk);
+    out
+}
+
+pub(crate) fn scrub(buf: &mut [u8]) {
+    buf.iter_mut().for_each(|b| unsafe { core::ptr::write_volatile(b, 0) });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, string::String, vec, vec::Vec};
+
+    // The construction is hash-agnostic; RFC 5869 publishes SHA-256 vectors,
+    // so run the same extract/expand code over HMAC-SHA-256.
+    fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; HASH_LEN] {
+        hmac::<sha2::Sha256, 64>(key, parts)
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn rfc5869(ikm: &str, salt: &str, info: &str, prk: &str, okm: &str) {
+        let (ikm, salt, info, okm) = (hex(ikm), hex(salt), hex(info), hex(okm));
+        let got_prk = hmac_sha256(&salt, &[&ikm]);
+        assert_eq!(&got_prk[..], &hex(prk)[..]);
+        let mut out = vec![0u8; okm.len()];
+        expand_with(hmac_sha256, &got_prk, &[&info], &mut out).unwrap();
+        assert_eq!(out, okm);
+    }
+
+    #[test]
+    fn rfc5869_case_1() {
+        rfc5869(
+            "0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b",
+            "000102030405060708090a0b0c",
+            "f0f1f2f3f4f5f6f7f8f9",
+            "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5",
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865",
+        );
+    }
+
+    #[test]
+    fn rfc5869_case_2_long_inputs() {
+        let ikm: String = (0x00..=0x4fu8).map(|b| format!("{:02x}", b)).collect();
+        let salt: String = (0x60..=0xafu8).map(|b| format!("{:02x}", b)).collect();
+        let info: String = (0xb0..=0xffu8).map(|b| format!("{:02x}", b)).collect();
+        rfc5869(
+            &ikm,
+            &salt,
+            &info,
+            "06a6b88c5853361a06104c9ceb35b45cef760014904671014a193f40c15fc244",
+            "b11e398dc80327a1c8e7f78c596a49344f012eda2d4efad8a050cc4c19afa97c\
+             59045a99cac7827271cb41c65e590e09da3275600c2f09b8367793a9aca3db71\
+             cc30c58179ec3e87c14c01d5c1f3434f1d87",
+        );
+    }
+
+    #[test]
+    fn rfc5869_case_3_empty_salt_and_info() {
+        rfc5869(
+            "0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b",
+            "",
+            "",
+            "19ef24a32c717b167f33a91d6f648bdf96596776afdb6377ac434c1c293ccb04",
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8",
+        );
+    }
+
+    #[test]
+    fn expand_rejects_oversized_output() {
+        let mut out = vec![0u8; MAX_OUTPUT + 1];
+        assert!(expand(&[0u8; HASH_LEN], &[], &mut out).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! NØNOS Poly1305 One-Time Authenticator (RFC 8439)
+//!
+//! Portable 26-bit-limb implementation (poly1305-donna layout), constant time
+//! in the message and key. Used by `aead` for ChaCha20-Poly1305; a key must
+//! never authenticate more than one message.
+
+pub const KEY_LEN: usize = 32;
+pub const TAG_LEN: usize = 16;
+const BLOCK_LEN: usize = 16;
+const MASK26: u32 = 0x3ff_ffff;
+
+pub struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    pad: [u32; 4],
+    buf: [u8; BLOCK_LEN],
+    buf_len: usize,
+}
+
+#[inline(always)]
+fn le32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+impl Poly1305 {
+    pub fn new(key: &[u8; KEY_LEN]) -> Self {
+        // r is clamped per RFC 8439 §2.5
+        let r = [
+            le32(&key[0..]) & 0x3ff_ffff,
+            (le32(&key[3..]) >> 2) & 0x3ff_ff03,
+            (le32(&key[6..]) >> 4) & 0x3ff_c0ff,
+            (le32(&key[9..]) >> 6) & 0x3f0_3fff,
+            (le32(&key[12..]) >> 8) & 0x00f_ffff,
+        ];
+        let pad = [le32(&key[16..]), le32(&key[20..]), le32(&key[24..]), le32(&key[28..])];
+        Self { r, h: [0; 5], pad, buf: [0; BLOCK_LEN], buf_len: 0 }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        if self.buf_len > 0 {
+            let take = (BLOCK_LEN - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + take].copy_from_slice(&data[..take]);
+            self.buf_len += take;
+            data = &data[take..];
+            if self.buf_len < BLOCK_LEN {
+                return;
+            }
+            let block = self.buf;
+            self.block(&block, 1 << 24);
+            self.buf_len = 0;
+        }
+        while data.len() >= BLOCK_LEN {
+            let mut block = [0u8; BLOCK_LEN];
+            block.copy_from_slice(&data[..BLOCK_LEN]);
+            self.block(&block, 1 << 24);
+            data = &data[BLOCK_LEN..];
+        }
+        self.buf[..data.len()].copy_from_slice(data);
+        self.buf_len = data.len();
+    }
+
+    /// Absorb zero bytes up to the next 16-byte boundary (AEAD padding)
+    pub fn pad_to_block(&mut self) {
+        if self.buf_len > 0 {
+            self.update(&[0u8; BLOCK_LEN][..BLOCK_LEN - self.buf_len]);
+        }
+    }
+
+    fn block(&mut self, m: &[u8; BLOCK_LEN], hibit: u32) {
+        let [r0, r1, r2, r3, r4] = self.r;
+        let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+        let h = &mut self.h;
+
+        h[0] += le32(&m[0..]) & MASK26;
+        h[1] += (le32(&m[3..]) >> 2) & MASK26;
+        h[2] += (le32(&m[6..]) >> 4) & MASK26;
+        h[3] += (le32(&m[9..]) >> 6) & MASK26;
+        h[4] += (le32(&m[12..]) >> 8) | hibit;
+
+        let mul = |a: u32, b: u32| a as u64 * b as u64;
+        let d0 = mul(h[0], r0) + mul(h[1], s4) + mul(h[2], s3) + mul(h[3], s2) + mul(h[4], s1);
+        let mut d1 = mul(h[0], r1) + mul(h[1], r0) + mul(h[2], s4) + mul(h[3], s3) + mul(h[4], s2);
+        let mut d2 = mul(h[0], r2) + mul(h[1], r1) + mul(h[2], r0) + mul(h[3], s4) + mul(h[4], s3);
+        let mut d3 = mul(h[0], r3) + mul(h[1], r2) + mul(h[2], r1) + mul(h[3], r0) + mul(h[4], s4);
+        let mut d4 = mul(h[0], r4) + mul(h[1], r3) + mul(h[2], r2) + mul(h[3], r1) + mul(h[4], r0);
+
+        h[0] = d0 as u32 & MASK26;
+        d1 += d0 >> 26;
+        h[1] = d1 as u32 & MASK26;
+        d2 += d1 >> 26;
+        h[2] = d2 as u32 & MASK26;
+        d3 += d2 >> 26;
+        h[3] = d3 as u32 & MASK26;
+        d4 += d3 >> 26;
+        h[4] = d4 as u32 & MASK26;
+        h[0] += (d4 >> 26) as u32 * 5;
+        h[1] += h[0] >> 26;
+        h[0] &= MASK26;
+    }
+
+    pub fn finalize(mut self) -> [u8; TAG_LEN] {
+        if self.buf_len > 0 {
+            let mut block = [0u8; BLOCK_LEN];
+            block[..self.buf_len].copy_from_slice(&self.buf[..self.buf_len]);
+            block[self.buf_len] = 1;
+            self.block(&block, 0);
+        }
+
+        let h = &mut self.h;
+        // full carry
+        let mut c;
+        c = h[1] >> 26; h[1] &= MASK26; h[2] += c;
+        c = h[2] >> 26; h[2] &= MASK26; h[3] += c;
+        c = h[3] >> 26; h[3] &= MASK26; h[4] += c;
+        c = h[4] >> 26; h[4] &= MASK26; h[0] += c * 5;
+        c = h[0] >> 26; h[0] &= MASK26; h[1] += c;
+
+        // g = h + 5 - 2^130; select g if it did not underflow
+        let mut g = [0u32; 5];
+        g[0] = h[0].wrapping_add(5); c = g[0] >> 26; g[0] &= MASK26;
+        g[1] = h[1].wrapping_add(c); c = g[1] >> 26; g[1] &= MASK26;
+        g[2] = h[2].wrapping_add(c); c = g[2] >> 26; g[2] &= MASK26;
+        g[3] = h[3].wrapping_add(c); c = g[3] >> 26; g[3] &= MASK26;
+        g[4] = h[4].wrapping_add(c).wrapping_sub(1 << 26);
+
+        let select = (g[4] >> 31).wrapping_sub(1);
+        for i in 0..5 {
+            h[i] = (h[i] & !select) | (g[i] & select);
+        }
+
+        let w = [
+            h[0] | (h[1] << 26),
+            (h[1] >> 6) | (h[2] << 20),
+            (h[2] >> 12) | (h[3] << 14),
+            (h[3] >> 18) | (h[4] << 8),
+        ];
+
+        let mut tag = [0u8; TAG_LEN];
+        let mut f = 0u64;
+        for i in 0..4 {
+            f = w[i] as u64 + self.pad[i] as u64 + (f >> 32);
+            tag[4 * i..4 * i + 4].copy_from_slice(&(f as u32).to_le_bytes());
+        }
+        tag
+    }
+}
+
+impl Drop for Poly1305 {
+    fn drop(&mut self) {
+        self.r.iter_mut().chain(self.h.iter_mut()).chain(self.pad.iter_mut())
+            .for_each(|w| unsafe { core::ptr::write_volatile(w, 0) });
+        self.buf.iter_mut().for_each(|b| unsafe { core::ptr::write_volatile(b, 0) });
+    }
+}
+
+/// One-shot MAC
+pub fn mac(key: &[u8; KEY_LEN], data: &[u8]) -> [u8; TAG_LEN] {
+    let mut p = Poly1305::new(key);
+    p.update(data);
+    p.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::String, vec::Vec};
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn key(s: &str) -> [u8; KEY_LEN] {
+        hex(s).try_into().unwrap()
+    }
+
+    #[test]
+    fn rfc8439_2_5_2() {
+        let k = key("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b");
+        let tag = mac(&k, b"Cryptographic Forum Research Group");
+        assert_eq!(&tag[..], &hex("a8061dc1305136c6c22b8baf0c0127a9")[..]);
+    }
+
+    #[test]
+    fn rfc8439_a3_zero_key() {
+        assert_eq!(mac(&[0u8; KEY_LEN], &[0u8; 64]), [0u8; TAG_LEN]);
+    }
+
+    #[test]
+    fn rfc8439_a3_vector_2() {
+        let k = key("0000000000000000000000000000000036e5f6b5c5e06070f0efca96227a863e");
+        let msg = b"Any submission to the IETF intended by the Contributor for publication as all or part of an IETF Internet-Draft or RFC and any statement made within the context of an IETF activity is considered an \"IETF Contribution\". Such statements include oral statements in IETF sessions, as well as written and electronic communications made at any time or place, which are addressed to";
+        assert_eq!(&mac(&k, msg)[..], &hex("36e5f6b5c5e06070f0efca96227a863e")[..]);
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let k = key("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b");
+        let msg = b"Cryptographic Forum Research Group";
+        let mut p = Poly1305::new(&k);
+        for chunk in msg.chunks(5) {
+            p.update(chunk);
+        }
+        assert_eq!(p.finalize(), mac(&k, msg));
+    }
+}
@@ -0,0 +1,54 @@
+//! NØNOS Post-Quantum Signature Verification
+//!
+//! ML-DSA-65 (FIPS 204, Dilithium security level 3) verification for
+//! long-lived manifest and capsule signatures. Built only with the
+//! `nonos-crypto-pq` feature; without it every PQ signature is rejected, so a
+//! policy that requires one fails closed.
+
+/// Encoded ML-DSA-65 verifying key length
+pub const MLDSA65_PUBKEY_LEN: usize = 1952;
+/// Encoded ML-DSA-65 signature length
+pub const MLDSA65_SIG_LEN: usize = 3309;
+
+/// True when the kernel was built with a post-quantum verifier
+pub const fn pq_available() -> bool {
+    cfg!(feature = "nonos-crypto-pq")
+}
+
+/// Verify an ML-DSA-65 signature (empty context string)
+#[cfg(feature = "nonos-crypto-pq")]
+pub fn verify_mldsa65(pubkey: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    use ml_dsa::signature::Verifier;
+    use ml_dsa::{EncodedSignature, EncodedVerifyingKey, MlDsa65, Signature, VerifyingKey};
+
+    if pubkey.len() != MLDSA65_PUBKEY_LEN || signature.len() != MLDSA65_SIG_LEN {
+        return false;
+    }
+
+    let key = match EncodedVerifyingKey::<MlDsa65>::try_from(pubkey) {
+        Ok(encoded) => VerifyingKey::<MlDsa65>::decode(&encoded),
+        Err(_) => return false,
+    };
+    let sig = match EncodedSignature::<MlDsa65>::try_from(signature) {
+        Ok(encoded) => match Signature::<MlDsa65>::decode(&encoded) {
+            Some(sig) => sig,
+            None => return false,
+        },
+        Err(_) => return false,
+    };
+
+    key.verify(message, &sig).is_ok()
+}
+
+#[cfg(not(feature = "nonos-crypto-pq"))]
+pub fn verify_mldsa65(_pubkey: &[u8], _message: &[u8], _signature: &[u8]) -> bool {
+    audit("[pq] ML-DSA-65 signature rejected: kernel built without nonos-crypto-pq");
+    false
+}
+
+#[cfg(not(feature = "nonos-crypto-pq"))]
+fn audit(msg: &str) {
+    if let Some(logger) = crate::log::logger::try_get_logger() {
+        logger.log(msg);
+    }
+}
@@ -0,0 +1,45 @@
+//! NØNOS SHA3 / Keccak Hashers
+//!
+//! Streaming SHA3-256 and Keccak-256 with a fixed-size `finalize_bytes`, as
+//! used by `memory::proof` and `memory::kaslr`. Digests produced here carry
+//! `HashAlgo::Sha3_256` / `HashAlgo::Keccak256` when exported.
+
+use ::sha3::Digest as _;
+
+/// Streaming SHA3-256
+pub struct Sha3_256(::sha3::Sha3_256);
+
+impl Sha3_256 {
+    pub fn new() -> Self {
+        Self(::sha3::Sha3_256::new())
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub fn finalize_bytes(self) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        out.copy_from_slice(&self.0.finalize());
+        out
+    }
+}
+
+/// Streaming Keccak-256 (pre-standard padding, Ethereum-compatible)
+pub struct Keccak256(::sha3::Keccak256);
+
+impl Keccak256 {
+    pub fn new() -> Self {
+        Self(::sha3::Keccak256::new())
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub fn finalize_bytes(self) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        out.copy_from_slice(&self.0.finalize());
+        out
+    }
+}
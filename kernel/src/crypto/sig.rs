@@ -1,56 +1,183 @@
 //! NØNOS Signature Verification Interface – Production-Grade
 //!
 //! Cryptographically validates `.mod` manifests and ZeroState attestations using
-//! Ed25519 by default, with planned support for ECDSA and other curves. This layer
-//! ensures that all boot artifacts are cryptographically authorized.
+//! Ed25519 by default, ECDSA P-256 for hardware tokens and PKI signers, and
+//! optionally ML-DSA-65 (`crypto::pq`) for long-lived signatures. A signature
+//! block may be hybrid: several algorithm-tagged signatures, checked against
+//! keys and a policy the verifier pins (`modules::auth` for manifests). This
+//! layer ensures that all boot artifacts are
+//! cryptographically authorized.
 
 use ed25519_dalek::{Verifier, PublicKey, Signature};
 use ed25519_dalek::ed25519::signature::Signature as _;
+#[cfg(feature = "nonos-crypto-p256")]
+use p256::ecdsa::{Signature as P256Signature, VerifyingKey as P256VerifyingKey};
+#[cfg(feature = "nonos-crypto-p256")]
+use p256::ecdsa::signature::Verifier as _;
 use sha3::{Digest, Sha3_256};
+use alloc::vec::Vec;
 
-/// Supported signature verification schemes
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Supported signature verification schemes.
+/// The discriminant is the wire identifier carried in manifests and
+/// signature blocks; new schemes get new ids, existing ids never change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
 pub enum SigAlgo {
-    Ed25519,
-    EcdsaP256, // Placeholder for future implementation
-    Unsupported,
+    Ed25519 = 0x01,
+    EcdsaP256 = 0x02,
+    MlDsa65 = 0x03,
+    Unsupported = 0xFF,
 }
 
-/// A structured signature proof for manifest verification
-#[derive(Debug)]
-pub struct SignatureBlock {
+impl SigAlgo {
+    pub fn from_id(id: u8) -> Self {
+        match id {
+            0x01 => SigAlgo::Ed25519,
+            0x02 => SigAlgo::EcdsaP256,
+            0x03 => SigAlgo::MlDsa65,
+            _ => SigAlgo::Unsupported,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn is_post_quantum(&self) -> bool {
+        matches!(self, SigAlgo::MlDsa65)
+    }
+}
+
+/// How many signatures of a hybrid block must verify
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HybridPolicy {
+    /// Every signature must verify
+    All,
+    /// At least one signature must verify
+    Any,
+    /// At least `n` signatures must verify
+    AtLeast(usize),
+    /// At least one classical and one post-quantum signature must verify
+    ClassicalAndPq,
+}
+
+impl HybridPolicy {
+    /// `valid` lists the schemes of the signatures that verified
+    pub fn satisfied(&self, valid: &[SigAlgo], total: usize) -> bool {
+        match *self {
+            HybridPolicy::All => total > 0 && valid.len() == total,
+            HybridPolicy::Any => !valid.is_empty(),
+            HybridPolicy::AtLeast(n) => n > 0 && valid.len() >= n,
+            HybridPolicy::ClassicalAndPq => {
+                valid.iter().any(|a| a.is_post_quantum()) && valid.iter().any(|a| !a.is_post_quantum())
+            }
+        }
+    }
+}
+
+/// One algorithm-tagged signature inside a block
+#[derive(Debug, Clone, Copy)]
+pub struct SignatureEntry<'a> {
     pub algo: SigAlgo,
-    pub pubkey: [u8; 32],
-    pub signature: [u8; 64],
+    /// Ed25519: 32 bytes; P-256: SEC1 compressed (33) or uncompressed (65);
+    /// ML-DSA-65: 1952 bytes
+    pub pubkey: &'a [u8],
+    /// Ed25519: 64 bytes; P-256: fixed r||s (64) or DER; ML-DSA-65: 3309 bytes
+    pub signature: &'a [u8],
+    pub signer: &'a str,
+}
+
+/// A structured signature proof for manifest verification.
+/// May carry several signatures (hybrid). The block carries no policy and
+/// no trust of its own: the verifier pins both.
+#[derive(Debug)]
+pub struct SignatureBlock<'a> {
+    pub entries: &'a [SignatureEntry<'a>],
     pub payload_digest: [u8; 32],
-    pub signer: &'static str,
 }
 
-/// High-level manifest verification entrypoint
-pub fn validate_signature_block(block: &SignatureBlock, payload: &[u8]) -> bool {
+/// High-level manifest verification entrypoint. An entry only counts if
+/// `trusted` accepts its (scheme, key) and it verifies; `policy` then
+/// decides whether enough of them did.
+pub fn validate_signature_block(
+    block: &SignatureBlock,
+    payload: &[u8],
+    policy: HybridPolicy,
+    trusted: impl Fn(SigAlgo, &[u8]) -> bool,
+) -> bool {
     let digest = sha3_digest(payload);
     if block.payload_digest != digest {
         audit("[sig] digest mismatch on payload");
         return false;
     }
 
-    match block.algo {
+    let mut valid = Vec::with_capacity(block.entries.len());
+    for entry in block.entries {
+        if !trusted(entry.algo, entry.pubkey) {
+            audit(&format!("[sig] {:?} key not pinned: {}", entry.algo, entry.signer));
+        } else if verify(entry.algo, entry.pubkey, payload, entry.signature) {
+            audit(&format!("[sig] {:?} verified: {}", entry.algo, entry.signer));
+            valid.push(entry.algo);
+        } else {
+            audit(&format!("[sig] {:?} INVALID: {}", entry.algo, entry.signer));
+        }
+    }
+
+    let ok = policy.satisfied(&valid, block.entries.len());
+    if !ok {
+        audit(&format!(
+            "[sig] hybrid policy {:?} not met: {}/{} valid",
+            policy, valid.len(), block.entries.len()
+        ));
+    }
+    ok
+}
+
+/// Verify `signature` over `message` under `pubkey` with the given scheme
+pub fn verify(algo: SigAlgo, pubkey: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    match algo {
         SigAlgo::Ed25519 => {
-            let valid = verify_ed25519_signature(&block.pubkey, payload, &block.signature);
-            if valid {
-                audit(&format!("[sig] Ed25519 verified: {}", block.signer));
-            } else {
-                audit(&format!("[sig] Ed25519 INVALID: {}", block.signer));
-            }
-            valid
+            let (pk, sig) = match (<&[u8; 32]>::try_from(pubkey), <&[u8; 64]>::try_from(signature)) {
+                (Ok(pk), Ok(sig)) => (pk, sig),
+                _ => return false,
+            };
+            verify_ed25519_signature(pk, message, sig)
         },
-        SigAlgo::Unsupported | SigAlgo::EcdsaP256 => {
+        SigAlgo::EcdsaP256 => verify_ecdsa_p256_signature(pubkey, message, signature),
+        SigAlgo::MlDsa65 => crate::crypto::pq::verify_mldsa65(pubkey, message, signature),
+        SigAlgo::Unsupported => {
             audit("[sig] unsupported signature scheme");
             false
         },
     }
 }
 
+/// Verifies an ECDSA P-256 / SHA-256 signature (hardware tokens, PKI signers).
+/// Accepts SEC1 public keys and either fixed-size or DER signatures.
+#[cfg(feature = "nonos-crypto-p256")]
+pub fn verify_ecdsa_p256_signature(pubkey_sec1: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let key = match P256VerifyingKey::from_sec1_bytes(pubkey_sec1) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+
+    let sig = match P256Signature::from_slice(signature) {
+        Ok(sig) => sig,
+        Err(_) => match P256Signature::from_der(signature) {
+            Ok(sig) => sig,
+            Err(_) => return false,
+        },
+    };
+
+    key.verify(message, &sig).is_ok()
+}
+
+#[cfg(not(feature = "nonos-crypto-p256"))]
+pub fn verify_ecdsa_p256_signature(_pubkey_sec1: &[u8], _message: &[u8], _signature: &[u8]) -> bool {
+    audit("[sig] ECDSA P-256 signature rejected: kernel built without nonos-crypto-p256");
+    false
+}
+
 /// Verifies Ed25519 signature against message
 pub fn verify_ed25519_signature(
     public_key_bytes: &[u8; 32],
@@ -0,0 +1,251 @@
+//! NØNOS Vault Signing Service
+//!
+//! Private keys never leave the vault. A capsule is granted a key handle by
+//! the kernel and asks the vault to sign under it; it only ever gets back the
+//! signature. Every handle carries a policy:
+//! - Owner: only the granted principal may use it; the kernel is its own
+//!   principal, so no capsule name can stand in for it
+//! - Domains: allowed message prefixes / domain tags
+//! - Rate limit: signatures per window
+//!
+//! Exposed to capsules through `Syscall::VaultSign` and the `kernel.vault`
+//! IPC endpoint. Keys are Ed25519, derived from the vault root per
+//! (usage, owner, label) so a capsule keeps its key across restarts.
+
+use crate::capabilities::{Capability, CapabilityToken};
+use crate::crypto::vault::{derive_scoped_key, KeyUsage, VaultDerivationMode, VaultPublicKey};
+use crate::ipc::channel::{IpcMessage, IPC_BUS};
+use crate::ipc::message::IpcEnvelope;
+use crate::log::logger::log_warn;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use spin::Mutex;
+
+/// IPC endpoint capsules send signing requests to
+pub const VAULT_ENDPOINT: &str = "kernel.vault";
+/// Label of the per-capsule snapshot key
+pub const SNAPSHOT_LABEL: &str = "snapshot";
+/// Domain tag every snapshot statement starts with
+pub const SNAPSHOT_DOMAIN: &[u8] = b"NONOS:SNAPSHOT:v1";
+/// Domain tags the kernel's own key may sign
+const KERNEL_DOMAINS: &[&[u8]] = &[b"NONOS:"];
+
+/// Capability a token needs to use the vault, on every entry path
+pub const SIGN_CAPABILITY: Capability = Capability::CryptoOps;
+
+/// Opaque reference to a vault-held signing key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct KeyHandle(pub u32);
+
+/// Who a handle is granted to. The kernel is not a module name: only
+/// kernel code can construct `Kernel`, capsules always sign as `Capsule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Principal {
+    Kernel,
+    Capsule(&'static str),
+}
+
+impl Principal {
+    /// Key-derivation context prefix; tagged so a capsule named "kernel"
+    /// lands in a different branch than the kernel
+    fn context(&self) -> Vec<u8> {
+        match self {
+            Principal::Kernel => alloc::vec![0u8],
+            Principal::Capsule(name) => {
+                let mut ctx = Vec::with_capacity(name.len() + 1);
+                ctx.push(1);
+                ctx.extend_from_slice(name.as_bytes());
+                ctx
+            }
+        }
+    }
+}
+
+/// What a handle may sign, and how often
+#[derive(Debug, Clone)]
+pub struct SigningPolicy {
+    /// Allowed message prefixes; empty means any message
+    pub domains: Vec<&'static [u8]>,
+    pub max_signatures: u32,
+    pub window: Duration,
+}
+
+impl SigningPolicy {
+    pub fn new(domains: &[&'static [u8]], max_signatures: u32, window: Duration) -> Self {
+        Self { domains: domains.to_vec(), max_signatures, window }
+    }
+
+    /// Capsule state snapshots: one domain, a few per second
+    pub fn snapshots() -> Self {
+        Self::new(&[SNAPSHOT_DOMAIN], 16, Duration::from_secs(1))
+    }
+
+    fn allows(&self, message: &[u8]) -> bool {
+        self.domains.is_empty() || self.domains.iter().any(|d| message.starts_with(d))
+    }
+}
+
+struct HandleEntry {
+    owner: Principal,
+    label: &'static str,
+    keypair: Keypair,
+    policy: SigningPolicy,
+    window_start_ns: u64,
+    used_in_window: u32,
+}
+
+static HANDLES: Mutex<BTreeMap<KeyHandle, HandleEntry>> = Mutex::new(BTreeMap::new());
+static NEXT_HANDLE: AtomicU32 = AtomicU32::new(1);
+static KERNEL_HANDLE: Mutex<Option<KeyHandle>> = Mutex::new(None);
+
+/// Grant `owner` a signing key under `label`. Granting the same
+/// (owner, label) twice returns the existing handle.
+pub fn grant(
+    owner: Principal,
+    usage: KeyUsage,
+    label: &'static str,
+    policy: SigningPolicy,
+) -> Result<KeyHandle, &'static str> {
+    if let Some(handle) = handle_for(owner, label) {
+        return Ok(handle);
+    }
+
+    let mut context = owner.context();
+    context.push(0);
+    context.extend_from_slice(label.as_bytes());
+
+    let seed = derive_scoped_key(usage, &context, VaultDerivationMode::Direct)?;
+    let secret = SecretKey::from_bytes(&seed.key_bytes).map_err(|_| "Signing key derivation failed")?;
+    let public = PublicKey::from(&secret);
+
+    let mut handles = HANDLES.lock();
+    if let Some((handle, _)) = handles.iter().find(|(_, e)| e.owner == owner && e.label == label) {
+        return Ok(*handle);
+    }
+    let handle = KeyHandle(NEXT_HANDLE.fetch_add(1, Ordering::SeqCst));
+    handles.insert(handle, HandleEntry {
+        owner,
+        label,
+        keypair: Keypair { secret, public },
+        policy,
+        window_start_ns: 0,
+        used_in_window: 0,
+    });
+    Ok(handle)
+}
+
+/// Look up a previously granted handle
+pub fn handle_for(owner: Principal, label: &str) -> Option<KeyHandle> {
+    HANDLES
+        .lock()
+        .iter()
+        .find(|(_, e)| e.owner == owner && e.label == label)
+        .map(|(h, _)| *h)
+}
+
+/// Public half of a handle's key
+pub fn public_key(handle: KeyHandle) -> Option<VaultPublicKey> {
+    HANDLES.lock().get(&handle).map(|e| VaultPublicKey(e.keypair.public.to_bytes()))
+}
+
+/// Check that `token` may use the vault from a capsule entry path
+/// (`Syscall::VaultSign`, the `kernel.vault` endpoint)
+pub fn authorize(token: &CapabilityToken) -> Result<(), &'static str> {
+    if token.is_expired() {
+        return Err("Capability token expired");
+    }
+    if !token.has(SIGN_CAPABILITY) {
+        return Err("CryptoOps capability required for vault signing");
+    }
+    Ok(())
+}
+
+/// Sign `message` under `handle` on behalf of the token's owner
+pub fn sign(token: &CapabilityToken, handle: KeyHandle, message: &[u8]) -> Result<[u8; 64], &'static str> {
+    if token.is_expired() {
+        return Err("Capability token expired");
+    }
+    sign_as(Principal::Capsule(token.owner_module), handle, message)
+}
+
+/// Sign under the kernel's own key (termination records, attestations)
+pub fn kernel_sign(message: &[u8]) -> Result<[u8; 64], &'static str> {
+    sign_as(Principal::Kernel, kernel_handle()?, message)
+}
+
+/// The kernel's signing handle, created on first use
+pub fn kernel_handle() -> Result<KeyHandle, &'static str> {
+    let mut slot = KERNEL_HANDLE.lock();
+    if let Some(handle) = *slot {
+        return Ok(handle);
+    }
+    let handle = grant(
+        Principal::Kernel,
+        KeyUsage::KernelIntegrity,
+        "kernel",
+        SigningPolicy::new(KERNEL_DOMAINS, u32::MAX, Duration::from_secs(1)),
+    )?;
+    *slot = Some(handle);
+    Ok(handle)
+}
+
+/// Drop every handle granted to capsule `owner`; returns how many were released
+pub fn release_handles(owner: &str) -> usize {
+    let mut handles = HANDLES.lock();
+    let before = handles.len();
+    handles.retain(|_, e| !matches!(e.owner, Principal::Capsule(name) if name == owner));
+    before - handles.len()
+}
+
+/// Serve a signing request addressed to `VAULT_ENDPOINT`.
+///
+/// Payload: handle (u32 LE) || message. The 64-byte signature is sent back
+/// on the `kernel.vault -> owner` channel of the token that signed, never
+/// to an address the envelope names.
+pub fn serve_ipc(envelope: &IpcEnvelope, token: &CapabilityToken) -> Result<(), &'static str> {
+    if envelope.from != token.owner_module {
+        return Err("Vault request sender does not own token");
+    }
+    authorize(token)?;
+    if envelope.data.len() < 4 {
+        return Err("Malformed vault signing request");
+    }
+
+    let handle = KeyHandle(u32::from_le_bytes([envelope.data[0], envelope.data[1], envelope.data[2], envelope.data[3]]));
+    let signature = sign(token, handle, &envelope.data[4..])?;
+
+    let owner = token.owner_module;
+    let reply = IPC_BUS
+        .find_channel(VAULT_ENDPOINT, owner)
+        .ok_or("No vault reply channel")?;
+    reply.send(IpcMessage::new(VAULT_ENDPOINT, owner, &signature)?)
+}
+
+pub(crate) fn sign_as(owner: Principal, handle: KeyHandle, message: &[u8]) -> Result<[u8; 64], &'static str> {
+    let mut handles = HANDLES.lock();
+    let entry = handles.get_mut(&handle).ok_or("Unknown key handle")?;
+
+    if entry.owner != owner {
+        log_warn("vault", &format!("{:?} tried to sign with a handle granted to {:?}", owner, entry.owner));
+        return Err("Key handle not granted to caller");
+    }
+    if !entry.policy.allows(message) {
+        return Err("Message outside handle's signing domains");
+    }
+
+    let now = crate::arch::x86_64::time::timer::now_ns();
+    if now.saturating_sub(entry.window_start_ns) >= entry.policy.window.as_nanos() as u64 {
+        entry.window_start_ns = now;
+        entry.used_in_window = 0;
+    }
+    if entry.used_in_window >= entry.policy.max_signatures {
+        return Err("Signing rate limit exceeded");
+    }
+    entry.used_in_window += 1;
+
+    Ok(entry.keypair.sign(message).to_bytes())
+}
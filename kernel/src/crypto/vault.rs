@@ -8,28 +8,55 @@
 //! - Integrates with future sealed module manifests for `.mod` loading
 //! - Performs deterministic key generation compatible with zkProofs
 
-use core::sync::atomic::{AtomicBool, Ordering};
-use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use core::fmt::{self, Debug, Formatter};
-use crate::crypto::vault::VaultDerivationMode::*;
+use alloc::vec::Vec;
+use spin::{Mutex, RwLock};
+use crate::crypto::hkdf;
+use crate::memory::alloc::kalloc_pages_guarded;
+use crate::memory::virt::VmFlags;
 
-/// Represents a 256-bit volatile key issued to kernel subsystems
+/// Domain salt for extracting the vault root from boot entropy
+const ROOT_SALT: &[u8] = b"NONOS:VAULT:ROOT:v1";
+const EPOCH_LABEL: &[u8] = b"NONOS:VAULT:EPOCH:v1";
+const USAGE_LABEL: &[u8] = b"NONOS:VAULT:KEY:v1";
+
+/// Represents a 256-bit volatile key issued to kernel subsystems.
+/// Zeroized on drop.
 #[derive(Clone)]
 pub struct VaultKey {
     pub key_bytes: [u8; 32],
     pub id: &'static str,
     pub derived: bool,
     pub usage: KeyUsage,
+    /// Vault epoch the key was derived under
+    pub epoch: u64,
 }
 
 impl Debug for VaultKey {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "VaultKey(id={}, derived={}, usage={:?})", self.id, self.derived, self.usage)
+        write!(f, "VaultKey(id={}, derived={}, usage={:?}, epoch={})", self.id, self.derived, self.usage, self.epoch)
     }
 }
 
-/// Tracks declared usage of a Vault key (future audit trail)
-#[derive(Debug, Clone)]
+impl Drop for VaultKey {
+    fn drop(&mut self) {
+        hkdf::scrub(&mut self.key_bytes);
+    }
+}
+
+/// Ed25519 public key of a manifest signer or trust root
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VaultPublicKey(pub [u8; 32]);
+
+impl VaultPublicKey {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// Declared usage of a Vault key; each usage is its own branch of the key tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyUsage {
     KernelIntegrity,
     ModuleIsolation,
@@ -38,9 +65,35 @@ pub enum KeyUsage {
     TestDev,
 }
 
+impl KeyUsage {
+    /// HKDF label for this branch
+    pub const fn label(&self) -> &'static str {
+        match self {
+            KeyUsage::KernelIntegrity => "kernel-integrity",
+            KeyUsage::ModuleIsolation => "module-isolation",
+            KeyUsage::IPCStream => "ipc-stream",
+            KeyUsage::NetworkAuth => "network-auth",
+            KeyUsage::TestDev => "test-dev",
+        }
+    }
+}
+
+/// Root and current epoch key; lives alone in a guard-paged page
+#[repr(C, align(4096))]
+struct KeyStore {
+    root: [u8; 32],
+    epoch_key: [u8; 32],
+}
+
+struct StoreSlot(*mut KeyStore);
+unsafe impl Send for StoreSlot {}
+
 /// Vault internal runtime state
 static VAULT_READY: AtomicBool = AtomicBool::new(false);
-static mut VAULT_PRIMARY: UnsafeCell<Option<VaultKey>> = UnsafeCell::new(None);
+static STORE: Mutex<Option<StoreSlot>> = Mutex::new(None);
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+/// Module-admission trust roots provisioned at boot (bootloader handoff)
+static ROOT_PUBKEYS: RwLock<Vec<[u8; 32]>> = RwLock::new(Vec::new());
 
 /// Vault metadata sealed at boot time
 #[derive(Debug, Clone)]
@@ -52,26 +105,42 @@ pub struct VaultMetadata {
     pub entropy_bits: u64,
 }
 
-/// Supported derivation modes for future deterministic HMAC-based expansions
+/// Where a derived key hangs in the tree
 #[derive(Debug, Clone)]
 pub enum VaultDerivationMode {
+    /// Under the current epoch key; changes on `rotate_epoch`
     HKDF,
+    /// Directly under the root; stable for the whole boot
     Direct,
-    ZeroizedFallback,
 }
 
-/// Initializes the Vault by provisioning the primary key in RAM.
-/// This should later be sealed to a trusted TPM or UEFI-measured region.
+/// Initializes the Vault: extracts the root key from DRBG output (seeded
+/// from the bootloader handoff) into a page fenced by guard pages on both
+/// sides, whatever the heap guard policy, and opens epoch 0.
 pub fn init_vault() {
+    if VAULT_READY.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let page = unsafe { kalloc_pages_guarded(1, VmFlags::RW | VmFlags::NX) };
+    if page.is_null() {
+        audit("[vault] failed to allocate key page");
+        return;
+    }
+    let store = page.as_mut_ptr::<KeyStore>();
+
+    let mut ikm = [0u8; 64];
+    crate::crypto::entropy::fill_bytes(&mut ikm);
     unsafe {
-        *VAULT_PRIMARY.get() = Some(VaultKey {
-            key_bytes: [0x42; 32],
-            id: "bootkey:dev",
-            derived: false,
-            usage: KeyUsage::KernelIntegrity,
-        });
+        (*store).root = hkdf::extract(ROOT_SALT, &ikm);
+        (*store).epoch_key = epoch_key(&(*store).root, 0);
     }
+    hkdf::scrub(&mut ikm);
+
+    *STORE.lock() = Some(StoreSlot(store));
+    EPOCH.store(0, Ordering::SeqCst);
     VAULT_READY.store(true, Ordering::SeqCst);
+    audit("[vault] HKDF key tree online (epoch 0)");
 }
 
 /// Check if vault has been initialized
@@ -79,34 +148,112 @@ pub fn is_vault_ready() -> bool {
     VAULT_READY.load(Ordering::SeqCst)
 }
 
-/// Returns the main volatile kernel key
-pub fn get_test_key() -> VaultKey {
+/// Current key epoch
+pub fn current_epoch() -> u64 {
+    EPOCH.load(Ordering::SeqCst)
+}
+
+/// Advance to the next epoch. Every `HKDF`-mode key derived afterwards is
+/// unrelated to those of the previous epoch, whose key is erased.
+pub fn rotate_epoch() -> u64 {
+    let guard = STORE.lock();
+    let store = match guard.as_ref() {
+        Some(slot) => slot.0,
+        None => return current_epoch(),
+    };
+    let next = EPOCH.load(Ordering::SeqCst) + 1;
     unsafe {
-        (*VAULT_PRIMARY.get()).as_ref().unwrap().clone()
+        let mut fresh = epoch_key(&(*store).root, next);
+        hkdf::scrub(&mut (*store).epoch_key);
+        (*store).epoch_key = fresh;
+        hkdf::scrub(&mut fresh);
     }
+    EPOCH.store(next, Ordering::SeqCst);
+    audit("[vault] key epoch rotated");
+    next
 }
 
-/// Derives a new runtime key from the base vault key (e.g. for IPC or module scopes)
-pub fn derive_key(usage: KeyUsage, mode: VaultDerivationMode) -> VaultKey {
-    let base = get_test_key();
-    let mut new_key = [0u8; 32];
+/// Development key for the `TestDev` branch (never the root)
+pub fn get_test_key() -> Result<VaultKey, &'static str> {
+    derive_key(KeyUsage::TestDev, VaultDerivationMode::HKDF)
+}
 
-    for i in 0..32 {
-        new_key[i] = base.key_bytes[i] ^ match mode {
-            HKDF => 0xAB,
-            Direct => 0x55,
-            ZeroizedFallback => 0x00,
-        };
-    }
+/// Derives a runtime key for a usage branch (e.g. for IPC or module scopes).
+/// Fails until `init_vault` has run.
+pub fn derive_key(usage: KeyUsage, mode: VaultDerivationMode) -> Result<VaultKey, &'static str> {
+    derive_scoped_key(usage, &[], mode)
+}
 
-    VaultKey {
-        key_bytes: new_key,
-        id: "derived:scope",
+/// Derives a key bound to a usage branch and a context (capsule exec_id,
+/// channel name, ...). Distinct contexts give independent keys.
+pub fn derive_scoped_key(
+    usage: KeyUsage,
+    context: &[u8],
+    mode: VaultDerivationMode,
+) -> Result<VaultKey, &'static str> {
+    let guard = STORE.lock();
+    let slot = guard.as_ref().ok_or("Vault not initialized")?;
+    let store = match mode {
+        VaultDerivationMode::HKDF => unsafe { &(*slot.0).epoch_key },
+        VaultDerivationMode::Direct => unsafe { &(*slot.0).root },
+    };
+
+    let mut key = VaultKey {
+        key_bytes: [0u8; 32],
+        id: usage.label(),
         derived: true,
         usage,
+        epoch: current_epoch(),
+    };
+    hkdf::expand(
+        store,
+        &[USAGE_LABEL, usage.label().as_bytes(), &(context.len() as u32).to_le_bytes(), context],
+        &mut key.key_bytes,
+    )?;
+    Ok(key)
+}
+
+/// HKDF subkey under the vault root for any subsystem: `label` names the
+/// purpose, `context` binds it to an instance. Stable for the whole boot.
+pub fn derive_subkey(label: &[u8], context: &[u8], out: &mut [u8]) -> Result<(), &'static str> {
+    let guard = STORE.lock();
+    let slot = guard.as_ref().ok_or("Vault not initialized")?;
+    let root = unsafe { &(*slot.0).root };
+    hkdf::expand(root, &[b"NONOS:VAULT:SUBKEY:v1", label, &(context.len() as u32).to_le_bytes(), context], out)
+}
+
+fn epoch_key(root: &[u8; 32], epoch: u64) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let _ = hkdf::expand(root, &[EPOCH_LABEL, &epoch.to_le_bytes()], &mut out);
+    out
+}
+
+/// Emits a vault log line
+fn audit(msg: &str) {
+    if let Some(logger) = crate::log::logger::try_get_logger() {
+        logger.log(msg);
+    }
+}
+
+/// Provision a module-admission trust root; the first key provisioned is
+/// the signer-policy root
+pub fn provision_root_pubkey(key: [u8; 32]) {
+    let mut roots = ROOT_PUBKEYS.write();
+    if !roots.contains(&key) {
+        roots.push(key);
     }
 }
 
+/// Trust roots provisioned at boot, in provisioning order
+pub fn get_root_pubkeys() -> Vec<[u8; 32]> {
+    ROOT_PUBKEYS.read().clone()
+}
+
+/// Verify an Ed25519 signature over a 32-byte digest
+pub fn verify_signature(digest: &[u8; 32], signature: &[u8; 64], signer: &VaultPublicKey) -> bool {
+    crate::crypto::sig::verify_ed25519_signature(signer.as_bytes(), digest, signature)
+}
+
 /// Provides sealed runtime metadata tied to the boot environment
 pub fn get_vault_metadata() -> VaultMetadata {
     VaultMetadata {
@@ -0,0 +1,147 @@
+//! NØNOS X25519 Key Agreement (RFC 7748)
+//!
+//! Montgomery-ladder Diffie–Hellman on Curve25519 via `curve25519-dalek`
+//! (`nonos-crypto-x25519`). Interoperates with the CLI's `x25519-dalek`
+//! mesh keys. All-zero shared secrets (small-order peer points) are rejected.
+//! Session keys are derived from the shared secret with `session_key`, never
+//! used raw.
+
+use super::ct::ct_is_zero;
+use super::{entropy, hkdf};
+
+pub const KEY_LEN: usize = 32;
+
+/// Ephemeral or static secret; zeroized on drop
+pub struct SecretKey([u8; KEY_LEN]);
+
+impl SecretKey {
+    /// Fresh secret from the kernel DRBG
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; KEY_LEN];
+        entropy::fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    pub fn from_bytes(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn public_key(&self) -> [u8; KEY_LEN] {
+        scalar_mult_base(&self.0)
+    }
+
+    /// Raw X25519 output; `None` if the peer point was of small order
+    pub fn diffie_hellman(&self, peer: &[u8; KEY_LEN]) -> Option<[u8; KEY_LEN]> {
+        let shared = scalar_mult(&self.0, peer);
+        if ct_is_zero(&shared) {
+            return None;
+        }
+        Some(shared)
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        hkdf::scrub(&mut self.0);
+    }
+}
+
+/// Bind a shared secret to both public keys and a purpose label:
+/// `HKDF(salt = label, ikm = shared, info = pk_a || pk_b)`
+pub fn session_key(
+    shared: &[u8; KEY_LEN],
+    label: &[u8],
+    pk_a: &[u8; KEY_LEN],
+    pk_b: &[u8; KEY_LEN],
+    out: &mut [u8],
+) -> Result<(), &'static str> {
+    let mut prk = hkdf::extract(label, shared);
+    let res = hkdf::expand(&prk, &[b"NONOS:X25519:SESSION:v1", pk_a, pk_b], out);
+    hkdf::scrub(&mut prk);
+    res
+}
+
+#[cfg(feature = "nonos-crypto-x25519")]
+fn clamp(scalar: &[u8; KEY_LEN]) -> curve25519_dalek::scalar::Scalar {
+    let mut s = *scalar;
+    s[0] &= 248;
+    s[31] &= 127;
+    s[31] |= 64;
+    let clamped = curve25519_dalek::scalar::Scalar::from_bits(s);
+    hkdf::scrub(&mut s);
+    clamped
+}
+
+#[cfg(feature = "nonos-crypto-x25519")]
+fn scalar_mult(scalar: &[u8; KEY_LEN], point: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    use curve25519_dalek::montgomery::MontgomeryPoint;
+    (clamp(scalar) * MontgomeryPoint(*point)).to_bytes()
+}
+
+#[cfg(feature = "nonos-crypto-x25519")]
+fn scalar_mult_base(scalar: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    use curve25519_dalek::constants::X25519_BASEPOINT;
+    (clamp(scalar) * X25519_BASEPOINT).to_bytes()
+}
+
+// Without the feature every agreement fails: an all-zero output is rejected
+// by `diffie_hellman`, so no session can be keyed.
+#[cfg(not(feature = "nonos-crypto-x25519"))]
+fn scalar_mult(_scalar: &[u8; KEY_LEN], _point: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    [0u8; KEY_LEN]
+}
+
+#[cfg(not(feature = "nonos-crypto-x25519"))]
+fn scalar_mult_base(_scalar: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    [0u8; KEY_LEN]
+}
+
+#[cfg(all(test, feature = "nonos-crypto-x25519"))]
+mod tests {
+    use super::*;
+
+    fn hex32(s: &str) -> [u8; KEY_LEN] {
+        core::array::from_fn(|i| u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap())
+    }
+
+    // RFC 7748 §5.2
+    #[test]
+    fn rfc7748_scalar_mult() {
+        let vectors = [
+            (
+                "a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4",
+                "e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c",
+                "c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552",
+            ),
+            (
+                "4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d",
+                "e5210f12786811d3f4b7959d0538ae2c31dbe7106fc03c3efc4cd549c715a493",
+                "95cbde9476e8907d7aade45cb4b873f88b595a68799fa152e6f8f7647aac7957",
+            ),
+        ];
+        for (scalar, u, out) in vectors {
+            assert_eq!(scalar_mult(&hex32(scalar), &hex32(u)), hex32(out));
+        }
+    }
+
+    // RFC 7748 §6.1
+    #[test]
+    fn rfc7748_diffie_hellman() {
+        let alice = SecretKey::from_bytes(hex32("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a"));
+        let bob = SecretKey::from_bytes(hex32("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb"));
+        let alice_pk = hex32("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+        let bob_pk = hex32("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+        let shared = hex32("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+
+        assert_eq!(alice.public_key(), alice_pk);
+        assert_eq!(bob.public_key(), bob_pk);
+        assert_eq!(alice.diffie_hellman(&bob_pk), Some(shared));
+        assert_eq!(bob.diffie_hellman(&alice_pk), Some(shared));
+    }
+
+    #[test]
+    fn small_order_peer_is_rejected() {
+        let alice = SecretKey::from_bytes(hex32("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a"));
+        assert_eq!(alice.diffie_hellman(&[0u8; KEY_LEN]), None);
+    }
+}
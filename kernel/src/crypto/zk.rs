@@ -1,11 +1,22 @@
 //! NØNOS Zero-Knowledge Proof Framework (zk.rs)
 //!
 //! zk verification interface for anonymous module proofs.
-//! Future-proofed to support SNARK/STARK protocols like Groth16, Halo2, or Spartan.
+//! Backed by the Ristretto sigma protocols in `shared/zk_sigma.rs` (feature
+//! `nonos-zk`): Schnorr proofs of knowledge for `ModSig` and ring-membership
+//! OR-proofs for `AnonAuth`, each against a fixed verifying key. Without the
+//! feature every proof is `Unsupported`, so ZK-gated admission fails closed.
 //! Used during module admission, protocol bootstrapping, and anonymous governance.
 
 use core::fmt::{self, Debug};
 
+/// Sigma-protocol verifier shared with the bootloader (`zkverify`)
+#[cfg(feature = "nonos-zk")]
+#[path = "../../../shared/zk_sigma.rs"]
+pub mod zk_sigma;
+
+/// Statements are 32-byte digests (manifest hash, capsule commitment, ...)
+pub const STATEMENT_LEN: usize = 32;
+
 /// Enumeration of supported zk circuit types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ZkCircuitType {
@@ -15,7 +26,10 @@ pub enum ZkCircuitType {
     Custom(&'static str),
 }
 
-/// zkProof payload passed by `.mod` or system actor
+/// zkProof payload passed by `.mod` or system actor.
+/// `public_inputs` is the 32-byte statement the proof is bound to; the
+/// circuit's verifying key and the statement together form the public input
+/// encoding absorbed into the Fiat–Shamir challenge.
 #[derive(Clone)]
 pub struct ZkProof {
     pub circuit: ZkCircuitType,
@@ -42,22 +56,72 @@ pub enum ZkValidation {
 
 /// Top-level verifier for generic zkProof structure
 pub fn verify_proof(proof: &ZkProof) -> ZkValidation {
-    match proof.circuit {
-        ZkCircuitType::AnonAuth if !proof.proof_data.is_empty() => {
-            audit(&format!("[zk] anon-auth validated from {}", proof.issuer));
-            ZkValidation::Valid
-        },
-        ZkCircuitType::ModSig => {
-            // stub logic — future: cryptographic transcript validation
-            if proof.proof_data.len() > 64 {
-                audit(&format!("[zk] modsig OK from {}", proof.issuer));
-                ZkValidation::Valid
-            } else {
-                ZkValidation::Invalid
-            }
-        },
-        _ => ZkValidation::Unsupported
+    let result = verify_statement(proof.circuit, proof.public_inputs, proof.proof_data);
+    match result {
+        ZkValidation::Valid => audit(&format!("[zk] {:?} proof valid from {}", proof.circuit, proof.issuer)),
+        ZkValidation::Invalid => audit(&format!("[zk] {:?} proof rejected from {}", proof.circuit, proof.issuer)),
+        _ => {}
     }
+    result
+}
+
+/// Verify `proof` for `circuit` over a 32-byte `statement` against the
+/// circuit's fixed verifying key
+#[cfg(feature = "nonos-zk")]
+pub fn verify_statement(circuit: ZkCircuitType, statement: &[u8], proof: &[u8]) -> ZkValidation {
+    let vk = match verifying_key(circuit) {
+        Some(vk) => vk,
+        None => return ZkValidation::Unsupported,
+    };
+    if statement.len() != STATEMENT_LEN {
+        return ZkValidation::Invalid;
+    }
+    if zk_sigma::verify(&vk, statement, proof) {
+        ZkValidation::Valid
+    } else {
+        ZkValidation::Invalid
+    }
+}
+
+#[cfg(not(feature = "nonos-zk"))]
+pub fn verify_statement(circuit: ZkCircuitType, _statement: &[u8], _proof: &[u8]) -> ZkValidation {
+    audit(&format!("[zk] {:?} proof unsupported: kernel built without nonos-zk", circuit));
+    ZkValidation::Unsupported
+}
+
+/// Fixed verifying key per circuit; `ZkLogin` and `Custom` have no verifier
+#[cfg(feature = "nonos-zk")]
+pub fn verifying_key(circuit: ZkCircuitType) -> Option<zk_sigma::VerifyingKey<'static>> {
+    match circuit {
+        ZkCircuitType::ModSig => Some(zk_sigma::keys::MODSIG),
+        ZkCircuitType::AnonAuth => Some(zk_sigma::keys::ANON_AUTH),
+        ZkCircuitType::ZkLogin | ZkCircuitType::Custom(_) => None,
+    }
+}
+
+/// Manifest `ZkAttestation`: a `ModSig` Schnorr proof (`R || s`) over the
+/// manifest's signed digest (`ModuleManifest::signed_digest`)
+pub fn verify_manifest_proof(hash: &[u8; 32], proof: &[u8; 64]) -> bool {
+    verify_statement(ZkCircuitType::ModSig, hash, proof) == ZkValidation::Valid
+}
+
+/// Known-answer check of both circuits against the embedded test vectors
+#[cfg(feature = "nonos-zk")]
+pub fn self_test() -> bool {
+    use zk_sigma::vectors;
+
+    let modsig = verify_statement(ZkCircuitType::ModSig, &vectors::MODSIG_STATEMENT, &vectors::MODSIG_PROOF);
+    let anon = verify_statement(ZkCircuitType::AnonAuth, &vectors::ANON_AUTH_STATEMENT, &vectors::ANON_AUTH_PROOF);
+    let mut tampered = vectors::MODSIG_PROOF;
+    tampered[40] ^= 0x01;
+    let negative = verify_statement(ZkCircuitType::ModSig, &vectors::MODSIG_STATEMENT, &tampered);
+
+    modsig == ZkValidation::Valid && anon == ZkValidation::Valid && negative == ZkValidation::Invalid
+}
+
+#[cfg(not(feature = "nonos-zk"))]
+pub fn self_test() -> bool {
+    true
 }
 
 /// Secure entrypoint to validate module identity via zkProof
@@ -123,6 +123,39 @@ impl IpcBus {
         Err("Maximum IPC channels reached")
     }
 
+    /// Close a channel by source and destination, dropping queued messages.
+    pub fn close_channel(&self, from: &str, to: &str) -> bool {
+        let mut slots = self.channels.lock();
+        for slot in slots.iter_mut() {
+            let matches = slot.as_ref().map_or(false, |ch| ch.from == from && ch.to == to);
+            if matches {
+                if let Some(ch) = slot.take() {
+                    ch.queue.lock().clear();
+                }
+                self.active_count.fetch_sub(1, Ordering::SeqCst);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Close every channel a module sends or receives on. Returns count closed.
+    pub fn close_channels_for(&self, module: &str) -> usize {
+        let mut slots = self.channels.lock();
+        let mut closed = 0;
+        for slot in slots.iter_mut() {
+            let owned = slot.as_ref().map_or(false, |ch| ch.from == module || ch.to == module);
+            if owned {
+                if let Some(ch) = slot.take() {
+                    ch.queue.lock().clear();
+                }
+                closed += 1;
+            }
+        }
+        self.active_count.fetch_sub(closed, Ordering::SeqCst);
+        closed
+    }
+
     /// Find an active channel by source and destination.
     pub fn find_channel(&self, from: &str, to: &str) -> Option<Arc<IpcChannel>> {
         let slots = self.channels.lock();
@@ -47,6 +47,16 @@ pub fn send_envelope(
         }
     }
 
+    // Heartbeat replies terminate in the kernel supervisor, not on a channel
+    if crate::modules::supervisor::is_probe_reply(&envelope) {
+        return crate::modules::supervisor::heartbeat(envelope.from, token);
+    }
+
+    // Signing requests are answered by the vault, never by a capsule
+    if envelope.to == crate::crypto::signer::VAULT_ENDPOINT {
+        return crate::crypto::signer::serve_ipc(&envelope, token);
+    }
+
     if let Some(channel) = IPC_BUS.find_channel(envelope.from, envelope.to) {
         channel.send(IpcMessage::new(envelope.from, envelope.to, &envelope.data)?)
     } else {
@@ -73,7 +83,7 @@ pub fn list_routes() -> Vec<(String, String)> {
 pub fn get_ipc_status() -> IpcStatus {
     IpcStatus {
         active_routes: IPC_BUS.list_routes().len(),
-        open_streams: 0, // Placeholder: later track open IpcStream instances
+        open_streams: transport::open_stream_count(),
         messages_in_flight: 0, // Hook into scheduler or channel telemetry
     }
 }
@@ -6,6 +6,7 @@
 
 use crate::ipc::message::{IpcEnvelope, MessageType, MsgFlags};
 use crate::capabilities::CapabilityToken;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::time::Duration;
 use core::sync::atomic::{AtomicU64, Ordering};
@@ -14,6 +15,9 @@ use spin::Mutex;
 /// Maximum IPC stream chunk size
 pub const MAX_CHUNK_SIZE: usize = 1024;
 
+/// Open stream sessions, tracked so teardown can close them
+static OPEN_STREAMS: Mutex<Vec<Arc<IpcStream>>> = Mutex::new(Vec::new());
+
 /// Stream transfer session struct
 #[derive(Debug)]
 pub struct IpcStream {
@@ -70,6 +74,15 @@ impl IpcStream {
         )
     }
 
+    /// Drop and scrub any buffered, not-yet-flushed payload
+    pub fn scrub(&self) {
+        let mut buf = self.buffer.lock();
+        for b in buf.iter_mut() {
+            unsafe { core::ptr::write_volatile(b, 0) };
+        }
+        buf.clear();
+    }
+
     pub fn is_idle(&self, now: Duration, timeout: Duration) -> bool {
         now.checked_sub(self.last_activity)
             .map(|delta| delta > timeout)
@@ -89,3 +102,34 @@ pub fn send_stream_payload(
     }
     Ok(())
 }
+
+/// Open and track a stream session
+pub fn open_stream(
+    session_id: &'static str,
+    from: &'static str,
+    to: &'static str,
+    encrypted: bool,
+) -> Arc<IpcStream> {
+    let stream = Arc::new(IpcStream::new(session_id, from, to, encrypted));
+    OPEN_STREAMS.lock().push(stream.clone());
+    stream
+}
+
+/// Close every stream a module is an endpoint of. Returns count closed.
+pub fn close_streams_for(module: &str) -> usize {
+    let mut streams = OPEN_STREAMS.lock();
+    let before = streams.len();
+    streams.retain(|s| {
+        let owned = s.from == module || s.to == module;
+        if owned {
+            s.scrub();
+        }
+        !owned
+    });
+    before - streams.len()
+}
+
+/// Number of tracked open streams
+pub fn open_stream_count() -> usize {
+    OPEN_STREAMS.lock().len()
+}
@@ -7,7 +7,7 @@ use core::fmt::{self, Write};
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use spin::Mutex;
 
-use crate::crypto::hash::blake3_hash;
+use crate::crypto::hash::{blake3_hash, digest::blake3_tagged, domain};
 use crate::arch::x86_64::{serial, vga};
 
 /// Log severity levels
@@ -108,7 +108,7 @@ impl Logger {
         data.push(severity as u8);
         data.extend_from_slice(message.as_bytes());
         data.extend_from_slice(&prev_hash);
-        let hash = blake3_hash(&data);
+        let hash = blake3_tagged(domain::LOG_CHAIN, &data).bytes;
         
         // Create entry
         let entry = LogEntry {
@@ -210,7 +210,7 @@ impl Logger {
             data.push(entry.severity as u8);
             data.extend_from_slice(entry.message.as_bytes());
             data.extend_from_slice(&entry.prev_hash);
-            let computed = blake3_hash(&data);
+            let computed = blake3_tagged(domain::LOG_CHAIN, &data).bytes;
             
             if computed != entry.hash {
                 return false;
@@ -2,49 +2,100 @@
 //!
 //! Design
 //!  - Small/medium sizes served from per-CPU magazines over page-backed slabs.
-//!  - Large sizes served by VM: page-granular map/unmap (+ optional guard pages).
+//!  - Large sizes served by VM: page-granular map/unmap (+ optional guard pages);
+//!    spans of 2 MiB or more are placed on a 2 MiB boundary and mapped with
+//!    2 MiB pages from `phys::alloc_contig`, falling back to 4 KiB frames.
 //!  - Zero-on-free by default (zero-state posture). Zero-on-alloc optional.
 //!  - NUMA/zone hints forwarded to phys (DMA32/LOWMEM supported).
 //!  - Proof posture: page map/unmap audited via virt hooks; phys frames audited.
+//!  - Hardening, runtime-selectable via `HeapPolicy`: trailing canaries,
+//!    freed-object quarantine with delayed reuse, poison verified on reuse
+//!    (catches use-after-free writes), double/invalid-free detection.
 //!
-//! Not a global #[alloc_error_handler]; this is the kernel-internal heap API.
-//! Exposes: kmalloc/kfree, kalloc_aligned, kalloc_pages/kfree_pages, stats().
+//! Backs the kernel `#[global_allocator]` (`memory::heap`) via kmem_alloc /
+//! kmem_free. Not a global #[alloc_error_handler].
+//! Exposes: kmem_alloc/kmem_alloc_zero/kmem_free, kmalloc/kfree, kalloc_aligned,
+//! kalloc_pages/kfree_pages, set_policy(), stats(), trim_magazines() (memory
+//! pressure reclaim).
 
 #![allow(dead_code)]
 
 use core::{ptr, mem, cell::UnsafeCell};
+use core::sync::atomic::{AtomicU64, Ordering};
 use spin::{Mutex, Lazy};
 use x86_64::{VirtAddr, PhysAddr};
 
-use crate::memory::layout::PAGE_SIZE;
+use crate::memory::layout::{self, align_up, HUGE_2M, PAGE_SIZE};
+use crate::memory::mapcheck;
 use crate::memory::virt::{self, VmFlags};
-use crate::memory::phys::{self, Frame, AllocFlags as PFlags};
-use crate::memory::proof::{self, CapTag};
+use crate::memory::phys::{self, AllocFlags as PFlags};
 
 /// Zero policy for small/large allocs.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ZeroPolicy { OnFree, OnAlloc, Never }
 
-/// Heap policy (set at init).
+/// Heap policy (set at init, replaceable at runtime via `set_policy`).
+/// Hardening state is recorded per object, so switching policy never
+/// misjudges objects allocated under the previous one.
 #[derive(Clone, Copy, Debug)]
 pub struct HeapPolicy {
     pub zero: ZeroPolicy,         // default: OnFree
     pub guard_large: bool,        // add 1 guard page before/after large-alloc mappings
     pub prefer_lowmem: bool,      // prefer <=4GiB phys frames for slabs
+    pub canaries: bool,           // trailing canary after small objects, checked on free
+    pub quarantine: usize,        // freed small objects held back before reuse (0 = off, max QUARANTINE_MAX)
+    pub poison: bool,             // fill freed objects with POISON, verified when reused
+    pub double_free: bool,        // trap frees of objects that are not live
+    pub huge_pages: bool,         // map large spans with 2 MiB pages when contiguous frames exist
+}
+
+impl HeapPolicy {
+    /// Every check on: debug builds and fuzzing.
+    pub const fn debug() -> Self {
+        Self {
+            zero: ZeroPolicy::OnFree,
+            guard_large: true,
+            prefer_lowmem: true,
+            canaries: true,
+            quarantine: QUARANTINE_MAX,
+            poison: true,
+            double_free: true,
+            huge_pages: true,
+        }
+    }
+
+    /// Release posture: only the checks that cost nothing on the fast path.
+    pub const fn release() -> Self {
+        Self {
+            zero: ZeroPolicy::OnFree,
+            guard_large: cfg!(feature = "nonos-heap-guard"),
+            prefer_lowmem: true,
+            canaries: false,
+            quarantine: 0,
+            poison: false,
+            double_free: true,
+            huge_pages: true,
+        }
+    }
 }
 
 impl Default for HeapPolicy {
     fn default() -> Self {
-        Self { zero: ZeroPolicy::OnFree, guard_large: true, prefer_lowmem: true }
+        if cfg!(debug_assertions) { Self::debug() } else { Self::release() }
     }
 }
 
 /// Size classes (bytes). Keep power-of-two to simplify magazines.
+/// Anything above the last class takes the VM path: a 64 KiB object plus
+/// its header would not fit a `SLAB_MAX_PAGES` slab.
 const CLASSES: &[usize] = &[
     16, 32, 64, 128, 256, 512,
-    1024, 2048, 4096, 8192, 16384, 32768, 65536,
+    1024, 2048, 4096, 8192, 16384, 32768,
 ];
 
+/// Alignment every small object gets; larger alignments take the VM path.
+pub const MIN_ALIGN: usize = 16;
+
 /// Slab header precedes objects in the same mapped pages.
 #[repr(C)]
 struct Slab {
@@ -52,21 +103,80 @@ struct Slab {
     obj_size: u32,        // bytes per object (class size)
     used: u32,            // in-use count
     cap:  u32,            // capacity (#objects)
-    free_head: u32,       // index of first free; 0xFFFF_FFFF = none
+    free_head: u32,       // index of first free; FREE_NONE = none
     next: *mut Slab,      // linked list in a magazine
     base_va: VirtAddr,    // base VA of the slab's mapped pages
     pages: u32,           // #4K pages backing this slab
 }
 
+/// Slab header rounded so payloads stay MIN_ALIGN-aligned.
+const SLAB_META: usize = (mem::size_of::<Slab>() + MIN_ALIGN - 1) & !(MIN_ALIGN - 1);
+
+// Object layout: [*mut Slab backptr (8B)][state (8B)][payload (class size)]
+//
+// state = magic << 32 | flags | value
+//   LIVE: value = requested size (canary sits right after it)
+//   FREE: value = next free index
+//   QUAR: value unused (object sits in the quarantine ring)
+const OBJ_HDR: usize = 16;
+const MAGIC_LIVE: u64 = 0x11FE_A11C;
+const MAGIC_FREE: u64 = 0xF2EE_F2EE;
+const MAGIC_QUAR: u64 = 0x0DEA_D0D0;
+const F_CANARY: u64 = 1 << 24;
+const F_POISON: u64 = 1 << 25;
+const VALUE_MASK: u64 = 0x00FF_FFFF;
+const FREE_NONE: u32 = 0x00FF_FFFF;
+
+/// Canary base, mixed with the object address so one leaked canary does not
+/// forge another.
+const CANARY: u64 = 0x4E4F_4E4F_5343_4E59;
+/// Fill pattern for freed objects.
+const POISON: u8 = 0xDB;
+/// Upper bound on `HeapPolicy::quarantine`.
+pub const QUARANTINE_MAX: usize = 256;
+
 /// Per-CPU magazine: intrusive list of slabs for each class + a local bump.
 struct Magazine {
     head: [*mut Slab; CLASSES_LEN],
+    /// Empty slabs kept mapped per class (bounded by `MAG_EMPTY_MAX`)
+    empty: [u8; CLASSES_LEN],
 }
 
-const CLASSES_LEN: usize = 13;
+const CLASSES_LEN: usize = 12;
+
+/// Empty slabs a class keeps cached before freeing pages back to phys.
+/// `trim_magazines` drops them all under memory pressure.
+const MAG_EMPTY_MAX: u8 = 2;
 
 impl Magazine {
-    const fn new() -> Self { Self { head: [core::ptr::null_mut(); CLASSES_LEN] } }
+    const fn new() -> Self {
+        Self { head: [core::ptr::null_mut(); CLASSES_LEN], empty: [0; CLASSES_LEN] }
+    }
+}
+
+/// FIFO of freed payloads awaiting reuse.
+struct Quarantine {
+    slots: [*mut u8; QUARANTINE_MAX],
+    head: usize,
+    len: usize,
+}
+
+impl Quarantine {
+    const fn new() -> Self { Self { slots: [core::ptr::null_mut(); QUARANTINE_MAX], head: 0, len: 0 } }
+
+    fn push(&mut self, p: *mut u8) {
+        let tail = (self.head + self.len) % QUARANTINE_MAX;
+        self.slots[tail] = p;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<*mut u8> {
+        if self.len == 0 { return None; }
+        let p = self.slots[self.head];
+        self.head = (self.head + 1) % QUARANTINE_MAX;
+        self.len -= 1;
+        Some(p)
+    }
 }
 
 /// Global heap state.
@@ -74,13 +184,16 @@ struct Heap {
     pol: HeapPolicy,
     /// Per-CPU magazines. For now single-CPU bring-up uses slot 0 only.
     mags: [UnsafeCell<Magazine>; MAX_CPUS],
+    quarantine: UnsafeCell<Quarantine>,
     /// Bump VA for large VM allocations.
     vm_cursor: UnsafeCell<u64>,
     /// Stats (best-effort).
-    alloc_small: core::sync::atomic::AtomicU64,
-    free_small:  core::sync::atomic::AtomicU64,
-    alloc_large: core::sync::atomic::AtomicU64,
-    free_large:  core::sync::atomic::AtomicU64,
+    alloc_small: AtomicU64,
+    free_small:  AtomicU64,
+    alloc_large: AtomicU64,
+    free_large:  AtomicU64,
+    huge_maps:   AtomicU64,
+    huge_fallbacks: AtomicU64,
 }
 
 unsafe impl Sync for Heap {} // UnsafeCell per-CPU is fine under our discipline.
@@ -92,14 +205,20 @@ static HEAP: Lazy<Mutex<Heap>> = Lazy::new(|| {
     Mutex::new(Heap {
         pol: HeapPolicy::default(),
         mags: [const { UnsafeCell::new(Magazine::new()) }; MAX_CPUS],
-        vm_cursor: UnsafeCell::new(0xffff_ffff_c000_0000u64), // example high VA pool
+        quarantine: UnsafeCell::new(Quarantine::new()),
+        vm_cursor: UnsafeCell::new(unsafe { layout::LAYOUT.heap_lo }), // KASLR picks it before first use
         alloc_small: 0u64.into(),
         free_small:  0u64.into(),
         alloc_large: 0u64.into(),
         free_large:  0u64.into(),
+        huge_maps:   0u64.into(),
+        huge_fallbacks: 0u64.into(),
     })
 });
 
+/// Hardening detections (best-effort; each also panics, see `heap_fault`).
+static FAULTS: AtomicU64 = AtomicU64::new(0);
+
 #[inline(always)]
 fn cpu_id() -> usize { 0 } // TODO: wire to real per-CPU id
 
@@ -108,62 +227,174 @@ fn cpu_id() -> usize { 0 } // TODO: wire to real per-CPU id
 // ───────────────────────────────────────────────────────────────────────────────
 
 pub fn init(policy: HeapPolicy) {
+    set_policy(policy);
+}
+
+/// Replace the heap policy at runtime. Shrinking the quarantine releases the
+/// overflow immediately.
+pub fn set_policy(mut policy: HeapPolicy) {
+    policy.quarantine = policy.quarantine.min(QUARANTINE_MAX);
     let mut h = HEAP.lock();
     h.pol = policy;
+    if let Err((what, p)) = unsafe { drain_quarantine(&h, policy.quarantine) } {
+        drop(h);
+        heap_fault(what, p);
+    }
 }
 
-/// Allocate `size` bytes, alignment = class size (<=64K). Returns ptr or null.
-pub unsafe fn kmalloc(size: usize) -> *mut u8 {
-    if size == 0 { return core::ptr::null_mut(); }
-    if let Some((class, sz)) = class_for(size) {
-        alloc_small(class, sz)
-    } else {
-        // route to large VM path (page-granular)
-        kalloc_large(size, 16, /*align hint*/ true)
+pub fn policy() -> HeapPolicy {
+    HEAP.lock().pol
+}
+
+/// Allocate `size` bytes aligned to `align` (power-of-two, <= PAGE_SIZE).
+/// Small sizes at `align <= MIN_ALIGN` come from slabs, the rest from VM.
+/// Returns null on failure. Free with `kmem_free` and the same size/align.
+pub unsafe fn kmem_alloc(size: usize, align: usize) -> *mut u8 {
+    if size == 0 || !align.is_power_of_two() || align > PAGE_SIZE { return core::ptr::null_mut(); }
+    match small_class(size, align) {
+        Some(class) => alloc_small(class, size, false),
+        None => kalloc_large(size, align, false),
     }
 }
 
-/// Allocate `size` bytes aligned to `align` (power-of-two). Falls back to VM path if >64K or alignment > class.
-pub unsafe fn kalloc_aligned(size: usize, align: usize) -> *mut u8 {
-    if size == 0 { return core::ptr::null_mut(); }
-    if align.is_power_of_two() && align <= 65536 {
-        if let Some((class, sz)) = class_for(size.max(align)) {
-            return alloc_small(class, sz);
+/// `kmem_alloc`, zero-filled.
+pub fn kmem_alloc_zero(size: usize, align: usize) -> Option<*mut u8> {
+    if size == 0 || !align.is_power_of_two() || align > PAGE_SIZE { return None; }
+    let p = unsafe {
+        match small_class(size, align) {
+            Some(class) => alloc_small(class, size, true),
+            None => kalloc_large(size, align, true),
         }
-    }
-    kalloc_large(size, align.max(16), true)
+    };
+    if p.is_null() { None } else { Some(p) }
 }
 
-/// Free a pointer returned by kmalloc/kalloc_aligned.
-pub unsafe fn kfree(p: *mut u8, size: usize) {
+/// Free memory from `kmem_alloc`/`kmem_alloc_zero` (same size and align).
+pub unsafe fn kmem_free(p: *mut u8, size: usize, align: usize) {
     if p.is_null() { return; }
-    if let Some((class, sz)) = class_for(size) {
-        free_small(p, class, sz);
+    if small_class(size, align).is_some() {
+        free_small(p);
     } else {
         kfree_large(p, size);
     }
 }
 
+/// Allocate `size` bytes at MIN_ALIGN. Returns ptr or null.
+pub unsafe fn kmalloc(size: usize) -> *mut u8 {
+    kmem_alloc(size, MIN_ALIGN)
+}
+
+/// Allocate `size` bytes aligned to `align` (power-of-two, <= PAGE_SIZE).
+/// Free with `kmem_free(p, size, align)`.
+pub unsafe fn kalloc_aligned(size: usize, align: usize) -> *mut u8 {
+    kmem_alloc(size, align.max(MIN_ALIGN))
+}
+
+/// Free a pointer returned by kmalloc.
+pub unsafe fn kfree(p: *mut u8, size: usize) {
+    kmem_free(p, size, MIN_ALIGN)
+}
+
 /// Page-granular allocation (N pages). Optional guard pages from policy.
 pub unsafe fn kalloc_pages(pages: usize, flags: VmFlags) -> VirtAddr {
     if pages == 0 { return VirtAddr::zero(); }
-    map_large_pages(pages, flags, /*guard=*/HEAP.lock().pol.guard_large)
+    let h = HEAP.lock();
+    let guard = h.pol.guard_large;
+    map_large_pages(&h, pages, flags, guard)
+}
+
+/// Page-granular allocation with guard pages before and after, whatever
+/// the heap policy says. For secrets (key pages) that must never sit next
+/// to another mapping. Free with `kfree_pages`.
+pub unsafe fn kalloc_pages_guarded(pages: usize, flags: VmFlags) -> VirtAddr {
+    if pages == 0 { return VirtAddr::zero(); }
+    let h = HEAP.lock();
+    map_large_pages(&h, pages, flags, true)
 }
 
 /// Free page-granular allocation (must match pages used).
 pub unsafe fn kfree_pages(base: VirtAddr, pages: usize) {
     if pages == 0 { return; }
-    unmap_large_pages(base, pages, /*guard=*/HEAP.lock().pol.guard_large)
+    mapcheck::untrack(base);
+    unmap_large_pages(base, pages)
+}
+
+/// Release every cached empty slab on every CPU (and the quarantine, whose
+/// objects pin slabs). Returns pages freed.
+/// Skips (returns 0) if the heap lock is held, e.g. when phys runs out of
+/// frames underneath a heap allocation.
+pub fn trim_magazines() -> usize {
+    let h = match HEAP.try_lock() {
+        Some(h) => h,
+        None => return 0,
+    };
+    if let Err((what, p)) = unsafe { drain_quarantine(&h, 0) } {
+        drop(h);
+        heap_fault(what, p);
+    }
+    let mut pages = 0;
+    for cell in h.mags.iter() {
+        let mag = unsafe { &mut *cell.get() };
+        for class in 0..CLASSES_LEN {
+            if mag.empty[class] == 0 { continue; }
+            let mut prev: *mut Slab = core::ptr::null_mut();
+            let mut cur = mag.head[class];
+            unsafe {
+                while !cur.is_null() {
+                    let next = (*cur).next;
+                    if (*cur).used == 0 {
+                        if prev.is_null() { mag.head[class] = next; }
+                        else { (*prev).next = next; }
+                        pages += (*cur).pages as usize;
+                        unmap_slab_pages(cur);
+                    } else {
+                        prev = cur;
+                    }
+                    cur = next;
+                }
+            }
+            mag.empty[class] = 0;
+        }
+    }
+    pages
+}
+
+/// Heap counters (best-effort).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapStats {
+    pub alloc_small: u64,
+    pub free_small: u64,
+    pub alloc_large: u64,
+    pub free_large: u64,
+    /// 2 MiB pages the heap has mapped since boot
+    pub huge_maps: u64,
+    /// 2 MiB-eligible chunks that fell back to 4 KiB (no contiguous frames)
+    pub huge_fallbacks: u64,
+    /// 2 MiB pages mapped right now, kernel-wide (`virt::huge_stats`)
+    pub huge_live: u64,
+    /// 2 MiB pages split by partial protect/unmap since boot
+    pub huge_splits: u64,
+}
+
+pub fn stats() -> HeapStats {
+    let h = HEAP.lock();
+    let (huge_live, huge_splits) = virt::huge_stats();
+    HeapStats {
+        alloc_small: h.alloc_small.load(Ordering::Relaxed),
+        free_small: h.free_small.load(Ordering::Relaxed),
+        alloc_large: h.alloc_large.load(Ordering::Relaxed),
+        free_large: h.free_large.load(Ordering::Relaxed),
+        huge_maps: h.huge_maps.load(Ordering::Relaxed),
+        huge_fallbacks: h.huge_fallbacks.load(Ordering::Relaxed),
+        huge_live,
+        huge_splits,
+    }
 }
 
-pub fn stats() -> (u64, u64, u64, u64) {
+/// (objects in quarantine, hardening faults detected)
+pub fn hardening_stats() -> (usize, u64) {
     let h = HEAP.lock();
-    (
-        h.alloc_small.load(core::sync::atomic::Ordering::Relaxed),
-        h.free_small.load(core::sync::atomic::Ordering::Relaxed),
-        h.alloc_large.load(core::sync::atomic::Ordering::Relaxed),
-        h.free_large.load(core::sync::atomic::Ordering::Relaxed),
-    )
+    (unsafe { (*h.quarantine.get()).len }, FAULTS.load(Ordering::Relaxed))
 }
 
 // ───────────────────────────────────────────────────────────────────────────────
@@ -178,56 +409,143 @@ fn class_for(n: usize) -> Option<(usize, usize)> {
     None
 }
 
-unsafe fn alloc_small(class: usize, sz: usize) -> *mut u8 {
+/// Slab class for a request, independent of policy (so frees route the same
+/// way as the allocation did). The canary only goes in if it fits.
+#[inline]
+fn small_class(size: usize, align: usize) -> Option<usize> {
+    if align > MIN_ALIGN { return None; }
+    class_for(size).map(|(class, _)| class)
+}
+
+unsafe fn alloc_small(class: usize, size: usize, zero: bool) -> *mut u8 {
     let h = HEAP.lock();
     let cpu = cpu_id();
     let mag = &mut *h.mags[cpu].get();
 
-    // try fast path: use head slab
-    let head = mag.head[class];
-    if !head.is_null() {
-        if let Some(p) = carve_obj_from_slab(head, &h.pol) {
-            h.alloc_small.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
-            return p;
+    // try fast path: first slab with a free object (cached empties included)
+    let mut got = None;
+    let mut cur = mag.head[class];
+    while !cur.is_null() {
+        let was_empty = (*cur).used == 0;
+        match pop_free(cur) {
+            Ok(Some(hit)) => {
+                if was_empty { mag.empty[class] -= 1; }
+                got = Some(hit);
+                break;
+            }
+            Ok(None) => cur = (*cur).next,
+            Err(fault) => {
+                drop(h);
+                heap_fault(fault.0, fault.1);
+            }
         }
     }
 
     // need a new slab: allocate page(s), map, format freelist
-    let slab = new_slab(class, sz, &h.pol);
-    if slab.is_null() { return core::ptr::null_mut(); }
-    (*slab).next = head;
-    mag.head[class] = slab;
+    if got.is_none() {
+        let slab = new_slab(class, CLASSES[class], &h);
+        if slab.is_null() { return core::ptr::null_mut(); }
+        (*slab).next = mag.head[class];
+        mag.head[class] = slab;
+        got = pop_free(slab).ok().flatten();
+    }
 
-    let p = carve_obj_from_slab(slab, &h.pol).unwrap_or(core::ptr::null_mut());
-    h.alloc_small.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    let (p, poisoned) = match got {
+        Some(hit) => hit,
+        None => return core::ptr::null_mut(),
+    };
+    arm(p, size, &h.pol, zero, poisoned);
+    h.alloc_small.fetch_add(1, Ordering::Relaxed);
     p
 }
 
-unsafe fn free_small(p: *mut u8, class: usize, sz: usize) {
+unsafe fn free_small(p: *mut u8) {
     let h = HEAP.lock();
-    let cpu = cpu_id();
-    let mag = &mut *h.mags[cpu].get();
+    let pol = h.pol;
+    let state = *state_ptr(p);
+
+    match state >> 32 {
+        MAGIC_LIVE => {}
+        MAGIC_FREE | MAGIC_QUAR if pol.double_free => {
+            drop(h);
+            heap_fault("double free", p);
+        }
+        _ if pol.double_free => {
+            drop(h);
+            heap_fault("free of a pointer the heap does not own", p);
+        }
+        // detection off: leaking beats corrupting the freelist
+        _ => return,
+    }
 
-    // locate slab header: lives at base_va (first page): we stash a back-pointer
-    // Layout: [Slab hdr | free list | objects...]; hdr at base_va.
-    // For simplicity, store the slab hdr pointer just before each object (8B).
-    let slab_ptr = *((p as usize - mem::size_of::<*mut Slab>()) as *const *mut Slab);
-    let slab = slab_ptr;
+    let size = (state & VALUE_MASK) as usize;
+    if state & F_CANARY != 0 && ptr::read_unaligned(p.add(size) as *const u64) != canary_for(p) {
+        drop(h);
+        heap_fault("heap overflow (canary clobbered)", p);
+    }
+
+    let sz = (*slab_of(p)).obj_size as usize;
+    let scrub = retire(p, sz, &pol);
+    h.free_small.fetch_add(1, Ordering::Relaxed);
 
-    // zero policy
-    if matches!(h.pol.zero, ZeroPolicy::OnFree) {
-        ptr::write_bytes(p, 0, sz);
+    if pol.quarantine > 0 {
+        *state_ptr(p) = (MAGIC_QUAR << 32) | scrub;
+        if let Err((what, at)) = drain_quarantine(&h, pol.quarantine - 1) {
+            drop(h);
+            heap_fault(what, at);
+        }
+        (*h.quarantine.get()).push(p);
+    } else {
+        link_free(&h, p, scrub);
     }
+}
 
-    // push back into freelist
-    let idx = obj_index_in_slab(slab, p, sz);
-    let head = (*slab).free_head;
-    write_u32_at(slab, idx, head); // store next idx at object prefix
+/// Release quarantined objects (oldest first) until at most `keep` remain.
+/// Each is checked for writes that happened while it sat in quarantine.
+unsafe fn drain_quarantine(h: &Heap, keep: usize) -> Result<(), (&'static str, *mut u8)> {
+    let q = &mut *h.quarantine.get();
+    while q.len > keep {
+        let p = match q.pop() { Some(p) => p, None => break };
+        let scrub = *state_ptr(p) & (F_POISON | F_CANARY);
+        let sz = (*slab_of(p)).obj_size as usize;
+        if scrub & F_POISON != 0 && !poison_intact(p, sz) {
+            return Err(("use-after-free write (quarantined object)", p));
+        }
+        link_free(h, p, scrub & F_POISON);
+    }
+    Ok(())
+}
+
+/// Scrub a freed payload per policy; returns F_POISON if it was poisoned.
+#[inline]
+unsafe fn retire(p: *mut u8, sz: usize, pol: &HeapPolicy) -> u64 {
+    if pol.poison {
+        ptr::write_bytes(p, POISON, sz);
+        F_POISON
+    } else {
+        if matches!(pol.zero, ZeroPolicy::OnFree) {
+            ptr::write_bytes(p, 0, sz);
+        }
+        0
+    }
+}
+
+/// Push a retired payload onto its slab's freelist; reclaim the slab if the
+/// class already caches enough empties.
+unsafe fn link_free(h: &Heap, p: *mut u8, scrub: u64) {
+    let slab = slab_of(p);
+    let class = (*slab).class as usize;
+    let mag = &mut *h.mags[cpu_id()].get();
+
+    let idx = obj_index_in_slab(slab, p);
+    *state_ptr(p) = (MAGIC_FREE << 32) | scrub | (*slab).free_head as u64;
     (*slab).free_head = idx;
     (*slab).used -= 1;
 
-    // if slab empty and not the head, we can reclaim pages (optional policy)
-    if (*slab).used == 0 {
+    // keep a few empty slabs per class; beyond that, reclaim pages now
+    if (*slab).used == 0 && mag.empty[class] < MAG_EMPTY_MAX {
+        mag.empty[class] += 1;
+    } else if (*slab).used == 0 {
         // unlink from magazine list
         let mut prev: *mut Slab = core::ptr::null_mut();
         let mut cur = mag.head[class];
@@ -242,32 +560,80 @@ unsafe fn free_small(p: *mut u8, class: usize, sz: usize) {
         // unmap pages backing this slab
         unmap_slab_pages(slab);
     }
+}
 
-    h.free_small.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+/// Mark a payload live, place its canary and apply zeroing.
+#[inline]
+unsafe fn arm(p: *mut u8, size: usize, pol: &HeapPolicy, zero: bool, poisoned: bool) {
+    let sz = (*slab_of(p)).obj_size as usize;
+    let mut flags = 0;
+    if pol.canaries && size + mem::size_of::<u64>() <= sz {
+        ptr::write_unaligned(p.add(size) as *mut u64, canary_for(p));
+        flags |= F_CANARY;
+    }
+    *state_ptr(p) = (MAGIC_LIVE << 32) | flags | size as u64;
+
+    // poison is not zero: a poisoned object is only handed out as zero if
+    // the zero-state posture asks for it
+    if zero || matches!(pol.zero, ZeroPolicy::OnAlloc) || (poisoned && !matches!(pol.zero, ZeroPolicy::Never)) {
+        ptr::write_bytes(p, 0, size);
+    }
+}
+
+/// Take the first free object of a slab: Ok(None) if full, Err on a
+/// corrupted freelist or a poisoned object that was written after free.
+#[inline]
+unsafe fn pop_free(slab: *mut Slab) -> Result<Option<(*mut u8, bool)>, (&'static str, *mut u8)> {
+    let head = (*slab).free_head;
+    if head == FREE_NONE { return Ok(None); }
+    let sz = (*slab).obj_size as usize;
+    let p = payload_ptr(slab, head as usize, sz);
+    let state = *state_ptr(p);
+    if state >> 32 != MAGIC_FREE {
+        return Err(("freelist corrupted", p));
+    }
+    let poisoned = state & F_POISON != 0;
+    if poisoned && !poison_intact(p, sz) {
+        return Err(("use-after-free write", p));
+    }
+    (*slab).free_head = (state & VALUE_MASK) as u32;
+    (*slab).used += 1;
+    Ok(Some((p, poisoned)))
 }
 
 /// Allocate and format a new slab for class `class` size `sz`.
-unsafe fn new_slab(class: usize, sz: usize, pol: &HeapPolicy) -> *mut Slab {
-    // Choose pages: try to fit reasonable number of objects. Target ~4–8 KiB object space.
+/// Largest slab (64 KiB)
+const SLAB_MAX_PAGES: usize = 16;
+
+/// (pages, objects) of a slab for `sz`-byte objects: room for ~32 objects,
+/// at most `SLAB_MAX_PAGES`.
+const fn slab_geometry(sz: usize) -> (usize, usize) {
     let mut pages = 1usize;
-    while pages * PAGE_SIZE < (sz * 32 + mem::size_of::<Slab>() + 256) {
+    while pages < SLAB_MAX_PAGES && pages * PAGE_SIZE < sz * 32 + SLAB_META + 256 {
         pages *= 2;
-        if pages >= 16 { break; } // cap slab at 64 KiB
     }
+    (pages, (pages * PAGE_SIZE - SLAB_META) / (OBJ_HDR + sz))
+}
+
+// Every class must fit at least one object in its slab
+const _: () = {
+    let mut i = 0;
+    while i < CLASSES.len() {
+        assert!(slab_geometry(CLASSES[i]).1 >= 1);
+        i += 1;
+    }
+    assert!(CLASSES.len() == CLASSES_LEN);
+};
+
+unsafe fn new_slab(class: usize, sz: usize, h: &Heap) -> *mut Slab {
+    let (pages, cap) = slab_geometry(sz);
     // Map pages for the slab
-    let va = map_large_pages(pages, VmFlags::RW | VmFlags::NX | VmFlags::GLOBAL, false);
+    let va = map_large_pages(h, pages, VmFlags::RW | VmFlags::NX | VmFlags::GLOBAL, false);
     if va.is_null() { return core::ptr::null_mut(); }
 
-    // Layout: [Slab hdr][(obj_count * 4B) freelist array][objs each with 8B backptr prefix]
+    // Layout: [Slab hdr (SLAB_META)][objs: backptr | state | payload]
     let hdr = va.as_u64() as *mut Slab;
-    ptr::write_bytes(hdr as *mut u8, 0, mem::size_of::<Slab>());
-
-    let slab_bytes = pages * PAGE_SIZE;
-    let meta_off = mem::size_of::<Slab>();
-    let area = slab_bytes - meta_off;
-    // Each object stored as: [*mut Slab backptr (8B)] [payload (sz bytes)]
-    let stride = mem::size_of::<*mut Slab>() + sz;
-    let cap = area / stride;
+    ptr::write_bytes(hdr as *mut u8, 0, SLAB_META);
 
     (*hdr).class = class as u32;
     (*hdr).obj_size = sz as u32;
@@ -278,73 +644,64 @@ unsafe fn new_slab(class: usize, sz: usize, pol: &HeapPolicy) -> *mut Slab {
     (*hdr).base_va = VirtAddr::new(va.as_u64());
     (*hdr).pages = pages as u32;
 
-    // Build freelist: obj i's first 8B store slab backptr, next 4B store next index
+    // Build freelist: each header holds the slab backptr and a FREE state
+    // carrying the next index. Fresh pages are zero, not poisoned.
     for i in 0..cap {
-        let obj = obj_ptr(hdr, i, sz);
-        // back-pointer to slab
-        *(obj as *mut *mut Slab) = hdr;
-        // next index (u32) placed right after backptr
-        let next = if i + 1 < cap { (i + 1) as u32 } else { 0xffff_ffff };
-        let next_slot = obj.add(mem::size_of::<*mut Slab>()) as *mut u32;
-        *next_slot = next;
+        let p = payload_ptr(hdr, i, sz);
+        *(p.sub(OBJ_HDR) as *mut *mut Slab) = hdr;
+        let next = if i + 1 < cap { (i + 1) as u32 } else { FREE_NONE };
+        *state_ptr(p) = (MAGIC_FREE << 32) | next as u64;
     }
 
     hdr
 }
 
 #[inline]
-unsafe fn carve_obj_from_slab(slab: *mut Slab, pol: &HeapPolicy) -> Option<*mut u8> {
-    let head = (*slab).free_head;
-    if head == 0xffff_ffff { return None; }
-    let sz = (*slab).obj_size as usize;
-    let obj = obj_ptr(slab, head as usize, sz);
-    // advance head to "next" index
-    (*slab).free_head = read_u32_at(slab, head);
-    (*slab).used += 1;
+unsafe fn payload_ptr(slab: *mut Slab, index: usize, sz: usize) -> *mut u8 {
+    let base = (*slab).base_va.as_u64() as usize + SLAB_META;
+    (base + index * (OBJ_HDR + sz) + OBJ_HDR) as *mut u8
+}
 
-    let payload = obj.add(mem::size_of::<*mut Slab>());
-    if matches!(pol.zero, ZeroPolicy::OnAlloc) {
-        ptr::write_bytes(payload, 0, sz);
-    }
-    Some(payload)
+#[inline]
+unsafe fn obj_index_in_slab(slab: *mut Slab, payload: *mut u8) -> u32 {
+    let base = (*slab).base_va.as_u64() as usize + SLAB_META;
+    let stride = OBJ_HDR + (*slab).obj_size as usize;
+    ((payload as usize - OBJ_HDR - base) / stride) as u32
 }
 
 #[inline]
-unsafe fn obj_ptr(slab: *mut Slab, index: usize, sz: usize) -> *mut u8 {
-    let base = (*slab).base_va.as_u64() as usize + mem::size_of::<Slab>();
-    let stride = mem::size_of::<*mut Slab>() + sz;
-    (base + index * stride) as *mut u8
+unsafe fn slab_of(payload: *mut u8) -> *mut Slab {
+    *(payload.sub(OBJ_HDR) as *const *mut Slab)
 }
 
 #[inline]
-unsafe fn obj_index_in_slab(slab: *mut Slab, payload: *mut u8, sz: usize) -> u32 {
-    let base = (*slab).base_va.as_u64() as usize + mem::size_of::<Slab>();
-    let stride = mem::size_of::<*mut Slab>() + sz;
-    let obj = (payload as usize) - mem::size_of::<*mut Slab>();
-    ((obj - base) / stride) as u32
+unsafe fn state_ptr(payload: *mut u8) -> *mut u64 {
+    payload.sub(mem::size_of::<u64>()) as *mut u64
 }
 
 #[inline]
-unsafe fn read_u32_at(slab: *mut Slab, idx: u32) -> u32 {
-    let sz = (*slab).obj_size as usize;
-    let obj = obj_ptr(slab, idx as usize, sz);
-    let next_slot = obj.add(mem::size_of::<*mut Slab>()) as *const u32;
-    *next_slot
+fn canary_for(p: *mut u8) -> u64 {
+    CANARY ^ (p as u64).rotate_left(17)
 }
 
 #[inline]
-unsafe fn write_u32_at(slab: *mut Slab, idx: u32, val: u32) {
-    let sz = (*slab).obj_size as usize;
-    let obj = obj_ptr(slab, idx as usize, sz);
-    let next_slot = obj.add(mem::size_of::<*mut Slab>()) as *mut u32;
-    *next_slot = val;
+unsafe fn poison_intact(p: *mut u8, sz: usize) -> bool {
+    core::slice::from_raw_parts(p, sz).iter().all(|&b| b == POISON)
 }
 
 unsafe fn unmap_slab_pages(slab: *mut Slab) {
     // Unmap slab->pages pages and free their frames.
     let pages = (*slab).pages as usize;
     let base = (*slab).base_va;
-    unmap_large_pages(base, pages, false);
+    unmap_large_pages(base, pages);
+}
+
+/// Report a heap integrity violation. Called with the heap lock released
+/// (the panic path may allocate).
+#[cold]
+fn heap_fault(what: &'static str, p: *mut u8) -> ! {
+    FAULTS.fetch_add(1, Ordering::Relaxed);
+    panic!("[HEAP] {} at {:p}", what, p);
 }
 
 // ───────────────────────────────────────────────────────────────────────────────
@@ -352,69 +709,103 @@ unsafe fn unmap_slab_pages(slab: *mut Slab) {
 // ───────────────────────────────────────────────────────────────────────────────
 
 unsafe fn kalloc_large(size: usize, align: usize, zero_on_alloc: bool) -> *mut u8 {
-    // Round up to pages
+    // Round up to pages; mappings are page-aligned, which covers align <= PAGE_SIZE
+    debug_assert!(align <= PAGE_SIZE);
     let pages = ((size + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
-    let base = map_large_pages(pages, VmFlags::RW | VmFlags::NX | VmFlags::GLOBAL, HEAP.lock().pol.guard_large);
+    let h = HEAP.lock();
+    let base = map_large_pages(&h, pages, VmFlags::RW | VmFlags::NX | VmFlags::GLOBAL, h.pol.guard_large);
     if base.is_null() { return core::ptr::null_mut(); }
 
-    // Alignment: if caller asked for > 4K, we can over-map and return aligned subrange.
-    // For now keep it simple: require align <= 4K for large path. (Extend later: overmap+trim)
-    debug_assert!(align <= PAGE_SIZE);
-
     let ptr = base.as_u64() as *mut u8;
-    if zero_on_alloc || matches!(HEAP.lock().pol.zero, ZeroPolicy::OnAlloc) {
+    if zero_on_alloc || matches!(h.pol.zero, ZeroPolicy::OnAlloc) {
         ptr::write_bytes(ptr, 0, pages * PAGE_SIZE);
     }
 
-    HEAP.lock().alloc_large.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    h.alloc_large.fetch_add(1, Ordering::Relaxed);
     ptr
 }
 
 unsafe fn kfree_large(p: *mut u8, size: usize) {
     let pages = ((size + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
-    // We assume kalloc_large mapped exactly `pages` (plus guards managed by helper)
-    unmap_large_pages(VirtAddr::new(p as u64), pages, HEAP.lock().pol.guard_large);
-    HEAP.lock().free_large.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    let pol = HEAP.lock().pol;
+    // A large allocation is live iff its first page is mapped
+    if virt::translate(VirtAddr::new(p as u64)).is_err() {
+        if pol.double_free {
+            heap_fault("double free (large)", p);
+        }
+        return;
+    }
+    if matches!(pol.zero, ZeroPolicy::OnFree) {
+        ptr::write_bytes(p, 0, pages * PAGE_SIZE);
+    }
+    // Guard pages were never mapped; only the body comes back
+    mapcheck::untrack(VirtAddr::new(p as u64));
+    unmap_large_pages(VirtAddr::new(p as u64), pages);
+    HEAP.lock().free_large.fetch_add(1, Ordering::Relaxed);
 }
 
-/// Map `pages` of anonymous kernel memory; if guard=true add one guard page before & after.
-unsafe fn map_large_pages(pages: usize, flags: VmFlags, guard: bool) -> VirtAddr {
-    let h = HEAP.lock();
+/// Map `pages` of anonymous kernel memory; if guard=true leave one unmapped
+/// guard page before & after. Spans of 2 MiB or more start on a 2 MiB
+/// boundary and use 2 MiB pages where contiguous frames exist (policy
+/// `huge_pages`). Returns a null VA (nothing left mapped) if phys or the
+/// mapper runs out.
+unsafe fn map_large_pages(h: &Heap, pages: usize, flags: VmFlags, guard: bool) -> VirtAddr {
+    const HUGE_PAGES: usize = HUGE_2M / PAGE_SIZE;
     let mut cursor = *h.vm_cursor.get();
     // align cursor to page
     cursor = (cursor + (PAGE_SIZE as u64 - 1)) & !((PAGE_SIZE as u64) - 1);
 
     let guard_pages = if guard { 1 } else { 0 };
     let total = pages + 2 * guard_pages;
+    let huge = h.pol.huge_pages && pages >= HUGE_PAGES;
+    if huge {
+        // body on a 2 MiB boundary, guard page just below it
+        let guard_bytes = (guard_pages * PAGE_SIZE) as u64;
+        cursor = align_up(cursor + guard_bytes, HUGE_2M as u64) - guard_bytes;
+    }
 
     // Map pages with fresh frames
     let start_va = VirtAddr::new(cursor + (guard_pages * PAGE_SIZE) as u64);
-    for i in 0..pages {
-        // pick zone prefs
-        let mut pflags = if h.pol.prefer_lowmem { PFlags::LOWMEM } else { PFlags::empty() };
-        // allocate one frame
-        let f = phys::alloc(pflags).expect("OOM phys in map_large_pages");
-        // map VA -> PA
+    let pflags = if h.pol.prefer_lowmem { PFlags::LOWMEM } else { PFlags::empty() };
+    let mut i = 0;
+    while i < pages {
         let va = VirtAddr::new(start_va.as_u64() + (i * PAGE_SIZE) as u64);
-        virt::map4k_at(va, PhysAddr::new(f.0), flags).expect("map4k failed");
+        if huge && pages - i >= HUGE_PAGES && va.is_aligned(HUGE_2M as u64) {
+            if let Some(f) = phys::alloc_contig(HUGE_PAGES, HUGE_PAGES, pflags) {
+                if virt::map2m_at(va, PhysAddr::new(f.0), flags).is_ok() {
+                    h.huge_maps.fetch_add(1, Ordering::Relaxed);
+                    i += HUGE_PAGES;
+                    continue;
+                }
+                phys::free_contig(f, HUGE_PAGES);
+            }
+            h.huge_fallbacks.fetch_add(1, Ordering::Relaxed);
+        }
+        let mapped = match phys::alloc(pflags) {
+            Some(f) => virt::map4k_at(va, PhysAddr::new(f.0), flags).map_err(|_| phys::free(f)).is_ok(),
+            None => false,
+        };
+        if !mapped {
+            unmap_large_pages(start_va, i);
+            return VirtAddr::zero();
+        }
         // phys allocation already audited by phys; map audited by virt
+        i += 1;
     }
     // Move cursor
     *h.vm_cursor.get() = cursor + (total * PAGE_SIZE) as u64;
+    if guard {
+        mapcheck::track_fenced(start_va, pages);
+    }
 
     start_va
 }
 
-unsafe fn unmap_large_pages(base: VirtAddr, pages: usize, guard: bool) {
-    let guard_pages = if guard { 1 } else { 0 };
-    // Unmap in reverse to reduce transient overlap (cosmetic)
-    for i in (0..pages).rev() {
-        let va = VirtAddr::new(base.as_u64() + (i * PAGE_SIZE) as u64);
-        // translate to get PA to free frame
-        if let Ok((pa, _f, _sz)) = virt::translate(va) {
-            virt::unmap4k(va).expect("unmap4k");
-            phys::free(Frame(pa.as_u64()));
-        }
+unsafe fn unmap_large_pages(base: VirtAddr, pages: usize) {
+    // Whole 2 MiB pages go back as one contiguous run, 4 KiB pages one by
+    // one; either way every frame returns to phys
+    if pages > 0 {
+        let _ = virt::unmap_range(base, pages * PAGE_SIZE);
     }
     // (guard pages are unmapped implicitly as we never mapped them)
 }
@@ -422,8 +813,36 @@ unsafe fn unmap_large_pages(base: VirtAddr, pages: usize, guard: bool) {
 // ───────────────────────────────────────────────────────────────────────────────
 // Notes
 //  - Magazine is per-CPU but we haven't implemented stealing/cross-CPU yet.
-//  - Double-free defense is minimal (slab backptr + index math).
-//  - For real SMP, add a small quarantine ring per class to catch ABA.
-//  - Overaligned large allocations could overmap+trim (TODO).
-//  - Fragmentation: slab empty reclaim does page unmap immediately.
+//  - Double-free defense: per-object state word (LIVE/FREE/QUAR), optional.
+//  - Quarantine is global; for real SMP, make it per-CPU per class.
+//  - Overaligned large allocations (> PAGE_SIZE) are refused.
+//  - Fragmentation: slab empty reclaim caches MAG_EMPTY_MAX per class.
 // ───────────────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_class_fits_its_slab() {
+        for (class, &sz) in CLASSES.iter().enumerate() {
+            let (pages, cap) = slab_geometry(sz);
+            assert!(cap >= 1, "class {} ({} B) has no room in {} pages", class, sz, pages);
+            assert!(pages <= SLAB_MAX_PAGES, "class {} ({} B) slab too large", class, sz);
+            assert!(SLAB_META + cap * (OBJ_HDR + sz) <= pages * PAGE_SIZE);
+            assert!((cap as u32) < FREE_NONE);
+        }
+    }
+
+    #[test]
+    fn sizes_route_to_the_smallest_class() {
+        for (class, &sz) in CLASSES.iter().enumerate() {
+            assert_eq!(small_class(sz, MIN_ALIGN), Some(class));
+            assert_eq!(small_class(sz - 1, MIN_ALIGN), Some(class));
+        }
+        // 64 KiB and up, or over-aligned, take the VM path
+        assert_eq!(small_class(CLASSES[CLASSES_LEN - 1] + 1, MIN_ALIGN), None);
+        assert_eq!(small_class(65536, MIN_ALIGN), None);
+        assert_eq!(small_class(64, MIN_ALIGN * 2), None);
+    }
+}
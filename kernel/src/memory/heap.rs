@@ -1,15 +1,20 @@
 //! NØNOS Kernel Heap Initialization
 //!
-//! This module sets up a virtual heap for dynamic memory allocation in the kernel
-//! using `linked_list_allocator`. The heap is mapped during paging init and supports
-//! RAM-only operation under the ZeroState runtime. Future extensions may include
-//! multiple heap pools, fragmentation diagnostics, and mod-specific allocators.
+//! This module owns the kernel `#[global_allocator]`. Early boot allocations are
+//! served from a small `linked_list_allocator` arena mapped during paging init;
+//! once phys and virt are online, `init` hands the allocator over to the slab/VM
+//! heap in `memory::alloc` (canaries, quarantine, poison, double-free checks per
+//! `HeapPolicy`). Arena blocks stay valid and are returned to the arena when freed.
+//! Supports RAM-only operation under the ZeroState runtime.
 
 use core::alloc::{GlobalAlloc, Layout};
 use core::ptr::null_mut;
 use core::sync::atomic::{AtomicBool, Ordering};
 use linked_list_allocator::LockedHeap;
-use spin::Mutex;
+
+use crate::memory::alloc as kalloc;
+
+pub use crate::memory::alloc::HeapPolicy;
 
 /// Static bounds for heap (will later support dynamic regions)
 pub const HEAP_START: usize = 0x_4444_0000;
@@ -17,20 +22,41 @@ pub const HEAP_SIZE: usize = 1024 * 1024 * 2; // 2 MiB
 
 /// Global kernel heap instance
 #[global_allocator]
-static KERNEL_HEAP: LockedHeap = LockedHeap::empty();
+static KERNEL_HEAP: KernelHeap = KernelHeap;
+
+/// Early boot arena (before phys/virt are up)
+static EARLY_HEAP: LockedHeap = LockedHeap::empty();
 
 /// Optional heap enablement tracking
 static HEAP_ENABLED: AtomicBool = AtomicBool::new(false);
 
-/// Initializes the global heap for kernel use
+/// Set once `memory::alloc` serves new allocations
+static SLAB_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Initializes the early boot arena (must already be mapped)
 pub fn init_kernel_heap() {
     unsafe {
-        KERNEL_HEAP.lock().init(HEAP_START as *mut u8, HEAP_SIZE);
+        EARLY_HEAP.lock().init(HEAP_START as *mut u8, HEAP_SIZE);
         HEAP_ENABLED.store(true, Ordering::SeqCst);
     }
     log_heap_status("[HEAP] Kernel heap initialized");
 }
 
+/// Switches the global allocator to the slab/VM heap with `policy`.
+/// Requires phys and virt; the policy can be changed later via
+/// `memory::alloc::set_policy`.
+pub fn init(policy: HeapPolicy) {
+    kalloc::init(policy);
+    SLAB_ENABLED.store(true, Ordering::SeqCst);
+    log_heap_status("[HEAP] Slab heap online");
+}
+
+#[inline]
+fn in_early_arena(ptr: *mut u8) -> bool {
+    let p = ptr as usize;
+    p >= HEAP_START && p < HEAP_START + HEAP_SIZE
+}
+
 /// Log message to VGA or logging backend
 fn log_heap_status(msg: &str) {
     if let Some(logger) = crate::log::logger::try_get_logger() {
@@ -46,21 +72,37 @@ fn log_heap_status(msg: &str) {
     }
 }
 
-/// Custom allocator fallback used in early boot
-pub struct DummyAllocator;
+/// Global allocator: early arena until `init`, then `memory::alloc`
+pub struct KernelHeap;
 
-unsafe impl GlobalAlloc for DummyAllocator {
+unsafe impl GlobalAlloc for KernelHeap {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        if HEAP_ENABLED.load(Ordering::SeqCst) {
-            KERNEL_HEAP.alloc(layout)
+        if SLAB_ENABLED.load(Ordering::Acquire) {
+            kalloc::kmem_alloc(layout.size(), layout.align())
+        } else if HEAP_ENABLED.load(Ordering::Acquire) {
+            EARLY_HEAP.alloc(layout)
+        } else {
+            null_mut()
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if SLAB_ENABLED.load(Ordering::Acquire) {
+            kalloc::kmem_alloc_zero(layout.size(), layout.align()).unwrap_or(null_mut())
+        } else if HEAP_ENABLED.load(Ordering::Acquire) {
+            EARLY_HEAP.alloc_zeroed(layout)
         } else {
             null_mut()
         }
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        if HEAP_ENABLED.load(Ordering::SeqCst) {
-            KERNEL_HEAP.dealloc(ptr, layout)
+        if in_early_arena(ptr) {
+            if HEAP_ENABLED.load(Ordering::Acquire) {
+                EARLY_HEAP.dealloc(ptr, layout)
+            }
+        } else if SLAB_ENABLED.load(Ordering::Acquire) {
+            kalloc::kmem_free(ptr, layout.size(), layout.align())
         }
     }
 }
@@ -3,7 +3,11 @@
 // NØNOS KASLR — hardened, policy-driven.
 //  - Entropy transcript (RDSEED/RDRAND + TSC jitter + CPUID + optional boot salt)
 //  - SHA3-based DRBG (counter mode) for deterministic, audit-friendly randomness
-//  - Slide chooser with alignment/window + forbidden-range avoidance
+//  - Image slide is applied by the bootloader (relocated for and mapped at
+//    KERNEL_BASE + slide, boot/src/kaslr.rs + paging.rs) and adopted here
+//    from the handoff after a window check; the transcript commits to it
+//  - Region bases (heap, direct map, per-CPU) chosen in-window with
+//    alignment + forbidden-range avoidance (`nonos-kaslr`)
 //  - Per-CPU offsets (within PERCPU_STRIDE) + per-CPU stack canary seeds
 //  - HKDF-style subkey derivation for other subsystems
 //  - Proof hooks: commit transcript hash + chosen params; no raw entropy
@@ -12,7 +16,9 @@
 
 #![allow(dead_code)]
 
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use spin::Once;
+use crate::boot::handoff::ZeroStateBootInfo;
 use crate::crypto::sha3::Sha3_256;
 use crate::memory::layout as L;
 use crate::memory::proof::{self, CapTag};
@@ -20,7 +26,7 @@ use crate::memory::proof::{self, CapTag};
 /// Public result
 #[derive(Clone, Copy)]
 pub struct Kaslr {
-    pub slide: u64,               // kernel image slide (bytes, 2MiB-aligned)
+    pub slide: u64,               // image run - link address (2MiB-aligned, < SLIDE_WINDOW; 0 = unslid)
     pub transcript_hash: [u8;32], // Keccak-256 of entropy transcript
     pub boot_nonce: u64,          // public nonce (for proof domain sep)
 }
@@ -32,7 +38,7 @@ pub struct Policy {
     pub align: u64,
     /// Max slide window (bytes) from base (exclusive of forbidden).
     pub window_bytes: u64,
-    /// Forbidden virtual ranges (VA) to avoid (e.g., debug windows).
+    /// Forbidden virtual ranges (VA) no slid image or region base may start in.
     pub deny: &'static [Range],
     /// Per-CPU offset max (bytes) within PERCPU_STRIDE (must be page-aligned).
     pub percpu_jitter_max: u64,
@@ -40,8 +46,50 @@ pub struct Policy {
     pub cpu_count: u32,
     /// Optional bootloader-provided salt (digest already computed outside).
     pub boot_salt: Option<[u8;32]>,
+    /// Slide the bootloader already applied to the image (None = unslid).
+    pub boot_slide: Option<u64>,
 }
 
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            align: SLIDE_ALIGN,
+            window_bytes: SLIDE_WINDOW,
+            deny: &[],
+            percpu_jitter_max: L::PERCPU_STRIDE / 2,
+            cpu_count: MAX_CPUS as u32,
+            boot_salt: None,
+            boot_slide: None,
+        }
+    }
+}
+
+impl Policy {
+    /// Default policy seeded from the handoff block: its entropy as boot salt,
+    /// its image slide (if the bootloader applied one)
+    pub fn from_boot(info: Option<&ZeroStateBootInfo>) -> Self {
+        let mut p = Self::default();
+        if let Some(zs) = info {
+            let mut h = Sha3_256::new();
+            h.update(b"NONOS:KASLR:BOOTSALT");
+            h.update(&zs.entropy());
+            p.boot_salt = Some(h.finalize_bytes());
+            p.boot_slide = zs.kaslr_slide();
+        }
+        p
+    }
+}
+
+/// Image slide granularity / window; must match boot/src/kaslr.rs
+pub const SLIDE_ALIGN: u64 = 2 * 1024 * 1024;
+pub const SLIDE_WINDOW: u64 = 1024 * 1024 * 1024;
+/// CPUs with a per-CPU offset slot
+pub const MAX_CPUS: usize = 64;
+
+/// `applied()` bits
+pub const APPLIED_IMAGE: u8 = 1 << 0;   // image runs slid (bootloader relocated it)
+pub const APPLIED_REGIONS: u8 = 1 << 1; // heap / direct map / per-CPU bases randomized
+
 /// Half-open VA range.
 #[derive(Clone, Copy)]
 pub struct Range { pub lo: u64, pub hi: u64 }
@@ -49,12 +97,19 @@ impl Range { #[inline] pub const fn contains(&self, x: u64) -> bool { x >= self.
 
 /// Global boot nonce for other subsystems (proof domain sep).
 static BOOT_NONCE: AtomicU64 = AtomicU64::new(0);
-
-/// Initialize KASLR with a strict policy. Call exactly once in early boot.
+/// Transcript hash of the first (boot) KASLR run, for attestation.
+static TRANSCRIPT: Once<[u8;32]> = Once::new();
+/// What the boot run actually applied (`APPLIED_*`).
+static APPLIED: AtomicU8 = AtomicU8::new(0);
+/// Per-CPU page offset inside each PERCPU stripe.
+static PERCPU_OFF: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+/// Initialize KASLR with a strict policy. Call exactly once in early boot,
+/// after virt and before the heap (region bases are read by the allocator).
 pub unsafe fn init(policy: Policy) -> Kaslr {
-    // 1) Collect entropy transcript
+    // 1) Collect entropy transcript (commits to the bootloader's slide)
     let mut buf = [0u8; 384];
-    let used = collect_entropy(&mut buf, policy.boot_salt);
+    let used = collect_entropy(&mut buf, policy.boot_salt, policy.boot_slide);
 
     // 2) Transcript hash (public)
     let mut h = Sha3_256::new();
@@ -64,18 +119,31 @@ pub unsafe fn init(policy: Policy) -> Kaslr {
     // 3) Seed DRBG from transcript
     let mut drbg = Drbg::seed(&transcript);
 
-    // 4) Choose kernel slide
-    let slide = choose_slide(&mut drbg, policy);
+    // 4) Adopt the image slide: the image is wherever the bootloader put it
+    let slide = policy.boot_slide.unwrap_or(0);
+    let mut applied = 0;
+    if slide != 0 {
+        applied |= APPLIED_IMAGE;
+        if !slide_in_policy(slide, policy) {
+            crate::serial_println!("[KASLR] boot slide outside policy window");
+        }
+    }
 
     // 5) Publish boot nonce (public) from transcript (bytes 8..16 LE)
     let mut nb = [0u8; 8]; nb.copy_from_slice(&transcript[8..16]);
     let nonce = u64::from_le_bytes(nb);
     BOOT_NONCE.store(nonce, Ordering::Relaxed);
+    TRANSCRIPT.call_once(|| transcript);
 
     // 6) Write into runtime layout
     L::LAYOUT.slide = slide;
+    if cfg!(feature = "nonos-kaslr") {
+        randomize_regions(&mut drbg, policy);
+        applied |= APPLIED_REGIONS;
+    }
+    APPLIED.store(applied, Ordering::Relaxed);
 
-    // 7) Seed per-CPU offsets/canaries deterministically (no mapping here; just recordable randomness)
+    // 7) Seed per-CPU offsets/canaries deterministically (consumed by layout::percpu_base)
     seed_percpu(&mut drbg, policy);
 
     // 8) Proof events (public commitments only)
@@ -95,11 +163,22 @@ pub unsafe fn init(policy: Policy) -> Kaslr {
 /// Get public boot nonce for other subsystems (proof domain sep).
 #[inline] pub fn boot_nonce() -> u64 { BOOT_NONCE.load(Ordering::Relaxed) }
 
+/// Public transcript hash of the boot KASLR run (zeros before init).
+#[inline] pub fn transcript_hash() -> [u8;32] { TRANSCRIPT.get().copied().unwrap_or([0;32]) }
+
+/// What the boot run applied (`APPLIED_IMAGE` | `APPLIED_REGIONS`; 0 before init).
+#[inline] pub fn applied() -> u8 { APPLIED.load(Ordering::Relaxed) }
+
+/// Page-aligned offset of `cpu`'s area inside its PERCPU stripe.
+#[inline] pub fn percpu_offset(cpu: usize) -> u64 {
+    PERCPU_OFF.get(cpu).map_or(0, |o| o.load(Ordering::Relaxed))
+}
+
 // ───────────────────────────────────────────────────────────────────────────────
 // Entropy collection (with basic health checks)
 // ───────────────────────────────────────────────────────────────────────────────
 
-unsafe fn collect_entropy(out: &mut [u8], boot_salt: Option<[u8;32]>) -> usize {
+unsafe fn collect_entropy(out: &mut [u8], boot_salt: Option<[u8;32]>, boot_slide: Option<u64>) -> usize {
     let mut w = 0;
 
     // A) CPUID salt (model/stepping/vendor/feature bits)
@@ -110,6 +189,13 @@ unsafe fn collect_entropy(out: &mut [u8], boot_salt: Option<[u8;32]>) -> usize {
         if out.len() - w >= 32 { out[w..w+32].copy_from_slice(&s); w += 32; }
     }
 
+    // B') Bootloader image slide, tagged so "no slide" and "slide 0" differ
+    if out.len() - w >= 16 {
+        out[w..w+8].copy_from_slice(b"KSLIDE\0\0");
+        out[w+8..w+16].copy_from_slice(&boot_slide.unwrap_or(u64::MAX).to_le_bytes());
+        w += 16;
+    }
+
     // C) RDSEED / RDRAND (with repetition test)
     let mut rng_ok = false;
     if has_rdseed() { w += fill_rdseed_checked(&mut out[w..], &mut rng_ok); }
@@ -225,29 +311,41 @@ pub fn derive_subkey(label:&[u8], ctx:&[u8], out:&mut [u8], transcript:&[u8;32])
 }
 
 // ───────────────────────────────────────────────────────────────────────────────
-// Slide chooser with alignment/window + forbidden range avoidance
+// Slide policy + region base chooser (alignment/window + forbidden-range avoidance)
 // ───────────────────────────────────────────────────────────────────────────────
 
-fn choose_slide(drbg:&mut Drbg, p:Policy)->u64{
-    let gran = if p.align==0 { 2*1024*1024 } else { p.align };
-    let steps = (p.window_bytes.max(gran) / gran) as u64;
+fn slide_in_policy(slide:u64, p:Policy)->bool{
+    let gran = if p.align==0 { SLIDE_ALIGN } else { p.align };
+    slide % gran == 0 && slide < p.window_bytes.max(gran) && !violates_deny(L::KERNEL_BASE.wrapping_add(slide), p.deny)
+}
+
+/// Pick a `gran`-aligned base in [lo, lo+span) avoiding denied ranges; `lo` if everything collides
+fn choose_base(drbg:&mut Drbg, lo:u64, span:u64, gran:u64, deny:&[Range])->u64{
+    let steps = span / gran;
     // try a few times to avoid deny ranges
     for _ in 0..64 {
-        let step = drbg.choose_u64(steps);
-        let slide = step * gran;
-        if !violates_deny(slide, p.deny) { return slide; }
+        let base = lo + drbg.choose_u64(steps) * gran;
+        if !violates_deny(base, deny) { return base; }
     }
-    // fallback: 0 (still valid) if everything collides
-    0
+    lo
 }
-fn violates_deny(slide:u64, deny:&[Range])->bool {
-    // We only slide kernel image regions; check text/data windows quickly if you want.
-    let base = L::KERNEL_BASE.wrapping_add(slide);
-    for r in deny {
-        // If the start of text falls inside a denied range, treat as violation
-        if r.contains(base) { return true; }
-    }
-    false
+fn violates_deny(base:u64, deny:&[Range])->bool {
+    // If the start of the region falls inside a denied range, treat as violation
+    deny.iter().any(|r| r.contains(base))
+}
+
+/// Randomize region bases inside their fixed windows. Each region keeps half
+/// its window; the other half is the randomization slack.
+unsafe fn randomize_regions(drbg:&mut Drbg, p:Policy){
+    const GIB: u64 = 1024 * 1024 * 1024;
+    // heap: 2 MiB steps (slabs + large mappings are carved from heap_lo)
+    L::LAYOUT.heap_sz = L::KHEAP_SIZE / 2;
+    L::LAYOUT.heap_lo = choose_base(drbg, L::KHEAP_BASE, L::KHEAP_SIZE - L::LAYOUT.heap_sz, SLIDE_ALIGN, p.deny);
+    // direct map: 1 GiB steps so it stays huge-page mappable
+    L::LAYOUT.directmap_sz = L::DIRECTMAP_SIZE / 2;
+    L::LAYOUT.directmap_lo = choose_base(drbg, L::DIRECTMAP_BASE, L::DIRECTMAP_SIZE - L::LAYOUT.directmap_sz, GIB, p.deny);
+    // per-CPU stripes: whole-window shift in stride steps; per-CPU jitter on top (seed_percpu)
+    L::LAYOUT.percpu_lo = choose_base(drbg, L::PERCPU_BASE, L::PERCPU_STRIDE * MAX_CPUS as u64, L::PERCPU_STRIDE, p.deny);
 }
 
 // ───────────────────────────────────────────────────────────────────────────────
@@ -257,7 +355,7 @@ fn violates_deny(slide:u64, deny:&[Range])->bool {
 fn seed_percpu(drbg:&mut Drbg, p:Policy){
     let stride = L::PERCPU_STRIDE;
     let jitter_cap = p.percpu_jitter_max.min(stride).max(L::PAGE_SIZE as u64);
-    for cpu in 0..p.cpu_count {
+    for cpu in 0..p.cpu_count.min(MAX_CPUS as u32) {
         // per-CPU offset (page-aligned) within stride
         let pages = (jitter_cap / L::PAGE_SIZE as u64) as u64;
         let off_pages = drbg.choose_u64(pages);
@@ -270,7 +368,11 @@ fn seed_percpu(drbg:&mut Drbg, p:Policy){
         // pack: len=offset, paddr=canary for visibility; tagged as KERNEL
         proof::audit_phys_alloc(canary, offset, CapTag::KERNEL);
 
-        // (actual mapping of PERCPU regions uses offset when you set up TLS)
-        let _ = (cpu, offset, canary); // consumed by percpu mapper later
+        // layout::percpu_base adds this on top of the stripe (virt::map_percpu
+        // maps the area there); regions-off keeps 0
+        if cfg!(feature = "nonos-kaslr") {
+            PERCPU_OFF[cpu as usize].store(offset, Ordering::Relaxed);
+        }
+        let _ = canary; // consumed by percpu mapper later
     }
 }
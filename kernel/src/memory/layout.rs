@@ -7,6 +7,7 @@
 #![allow(dead_code)]
 
 use core::ops::Range;
+use crate::boot::handoff::bootinfo::{MemoryRegion, MemoryRegionType};
 
 // ───────────────────────────────────────────────────────────────────────────────
 // Page sizes, masks, canonical
@@ -56,6 +57,10 @@ pub const KDATA_BASE: u64 = KERNEL_BASE + 0x0000_0200_0000; // .data/.bss/percpu
 pub const DIRECTMAP_BASE: u64 = KERNEL_BASE + 0x0000_4000_0000; // phys→virt linear window
 pub const DIRECTMAP_SIZE: u64 = 0x0002_0000_0000;               // 128 GiB direct map
 
+// Bootloader's alias of physical memory (boot/src/paging.rs PHYS_OFFSET);
+// page tables are reached through it until virt::map_directmap runs
+pub const BOOT_PHYS_OFFSET: u64 = 0xFFFF_8000_0000_0000;
+
 pub const KHEAP_BASE:  u64 = KERNEL_BASE + 0x0002_4000_0000; // kernel heap arena (VA only)
 pub const KHEAP_SIZE:  u64 = 0x0000_8000_0000;               // 32 GiB
 
@@ -166,7 +171,15 @@ pub fn directmap_va(paddr: u64) -> Option<u64> {
             }
         }
     }
-    if paddr < DIRECTMAP_SIZE { Some(DIRECTMAP_BASE + paddr) } else { None }
+    let (lo, sz) = unsafe { (LAYOUT.directmap_lo, LAYOUT.directmap_sz) };
+    if paddr < sz { Some(lo + paddr) } else { None }
+}
+
+/// Base of `cpu`'s per-CPU area: its stripe in the (possibly shifted) PERCPU
+/// window plus the KASLR per-CPU offset
+#[inline(always)]
+pub fn percpu_base(cpu: usize) -> u64 {
+    unsafe { LAYOUT.percpu_lo } + cpu as u64 * PERCPU_STRIDE + crate::memory::kaslr::percpu_offset(cpu)
 }
 
 // ───────────────────────────────────────────────────────────────────────────────
@@ -191,6 +204,20 @@ pub fn region_from_firmware(kind_code: u32, start: u64, len: u64) -> Region {
     Region { start, len, kind }
 }
 
+/// Region from the bootloader's memory map extension. Boot-loaded ranges
+/// (kernel image, capsule, handoff) stay out of the allocator; bad RAM is
+/// simply reserved.
+pub fn region_from_handoff(r: &MemoryRegion) -> Region {
+    let kind = match r.kind() {
+        MemoryRegionType::Usable => RegionKind::Usable,
+        MemoryRegionType::AcpiReclaimable | MemoryRegionType::AcpiNvs => RegionKind::Acpi,
+        MemoryRegionType::Mmio => RegionKind::Mmio,
+        MemoryRegionType::BootLoaded => RegionKind::Boot,
+        MemoryRegionType::Reserved | MemoryRegionType::BadMemory => RegionKind::Reserved,
+    };
+    Region { start: r.start, len: r.len, kind }
+}
+
 pub fn managed_span(rs: &[Region]) -> (u64, u64) {
     let mut lo = u64::MAX; let mut hi = 0u64;
     for r in rs {
@@ -215,7 +242,7 @@ pub fn managed_span(rs: &[Region]) -> (u64, u64) {
 #[inline(always)] pub const fn range(base: u64, size: u64) -> Range<u64> { base..(base + size) }
 
 // ───────────────────────────────────────────────────────────────────────────────
-// Runtime layout config (KASLR slide, dynamic window trims)
+// Runtime layout config (KASLR slide + randomized bases, dynamic window trims)
 // ───────────────────────────────────────────────────────────────────────────────
 
 #[derive(Clone, Copy, Debug)]
@@ -227,6 +254,9 @@ pub struct LayoutConfig {
     pub vm_sz:   u64,
     pub mmio_lo: u64,
     pub mmio_sz: u64,
+    pub directmap_lo: u64, // linear map base (KASLR may shift it inside DIRECTMAP window)
+    pub directmap_sz: u64,
+    pub percpu_lo: u64,    // first PERCPU stripe
 }
 
 impl Default for LayoutConfig {
@@ -236,6 +266,8 @@ impl Default for LayoutConfig {
             heap_lo: KHEAP_BASE, heap_sz: KHEAP_SIZE,
             vm_lo:   KVM_BASE,   vm_sz:   KVM_SIZE,
             mmio_lo: MMIO_BASE,  mmio_sz: MMIO_SIZE,
+            directmap_lo: DIRECTMAP_BASE, directmap_sz: DIRECTMAP_SIZE,
+            percpu_lo: PERCPU_BASE,
         }
     }
 }
@@ -245,6 +277,8 @@ pub static mut LAYOUT: LayoutConfig = LayoutConfig {
     heap_lo: KHEAP_BASE, heap_sz: KHEAP_SIZE,
     vm_lo:   KVM_BASE,   vm_sz:   KVM_SIZE,
     mmio_lo: MMIO_BASE,  mmio_sz: MMIO_SIZE,
+    directmap_lo: DIRECTMAP_BASE, directmap_sz: DIRECTMAP_SIZE,
+    percpu_lo: PERCPU_BASE,
 };
 
 #[inline(always)] pub fn apply_slide(va: u64, slide: u64) -> u64 { va.wrapping_add(slide) }
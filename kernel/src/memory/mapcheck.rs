@@ -0,0 +1,325 @@
+//! NØNOS Mapping Invariant Scanner
+//!
+//! Walks the live kernel page tables (`virt::walk_leaves`) and checks every
+//! present mapping, kernel and capsule alike, against mapping policy:
+//! - no mapping is both writable and executable (W^X), at any page size
+//! - user pages are never kernel-executable: no USER pages in the kernel
+//!   half, and no user-executable page while SMEP is off
+//! - registered stacks are NX (`nonos-nx-stack`)
+//! - registered guard pages are still unmapped
+//!
+//! Stacks and guards are registered by whoever maps them
+//! (`virt::map_stack_with_guard`, task stacks, IST stacks, guarded heap
+//! mappings) in a fixed table: registration never allocates, so it is safe
+//! underneath the heap lock.
+//!
+//! Scans run on the `memory.mapcheck` task and on demand (`mem.wx`). Each
+//! violation is recorded into `memory::proof` (`Kind::Violation`) and
+//! published as `Event::MapViolation`; a periodic scan that finds exactly the
+//! previous scan's violations does not re-record them. With `nonos-wx-audit`
+//! any violation is fatal. Totals are carried in attestation quotes.
+
+use alloc::format;
+use arrayvec::ArrayVec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::registers::control::{Cr4, Cr4Flags};
+use x86_64::VirtAddr;
+
+use crate::log::logger::{log_info, log_warn};
+use crate::memory::layout::PAGE_SIZE;
+use crate::memory::proof::{self, CapTag};
+use crate::memory::virt::{self, VmErr, VmFlags};
+use crate::ui::event::{self, Event, Pri};
+
+/// What a mapping got wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ViolationKind {
+    /// Writable and executable
+    Rwx = 1,
+    /// USER page in the kernel half
+    UserInKernelHalf = 2,
+    /// User-executable page the kernel could execute (SMEP off)
+    UserExec = 3,
+    /// Registered stack page without NX
+    ExecStack = 4,
+    /// Registered guard page is mapped
+    GuardMapped = 5,
+}
+
+impl ViolationKind {
+    pub const COUNT: usize = 5;
+
+    fn index(self) -> usize {
+        self as usize - 1
+    }
+}
+
+/// One finding
+#[derive(Debug, Clone, Copy)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub va: u64,
+    pub page_size: usize,
+    pub flags: VmFlags,
+}
+
+/// Result of one full scan
+#[derive(Debug, Clone, Copy)]
+pub struct ScanReport {
+    pub timestamp_ns: u64,
+    /// Leaf mappings visited
+    pub mappings: u64,
+    /// Registered stacks and guards checked
+    pub tracked: usize,
+    pub violations: u32,
+    pub by_kind: [u32; ViolationKind::COUNT],
+    pub first: Option<Violation>,
+}
+
+impl ScanReport {
+    fn new(now: u64) -> Self {
+        Self { timestamp_ns: now, mappings: 0, tracked: 0, violations: 0, by_kind: [0; ViolationKind::COUNT], first: None }
+    }
+
+    pub fn clean(&self) -> bool {
+        self.violations == 0
+    }
+}
+
+/// Running totals (CLI / attestation)
+#[derive(Debug, Clone, Copy)]
+pub struct MapcheckStats {
+    pub scans: u64,
+    pub violations_total: u64,
+    pub last: Option<ScanReport>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TrackKind {
+    Stack,
+    Guard,
+    /// Mapping with an unmapped guard page on each side
+    Fenced,
+}
+
+#[derive(Clone, Copy)]
+struct Tracked {
+    kind: TrackKind,
+    base: u64,
+    pages: u32,
+    /// Guard page below a stack (0 = none)
+    guard: u64,
+}
+
+/// Registered stacks + guards; beyond this, registrations are counted and dropped
+const TRACK_MAX: usize = 512;
+/// Periodic scan interval
+const SCAN_INTERVAL_NS: u64 = 5_000_000_000;
+
+static TRACKED: Mutex<[Option<Tracked>; TRACK_MAX]> = Mutex::new([None; TRACK_MAX]);
+static TRACK_OVERFLOW: AtomicU64 = AtomicU64::new(0);
+
+static SCANS: AtomicU64 = AtomicU64::new(0);
+static VIOLATIONS: AtomicU64 = AtomicU64::new(0);
+static LAST: Mutex<Option<ScanReport>> = Mutex::new(None);
+/// Fingerprint of the previous scan's violation set
+static LAST_PRINT: AtomicU64 = AtomicU64::new(0);
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Spawn the periodic scanner and run a first scan
+pub fn init() {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    use crate::sched::task;
+
+    extern "C" fn mapcheck_thread(_: usize) -> ! {
+        loop {
+            crate::arch::x86_64::time::timer::sleep_ns(SCAN_INTERVAL_NS);
+            let _ = scan(false);
+        }
+    }
+
+    match scan(true) {
+        Ok(r) => log_info("mapcheck", &format!(
+            "Mapping scanner online: {} mappings, {} tracked, {} violations",
+            r.mappings, r.tracked, r.violations
+        )),
+        Err(e) => log_warn("mapcheck", &format!("initial scan failed: {:?}", e)),
+    }
+
+    task::kspawn(
+        "memory.mapcheck",
+        mapcheck_thread,
+        0,
+        task::Priority::Low,
+        task::Affinity::ANY,
+    );
+}
+
+/// Register a stack of `pages` at `base`, with an optional unmapped guard below.
+/// A guard that is mapped (early stacks carved out of already-mapped memory)
+/// is not a guard: the stack is recorded without one.
+pub fn track_stack(guard: Option<VirtAddr>, base: VirtAddr, pages: usize) {
+    let guard = guard.filter(|g| virt::translate(*g).is_err());
+    insert(Tracked {
+        kind: TrackKind::Stack,
+        base: base.as_u64(),
+        pages: pages as u32,
+        guard: guard.map_or(0, |g| g.as_u64()),
+    });
+}
+
+/// Register `pages` guard pages at `base` that must stay unmapped
+pub fn track_guard(base: VirtAddr, pages: usize) {
+    insert(Tracked { kind: TrackKind::Guard, base: base.as_u64(), pages: pages as u32, guard: 0 });
+}
+
+/// Register a mapping of `pages` at `base` with guard pages directly below
+/// and above it (guarded heap mappings)
+pub fn track_fenced(base: VirtAddr, pages: usize) {
+    insert(Tracked { kind: TrackKind::Fenced, base: base.as_u64(), pages: pages as u32, guard: 0 });
+}
+
+/// Drop the registration starting at `base` (stack, guard or fenced mapping)
+pub fn untrack(base: VirtAddr) {
+    let mut t = TRACKED.lock();
+    if let Some(slot) = t.iter_mut().find(|s| matches!(s, Some(e) if e.base == base.as_u64())) {
+        *slot = None;
+    }
+}
+
+fn insert(entry: Tracked) {
+    let mut t = TRACKED.lock();
+    match t.iter_mut().find(|s| s.is_none()) {
+        Some(slot) => *slot = Some(entry),
+        None => { TRACK_OVERFLOW.fetch_add(1, Ordering::Relaxed); }
+    }
+}
+
+/// Walk every mapping and check it against policy. `force` records every
+/// violation even if the previous scan already reported the same set.
+pub fn scan(force: bool) -> Result<ScanReport, VmErr> {
+    let smep = Cr4::read().contains(Cr4Flags::SMEP);
+    let mut f = Findings::new(crate::arch::x86_64::time::timer::now_ns());
+
+    virt::walk_leaves(|va, size, flags| {
+        f.report.mappings += 1;
+        let exec = !flags.contains(VmFlags::NX);
+        let user = flags.contains(VmFlags::USER);
+        if exec && flags.contains(VmFlags::RW) {
+            f.note(ViolationKind::Rwx, va.as_u64(), size, flags);
+        }
+        if user && va.as_u64() >= KERNEL_HALF {
+            f.note(ViolationKind::UserInKernelHalf, va.as_u64(), size, flags);
+        } else if user && exec && !smep {
+            f.note(ViolationKind::UserExec, va.as_u64(), size, flags);
+        }
+    })?;
+
+    // translate() never allocates or registers, so holding the table is fine
+    for e in TRACKED.lock().iter().flatten() {
+        f.report.tracked += 1;
+        let end = e.base + e.pages as u64 * PAGE_SIZE as u64;
+        match e.kind {
+            TrackKind::Guard => {
+                for va in (e.base..end).step_by(PAGE_SIZE) {
+                    f.guard(va);
+                }
+            }
+            TrackKind::Fenced => {
+                f.guard(e.base - PAGE_SIZE as u64);
+                f.guard(end);
+            }
+            TrackKind::Stack => {
+                if e.guard != 0 {
+                    f.guard(e.guard);
+                }
+                if cfg!(feature = "nonos-nx-stack") {
+                    for va in (e.base..end).step_by(PAGE_SIZE) {
+                        if let Ok((_, flags, size)) = virt::translate(VirtAddr::new(va)) {
+                            if !flags.contains(VmFlags::NX) {
+                                f.note(ViolationKind::ExecStack, va, size, flags);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let report = f.report;
+    SCANS.fetch_add(1, Ordering::Relaxed);
+    *LAST.lock() = Some(report);
+    let repeat = LAST_PRINT.swap(f.print, Ordering::Relaxed) == f.print;
+    if report.violations > 0 && (force || !repeat) {
+        VIOLATIONS.fetch_add(report.violations as u64, Ordering::Relaxed);
+        escalate(&report, &f.found);
+    }
+    Ok(report)
+}
+
+/// Scan accumulator
+struct Findings {
+    report: ScanReport,
+    /// Itemized violations (the report keeps counting past this)
+    found: ArrayVec<Violation, 64>,
+    print: u64,
+}
+
+impl Findings {
+    fn new(now: u64) -> Self {
+        Self { report: ScanReport::new(now), found: ArrayVec::new(), print: 0 }
+    }
+
+    fn note(&mut self, kind: ViolationKind, va: u64, page_size: usize, flags: VmFlags) {
+        let v = Violation { kind, va, page_size, flags };
+        self.report.violations += 1;
+        self.report.by_kind[kind.index()] += 1;
+        self.report.first.get_or_insert(v);
+        self.print = self.print.rotate_left(7) ^ va ^ ((kind as u64) << 56);
+        let _ = self.found.try_push(v);
+    }
+
+    /// `va` must be unmapped
+    fn guard(&mut self, va: u64) {
+        if let Ok((_, flags, size)) = virt::translate(VirtAddr::new(va)) {
+            self.note(ViolationKind::GuardMapped, va, size, flags);
+        }
+    }
+}
+
+pub fn stats() -> MapcheckStats {
+    MapcheckStats {
+        scans: SCANS.load(Ordering::Relaxed),
+        violations_total: VIOLATIONS.load(Ordering::Relaxed),
+        last: *LAST.lock(),
+    }
+}
+
+/// Registrations dropped because the table was full
+pub fn untracked() -> u64 {
+    TRACK_OVERFLOW.load(Ordering::Relaxed)
+}
+
+/// First address of the kernel half
+const KERNEL_HALF: u64 = 0xFFFF_8000_0000_0000;
+
+fn escalate(report: &ScanReport, found: &[Violation]) {
+    for v in found {
+        let cap = if v.flags.contains(VmFlags::USER) { CapTag::USER } else { CapTag::KERNEL };
+        let cap = if v.kind == ViolationKind::GuardMapped { cap | CapTag::GUARD } else { cap };
+        proof::audit_violation(v.va, 0, v.page_size as u64, v.kind as u64, cap);
+        event::publish_pri(Event::MapViolation { va: v.va, kind: v.kind as u8, flags: v.flags.bits() }, Pri::High);
+        log_warn("mapcheck", &format!("{:?} at {:#x} ({} bytes, {:?})", v.kind, v.va, v.page_size, v.flags));
+    }
+    if report.violations as usize > found.len() {
+        log_warn("mapcheck", &format!("{} further violations not itemized", report.violations as usize - found.len()));
+    }
+
+    #[cfg(feature = "nonos-wx-audit")]
+    panic!("[mapcheck] {} mapping policy violations (first: {:?})", report.violations, report.first);
+}
@@ -16,7 +16,12 @@
 //!   - Optional audit sink to emit proof-friendly events (no persistence).
 //!
 //! Safety notes:
-//!   - Caller must reserve kernel image, boot stacks, MMIO, ACPI before use.
+//!   - Zone spans may cover firmware holes; every frame not reported usable
+//!     (reserved, ACPI, MMIO, bad RAM, boot-loaded) is reserved at init.
+//!     Caller must still reserve anything it carved out of usable RAM.
+//!   - Firmware boot services memory is boot-loaded: the kernel enters on the
+//!     firmware's stack and page tables, which live there. Spans cover it so
+//!     `release_boot_services` can free it once the kernel runs on its own.
 //!   - The bitmap backing memory must be identity-mapped and excluded from
 //!     allocation (reserved) prior to allocator use.
 //!
@@ -28,7 +33,8 @@ use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use core::{cmp, ptr};
 use spin::Mutex;
 
-use crate::memory::layout::{align_down, align_up, Region, RegionKind, PAGE_SIZE};
+use crate::boot::handoff::bootinfo::MEMMAP_MAX_ENTRIES;
+use crate::memory::layout::{align_down, align_up, region_from_handoff, Region, RegionKind, PAGE_SIZE};
 
 /// Physical frame (4 KiB aligned physical address).
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -57,6 +63,12 @@ bitflags::bitflags! {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ZoneKind { Dma32, Normal, High }
 
+impl ZoneKind {
+    pub const ALL: [ZoneKind; 3] = [ZoneKind::Dma32, ZoneKind::Normal, ZoneKind::High];
+
+    #[inline] pub fn index(self) -> usize { self as usize }
+}
+
 /// Per-zone stats (best-effort).
 #[derive(Default, Clone, Copy, Debug)]
 pub struct ZoneStats {
@@ -64,6 +76,8 @@ pub struct ZoneStats {
     pub frames_used:  usize,
     pub frames_free:  usize,
     pub high_watermark: usize,
+    /// Frames inside the zone span that firmware did not report usable
+    pub frames_reserved: usize,
 }
 
 /// Optional audit sink: kernel can register a callback to receive
@@ -117,6 +131,10 @@ impl Span {
         let _ = self.words[w].fetch_and(!(1u64 << b), Ordering::AcqRel);
     }
 
+    fn first_free(&self) -> usize {
+        (0..self.frames).find(|&i| !self.test_bit(i)).unwrap_or(0)
+    }
+
     fn mark_used(&mut self, idx: usize) {
         if idx >= self.frames { return; }
         if !self.test_bit(idx) {
@@ -215,6 +233,22 @@ impl Zone {
     }
 }
 
+/// Page-aligned part of a usable region that belongs to `zone`:
+/// DMA32 takes regions wholly below 4 GiB, NORMAL the upper part of one that
+/// straddles 4 GiB, HIGHMEM regions starting at or above it.
+fn zone_clip(zone: ZoneKind, start: u64, len: u64) -> Option<(u64, u64)> {
+    let end   = align_down(start.saturating_add(len), PAGE_SIZE as u64);
+    let start = align_up(start, PAGE_SIZE as u64);
+    if end <= start { return None; }
+    let four_g = 1u64 << 32;
+    match zone {
+        ZoneKind::Dma32 if end <= four_g => Some((start, end)),
+        ZoneKind::Normal if start < four_g && end > four_g => Some((four_g, end)),
+        ZoneKind::High if start >= four_g => Some((start, end)),
+        _ => None,
+    }
+}
+
 /// Global allocator: a small set of zones per node.
 /// For now we support up to 4 zones total (1 node), expandable later.
 struct PhysState {
@@ -229,7 +263,7 @@ static PHYS: Mutex<Option<PhysState>> = Mutex::new(None);
 impl PhysState {
     /// Initialize from firmware regions and a bitmap arena provider.
     ///
-    /// `carve(words) -> &'static mut [AtomicU64]` must return a fresh, zeroed
+    /// `carve(words) -> &'static mut [AtomicU64]` must return a fresh
     /// atomic array for bitmap backing; the caller must also reserve its
     /// physical range so it cannot be reallocated.
     pub unsafe fn init_from_regions<F>(
@@ -245,24 +279,15 @@ impl PhysState {
         let mut st = PhysState { scrub, audit, zones: heapless::Vec::new() };
 
         // Build zones: split usable memory into DMA32/NORMAL/HIGH per node (single node for now).
+        // Boot-loaded ranges widen the span (reserved) so they can be released later.
         for target in [ZoneKind::Dma32, ZoneKind::Normal, ZoneKind::High] {
             let mut lo = u64::MAX;
             let mut hi = 0u64;
 
             for r in regions {
-                if !matches!(r.kind, RegionKind::Usable) { continue; }
-                let start = align_up(r.start, PAGE_SIZE as u64);
-                let end   = align_down(r.start + r.len, PAGE_SIZE as u64);
-                if end <= start { continue; }
-                match target {
-                    ZoneKind::Dma32 if end <= (1u64 << 32) => { lo = lo.min(start); hi = hi.max(end); }
-                    ZoneKind::Normal if start < (1u64 << 32) && end > (1u64 << 32) => {
-                        // split around 4GiB boundary
-                        lo = lo.min(1u64 << 32);
-                        hi = hi.max(end);
-                    }
-                    ZoneKind::High if start >= (1u64 << 32) => { lo = lo.min(start); hi = hi.max(end); }
-                    _ => {}
+                if !matches!(r.kind, RegionKind::Usable | RegionKind::Boot) { continue; }
+                if let Some((start, end)) = zone_clip(target, r.start, r.len) {
+                    lo = lo.min(start); hi = hi.max(end);
                 }
             }
 
@@ -272,19 +297,41 @@ impl PhysState {
             if frames == 0 { continue; }
             let words = (frames + 63) / 64;
             let map = carve(words);
-            // initialize stats
-            let mut stats = ZoneStats::default();
-            stats.frames_total = frames;
-            stats.frames_free = frames;
 
-            // build span + zone
-            let span = Span {
+            // Everything in [lo, hi) starts reserved: holes between firmware
+            // regions, ACPI, MMIO, bad RAM and boot-loaded data never become
+            // allocatable. Only frames a usable region covers are opened, and
+            // any overlapping non-usable region closes them again.
+            for w in map.iter() { w.store(u64::MAX, Ordering::Relaxed); }
+            let mut span = Span {
                 base: lo,
                 frames,
                 words: map,
                 next_hint: AtomicUsize::new(0),
-                stats,
+                stats: ZoneStats::default(),
             };
+            let mut usable = 0usize;
+            for r in regions {
+                if !matches!(r.kind, RegionKind::Usable) { continue; }
+                let Some((start, end)) = zone_clip(target, r.start, r.len) else { continue };
+                for i in span.paddr_to_idx(start)..span.paddr_to_idx(end - 1) + 1 {
+                    if span.test_bit(i) { span.clear_bit(i); usable += 1; }
+                }
+            }
+            for r in regions {
+                if matches!(r.kind, RegionKind::Usable) { continue; }
+                let begin = align_down(r.start, PAGE_SIZE as u64).max(lo);
+                let end = align_up(r.start.saturating_add(r.len), PAGE_SIZE as u64).min(hi);
+                if end <= begin { continue; }
+                for i in span.paddr_to_idx(begin)..span.paddr_to_idx(end - 1) + 1 {
+                    if !span.test_bit(i) { span.claim_bit(i); usable -= 1; }
+                }
+            }
+
+            span.stats.frames_total = usable;
+            span.stats.frames_free = usable;
+            span.stats.frames_reserved = frames - usable;
+            span.next_hint.store(span.first_free(), Ordering::Relaxed);
             st.zones.push(Zone { node_id, kind: target, span }).ok();
         }
 
@@ -306,6 +353,38 @@ impl PhysState {
         }
     }
 
+    /// Open [paddr, paddr+len) to allocation: frames reserved at init because
+    /// firmware held them become free (scrubbed under OnFree). Frames already
+    /// free, and frames `keep` claims, are left alone. Returns the frames
+    /// released.
+    pub fn release_range(paddr: u64, len: u64, keep: impl Fn(u64) -> bool) -> usize {
+        let mut g = PHYS.lock(); let st = g.as_mut().expect("phys not initialized");
+        let mut released = 0;
+        for z in st.zones.iter_mut() {
+            let begin = align_up(paddr, PAGE_SIZE as u64).max(z.span.base);
+            let end = align_down(paddr.saturating_add(len), PAGE_SIZE as u64).min(z.span.end());
+            if end <= begin { continue; }
+            let s = z.span.paddr_to_idx(begin);
+            let e = z.span.paddr_to_idx(end - 1) + 1;
+            let mut n = 0;
+            for i in s..e {
+                if !z.span.test_bit(i) || keep(z.span.idx_to_paddr(i)) { continue; }
+                if st.scrub == ScrubPolicy::OnFree {
+                    unsafe { ptr::write_bytes(z.span.idx_to_paddr(i) as *mut u8, 0, PAGE_SIZE) }
+                }
+                z.span.clear_bit(i);
+                n += 1;
+            }
+            z.span.stats.frames_total += n;
+            z.span.stats.frames_reserved -= n;
+            z.span.stats.frames_free = z.span.stats.frames_total - z.span.stats.frames_used;
+            z.span.bump_hint_after(s);
+            if n > 0 { if let Some(a) = st.audit { a.on_free(begin, e - s); } }
+            released += n;
+        }
+        released
+    }
+
     /// Allocate 1 frame with flags (zone/node hints).
     pub fn alloc(flags: AllocFlags) -> Option<Frame> {
         Self::alloc_contig(1, 1, flags)
@@ -439,9 +518,67 @@ where
     unsafe { PhysState::init_from_regions(regions, node_id, scrub, carve, audit) }
 }
 
+/// Initialize from the bootloader's memory map extension. Returns `false`
+/// (leaving phys untouched) when the handoff carried no map.
+pub fn init_from_handoff<F>(
+    node_id: u8,
+    scrub: ScrubPolicy,
+    carve: F,
+    audit: Option<&'static dyn AuditSink>,
+) -> bool
+where
+    F: FnMut(usize) -> &'static mut [AtomicU64],
+{
+    let Some(map) = crate::boot::handoff::memory_map() else { return false };
+    let regions = FIRMWARE_REGIONS.call_once(|| {
+        let mut v = heapless::Vec::new();
+        for r in map.regions() { let _ = v.push(region_from_handoff(r)); }
+        v
+    });
+    BOOT_SERVICES.call_once(|| {
+        let mut v = heapless::Vec::new();
+        for r in map.regions().iter().filter(|r| r.is_boot_services()) {
+            let _ = v.push(region_from_handoff(r));
+        }
+        v
+    });
+    init_from_regions(regions, node_id, scrub, carve, audit);
+    true
+}
+
+/// Converted handoff regions; zones keep borrowing them for the kernel's lifetime
+static FIRMWARE_REGIONS: spin::Once<heapless::Vec<Region, MEMMAP_MAX_ENTRIES>> = spin::Once::new();
+/// The boot-loaded regions that held firmware boot services (reserved until
+/// `release_boot_services`)
+static BOOT_SERVICES: spin::Once<heapless::Vec<Region, MEMMAP_MAX_ENTRIES>> = spin::Once::new();
+
+/// Whether `paddr` lies in firmware boot services memory
+pub fn in_boot_services(paddr: u64) -> bool {
+    BOOT_SERVICES.get().is_some_and(|v| v.iter().any(|r| paddr >= r.start && paddr < r.end()))
+}
+
+/// Hand firmware boot services memory to the allocator, except frames `keep`
+/// claims (early boot carve-outs). Only call once the kernel runs on a stack,
+/// GDT/IDT and page tables of its own. Returns the frames released.
+pub fn release_boot_services(keep: impl Fn(u64) -> bool + Copy) -> usize {
+    let Some(v) = BOOT_SERVICES.get() else { return 0 };
+    v.iter().map(|r| PhysState::release_range(r.start, r.len, keep)).sum()
+}
+
 pub fn reserve_range(paddr: u64, len: u64) { PhysState::reserve_range(paddr, len) }
-pub fn alloc(flags: AllocFlags) -> Option<Frame> { PhysState::alloc(flags) }
-pub fn alloc_contig(n: usize, align_frames: usize, flags: AllocFlags) -> Option<Frame> { PhysState::alloc_contig(n, align_frames, flags) }
+pub fn alloc(flags: AllocFlags) -> Option<Frame> { alloc_contig(1, 1, flags) }
+
+/// Allocate N contiguous frames. A failure is reported to memory pressure,
+/// which reclaims on its own task; this path never reclaims (callers may
+/// hold the heap or page-table lock).
+pub fn alloc_contig(n: usize, align_frames: usize, flags: AllocFlags) -> Option<Frame> {
+    let f = PhysState::alloc_contig(n, align_frames, flags);
+    match f {
+        Some(_) => crate::memory::pressure::note_alloc(),
+        None => crate::memory::pressure::on_alloc_failure(n),
+    }
+    f
+}
 pub fn alloc_at(paddr: u64, flags: AllocFlags) -> bool { PhysState::alloc_at(paddr, flags) }
 pub fn free(f: Frame) { PhysState::free(f) }
 pub fn free_contig(base: Frame, n: usize) { PhysState::free_contig(base, n) }
@@ -0,0 +1,311 @@
+//! NØNOS Memory Pressure
+//!
+//! Watermark-based pressure detection per `phys::ZoneKind`, reclaim, and the
+//! capsule OOM policy.
+//! - Every zone is classified against its low/min watermarks; level changes
+//!   are published as `ui::event::Event::MemPressure`
+//! - Reclaim runs cheapest first: slab magazine trim (`alloc::trim_magazines`),
+//!   then page-table GC (`virt::gc_tables`)
+//! - A failed `phys::alloc` only records the failure; the `memory.pressure`
+//!   task reclaims, and requests an OOM kill if that frees too little
+//! - Reclaim, classification, events, logging and the OOM kill all run on
+//!   the task, never inside an allocation: they take the heap, page-table and
+//!   module locks (`modules::supervisor::oom_kill`)
+//! - Alloc-path hooks (`note_alloc`, `on_alloc_failure`) touch atomics only:
+//!   they run underneath the heap and page-table locks
+//!
+//! Capsule admission is refused at `Critical` and above.
+
+use alloc::format;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use spin::RwLock;
+
+use crate::log::logger::{log_info, log_warn};
+use crate::memory::phys::{self, ZoneKind, ZoneStats};
+use crate::memory::{alloc, virt};
+use crate::ui::event::{self, Event, Pri};
+
+/// Zone pressure, ordered by severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum PressureLevel {
+    /// Free frames above the low watermark
+    Normal = 0,
+    /// Below low: reclaim caches
+    Low = 1,
+    /// Below min: reclaim, refuse new capsules, kill if reclaim is not enough
+    Critical = 2,
+    /// An allocation failed while the zone was critical
+    Oom = 3,
+}
+
+impl PressureLevel {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            0 => PressureLevel::Normal,
+            1 => PressureLevel::Low,
+            2 => PressureLevel::Critical,
+            _ => PressureLevel::Oom,
+        }
+    }
+}
+
+/// Watermarks as per-mille of a zone's frames
+#[derive(Debug, Clone, Copy)]
+pub struct Watermarks {
+    pub low_permille: u16,
+    pub min_permille: u16,
+}
+
+impl Watermarks {
+    pub const fn default_marks() -> Self {
+        Self { low_permille: 100, min_permille: 30 }
+    }
+
+    fn classify(&self, stats: &ZoneStats) -> PressureLevel {
+        if stats.frames_total == 0 {
+            return PressureLevel::Normal;
+        }
+        let free = stats.frames_free as u64 * 1000;
+        let total = stats.frames_total as u64;
+        if free < total * self.min_permille as u64 {
+            PressureLevel::Critical
+        } else if free < total * self.low_permille as u64 {
+            PressureLevel::Low
+        } else {
+            PressureLevel::Normal
+        }
+    }
+}
+
+impl Default for Watermarks {
+    fn default() -> Self {
+        Self::default_marks()
+    }
+}
+
+/// Successful allocations between watermark checks on the alloc path
+const EVAL_EVERY: u64 = 64;
+/// Pressure task period
+const SERVICE_INTERVAL_NS: u64 = 100_000_000;
+/// Minimum gap between OOM kills, so reclaimed memory can settle
+const KILL_COOLDOWN_NS: u64 = 1_000_000_000;
+
+static MARKS: RwLock<Watermarks> = RwLock::new(Watermarks::default_marks());
+static ZONE_LEVEL: [AtomicU8; 3] = [AtomicU8::new(0), AtomicU8::new(0), AtomicU8::new(0)];
+static ALLOCS: AtomicU64 = AtomicU64::new(0);
+static OOM_PENDING: AtomicBool = AtomicBool::new(false);
+static EVAL_PENDING: AtomicBool = AtomicBool::new(false);
+static FAILED_FRAMES: AtomicU64 = AtomicU64::new(0);
+/// Zones (bit per `ZoneKind::index`) that hit OOM on the alloc path, not yet
+/// published by `evaluate`
+static OOM_ZONES: AtomicU8 = AtomicU8::new(0);
+const ALL_ZONES: u8 = (1 << ZoneKind::ALL.len()) - 1;
+static LAST_KILL_NS: AtomicU64 = AtomicU64::new(0);
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+// Counters for `mem.pressure`
+static RECLAIMED_PAGES: AtomicU64 = AtomicU64::new(0);
+static ALLOC_FAILURES: AtomicU64 = AtomicU64::new(0);
+static OOM_KILLS: AtomicU64 = AtomicU64::new(0);
+
+/// Telemetry view (CLI / gui_bridge)
+#[derive(Debug, Clone, Copy)]
+pub struct PressureStats {
+    pub level: PressureLevel,
+    pub zones: [PressureLevel; 3],
+    pub reclaimed_pages: u64,
+    pub alloc_failures: u64,
+    pub oom_kills: u64,
+}
+
+/// Spawn the pressure task (reclaim + OOM policy)
+pub fn init() {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    use crate::sched::task;
+
+    extern "C" fn pressure_thread(_: usize) -> ! {
+        loop {
+            service(crate::arch::x86_64::time::timer::now_ns());
+            crate::arch::x86_64::time::timer::sleep_ns(SERVICE_INTERVAL_NS);
+        }
+    }
+
+    task::kspawn(
+        "memory.pressure",
+        pressure_thread,
+        0,
+        task::Priority::High,
+        task::Affinity::ANY,
+    );
+
+    log_info("pressure", "Memory pressure monitor online");
+}
+
+/// Replace the zone watermarks
+pub fn set_watermarks(marks: Watermarks) {
+    *MARKS.write() = marks;
+}
+
+pub fn watermarks() -> Watermarks {
+    *MARKS.read()
+}
+
+/// Worst level across all zones
+pub fn level() -> PressureLevel {
+    ZoneKind::ALL
+        .iter()
+        .map(|z| zone_level(*z))
+        .max()
+        .unwrap_or(PressureLevel::Normal)
+}
+
+pub fn zone_level(zone: ZoneKind) -> PressureLevel {
+    PressureLevel::from_u8(ZONE_LEVEL[zone.index()].load(Ordering::Relaxed))
+}
+
+pub fn stats() -> PressureStats {
+    PressureStats {
+        level: level(),
+        zones: [
+            zone_level(ZoneKind::Dma32),
+            zone_level(ZoneKind::Normal),
+            zone_level(ZoneKind::High),
+        ],
+        reclaimed_pages: RECLAIMED_PAGES.load(Ordering::Relaxed),
+        alloc_failures: ALLOC_FAILURES.load(Ordering::Relaxed),
+        oom_kills: OOM_KILLS.load(Ordering::Relaxed),
+    }
+}
+
+/// Reclassify every zone against the watermarks and publish changes.
+/// Returns the worst level.
+pub fn evaluate() -> PressureLevel {
+    let marks = *MARKS.read();
+    let mut worst = PressureLevel::Normal;
+    for (kind, zs) in phys::zone_stats().iter() {
+        let mut next = marks.classify(zs);
+        let prev = zone_level(*kind);
+        let oom_hit = OOM_ZONES.fetch_and(!(1 << kind.index()), Ordering::AcqRel) & (1 << kind.index()) != 0;
+        if oom_hit && next >= PressureLevel::Critical {
+            next = PressureLevel::Oom;
+        }
+        // OOM sticks until the zone is back above its min watermark
+        if prev == PressureLevel::Oom && next == PressureLevel::Critical {
+            next = PressureLevel::Oom;
+        }
+        if next != prev {
+            set_zone(*kind, next, zs.frames_free);
+        }
+        worst = worst.max(next);
+    }
+    worst
+}
+
+/// Run every reclaim hook once. Returns pages released.
+/// Takes the heap and page-table locks: task or console context only.
+pub fn reclaim() -> usize {
+    let mut pages = alloc::trim_magazines();
+    pages += virt::gc_tables().unwrap_or(0);
+    RECLAIMED_PAGES.fetch_add(pages as u64, Ordering::Relaxed);
+    pages
+}
+
+/// Alloc-path hook: cheap periodic watermark check. Runs underneath the
+/// heap lock, so it only flags the pressure task; classification (which
+/// logs) happens there.
+#[inline]
+pub fn note_alloc() {
+    if ALLOCS.fetch_add(1, Ordering::Relaxed) % EVAL_EVERY == EVAL_EVERY - 1 {
+        EVAL_PENDING.store(true, Ordering::Release);
+    }
+}
+
+/// Alloc-path hook: `phys::alloc_contig` failed for `frames` frames. Only
+/// records the failure; the pressure task reclaims and, if that frees too
+/// little, picks an OOM victim. Every zone is flagged because the failing
+/// zone is not known here; `evaluate` only turns a flag into `Oom` for a
+/// zone that is at `Critical` anyway.
+#[inline]
+pub fn on_alloc_failure(frames: usize) {
+    ALLOC_FAILURES.fetch_add(1, Ordering::Relaxed);
+    FAILED_FRAMES.fetch_max(frames as u64, Ordering::Relaxed);
+    OOM_ZONES.fetch_or(ALL_ZONES, Ordering::AcqRel);
+    OOM_PENDING.store(true, Ordering::Release);
+}
+
+/// One pass of the pressure task: reclassify, reclaim, apply the OOM policy
+pub fn service(now: u64) {
+    let failed = FAILED_FRAMES.swap(0, Ordering::Relaxed);
+    if failed > 0 {
+        let pages = reclaim();
+        if pages as u64 >= failed {
+            // Enough is back for the failed request; let the retry decide
+            OOM_ZONES.store(0, Ordering::Release);
+            OOM_PENDING.store(false, Ordering::Release);
+            log_info("pressure", &format!("allocation of {} frames failed; reclaimed {} pages", failed, pages));
+        } else {
+            log_warn("pressure", &format!("allocation of {} frames failed; reclaim freed only {} pages", failed, pages));
+        }
+    }
+    // Frees only relieve pressure, so a quiet Normal system needs no rescan
+    // until the alloc path flags one
+    let due = EVAL_PENDING.swap(false, Ordering::AcqRel)
+        || OOM_ZONES.load(Ordering::Acquire) != 0
+        || level() > PressureLevel::Normal;
+    let mut level = if due { evaluate() } else { PressureLevel::Normal };
+    if level >= PressureLevel::Low {
+        if reclaim() > 0 {
+            level = evaluate();
+        }
+    }
+
+    let oom = OOM_PENDING.load(Ordering::Acquire);
+    if !oom && level < PressureLevel::Critical {
+        return;
+    }
+    if now.saturating_sub(LAST_KILL_NS.load(Ordering::Relaxed)) < KILL_COOLDOWN_NS {
+        return;
+    }
+
+    match crate::modules::supervisor::oom_kill() {
+        Some(kill) => {
+            OOM_KILLS.fetch_add(1, Ordering::Relaxed);
+            LAST_KILL_NS.store(now, Ordering::Relaxed);
+            OOM_PENDING.store(false, Ordering::Release);
+            event::publish_pri(Event::OomKill {
+                exec_id: kill.exec_id,
+                importance: kill.importance,
+                pages: kill.pages as u64,
+            }, Pri::High);
+            evaluate();
+        }
+        None => {
+            if oom {
+                log_warn("pressure", "out of memory and no capsule is eligible for OOM kill");
+                OOM_PENDING.store(false, Ordering::Release);
+            }
+        }
+    }
+}
+
+fn set_zone(zone: ZoneKind, level: PressureLevel, free_frames: usize) {
+    let prev = ZONE_LEVEL[zone.index()].swap(level as u8, Ordering::Relaxed);
+    if prev == level as u8 {
+        return;
+    }
+    let pri = if level >= PressureLevel::Critical { Pri::High } else { Pri::Norm };
+    event::publish_pri(Event::MemPressure {
+        zone: zone.index() as u8,
+        level: level as u8,
+        free_frames: free_frames as u64,
+    }, pri);
+    if level > PressureLevel::from_u8(prev) {
+        log_warn("pressure", &format!("{:?} zone pressure {:?} ({} frames free)", zone, level, free_frames));
+    } else {
+        log_info("pressure", &format!("{:?} zone pressure {:?} ({} frames free)", zone, level, free_frames));
+    }
+}
@@ -2,17 +2,24 @@
 // eK@nonos-tech.xyz
 // Goals:
 //   - Canonical, versioned audit events for memory state transitions.
-//   - Per-CPU bounded queues for low contention; batch hashing into a rolling
-//     commitment (Keccak/SHA3-256). Optional periodic checkpoints.
+//   - Per-CPU bounded queues for low contention; batches are committed as
+//     Merkle trees and chained into a per-CPU root (BLAKE3).
 //   - Zero-state posture: no persistent logs; root commitment exported on demand.
-//   - Export surface for user/capsule to fetch snapshots (copy-out).
+//   - Export surface for user/capsule to fetch snapshots and folded batches
+//     (copy-out), plus per-event inclusion proofs.
 //   - Works across phys.rs + virt.rs hooks; extensible for other subsystems.
 //
 // Design:
 //   - Each CPU has a ring buffer (heapless::spsc::Queue) of Event.
-//   - A per-CPU rolling root is updated by folding event batches (batch size tunable).
-//   - Global commitment = hash(root_prev || cpu0_root || cpu1_root || ... || epoch).
-//   - All fields little-endian, domain-separated, schema-versioned for ZK circuits.
+//   - Every BATCH_SIZE events fold into one batch: batch root = Merkle root over
+//     the event leaves; cpu root := chain(cpu root, batch index, count, batch root).
+//   - Folded batches are kept in a small per-CPU export ring so they can be
+//     copied out (export_batches) or proven against (inclusion_proof). If the
+//     ring laps before export, the overwritten batches are counted and show up
+//     to the verifier as a break in the root_before/root_after chain.
+//   - Global commitment = hash(schema || boot_nonce || epoch || cpu0_root || ...).
+//   - Encoding, commitments and the export format live in shared/memaudit.rs,
+//     which `nonosctl memaudit` uses to re-verify exports offline.
 //   - Backpressure: if queue is full, we either drop (with counter) OR block if
 //     called from non-IRQ context. Here we drop in IRQ paths and count drops.
 //
@@ -22,13 +29,14 @@
 //   - Timestamps use rdtsc best-effort; monotonic seq per CPU enforces ordering.
 //
 // Dependencies:
-//   - crate::crypto::sha3::Sha3_256
+//   - shared/memaudit.rs (+ crypto::merkle, crypto::hash::digest)
 //   - heapless = "0.8" (no_std)
 //   - spin, core::arch for rdtsc/irq fences
 //
 // Integrate:
 //   - Call proof::init(boot_nonce) once after CPU bring-up.
-//   - Wire phys.rs + virt.rs to audit_* hooks below.
+//   - Wire phys.rs + virt.rs to audit_* hooks below (mapcheck.rs reports
+//     policy violations through audit_violation).
 
 #![allow(dead_code)]
 
@@ -37,36 +45,32 @@ use core::{mem, ptr};
 use heapless::spsc::Queue;
 use spin::Mutex;
 
-use crate::crypto::sha3::Sha3_256;
+use crate::crypto::hash::{digest, Digest, HashAlgo};
+use crate::crypto::merkle;
+
+/// Event encoding, commitments and export records shared with `nonosctl`
+#[path = "../../../shared/memaudit.rs"]
+pub mod memaudit;
+
+pub use memaudit::{BatchHeader, Event, EventProof, Kind, SnapshotHeader, SCHEMA_VERSION};
 
 // ───────────────────────────────────────────────────────────────────────────────
 // Constants / schema
 // ───────────────────────────────────────────────────────────────────────────────
 
-pub const SCHEMA_VERSION: u32 = 1;
-
-// Large enough per-CPU; tune via perf. 1024 events * 40B ≈ 40 KiB/CPU worst.
+// Large enough per-CPU; tune via perf. 1024 events * 80B ≈ 80 KiB/CPU worst.
 pub const RING_CAPACITY: usize = 1024;
-// Fold N events at a time to amortize hashing cost.
-pub const BATCH_SIZE: usize = 32;
+// Fold N events at a time to amortize hashing cost (one Merkle tree per batch).
+pub const BATCH_SIZE: usize = memaudit::MAX_BATCH;
+// Folded batches retained per CPU for export / inclusion proofs.
+pub const EXPORT_DEPTH: usize = 8;
+// A proof for the oldest retained batch links through every later one.
+const _: () = assert!(EXPORT_DEPTH <= memaudit::MAX_LINKS + 1);
 
 // ───────────────────────────────────────────────────────────────────────────────
-// Event model (canonical, versioned, zk-friendly)
+// Event model (canonical, versioned, zk-friendly) — see shared/memaudit.rs
 // ───────────────────────────────────────────────────────────────────────────────
 
-#[repr(u8)]
-#[derive(Clone, Copy, Debug)]
-pub enum Kind {
-    Map4K        = 0x01,
-    Unmap4K      = 0x02,
-    Map2M        = 0x03,
-    Unmap2M      = 0x04,
-    PhysAlloc    = 0x10,
-    PhysFree     = 0x11,
-    Protect4K    = 0x20,
-    ProtectRange = 0x21,
-}
-
 bitflags::bitflags! {
     pub struct CapTag: u32 {
         // Capability tags to bind events to policy (useful in ZK circuits)
@@ -79,33 +83,36 @@ bitflags::bitflags! {
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug)]
-pub struct Event {
-    pub ver:    u32,     // schema version
-    pub kind:   u8,      // Kind as u8
-    pub _pad:   [u8;3],  // reserved/padding (domain sep)
-    pub cpu:    u32,     // cpu id (apic id or logical id)
-    pub seq:    u64,     // per-cpu monotonic
-    pub tsc:    u64,     // rdtsc snapshot (best-effort)
-    pub epoch:  u64,     // logical epoch if you rotate keys/roots
-    pub vaddr:  u64,     // 0 for phys-only
-    pub paddr:  u64,     // physical base (if applicable)
-    pub len:    u64,     // byte length (page or range)
-    pub flags:  u64,     // mapping/arch flags (virt) or alloc flags (phys)
-    pub captag: u32,     // capability tags
-    pub _rsvd:  u32,     // reserved
-}
-
 // ───────────────────────────────────────────────────────────────────────────────
 // Per-CPU state
 // ───────────────────────────────────────────────────────────────────────────────
 
+#[derive(Clone, Copy)]
+struct BatchRecord {
+    hdr: BatchHeader,
+    events: [Event; BATCH_SIZE],
+}
+
+impl BatchRecord {
+    const EMPTY: BatchRecord = BatchRecord {
+        hdr: BatchHeader { cpu: 0, count: 0, index: 0, root_before: [0; 32], root_after: [0; 32] },
+        events: [Event::ZERO; BATCH_SIZE],
+    };
+
+    fn events(&self) -> &[Event] {
+        &self.events[..self.hdr.count as usize]
+    }
+}
+
 struct CpuAudit {
     q: Queue<Event, RING_CAPACITY>,
-    root: [u8; 32],             // rolling root for this CPU
+    root: [u8; 32],             // chained root for this CPU
     seq: AtomicU64,
     drops: AtomicUsize,         // dropped due to full queue
+    batches: u64,               // batches folded so far (next batch index)
+    export: [BatchRecord; EXPORT_DEPTH], // last folded batches, by index % EXPORT_DEPTH
+    pending: usize,             // folded but not yet exported
+    export_drops: u64,          // batches overwritten before export
 }
 
 impl CpuAudit {
@@ -115,7 +122,44 @@ impl CpuAudit {
             root: [0;32],
             seq: AtomicU64::new(0),
             drops: AtomicUsize::new(0),
+            batches: 0,
+            export: [BatchRecord::EMPTY; EXPORT_DEPTH],
+            pending: 0,
+            export_drops: 0,
+        }
+    }
+
+    /// Commit one batch: Merkle root over its events, chained into `root`,
+    /// and a copy retained in the export ring.
+    fn fold(&mut self, cpu: u32, batch: &[Event]) {
+        let Some(broot) = memaudit::batch_root(batch) else { return };
+        let hdr = BatchHeader {
+            cpu,
+            count: batch.len() as u32,
+            index: self.batches,
+            root_before: self.root,
+            root_after: memaudit::chain(&self.root, self.batches, batch.len() as u32, &broot),
+        };
+        let rec = &mut self.export[(self.batches % EXPORT_DEPTH as u64) as usize];
+        rec.hdr = hdr;
+        rec.events[..batch.len()].copy_from_slice(batch);
+
+        self.root = hdr.root_after;
+        self.batches += 1;
+        if self.pending == EXPORT_DEPTH {
+            self.export_drops += 1;
+        } else {
+            self.pending += 1;
+        }
+    }
+
+    /// Retained batch with index `index`, if still in the ring
+    fn record(&self, index: u64) -> Option<&BatchRecord> {
+        let retained = (self.batches as usize).min(EXPORT_DEPTH) as u64;
+        if index >= self.batches || index < self.batches - retained {
+            return None;
         }
+        Some(&self.export[(index % EXPORT_DEPTH as u64) as usize])
     }
 }
 
@@ -143,6 +187,9 @@ pub unsafe fn init(boot_nonce: u64, cpu_buf: &'static mut [CpuAudit]) {
         c.seq.store(0, Ordering::Relaxed);
         c.root = [0; 32];
         c.drops.store(0, Ordering::Relaxed);
+        c.batches = 0;
+        c.pending = 0;
+        c.export_drops = 0;
     }
     *CPUS.lock() = Some(cpu_buf);
     *GLOBAL_ROOT.lock() = [0; 32];
@@ -175,51 +222,6 @@ fn cpu_id() -> u32 {
     0
 }
 
-// hash(prev_root || serialized_event)
-fn fold_one(prev: &[u8;32], ev: &Event) -> [u8;32] {
-    let mut h = Sha3_256::new();
-    h.update(prev);
-
-    // canonical LE serialization
-    h.update(&ev.ver.to_le_bytes());
-    h.update(&[ev.kind]);
-    h.update(&ev._pad);
-    h.update(&ev.cpu.to_le_bytes());
-    h.update(&ev.seq.to_le_bytes());
-    h.update(&ev.tsc.to_le_bytes());
-    h.update(&ev.epoch.to_le_bytes());
-    h.update(&ev.vaddr.to_le_bytes());
-    h.update(&ev.paddr.to_le_bytes());
-    h.update(&ev.len.to_le_bytes());
-    h.update(&ev.flags.to_le_bytes());
-    h.update(&ev.captag.to_le_bytes());
-    h.update(&ev._rsvd.to_le_bytes());
-
-    h.finalize_bytes()
-}
-
-/// Fold a small batch: root := H(root || ev1 || ev2 || ...).
-fn fold_batch(root: &mut [u8;32], batch: &[Event]) {
-    let mut h = Sha3_256::new();
-    h.update(root);
-    for ev in batch { // serialized in canonical order
-        h.update(&ev.ver.to_le_bytes());
-        h.update(&[ev.kind]);
-        h.update(&ev._pad);
-        h.update(&ev.cpu.to_le_bytes());
-        h.update(&ev.seq.to_le_bytes());
-        h.update(&ev.tsc.to_le_bytes());
-        h.update(&ev.epoch.to_le_bytes());
-        h.update(&ev.vaddr.to_le_bytes());
-        h.update(&ev.paddr.to_le_bytes());
-        h.update(&ev.len.to_le_bytes());
-        h.update(&ev.flags.to_le_bytes());
-        h.update(&ev.captag.to_le_bytes());
-        h.update(&ev._rsvd.to_le_bytes());
-    }
-    *root = h.finalize_bytes();
-}
-
 // ───────────────────────────────────────────────────────────────────────────────
 // Ingest path (called by phys/virt hooks)
 // ───────────────────────────────────────────────────────────────────────────────
@@ -274,8 +276,8 @@ fn push_event(ev: Event) {
         for _ in 0..BATCH_SIZE {
             if let Some(e) = cq.q.dequeue() { let _ = batch.push(e); }
         }
-        fold_batch(&mut cq.root, &batch);
-        // update global root cheaply (H(prev_global || cpu_root || epoch || boot_nonce))
+        cq.fold(ev.cpu, &batch);
+        // update global root cheaply (H(schema || boot_nonce || epoch || cpu roots))
         update_global_root_locked(&mut *GLOBAL_ROOT.lock(), s);
     }
 }
@@ -300,6 +302,11 @@ fn push_event(ev: Event) {
     push_event(make_event(Kind::Protect4K, vaddr, 0, len, flags, cap));
 }
 
+// Mapping policy violations (memory::mapcheck); `code` = violation kind
+#[inline] pub fn audit_violation(vaddr: u64, paddr: u64, len: u64, code: u64, cap: CapTag) {
+    push_event(make_event(Kind::Violation, vaddr, paddr, len, code, cap));
+}
+
 // Physical allocator hooks
 #[inline] pub fn audit_phys_alloc(paddr: u64, len: u64, cap: CapTag) {
     push_event(make_event(Kind::PhysAlloc, 0, paddr, len, 0, cap));
@@ -312,35 +319,126 @@ fn push_event(ev: Event) {
 // Export surface (for capsules / onion telemetry)
 // ───────────────────────────────────────────────────────────────────────────────
 
-#[derive(Clone, Copy, Debug)]
-pub struct SnapshotHeader {
-    pub schema: u32,
-    pub epoch:  u64,
-    pub boot_nonce: u64,
-    pub cpu_count: u32,
-    pub drop_total: u64,
-    pub root: [u8; 32], // global commitment at snapshot time
-}
-
 /// Copy current per-CPU roots into `roots_out` and fill `hdr_out`.
-/// Returns number of CPUs reported. Constant-time-ish over max CPUs.
+/// Returns number of CPUs reported (at most `roots_out.len()`); the header
+/// always commits to every CPU. Constant-time-ish over max CPUs.
 pub fn snapshot(roots_out: &mut [[u8;32]], hdr_out: &mut SnapshotHeader) -> usize {
     let cpus = CPUS.lock();
     let s = cpus.as_ref().expect("proof not initialized");
+    snapshot_locked(s, roots_out, hdr_out)
+}
+
+fn snapshot_locked(s: &[CpuAudit], roots_out: &mut [[u8;32]], hdr_out: &mut SnapshotHeader) -> usize {
     let mut drops = 0usize;
     for (i, cpu) in s.iter().enumerate() {
-        roots_out[i] = cpu.root;
+        if let Some(slot) = roots_out.get_mut(i) { *slot = cpu.root; }
         drops += cpu.drops.load(Ordering::Relaxed);
     }
+    // Recompute so the root matches the epoch reported alongside it
+    let mut root = GLOBAL_ROOT.lock();
+    update_global_root_locked(&mut root, s);
     *hdr_out = SnapshotHeader {
         schema: SCHEMA_VERSION,
         epoch: EPOCH.load(Ordering::Relaxed),
         boot_nonce: BOOT_NONCE.load(Ordering::Relaxed),
         cpu_count: s.len() as u32,
         drop_total: drops as u64,
-        root: *GLOBAL_ROOT.lock(),
+        root: *root,
     };
-    s.len()
+    s.len().min(roots_out.len())
+}
+
+/// Encode a snapshot record (`SnapshotHeader` followed by every CPU root)
+/// into `out`. Returns bytes written, 0 if `out` is too small.
+pub fn export_snapshot(out: &mut [u8]) -> usize {
+    let cpus = CPUS.lock();
+    let s = cpus.as_ref().expect("proof not initialized");
+    export_snapshot_locked(s, out)
+}
+
+fn export_snapshot_locked(s: &[CpuAudit], out: &mut [u8]) -> usize {
+    let mut hdr = SnapshotHeader::default();
+    let mut roots = [[0u8; 32]; MAX_CPUS];
+    let n = snapshot_locked(s, &mut roots, &mut hdr);
+    let need = memaudit::SNAPSHOT_HDR_LEN + n * 32;
+    if out.len() < need || n != hdr.cpu_count as usize { return 0; }
+    out[..memaudit::SNAPSHOT_HDR_LEN].copy_from_slice(&hdr.encode());
+    for (i, r) in roots[..n].iter().enumerate() {
+        let off = memaudit::SNAPSHOT_HDR_LEN + i * 32;
+        out[off..off + 32].copy_from_slice(r);
+    }
+    need
+}
+
+/// Copy not-yet-exported batches of CPU `cpu_idx` into `out`, oldest first,
+/// as `BatchHeader` + encoded events. Only whole records are written; the
+/// rest stay pending for the next call. Returns bytes written.
+pub fn export_batches(cpu_idx: usize, out: &mut [u8]) -> usize {
+    let mut cpus = CPUS.lock();
+    let s = cpus.as_mut().expect("proof not initialized");
+    match s.get_mut(cpu_idx) {
+        Some(c) => export_batches_locked(c, out),
+        None => 0,
+    }
+}
+
+fn export_batches_locked(c: &mut CpuAudit, out: &mut [u8]) -> usize {
+    let mut off = 0;
+    while c.pending > 0 {
+        let index = c.batches - c.pending as u64;
+        let Some(rec) = c.record(index) else { break };
+        if off + rec.hdr.record_len() > out.len() { break; }
+        out[off..off + memaudit::BATCH_HDR_LEN].copy_from_slice(&rec.hdr.encode());
+        off += memaudit::BATCH_HDR_LEN;
+        for ev in rec.events() {
+            out[off..off + memaudit::EVENT_ENCODED_LEN].copy_from_slice(&ev.encode());
+            off += memaudit::EVENT_ENCODED_LEN;
+        }
+        c.pending -= 1;
+    }
+    off
+}
+
+/// Snapshot record plus the pending batches of every CPU, taken under one
+/// lock: no batch folds in between, so each CPU's last exported batch ends
+/// at that CPU's root in the snapshot. Batches go to `batch_out` back to
+/// back, CPU by CPU, with CPU i's byte count in `batch_lens[i]` (CPUs past
+/// `batch_lens.len()` are not exported). Returns snapshot bytes, 0 if
+/// `snap_out` is too small (nothing is consumed then).
+pub fn export_all(snap_out: &mut [u8], batch_out: &mut [u8], batch_lens: &mut [usize]) -> usize {
+    let mut cpus = CPUS.lock();
+    let s = cpus.as_mut().expect("proof not initialized");
+    let n = export_snapshot_locked(s, snap_out);
+    if n == 0 { return 0; }
+    let mut off = 0;
+    for (c, len) in s.iter_mut().zip(batch_lens.iter_mut()) {
+        *len = export_batches_locked(c, &mut batch_out[off..]);
+        off += *len;
+    }
+    n
+}
+
+/// Inclusion proof for the event with sequence number `seq` on CPU `cpu_idx`,
+/// if its batch has been folded and is still retained, together with the CPU
+/// root it chains to. Both are read under one lock, and the proof links
+/// through every later retained batch, so `proof.verify(&cpu_root)` holds.
+pub fn inclusion_proof(cpu_idx: usize, seq: u64) -> Option<(EventProof, [u8; 32])> {
+    let cpus = CPUS.lock();
+    let s = cpus.as_ref().expect("proof not initialized");
+    let c = s.get(cpu_idx)?;
+    let oldest = c.batches.saturating_sub(EXPORT_DEPTH as u64);
+    for index in (oldest..c.batches).rev() {
+        let rec = c.record(index)?;
+        if let Some(i) = rec.events().iter().position(|e| e.seq == seq) {
+            let mut proof = EventProof::build(&rec.hdr, rec.events(), i)?;
+            for later in index + 1..c.batches {
+                let next = c.record(later)?;
+                if !proof.push_link(&next.hdr, next.events()) { return None; }
+            }
+            return Some((proof, c.root));
+        }
+    }
+    None
 }
 
 /// Drain up to `max` pending events from CPU `cpu_idx` into `out_buf`.
@@ -356,9 +454,11 @@ pub fn drain_events(cpu_idx: usize, out_buf: &mut [Event]) -> usize {
             out_buf[n] = e; n += 1;
         } else { break; }
     }
-    // fold any remainder to keep root fresh
+    // fold drained events too, so the root still covers them
     if n > 0 {
-        fold_batch(&mut s[cpu_idx].root, &out_buf[..n]);
+        for chunk in out_buf[..n].chunks(BATCH_SIZE) {
+            s[cpu_idx].fold(cpu_idx as u32, chunk);
+        }
         update_global_root_locked(&mut *GLOBAL_ROOT.lock(), s);
     }
     n
@@ -369,18 +469,22 @@ pub fn drain_events(cpu_idx: usize, out_buf: &mut [Event]) -> usize {
 // ───────────────────────────────────────────────────────────────────────────────
 
 fn update_global_root_locked(out: &mut [u8;32], cpus: &[CpuAudit]) {
-    let mut h = Sha3_256::new();
-    // Domain separation for global root
-    h.update(b"NONOS:MEM-ROOT:v1");
-    h.update(&BOOT_NONCE.load(Ordering::Relaxed).to_le_bytes());
-    h.update(&EPOCH.load(Ordering::Relaxed).to_le_bytes());
-    for c in cpus.iter() { h.update(&c.root); }
-    *out = h.finalize_bytes();
+    *out = memaudit::global_root(
+        BOOT_NONCE.load(Ordering::Relaxed),
+        EPOCH.load(Ordering::Relaxed),
+        cpus.iter().map(|c| &c.root),
+    );
 }
 
 /// Current global commitment (copy).
 pub fn root() -> [u8;32] { *GLOBAL_ROOT.lock() }
 
+/// Algorithm the commitment chain is built with.
+pub const ROOT_ALGO: HashAlgo = HashAlgo::Blake3;
+
+/// Current global commitment, tagged with its algorithm (for export).
+pub fn root_digest() -> Digest { Digest::new(ROOT_ALGO, root()) }
+
 // ───────────────────────────────────────────────────────────────────────────────
 // Stats / debug
 // ───────────────────────────────────────────────────────────────────────────────
@@ -393,6 +497,13 @@ pub fn dropped_events_total() -> u64 {
     d
 }
 
+/// Folded batches overwritten in the export ring before they were exported.
+pub fn dropped_batches_total() -> u64 {
+    let cpus = CPUS.lock();
+    let s = cpus.as_ref().expect("proof not initialized");
+    s.iter().map(|c| c.export_drops).sum()
+}
+
 // Utility to create a static CPU array in the caller.
 pub const fn empty_cpu_array<const N: usize>() -> [CpuAudit; N] {
     // const constructors not allowed for heapless::Queue; we rely on CpuAudit::new()
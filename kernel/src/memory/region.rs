@@ -1,9 +1,32 @@
 //! NØNOS Memory Region Management
 //!
 //! Provides memory region allocation and tracking for capsules and kernel subsystems.
+//!
+//! Two kinds of region:
+//! - eager (`allocate_region`): physically contiguous, fully mapped up front;
+//!   used for handoff buffers and anything touched from IRQ context
+//! - demand-paged (`reserve_region`): only virtual space is reserved; frames
+//!   are allocated and mapped on first touch by the page-fault handler
+//!   (`handle_page_fault`). Capsule sandboxes use these.
+//!
+//! Demand-paged regions may declare a read-only code prefix keyed by the
+//! manifest hash. Once an instance has loaded and sealed its code
+//! (`seal_code`), later instances of the same hash map those frames instead
+//! of getting their own; a write to a shared page takes a private copy
+//! (copy-on-write). Resident pages are accounted per region and globally,
+//! and the global total is capped by `set_resident_limit`.
 
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
 use core::ptr::NonNull;
-use x86_64::PhysAddr;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use spin::{Mutex, MutexGuard};
+use x86_64::{PhysAddr, VirtAddr};
+
+use crate::memory::layout::{align_up, directmap_va, HUGE_2M, PAGE_SIZE};
+use crate::memory::proof::{self, CapTag};
+use crate::memory::{phys, virt};
 
 /// Memory region descriptor
 #[derive(Debug, Clone, Copy)]
@@ -21,6 +44,8 @@ bitflags::bitflags! {
         const EXECUTABLE = 1 << 2;
         const USER = 1 << 3;
         const ZEROED = 1 << 4;
+        /// Demand-paged: frames are allocated on first touch
+        const LAZY = 1 << 5;
     }
 }
 
@@ -44,44 +69,60 @@ impl MemoryRegion {
     }
     
     pub fn zeroize(&self) {
+        if self.flags.contains(RegionFlags::LAZY) {
+            // Touching every page would fault the whole reservation in
+            scrub_resident(self);
+            return;
+        }
         unsafe {
             core::ptr::write_bytes(self.base.as_ptr(), 0, self.size);
         }
     }
 }
 
-/// Allocate a memory region for a capsule
+extern "Rust" {
+    fn __nonos_alloc_kvm_va(pages: usize) -> u64;
+}
+
+/// Pages in one 2 MiB page
+const HUGE_PAGES: usize = HUGE_2M / PAGE_SIZE;
+
+/// Reserve kernel VA for `pages`; 2 MiB-aligned when the span can hold a huge page
+fn alloc_va(pages: usize) -> u64 {
+    if pages < HUGE_PAGES {
+        return unsafe { __nonos_alloc_kvm_va(pages) };
+    }
+    match unsafe { __nonos_alloc_kvm_va(pages + HUGE_PAGES - 1) } {
+        0 => 0,
+        va => align_up(va, HUGE_2M as u64),
+    }
+}
+
+/// Allocate an eagerly backed, fully mapped memory region
 pub fn allocate_region(size: usize) -> Option<MemoryRegion> {
-    use crate::memory::{phys, virt};
-    use x86_64::{VirtAddr, PhysAddr};
-    
-    // Allocate physical frames
+    // Allocate physical frames (2 MiB-aligned if that lets us map huge)
     let pages = (size + 4095) / 4096;
-    let frame = phys::alloc_contig(pages, 1, phys::AllocFlags::ZERO)?;
+    let frame = if pages >= HUGE_PAGES {
+        phys::alloc_contig(pages, HUGE_PAGES, phys::AllocFlags::ZERO)
+            .or_else(|| phys::alloc_contig(pages, 1, phys::AllocFlags::ZERO))?
+    } else {
+        phys::alloc_contig(pages, 1, phys::AllocFlags::ZERO)?
+    };
     
     // Map to virtual memory
-    extern "Rust" {
-        fn __nonos_alloc_kvm_va(pages: usize) -> u64;
-    }
-    
-    let va = unsafe { __nonos_alloc_kvm_va(pages) };
+    let va = alloc_va(pages);
     if va == 0 {
         phys::free_contig(frame, pages);
         return None;
     }
     
-    // Map pages
-    for i in 0..pages {
-        let page_va = VirtAddr::new(va + (i * 4096) as u64);
-        let page_pa = PhysAddr::new(frame.0 + (i * 4096) as u64);
-        
-        unsafe {
-            virt::map4k_at(
-                page_va,
-                page_pa,
-                virt::VmFlags::RW | virt::VmFlags::NX | virt::VmFlags::GLOBAL
-            ).ok()?;
-        }
+    // Map pages (2 MiB where VA and PA line up)
+    let flags = virt::VmFlags::RW | virt::VmFlags::NX | virt::VmFlags::GLOBAL;
+    if virt::map_range_at(VirtAddr::new(va), PhysAddr::new(frame.0), pages * PAGE_SIZE, flags).is_err() {
+        // unmaps the mapped prefix, stops at the first hole
+        let _ = virt::unmap_range_keep(VirtAddr::new(va), pages * PAGE_SIZE);
+        phys::free_contig(frame, pages);
+        return None;
     }
     
     let base = NonNull::new(va as *mut u8)?;
@@ -94,22 +135,469 @@ pub fn allocate_region(size: usize) -> Option<MemoryRegion> {
     ))
 }
 
-/// Free a memory region
-pub fn free_region(region: &MemoryRegion) {
-    use crate::memory::{phys, virt};
-    use x86_64::VirtAddr;
-    
+/// Scrub, unmap and free a memory region.
+///
+/// Every page is zeroed through its mapping before it is unmapped, and each
+/// frame release is recorded as a `PhysFree` audit event. Returns the number
+/// of pages reclaimed.
+pub fn free_region(region: &MemoryRegion) -> usize {
+    if region.flags.contains(RegionFlags::LAZY) {
+        return free_lazy(region);
+    }
+
     let pages = (region.size + 4095) / 4096;
     let va_base = region.base.as_ptr() as u64;
+    let mut tag = cap_tag(region.flags);
+    if matches!(virt::translate(VirtAddr::new(va_base)), Ok((_, _, size)) if size == HUGE_2M) {
+        tag |= CapTag::HUGE_HINT;
+    }
     
-    // Unmap pages
+    // Scrub, then unmap at whatever granularity it was mapped (frames are
+    // returned below, in one contiguous free)
     for i in 0..pages {
-        let page_va = VirtAddr::new(va_base + (i * 4096) as u64);
-        unsafe {
-            let _ = virt::unmap4k(page_va);
-        }
+        unsafe { scrub_page(va_base + (i * 4096) as u64); }
     }
+    let _ = virt::unmap_range_keep(VirtAddr::new(va_base), pages * PAGE_SIZE);
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
     
     // Free physical frames
     phys::free_contig(phys::Frame(region.phys_base.as_u64()), pages);
+    for i in 0..pages {
+        proof::audit_phys_free(region.phys_base.as_u64() + (i * 4096) as u64, 4096, tag);
+    }
+    
+    pages
+}
+
+// ───────────────────────────────────────────────────────────────────────────
+// Demand paging + copy-on-write code sharing
+// ───────────────────────────────────────────────────────────────────────────
+
+/// Read-only code prefix of a demand-paged region, shared by manifest hash
+#[derive(Debug, Clone, Copy)]
+pub struct SharedCode {
+    pub key: [u8; 32],
+    pub bytes: usize,
+}
+
+/// Resident-set view of one demand-paged region
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RegionStats {
+    pub reserved_pages: usize,
+    /// Frames owned by this region alone (huge blocks included)
+    pub private_pages: usize,
+    /// Code frames mapped from a shared image
+    pub shared_pages: usize,
+    /// 2 MiB blocks mapped as huge pages
+    pub huge_blocks: usize,
+}
+
+impl RegionStats {
+    pub fn resident_bytes(&self) -> usize {
+        (self.private_pages + self.shared_pages) * PAGE_SIZE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backing {
+    Private(u64),
+    Shared(u64),
+    /// 2 MiB block starting at this index, one huge page
+    Huge(u64),
+}
+
+struct LazyRegion {
+    size: usize,
+    flags: RegionFlags,
+    code_key: Option<[u8; 32]>,
+    code_pages: usize,
+    pages: BTreeMap<usize, Backing>,
+}
+
+/// Sealed code frames of one manifest hash
+struct SharedImage {
+    frames: Vec<u64>,
+    refs: usize,
+}
+
+static LAZY: Mutex<BTreeMap<u64, LazyRegion>> = Mutex::new(BTreeMap::new());
+/// Taken only under `LAZY` or around plain map updates that never touch a
+/// demand-paged address, so it cannot be held by a faulting CPU
+static IMAGES: Mutex<BTreeMap<[u8; 32], SharedImage>> = Mutex::new(BTreeMap::new());
+/// APIC id of the CPU holding `LAZY`, `NO_OWNER` when free
+static LAZY_OWNER: AtomicU32 = AtomicU32::new(NO_OWNER);
+const NO_OWNER: u32 = u32::MAX;
+/// Frames backing demand-paged regions and shared images (each counted once)
+static RESIDENT_PAGES: AtomicUsize = AtomicUsize::new(0);
+/// Cap on `RESIDENT_PAGES`; 0 = unlimited
+static RESIDENT_LIMIT: AtomicUsize = AtomicUsize::new(0);
+
+fn this_cpu() -> u32 {
+    use crate::arch::x86_64::interrupt::apic;
+    if apic::ready() { apic::id() } else { 0 }
+}
+
+/// `LAZY` guard that records its holder, so the fault handler can tell a
+/// busy table on another CPU (wait for it) from a fault inside this CPU's
+/// own critical section (a kernel bug; report the fault)
+struct LazyGuard(MutexGuard<'static, BTreeMap<u64, LazyRegion>>);
+
+impl Deref for LazyGuard {
+    type Target = BTreeMap<u64, LazyRegion>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for LazyGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for LazyGuard {
+    fn drop(&mut self) {
+        LAZY_OWNER.store(NO_OWNER, Ordering::Release);
+    }
+}
+
+fn lock_lazy() -> LazyGuard {
+    let guard = LAZY.lock();
+    LAZY_OWNER.store(this_cpu(), Ordering::Release);
+    LazyGuard(guard)
+}
+
+#[inline]
+fn data_flags() -> virt::VmFlags {
+    virt::VmFlags::RW | virt::VmFlags::NX | virt::VmFlags::GLOBAL
+}
+
+/// Read-only and executable
+#[inline]
+fn code_flags() -> virt::VmFlags {
+    virt::VmFlags::GLOBAL
+}
+
+/// Reserve a demand-paged region of `size` bytes. No frame is allocated
+/// until a page is touched. `code` declares a read-only prefix shared with
+/// other instances of the same key once sealed.
+pub fn reserve_region(size: usize, code: Option<SharedCode>) -> Option<MemoryRegion> {
+    let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+    if pages == 0 {
+        return None;
+    }
+    let va = alloc_va(pages);
+    let base = NonNull::new(va as *mut u8)?;
+    let flags = RegionFlags::READABLE | RegionFlags::WRITABLE | RegionFlags::ZEROED | RegionFlags::LAZY;
+
+    let (code_key, code_pages) = match code {
+        Some(c) if c.bytes > 0 => (Some(c.key), ((c.bytes + PAGE_SIZE - 1) / PAGE_SIZE).min(pages)),
+        _ => (None, 0),
+    };
+    if let Some(key) = code_key {
+        IMAGES.lock().entry(key).or_insert(SharedImage { frames: Vec::new(), refs: 0 }).refs += 1;
+    }
+    lock_lazy().insert(va, LazyRegion { size, flags, code_key, code_pages, pages: BTreeMap::new() });
+
+    Some(MemoryRegion::new(base, size, PhysAddr::new(0), flags))
+}
+
+/// Resolve a fault on a demand-paged region: map a zero page (or the shared
+/// code frame) on first touch, or take a private copy on a write to a shared
+/// page. Returns false if the address is not ours or memory is exhausted;
+/// the caller then treats it as a real fault.
+pub fn handle_page_fault(addr: u64, write: bool, present: bool) -> bool {
+    // A fault while this CPU already holds the table is a kernel bug, not
+    // something to spin on; another CPU holding it is just contention
+    if LAZY_OWNER.load(Ordering::Acquire) == this_cpu() {
+        return false;
+    }
+    let mut lazy = lock_lazy();
+    let (base, region) = match lazy.range_mut(..=addr).next_back() {
+        Some((&base, r)) if addr < base + r.size as u64 => (base, r),
+        _ => return false,
+    };
+    let index = ((addr - base) as usize) / PAGE_SIZE;
+    let page_va = VirtAddr::new(base + (index * PAGE_SIZE) as u64);
+    let in_code = index < region.code_pages;
+
+    match (region.pages.get(&index).copied(), present) {
+        (None, false) => {
+            if in_code {
+                if let Some(pa) = shared_frame(region.code_key, index) {
+                    if virt::map4k_at(page_va, PhysAddr::new(pa), code_flags()).is_err() {
+                        return false;
+                    }
+                    region.pages.insert(index, Backing::Shared(pa));
+                    return true;
+                }
+            }
+            if !in_code && map_huge_block(base, region, index) {
+                return true;
+            }
+            // Unsealed code is private and writable until `seal_code`
+            match map_private(page_va, None) {
+                Some(pa) => {
+                    region.pages.insert(index, Backing::Private(pa));
+                    true
+                }
+                None => false,
+            }
+        }
+        (Some(Backing::Shared(src)), true) if write => {
+            if virt::unmap4k_keep(page_va).is_err() {
+                return false;
+            }
+            match map_private(page_va, Some(src)) {
+                Some(pa) => {
+                    region.pages.insert(index, Backing::Private(pa));
+                    true
+                }
+                None => {
+                    // Put the shared mapping back; the write faults for real
+                    let _ = virt::map4k_at(page_va, PhysAddr::new(src), code_flags());
+                    false
+                }
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Mark a region's loaded code prefix read-only + executable. The first
+/// instance to seal donates its frames to the shared image; later instances
+/// drop their private copies and fault the shared frames in on next use.
+pub fn seal_code(region: &MemoryRegion) -> Result<(), &'static str> {
+    let mut lazy = lock_lazy();
+    let r = lazy.get_mut(&(region.base.as_ptr() as u64)).ok_or("Region is not demand-paged")?;
+    let key = r.code_key.ok_or("Region has no code prefix")?;
+    let mut images = IMAGES.lock();
+    let image = images.get_mut(&key).ok_or("Shared image missing")?;
+    let donate = image.frames.is_empty();
+    let base = region.base.as_ptr() as u64;
+    let tag = cap_tag(r.flags);
+
+    for index in 0..r.code_pages {
+        let va = VirtAddr::new(base + (index * PAGE_SIZE) as u64);
+        match r.pages.get(&index).copied() {
+            Some(Backing::Private(pa)) if donate => {
+                virt::unmap4k_keep(va).map_err(|_| "Code page unmap failed")?;
+                virt::map4k_at(va, PhysAddr::new(pa), code_flags()).map_err(|_| "Code page remap failed")?;
+                image.frames.push(pa);
+                r.pages.insert(index, Backing::Shared(pa));
+            }
+            Some(Backing::Private(pa)) => {
+                unsafe { scrub_page(va.as_u64()); }
+                let _ = virt::unmap4k_keep(va);
+                release_frame(pa, tag);
+                r.pages.remove(&index);
+            }
+            None if donate => {
+                // Never-touched code page: donate a zero page so every
+                // instance sees the same image
+                let pa = map_private(va, None).ok_or("Out of memory sealing code")?;
+                virt::unmap4k_keep(va).map_err(|_| "Code page unmap failed")?;
+                virt::map4k_at(va, PhysAddr::new(pa), code_flags()).map_err(|_| "Code page remap failed")?;
+                image.frames.push(pa);
+                r.pages.insert(index, Backing::Shared(pa));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// True once some instance has sealed code under `key`; later instances
+/// need not write the image, its frames fault in shared
+pub fn code_sealed(key: &[u8; 32]) -> bool {
+    IMAGES.lock().get(key).map_or(false, |image| !image.frames.is_empty())
+}
+
+/// Resident-set accounting for a demand-paged region
+pub fn region_stats(region: &MemoryRegion) -> Option<RegionStats> {
+    let lazy = lock_lazy();
+    let r = lazy.get(&(region.base.as_ptr() as u64))?;
+    let mut st = RegionStats { reserved_pages: (r.size + PAGE_SIZE - 1) / PAGE_SIZE, ..Default::default() };
+    for b in r.pages.values() {
+        match b {
+            Backing::Private(_) => st.private_pages += 1,
+            Backing::Shared(_) => st.shared_pages += 1,
+            Backing::Huge(_) => {
+                st.private_pages += HUGE_PAGES;
+                st.huge_blocks += 1;
+            }
+        }
+    }
+    Some(st)
+}
+
+/// Bytes of frames currently backing demand-paged regions (shared code once)
+pub fn resident_bytes() -> usize {
+    RESIDENT_PAGES.load(Ordering::Relaxed) * PAGE_SIZE
+}
+
+/// Cap the frames demand paging may commit; faults beyond it fail
+pub fn set_resident_limit(bytes: usize) {
+    RESIDENT_LIMIT.store(bytes / PAGE_SIZE, Ordering::Relaxed);
+}
+
+fn shared_frame(key: Option<[u8; 32]>, index: usize) -> Option<u64> {
+    IMAGES.lock().get(&key?)?.frames.get(index).copied()
+}
+
+/// Allocate and map a private writable page, zeroed or copied from `src`
+fn map_private(va: VirtAddr, src: Option<u64>) -> Option<u64> {
+    let limit = RESIDENT_LIMIT.load(Ordering::Relaxed);
+    if limit != 0 && RESIDENT_PAGES.load(Ordering::Relaxed) >= limit {
+        return None;
+    }
+    let frame = phys::alloc(phys::AllocFlags::ZERO)?;
+    if let Some(src) = src {
+        match (directmap_va(src), directmap_va(frame.0)) {
+            (Some(from), Some(to)) => unsafe {
+                core::ptr::copy_nonoverlapping(from as *const u8, to as *mut u8, PAGE_SIZE);
+            },
+            _ => {
+                phys::free(frame);
+                return None;
+            }
+        }
+    }
+    if virt::map4k_at(va, PhysAddr::new(frame.0), data_flags()).is_err() {
+        phys::free(frame);
+        return None;
+    }
+    RESIDENT_PAGES.fetch_add(1, Ordering::Relaxed);
+    Some(frame.0)
+}
+
+/// Back the whole 2 MiB block around `index` with one zeroed huge page, if
+/// the block is 2 MiB-aligned, past the code prefix, inside the region and
+/// has nothing resident yet
+fn map_huge_block(base: u64, r: &mut LazyRegion, index: usize) -> bool {
+    let first = index - index % HUGE_PAGES;
+    let va = base + (first * PAGE_SIZE) as u64;
+    let reserved = (r.size + PAGE_SIZE - 1) / PAGE_SIZE;
+    if va % HUGE_2M as u64 != 0
+        || first < r.code_pages
+        || first + HUGE_PAGES > reserved
+        || r.pages.range(first..first + HUGE_PAGES).next().is_some()
+    {
+        return false;
+    }
+    let limit = RESIDENT_LIMIT.load(Ordering::Relaxed);
+    if limit != 0 && RESIDENT_PAGES.load(Ordering::Relaxed) + HUGE_PAGES > limit {
+        return false;
+    }
+    let frame = match phys::alloc_contig(HUGE_PAGES, HUGE_PAGES, phys::AllocFlags::ZERO) {
+        Some(f) => f,
+        None => return false,
+    };
+    if virt::map2m_at(VirtAddr::new(va), PhysAddr::new(frame.0), data_flags()).is_err() {
+        phys::free_contig(frame, HUGE_PAGES);
+        return false;
+    }
+    r.pages.insert(first, Backing::Huge(frame.0));
+    RESIDENT_PAGES.fetch_add(HUGE_PAGES, Ordering::Relaxed);
+    true
+}
+
+fn release_huge(pa: u64, tag: CapTag) {
+    phys::free_contig(phys::Frame(pa), HUGE_PAGES);
+    proof::audit_phys_free(pa, HUGE_2M as u64, tag | CapTag::HUGE_HINT);
+    RESIDENT_PAGES.fetch_sub(HUGE_PAGES, Ordering::Relaxed);
+}
+
+fn release_frame(pa: u64, tag: CapTag) {
+    phys::free(phys::Frame(pa));
+    proof::audit_phys_free(pa, PAGE_SIZE as u64, tag);
+    RESIDENT_PAGES.fetch_sub(1, Ordering::Relaxed);
+}
+
+fn free_lazy(region: &MemoryRegion) -> usize {
+    let base = region.base.as_ptr() as u64;
+    let r = match lock_lazy().remove(&base) {
+        Some(r) => r,
+        None => return 0,
+    };
+    let tag = cap_tag(r.flags);
+    let mut freed = 0;
+
+    for (&index, &backing) in r.pages.iter() {
+        let va = base + (index * PAGE_SIZE) as u64;
+        match backing {
+            Backing::Private(pa) => {
+                unsafe { scrub_page(va); }
+                let _ = virt::unmap4k_keep(VirtAddr::new(va));
+                release_frame(pa, tag);
+                freed += 1;
+            }
+            Backing::Shared(_) => {
+                let _ = virt::unmap4k_keep(VirtAddr::new(va));
+            }
+            Backing::Huge(pa) => {
+                for i in 0..HUGE_PAGES {
+                    unsafe { scrub_page(va + (i * PAGE_SIZE) as u64); }
+                }
+                // a partial protect may have split it; unmap at either size
+                let _ = virt::unmap_range_keep(VirtAddr::new(va), HUGE_2M);
+                release_huge(pa, tag);
+                freed += HUGE_PAGES;
+            }
+        }
+    }
+    core::sync::atomic::compiler_fence(Ordering::SeqCst);
+
+    // Last instance of a manifest hash releases the shared image
+    if let Some(key) = r.code_key {
+        let mut images = IMAGES.lock();
+        let last = match images.get_mut(&key) {
+            Some(image) => {
+                image.refs -= 1;
+                image.refs == 0
+            }
+            None => false,
+        };
+        if last {
+            if let Some(image) = images.remove(&key) {
+                for pa in image.frames {
+                    if let Some(va) = directmap_va(pa) {
+                        unsafe { scrub_page(va); }
+                    }
+                    release_frame(pa, tag);
+                    freed += 1;
+                }
+            }
+        }
+    }
+    freed
+}
+
+/// Zero every resident private page of a demand-paged region in place
+fn scrub_resident(region: &MemoryRegion) {
+    let base = region.base.as_ptr() as u64;
+    let lazy = lock_lazy();
+    if let Some(r) = lazy.get(&base) {
+        for (&index, backing) in r.pages.iter() {
+            let n = match backing {
+                Backing::Private(_) => 1,
+                Backing::Huge(_) => HUGE_PAGES,
+                Backing::Shared(_) => 0,
+            };
+            for i in 0..n {
+                unsafe { scrub_page(base + ((index + i) * PAGE_SIZE) as u64); }
+            }
+        }
+    }
+}
+
+unsafe fn scrub_page(va: u64) {
+    let p = va as *mut u64;
+    for w in 0..(PAGE_SIZE / 8) {
+        core::ptr::write_volatile(p.add(w), 0);
+    }
+}
+
+fn cap_tag(flags: RegionFlags) -> CapTag {
+    if flags.contains(RegionFlags::USER) { CapTag::USER } else { CapTag::KERNEL }
 }
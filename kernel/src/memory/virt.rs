@@ -2,13 +2,15 @@
 //
 // Features
 //  - 4-level x86_64 paging (4KiB + 2MiB), 1GiB reserved TODO
+//  - Huge-page aware range ops: 2MiB leaves where VA/PA alignment allows,
+//    split to 4KiB on partial protect/unmap
 //  - Self-referenced PML4 slot for in-place table introspection
-//  - AddressSpace object (CR3 handle) with PCID scaffold (KPTI later)
+//  - AddressSpace object (CR3 handle); PCID tagging behind `nonos-pcid` (KPTI later)
 //  - Map/Unmap/Protect single and range; Translate; Walk
 //  - W^X runtime validator; Guard-page helpers (stacks/IST)
 //  - Page-table GC: frees empty L1/L2/L3 safely (no dangling entries)
-//  - TLB shootdown scaffold (single-CPU now; IPI later)
-//  - KASLR slide helpers
+//  - TLB shootdown: targeted INVLPG/INVPCID, per-CPU stale PCIDs (IPI later)
+//  - KASLR helpers: slide, direct map and per-CPU areas at randomized bases
 //  - Cache attribute flags (PWT/PCD/PAT TBD)
 //  - Proof hooks: audit_map/unmap/protect
 //
@@ -18,18 +20,20 @@
 #![allow(dead_code)]
 
 use core::{fmt, ptr};
-use spin::Mutex;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::{Mutex, MutexGuard};
 use x86_64::{
     PhysAddr, VirtAddr,
     registers::control::{Cr3, Cr3Flags},
     structures::paging::{
-        FrameAllocator, Mapper, MapperAllSizes, Page, PageTable, PageTableFlags as PtF,
+        FrameAllocator, Mapper, MapperAllSizes, Page, PageTable, PageTableEntry, PageTableFlags as PtF,
         PhysFrame, Size2MiB, Size4KiB,
     },
 };
 
-use crate::memory::layout::{PAGE_SIZE, HUGE_2M, KERNEL_BASE, align_down, align_up};
-use crate::memory::phys::{Frame, alloc as phys_alloc, alloc_contig as phys_alloc_contig, free as phys_free};
+use crate::memory::layout::{self, PAGE_SIZE, HUGE_2M, BOOT_PHYS_OFFSET, align_down, align_up};
+use crate::memory::phys::{Frame, alloc as phys_alloc, alloc_contig as phys_alloc_contig, free as phys_free, free_contig as phys_free_contig};
 use crate::memory::kaslr::Kaslr;
 
 // Optional: your zk/onion audit hooks (implement these in memory/proof.rs)
@@ -91,30 +95,278 @@ pub fn selfref_l4_va() -> VirtAddr {
 
 pub struct AddressSpace {
     cr3_frame: PhysFrame,
-    pcid: Option<u16>, // TODO: PCID plumbing when CR4.PCIDE is enabled
+    /// Hardware PCID; `None` = untagged (feature off, unsupported, or pool
+    /// exhausted) and every install flushes the TLB
+    pcid: Option<u16>,
 }
 
 impl AddressSpace {
     /// Create an AddressSpace from a root page table physical address.
     /// Caller must ensure the page table is valid and mapped.
+    /// Untagged; used for the kernel space, which runs under PCID 0.
     pub unsafe fn from_root(root_phys: u64) -> Result<Self, VmErr> {
         let frame = PhysFrame::containing_address(PhysAddr::new(root_phys));
         Ok(AddressSpace { cr3_frame: frame, pcid: None })
     }
 
-    /// Install CR3 (no PCID yet). Returns previous CR3.
+    /// Install CR3. Returns previous CR3.
+    ///
+    /// A tagged space keeps its TLB entries across switches (NOFLUSH), unless
+    /// its PCID was marked stale since it last ran, in which case only that
+    /// PCID's entries are dropped.
     pub unsafe fn install(&self) -> (PhysFrame, Cr3Flags) {
         let (old, flags) = Cr3::read();
-        Cr3::write(self.cr3_frame, Cr3Flags::empty());
+        match self.pcid {
+            Some(id) if pcid::enabled() => {
+                let mut val = self.root_phys() | id as u64;
+                if !pcid::take_stale(id) {
+                    val |= pcid::CR3_NOFLUSH;
+                }
+                core::arch::asm!("mov cr3, {}", in(reg) val, options(nostack, preserves_flags));
+            }
+            _ => Cr3::write(self.cr3_frame, Cr3Flags::empty()),
+        }
+        pcid::set_current(self.tag());
         (old, flags)
     }
 
     pub fn root_phys(&self) -> u64 { self.cr3_frame.start_address().as_u64() }
+
+    pub fn pcid(&self) -> Option<u16> { self.pcid }
+
+    /// PCID this space runs under; untagged spaces share the kernel's 0
+    #[inline]
+    pub fn tag(&self) -> u16 { self.pcid.unwrap_or(0) }
+
+    /// Drop every non-global TLB entry of this space: now on this CPU, on
+    /// the others when it next runs there
+    pub fn flush(&self) {
+        pcid::flush(self.tag());
+    }
+
+    /// Drop the TLB entry for one page of this space (see `flush`)
+    pub fn flush_page(&self, va: VirtAddr) {
+        pcid::flush_page(self.tag(), va);
+    }
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        if let Some(id) = self.pcid.take() {
+            pcid::free(id);
+        }
+    }
+}
+
+// ───────────────────────────────────────────────────────────────────────────────
+// PCID allocation & targeted invalidation
+// ───────────────────────────────────────────────────────────────────────────────
+// PCID 0 is the kernel's (and every untagged space's). Capsule spaces get
+// 1..4095. A freed PCID is invalidated before it returns to the pool, so a
+// recycled tag never sees its previous owner's translations.
+//
+// Staleness is tracked per CPU. A flush invalidates this CPU's entries now:
+// INVPCID when available, otherwise INVLPG / a CR3 reload if the tag is the
+// one running here, otherwise a stale mark so its next install on this CPU
+// writes CR3 without NOFLUSH. Every other CPU gets a stale mark (no IPIs yet).
+
+pub mod pcid {
+    use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering};
+    use spin::Mutex;
+    use x86_64::VirtAddr;
+
+    pub const MAX_PCID: usize = 4096;
+    /// CR3 bit 63: keep TLB entries tagged with the new PCID
+    pub const CR3_NOFLUSH: u64 = 1 << 63;
+
+    const WORDS: usize = MAX_PCID / 64;
+    const MAX_CPUS: usize = 64;
+
+    /// INVPCID types (SDM Vol. 2A)
+    const INVPCID_ADDR: u64 = 0;
+    const INVPCID_SINGLE: u64 = 1;
+    const INVPCID_ALL_NONGLOBAL: u64 = 3;
+
+    static ENABLED: AtomicBool = AtomicBool::new(false);
+    static HAS_INVPCID: AtomicBool = AtomicBool::new(false);
+    /// 1 = in use; PCID 0 permanently reserved
+    static USED: Mutex<[u64; WORDS]> = Mutex::new(init_used());
+    /// Per CPU, 1 = must flush on next install there
+    static STALE: [[AtomicU64; WORDS]; MAX_CPUS] = [const { [const { AtomicU64::new(0) }; WORDS] }; MAX_CPUS];
+    /// PCID currently in CR3 on each CPU
+    static CURRENT: [AtomicU16; MAX_CPUS] = [const { AtomicU16::new(0) }; MAX_CPUS];
+    static RECYCLED: AtomicU64 = AtomicU64::new(0);
+
+    const fn init_used() -> [u64; WORDS] {
+        let mut w = [0u64; WORDS];
+        w[0] = 1;
+        w
+    }
+
+    fn this_cpu() -> usize {
+        use crate::arch::x86_64::interrupt::apic;
+        let id = if apic::ready() { apic::id() } else { 0 };
+        id as usize % MAX_CPUS
+    }
+
+    /// Called once CR4.PCIDE is set (`gdt::enable_pcid`, `nonos-pcid` only)
+    pub fn online(invpcid: bool) {
+        HAS_INVPCID.store(invpcid, Ordering::Release);
+        ENABLED.store(true, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn enabled() -> bool { ENABLED.load(Ordering::Acquire) }
+
+    #[inline]
+    fn has_invpcid() -> bool { enabled() && HAS_INVPCID.load(Ordering::Acquire) }
+
+    /// Allocate a PCID (1..4095); `None` if disabled or exhausted
+    pub fn alloc() -> Option<u16> {
+        if !enabled() { return None; }
+        let mut used = USED.lock();
+        for (w, word) in used.iter_mut().enumerate() {
+            if *word == u64::MAX { continue; }
+            let bit = (!*word).trailing_zeros() as usize;
+            *word |= 1 << bit;
+            return Some((w * 64 + bit) as u16);
+        }
+        None
+    }
+
+    /// Return a PCID to the pool after invalidating its entries
+    pub fn free(id: u16) {
+        if id == 0 || id as usize >= MAX_PCID { return; }
+        flush(id);
+        USED.lock()[id as usize / 64] &= !(1 << (id % 64));
+        RECYCLED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// (in use, recycled so far)
+    pub fn stats() -> (usize, u64) {
+        let used = USED.lock().iter().map(|w| w.count_ones() as usize).sum::<usize>() - 1;
+        (used, RECYCLED.load(Ordering::Relaxed))
+    }
+
+    /// PCID in CR3 on this CPU (0 when PCIDs are off)
+    #[inline]
+    pub fn current() -> u16 { CURRENT[this_cpu()].load(Ordering::Relaxed) }
+
+    /// Record the PCID just written to CR3 on this CPU
+    pub(super) fn set_current(id: u16) {
+        CURRENT[this_cpu()].store(id, Ordering::Relaxed);
+    }
+
+    /// Drop every non-global entry tagged `id`
+    pub fn flush(id: u16) {
+        let cpu = this_cpu();
+        if has_invpcid() {
+            unsafe { invpcid(INVPCID_SINGLE, id, 0) };
+        } else if current() == id {
+            unsafe { reload_cr3() };
+        } else {
+            mark_stale(cpu, id);
+        }
+        mark_stale_remote(cpu, id);
+    }
+
+    /// Drop the entry for `va` tagged `id`
+    pub fn flush_page(id: u16, va: VirtAddr) {
+        let cpu = this_cpu();
+        if has_invpcid() {
+            unsafe { invpcid(INVPCID_ADDR, id, va.as_u64()) };
+        } else if current() == id {
+            unsafe { invlpg(va) };
+        } else {
+            mark_stale(cpu, id);
+        }
+        mark_stale_remote(cpu, id);
+    }
+
+    /// Drop every non-global entry of every PCID on this CPU; other CPUs
+    /// drop theirs as each tag next runs there.
+    pub fn flush_all_nonglobal() {
+        let cpu = this_cpu();
+        if has_invpcid() {
+            unsafe { invpcid(INVPCID_ALL_NONGLOBAL, 0, 0) };
+        } else {
+            mark_used_stale(|c| c == cpu);
+            unsafe { reload_cr3() };
+            take_stale_on(cpu, current());
+        }
+        mark_used_stale(|c| c != cpu);
+    }
+
+    /// Mark every PCID in use, except the one running here, stale on every
+    /// CPU; the running one is marked stale on the other CPUs only. Used after
+    /// a targeted invalidation of the current space when the change (freed
+    /// tables) may also be cached under other tags.
+    pub fn mark_others_stale() {
+        if !enabled() { return; }
+        let (cpu, cur) = (this_cpu(), current());
+        mark_used_stale(|_| true);
+        take_stale_on(cpu, cur);
+    }
+
+    /// Clear and return this CPU's stale bit for `id`
+    pub(super) fn take_stale(id: u16) -> bool {
+        take_stale_on(this_cpu(), id)
+    }
+
+    fn take_stale_on(cpu: usize, id: u16) -> bool {
+        let bit = 1u64 << (id % 64);
+        STALE[cpu][id as usize / 64].fetch_and(!bit, Ordering::AcqRel) & bit != 0
+    }
+
+    fn mark_stale(cpu: usize, id: u16) {
+        if !enabled() { return; }
+        STALE[cpu][id as usize / 64].fetch_or(1 << (id % 64), Ordering::AcqRel);
+    }
+
+    fn mark_stale_remote(cpu: usize, id: u16) {
+        (0..MAX_CPUS).filter(|&c| c != cpu).for_each(|c| mark_stale(c, id));
+    }
+
+    fn mark_used_stale(on: impl Fn(usize) -> bool) {
+        if !enabled() { return; }
+        let used = USED.lock();
+        for (c, stale) in STALE.iter().enumerate().filter(|(c, _)| on(*c)) {
+            for (w, word) in used.iter().enumerate() {
+                stale[w].fetch_or(*word, Ordering::AcqRel);
+            }
+        }
+    }
+
+    /// Rewrite CR3 without NOFLUSH: drops the current PCID's entries
+    unsafe fn reload_cr3() {
+        let cr3: u64;
+        core::arch::asm!("mov {}, cr3", out(reg) cr3, options(nomem, nostack, preserves_flags));
+        core::arch::asm!("mov cr3, {}", in(reg) cr3 & !CR3_NOFLUSH, options(nostack, preserves_flags));
+    }
+
+    #[inline]
+    unsafe fn invlpg(va: VirtAddr) {
+        core::arch::asm!("invlpg [{}]", in(reg) va.as_u64(), options(nostack, preserves_flags));
+    }
+
+    #[inline]
+    unsafe fn invpcid(kind: u64, id: u16, va: u64) {
+        let desc: [u64; 2] = [id as u64 & 0xfff, va];
+        core::arch::asm!(
+            "invpcid {}, [{}]",
+            in(reg) kind,
+            in(reg) desc.as_ptr(),
+            options(nostack, preserves_flags)
+        );
+    }
 }
 
 // Singleton kernel address space handle + Mapper root (borrowed).
 static KSPACE: Mutex<Option<AddressSpace>> = Mutex::new(None);
 static ROOT_PT: Mutex<Option<&'static mut PageTable>> = Mutex::new(None);
+/// VA of physical 0 used to reach page tables: the bootloader's alias until
+/// `map_directmap`, then the (KASLR-chosen) direct-map base.
+static PHYS_WINDOW: AtomicU64 = AtomicU64::new(BOOT_PHYS_OFFSET);
 
 // ───────────────────────────────────────────────────────────────────────────────
 // Init & helpers
@@ -128,7 +380,7 @@ pub unsafe fn init(root_pt_phys: u64) -> Result<(), VmErr> {
     let (_old, _flags) = aspace.install();
 
     // Map the PML4 into the self-referenced slot if not already.
-    let l4_va = VirtAddr::new(root_pt_phys + KERNEL_BASE);
+    let l4_va = phys_va(PhysAddr::new(root_pt_phys));
     let root_pt: &mut PageTable = &mut *(l4_va.as_u64() as *mut PageTable);
 
     // Install self-ref (L4[SELFREF] points to itself).
@@ -144,9 +396,32 @@ pub unsafe fn init(root_pt_phys: u64) -> Result<(), VmErr> {
     Ok(())
 }
 
-/// Returns a mutable handle to the kernel root page table (guarded).
-fn root_mut<'a>() -> Result<&'a mut PageTable, VmErr> {
-    ROOT_PT.lock().as_deref_mut().ok_or(VmErr::NotInitialized)
+/// The kernel root page table, locked. Every walk and every table mutation
+/// (map, unmap, protect, split, GC) holds this for its whole duration, so
+/// `gc_tables` can never free a table another path is still using.
+///
+/// Lock order: heap -> root -> phys (and root -> split spares, a leaf).
+/// Nothing under the root lock may touch the heap or call back into this
+/// module.
+pub struct RootGuard(MutexGuard<'static, Option<&'static mut PageTable>>);
+
+impl Deref for RootGuard {
+    type Target = PageTable;
+    fn deref(&self) -> &PageTable {
+        self.0.as_deref().expect("root checked by root_lock")
+    }
+}
+
+impl DerefMut for RootGuard {
+    fn deref_mut(&mut self) -> &mut PageTable {
+        self.0.as_deref_mut().expect("root checked by root_lock")
+    }
+}
+
+fn root_lock() -> Result<RootGuard, VmErr> {
+    let g = ROOT_PT.lock();
+    if g.is_none() { return Err(VmErr::NotInitialized); }
+    Ok(RootGuard(g))
 }
 
 // ───────────────────────────────────────────────────────────────────────────────
@@ -197,47 +472,61 @@ unsafe impl FrameAllocator<Size2MiB> for PhysAllocShim {
 pub fn map4k_at(va: VirtAddr, pa: PhysAddr, flags: VmFlags) -> Result<(), VmErr> {
     if !is_aligned_4k(va.as_u64()) || !is_aligned_4k(pa.as_u64()) { return Err(VmErr::Misaligned); }
     let hw = to_ptf(flags)?;
-    let root = root_mut()?;
+    let mut root = root_lock()?;
 
     unsafe {
         let page = Page::<Size4KiB>::containing_address(va);
         let frame = PhysFrame::containing_address(pa);
         // prohibit overlaps: if already mapped, this errs
-        if let Ok((_f, _fl)) = translate(va) { return Err(VmErr::Overlap); }
+        if translate_in(&root, va).is_ok() { return Err(VmErr::Overlap); }
         root.map_to(page, frame, hw, &mut PhysAllocShim).map_err(|_| VmErr::NoMemory)?.flush();
     }
+    drop(root);
 
     audit_map(va.as_u64(), pa.as_u64(), PAGE_SIZE as u64, flags.bits());
     Ok(())
 }
 
 pub fn unmap4k(va: VirtAddr) -> Result<(), VmErr> {
+    let pa = unmap4k_keep(va)?;
+    phys_free(Frame(pa.as_u64()));
+    Ok(())
+}
+
+/// Unmap a 4 KiB page but leave its frame allocated (shared or caller-owned
+/// frames). Returns the frame that was mapped. A 2 MiB page under `va` is
+/// split with its reserved table; use `unmap_range_keep` to drop whole ones.
+pub fn unmap4k_keep(va: VirtAddr) -> Result<PhysAddr, VmErr> {
     if !is_aligned_4k(va.as_u64()) { return Err(VmErr::Misaligned); }
-    let root = root_mut()?;
+    let mut root = root_lock()?;
+    split_if_huge(&mut root, va)?;
 
-    unsafe {
+    let pa = unsafe {
         let page = Page::<Size4KiB>::containing_address(va);
         let (frame, flush) = root.unmap(page).map_err(|_| VmErr::NotMapped)?;
         flush.flush();
-        phys_free(Frame(frame.start_address().as_u64()));
-    }
+        frame.start_address()
+    };
+    drop(root);
 
     audit_unmap(va.as_u64(), PAGE_SIZE as u64);
-    Ok(())
+    Ok(pa)
 }
 
 pub fn protect4k(va: VirtAddr, flags: VmFlags) -> Result<(), VmErr> {
     if !is_aligned_4k(va.as_u64()) { return Err(VmErr::Misaligned); }
     let hw = to_ptf(flags)?;
-    let root = root_mut()?;
+    let mut root = root_lock()?;
+    split_if_huge(&mut root, va)?;
 
     unsafe {
         let page = Page::<Size4KiB>::containing_address(va);
-        let pte = walk_l1_entry_mut(root, va).ok_or(VmErr::NotMapped)?;
+        let pte = walk_l1_entry_mut(&mut root, va).ok_or(VmErr::NotMapped)?;
         let pa  = pte.addr();
         pte.set_addr(pa, hw);
         core::arch::asm!("invlpg [{}]", in(reg) va.as_u64(), options(nostack, preserves_flags));
     }
+    drop(root);
 
     audit_protect(va.as_u64(), PAGE_SIZE as u64, flags.bits());
     Ok(())
@@ -249,39 +538,218 @@ pub fn protect4k(va: VirtAddr, flags: VmFlags) -> Result<(), VmErr> {
 
 pub fn map2m_at(va: VirtAddr, pa: PhysAddr, flags: VmFlags) -> Result<(), VmErr> {
     if !is_aligned_2m(va.as_u64()) || !is_aligned_2m(pa.as_u64()) { return Err(VmErr::Misaligned); }
-    let hw = to_ptf(flags)? | PtF::HUGE_PAGE;
-    let root = root_mut()?;
+    let hw = to_ptf(flags)? | PtF::HUGE_PAGE | SPLIT_RESERVED;
+    // Reserve the table a later split would need while we may still allocate
+    let spare = phys_alloc_contig(1, 1, crate::memory::phys::AllocFlags::empty()).ok_or(VmErr::NoMemory)?;
+    let mut root = root_lock()?;
 
-    unsafe {
+    let mapped = unsafe {
         // ensure the L2 entry is free (not already split into 4K)
-        if has_split_l2(root, va) { return Err(VmErr::HugeConflict); }
-        let page = Page::<Size2MiB>::containing_address(va);
-        let frame = PhysFrame::containing_address(pa);
-        root.map_to(page, frame, hw, &mut PhysAllocShim).map_err(|_| VmErr::NoMemory)?.flush();
+        if has_split_l2(&mut root, va) {
+            Err(VmErr::HugeConflict)
+        } else {
+            let page = Page::<Size2MiB>::containing_address(va);
+            let frame = PhysFrame::containing_address(pa);
+            root.map_to(page, frame, hw, &mut PhysAllocShim).map(|f| f.flush()).map_err(|_| VmErr::NoMemory)
+        }
+    };
+    drop(root);
+    if let Err(e) = mapped {
+        phys_free(spare);
+        return Err(e);
     }
 
+    spare_push(spare.0);
+    HUGE_LIVE.fetch_add(1, Ordering::Relaxed);
     audit_map(va.as_u64(), pa.as_u64(), HUGE_2M as u64, flags.bits());
     Ok(())
 }
 
+/// Unmap a 2 MiB page and return its 512 frames to phys.
 pub fn unmap2m(va: VirtAddr) -> Result<(), VmErr> {
+    let pa = unmap2m_keep(va)?;
+    phys_free_contig(Frame(pa.as_u64()), HUGE_2M / PAGE_SIZE);
+    Ok(())
+}
+
+/// Unmap a 2 MiB page but leave its frames allocated. Returns the base frame.
+pub fn unmap2m_keep(va: VirtAddr) -> Result<PhysAddr, VmErr> {
     if !is_aligned_2m(va.as_u64()) { return Err(VmErr::Misaligned); }
-    let root = root_mut()?;
+    let mut root = root_lock()?;
 
-    unsafe {
+    let (pa, reserved) = unsafe {
         // cannot use root.unmap(Page::<Size2MiB>) safely if the entry was split
-        let (l2, i2) = walk_l2_entry_mut(root, va).ok_or(VmErr::NotMapped)?;
-        if !l2[i2].flags().contains(PtF::HUGE_PAGE) { return Err(VmErr::NotMapped); }
+        let (l2, i2) = walk_l2_entry_mut(&mut root, va).ok_or(VmErr::NotMapped)?;
+        let f = l2[i2].flags();
+        if !f.contains(PtF::HUGE_PAGE) { return Err(VmErr::NotMapped); }
         let pa = l2[i2].addr();
         l2[i2].set_unused();
         core::arch::asm!("invlpg [{}]", in(reg) va.as_u64(), options(nostack, preserves_flags));
-        phys_free(Frame(pa.as_u64())); // returns first 4KiB; if contig path used, you may want free_contig here
+        (pa, f.contains(SPLIT_RESERVED))
+    };
+    drop(root);
+
+    // The page can no longer be split; hand its reserved table back
+    if reserved {
+        if let Some(spare) = spare_pop() { phys_free(Frame(spare)); }
+        huge_dec();
     }
-
     audit_unmap(va.as_u64(), HUGE_2M as u64);
+    Ok(pa)
+}
+
+/// Change the flags of a whole 2 MiB page (stays huge).
+pub fn protect2m(va: VirtAddr, flags: VmFlags) -> Result<(), VmErr> {
+    if !is_aligned_2m(va.as_u64()) { return Err(VmErr::Misaligned); }
+    let hw = to_ptf(flags)? | PtF::HUGE_PAGE;
+    let mut root = root_lock()?;
+
+    unsafe {
+        let (l2, i2) = walk_l2_entry_mut(&mut root, va).ok_or(VmErr::NotMapped)?;
+        let old = l2[i2].flags();
+        if !old.contains(PtF::HUGE_PAGE) { return Err(VmErr::NotMapped); }
+        let pa = l2[i2].addr();
+        l2[i2].set_addr(pa, hw | (old & SPLIT_RESERVED));
+        core::arch::asm!("invlpg [{}]", in(reg) va.as_u64(), options(nostack, preserves_flags));
+    }
+    drop(root);
+
+    audit_protect(va.as_u64(), HUGE_2M as u64, flags.bits());
     Ok(())
 }
 
+/// Demote the 2 MiB page covering `va` to 512 4 KiB pages with the same
+/// frames and flags. The mapping is unchanged; only its granularity is.
+/// Also works on 2 MiB pages `map2m_at` did not make (bootloader, direct
+/// map): those carry no reserved table, so one is allocated here.
+pub fn split2m(va: VirtAddr) -> Result<(), VmErr> {
+    let fresh = phys_alloc_contig(1, 1, crate::memory::phys::AllocFlags::empty()).ok_or(VmErr::NoMemory)?;
+    let mut root = root_lock()?;
+    let (r, took_fresh) = match huge_leaf(&mut root, va) {
+        Some(f) if f.contains(SPLIT_RESERVED) => (split2m_in(&mut root, va), false),
+        Some(_) => (split2m_with(&mut root, va, fresh.0), true),
+        None => (Err(VmErr::NotMapped), false),
+    };
+    drop(root);
+    if !(took_fresh && r.is_ok()) {
+        phys_free(fresh);
+    }
+    r
+}
+
+/// Split a `map2m_at` page using the table reserved for it; never allocates,
+/// so it is safe on the unmap/free paths. Pages without a reservation are
+/// refused (HugeConflict) rather than handed another page's spare.
+fn split2m_in(root: &mut PageTable, va: VirtAddr) -> Result<(), VmErr> {
+    match huge_leaf(root, va) {
+        Some(f) if f.contains(SPLIT_RESERVED) => {}
+        Some(_) => return Err(VmErr::HugeConflict),
+        None => return Err(VmErr::NotMapped),
+    }
+    let table = spare_pop().ok_or(VmErr::NoMemory)?;
+    if let Err(e) = split2m_with(root, va, table) {
+        spare_push(table);
+        return Err(e);
+    }
+    huge_dec();
+    Ok(())
+}
+
+/// Flags of the 2 MiB leaf covering `va`, if it is one
+fn huge_leaf(root: &mut PageTable, va: VirtAddr) -> Option<PtF> {
+    let (l2, i2) = unsafe { walk_l2_entry_mut(root, va) }?;
+    let f = l2[i2].flags();
+    f.contains(PtF::PRESENT | PtF::HUGE_PAGE).then_some(f)
+}
+
+/// Replace the 2 MiB leaf covering `va` with L1 table `table`, a free frame
+/// the caller owns; it becomes part of the page tables.
+fn split2m_with(root: &mut PageTable, va: VirtAddr, table: u64) -> Result<(), VmErr> {
+    let base = VirtAddr::new(align_down(va.as_u64(), HUGE_2M as u64));
+
+    unsafe {
+        let (l2, i2) = walk_l2_entry_mut(root, base).ok_or(VmErr::NotMapped)?;
+        let huge = l2[i2].flags();
+        if !huge.contains(PtF::HUGE_PAGE) { return Err(VmErr::NotMapped); }
+        let pa = l2[i2].addr().as_u64();
+
+        // every entry is written below, so the table needs no zeroing
+        let l1 = table_mut(PhysAddr::new(table));
+        let leaf = huge - PtF::HUGE_PAGE - SPLIT_RESERVED;
+        for (i, e) in l1.iter_mut().enumerate() {
+            e.set_addr(PhysAddr::new(pa + (i * PAGE_SIZE) as u64), leaf);
+        }
+        // Leaf carries NX/GLOBAL/cache bits; the table entry only gates access
+        let mut parent = PtF::PRESENT | PtF::WRITABLE;
+        if huge.contains(PtF::USER_ACCESSIBLE) { parent |= PtF::USER_ACCESSIBLE; }
+        l2[i2].set_addr(PhysAddr::new(table), parent);
+        // invlpg drops the large-page TLB entry and the paging-structure caches for it
+        core::arch::asm!("invlpg [{}]", in(reg) base.as_u64(), options(nostack, preserves_flags));
+    }
+
+    HUGE_SPLITS.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Split the page covering `va` if it is a 2 MiB page; no-op otherwise.
+/// Only `map2m_at` pages can be split here (see `split2m_in`).
+fn split_if_huge(root: &mut PageTable, va: VirtAddr) -> Result<(), VmErr> {
+    match translate_in(root, va) {
+        Ok((_, _, size)) if size == HUGE_2M => split2m_in(root, va),
+        _ => Ok(()),
+    }
+}
+
+/// Software bit on 2 MiB leaves made by `map2m_at`: a spare table sits in
+/// SPLIT_SPARES for this page. Leaves without it (bootloader, direct map)
+/// have none and are never split on the free paths.
+const SPLIT_RESERVED: PtF = PtF::BIT_9;
+
+/// Spare L1 tables: one reserved per 2 MiB page `map2m_at` maps, so a partial
+/// unmap/protect can split without allocating. Linked through each frame's
+/// first word. The pool holds exactly one table per live SPLIT_RESERVED leaf.
+struct Spares { head: u64, len: usize }
+static SPLIT_SPARES: Mutex<Spares> = Mutex::new(Spares { head: 0, len: 0 });
+
+fn spare_push(pa: u64) {
+    let mut s = SPLIT_SPARES.lock();
+    unsafe { *(table_mut(PhysAddr::new(pa)) as *mut PageTable as *mut u64) = s.head; }
+    s.head = pa;
+    s.len += 1;
+}
+
+fn spare_pop() -> Option<u64> {
+    let mut s = SPLIT_SPARES.lock();
+    if s.len == 0 { return None; }
+    let pa = s.head;
+    s.head = unsafe { *(table_mut(PhysAddr::new(pa)) as *mut PageTable as *const u64) };
+    s.len -= 1;
+    Some(pa)
+}
+
+/// True if the L2 entry covering `va` points at a 4 KiB table.
+unsafe fn has_split_l2(root: &mut PageTable, va: VirtAddr) -> bool {
+    match walk_l2_entry_mut(root, va) {
+        Some((l2, i2)) => !l2[i2].is_unused() && !l2[i2].flags().contains(PtF::HUGE_PAGE),
+        None => false,
+    }
+}
+
+/// 2 MiB leaves currently mapped by `map2m_at`
+static HUGE_LIVE: AtomicU64 = AtomicU64::new(0);
+/// 2 MiB leaves demoted to 4 KiB by partial protect/unmap
+static HUGE_SPLITS: AtomicU64 = AtomicU64::new(0);
+
+/// Only SPLIT_RESERVED leaves are counted; never wrap regardless
+fn huge_dec() {
+    let _ = HUGE_LIVE.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| Some(n.saturating_sub(1)));
+}
+
+/// (2 MiB pages mapped now, splits since boot)
+pub fn huge_stats() -> (u64, u64) {
+    (HUGE_LIVE.load(Ordering::Relaxed), HUGE_SPLITS.load(Ordering::Relaxed))
+}
+
 // ───────────────────────────────────────────────────────────────────────────────
 // Range ops
 // ───────────────────────────────────────────────────────────────────────────────
@@ -308,6 +776,82 @@ pub fn unmap_range_4k(base: VirtAddr, len: usize) -> Result<(), VmErr> {
     Ok(())
 }
 
+/// Map `len` bytes of contiguous physical memory, with 2 MiB pages wherever
+/// VA and PA are both 2 MiB-aligned and a whole 2 MiB remains; 4 KiB elsewhere.
+/// Returns the number of 2 MiB pages used.
+pub fn map_range_at(base: VirtAddr, pa: PhysAddr, len: usize, flags: VmFlags) -> Result<usize, VmErr> {
+    if (len == 0) || !is_aligned_4k(base.as_u64()) || !is_aligned_4k(pa.as_u64()) { return Err(VmErr::Misaligned); }
+    let len = align_up(len as u64, PAGE_SIZE as u64) as usize;
+    let (mut off, mut huge) = (0usize, 0usize);
+    while off < len {
+        let va = VirtAddr::new(base.as_u64() + off as u64);
+        let p = PhysAddr::new(pa.as_u64() + off as u64);
+        if len - off >= HUGE_2M && is_aligned_2m(va.as_u64()) && is_aligned_2m(p.as_u64()) {
+            map2m_at(va, p, flags)?;
+            off += HUGE_2M;
+            huge += 1;
+        } else {
+            map4k_at(va, p, flags)?;
+            off += PAGE_SIZE;
+        }
+    }
+    Ok(huge)
+}
+
+/// Unmap `len` bytes mapped at any granularity, returning frames to phys.
+/// 2 MiB pages wholly inside the range are unmapped whole; ones straddling
+/// an edge are split first.
+pub fn unmap_range(base: VirtAddr, len: usize) -> Result<(), VmErr> {
+    unmap_range_with(base, len, |pa, size| phys_free_contig(Frame(pa.as_u64()), size / PAGE_SIZE))
+}
+
+/// `unmap_range`, leaving the frames allocated (caller-owned or contiguous).
+pub fn unmap_range_keep(base: VirtAddr, len: usize) -> Result<(), VmErr> {
+    unmap_range_with(base, len, |_, _| {})
+}
+
+fn unmap_range_with(base: VirtAddr, len: usize, mut release: impl FnMut(PhysAddr, usize)) -> Result<(), VmErr> {
+    if (len == 0) || !is_aligned_4k(base.as_u64()) { return Err(VmErr::Misaligned); }
+    let len = align_up(len as u64, PAGE_SIZE as u64) as usize;
+    let mut off = 0usize;
+    while off < len {
+        let va = VirtAddr::new(base.as_u64() + off as u64);
+        let whole_huge = is_aligned_2m(va.as_u64())
+            && len - off >= HUGE_2M
+            && matches!(translate(va), Ok((_, _, size)) if size == HUGE_2M);
+        if whole_huge {
+            release(unmap2m_keep(va)?, HUGE_2M);
+            off += HUGE_2M;
+        } else {
+            release(unmap4k_keep(va)?, PAGE_SIZE);
+            off += PAGE_SIZE;
+        }
+    }
+    Ok(())
+}
+
+/// Protect `len` bytes mapped at any granularity; 2 MiB pages wholly inside
+/// the range stay huge, ones straddling an edge are split.
+pub fn protect_range(base: VirtAddr, len: usize, flags: VmFlags) -> Result<(), VmErr> {
+    if (len == 0) || !is_aligned_4k(base.as_u64()) { return Err(VmErr::Misaligned); }
+    let len = align_up(len as u64, PAGE_SIZE as u64) as usize;
+    let mut off = 0usize;
+    while off < len {
+        let va = VirtAddr::new(base.as_u64() + off as u64);
+        let whole_huge = is_aligned_2m(va.as_u64())
+            && len - off >= HUGE_2M
+            && matches!(translate(va), Ok((_, _, size)) if size == HUGE_2M);
+        if whole_huge {
+            protect2m(va, flags)?;
+            off += HUGE_2M;
+        } else {
+            protect4k(va, flags)?;
+            off += PAGE_SIZE;
+        }
+    }
+    Ok(())
+}
+
 pub fn protect_range_4k(base: VirtAddr, len: usize, flags: VmFlags) -> Result<(), VmErr> {
     if (len == 0) || !is_aligned_4k(base.as_u64()) { return Err(VmErr::Misaligned); }
     for off in (0..len).step_by(PAGE_SIZE) {
@@ -322,7 +866,10 @@ pub fn protect_range_4k(base: VirtAddr, len: usize, flags: VmFlags) -> Result<()
 
 /// Returns (PA, flags, page_size). None if unmapped. Works for 4K/2M.
 pub fn translate(va: VirtAddr) -> Result<(PhysAddr, VmFlags, usize), VmErr> {
-    let root = root_mut()?;
+    translate_in(&root_lock()?, va)
+}
+
+fn translate_in(root: &PageTable, va: VirtAddr) -> Result<(PhysAddr, VmFlags, usize), VmErr> {
     unsafe {
         // Walk L4->L3->L2
         let (l4, i4) = (root, l4_idx(va));
@@ -353,6 +900,61 @@ pub fn translate(va: VirtAddr) -> Result<(PhysAddr, VmFlags, usize), VmErr> {
     }
 }
 
+/// Visit every present leaf mapping of the kernel root (4K, 2M and 1G) as
+/// (VA, page size, effective flags). Effective flags fold the hierarchy:
+/// RW/USER only if every level grants them, NX if any level sets it.
+/// The self-reference slot is skipped. `visit` runs under the root lock and
+/// must not call back into this module.
+pub fn walk_leaves<F: FnMut(VirtAddr, usize, VmFlags)>(mut visit: F) -> Result<(), VmErr> {
+    let root = root_lock()?;
+    unsafe {
+        for i4 in 0..512 {
+            let e4 = &root[i4];
+            if i4 == SELFREF_SLOT || !e4.flags().contains(PtF::PRESENT) { continue; }
+            let f4 = e4.flags();
+            let l3 = table_mut(e4.addr());
+            for i3 in 0..512 {
+                if !l3[i3].flags().contains(PtF::PRESENT) { continue; }
+                let f3 = fold_ptf(f4, l3[i3].flags());
+                if l3[i3].flags().contains(PtF::HUGE_PAGE) {
+                    visit(va_of(i4, i3, 0, 0), 1 << 30, vmflags_from_ptf(f3));
+                    continue;
+                }
+                let l2 = table_mut(l3[i3].addr());
+                for i2 in 0..512 {
+                    if !l2[i2].flags().contains(PtF::PRESENT) { continue; }
+                    let f2 = fold_ptf(f3, l2[i2].flags());
+                    if l2[i2].flags().contains(PtF::HUGE_PAGE) {
+                        visit(va_of(i4, i3, i2, 0), HUGE_2M, vmflags_from_ptf(f2));
+                        continue;
+                    }
+                    let l1 = table_mut(l2[i2].addr());
+                    for i1 in 0..512 {
+                        if !l1[i1].flags().contains(PtF::PRESENT) { continue; }
+                        visit(va_of(i4, i3, i2, i1), PAGE_SIZE, vmflags_from_ptf(fold_ptf(f2, l1[i1].flags())));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Effective flags of `child` under `parent` (leaf attributes from `child`)
+#[inline]
+fn fold_ptf(parent: PtF, child: PtF) -> PtF {
+    let mut f = child;
+    if !parent.contains(PtF::WRITABLE) { f.remove(PtF::WRITABLE); }
+    if !parent.contains(PtF::USER_ACCESSIBLE) { f.remove(PtF::USER_ACCESSIBLE); }
+    if parent.contains(PtF::NO_EXECUTE) { f.insert(PtF::NO_EXECUTE); }
+    f
+}
+
+#[inline]
+fn va_of(i4: usize, i3: usize, i2: usize, i1: usize) -> VirtAddr {
+    VirtAddr::new_truncate(((i4 as u64) << 39) | ((i3 as u64) << 30) | ((i2 as u64) << 21) | ((i1 as u64) << 12))
+}
+
 #[inline] fn l4_idx(va: VirtAddr) -> usize { ((va.as_u64() >> 39) & 0x1ff) as usize }
 #[inline] fn l3_idx(va: VirtAddr) -> usize { ((va.as_u64() >> 30) & 0x1ff) as usize }
 #[inline] fn l2_idx(va: VirtAddr) -> usize { ((va.as_u64() >> 21) & 0x1ff) as usize }
@@ -360,7 +962,12 @@ pub fn translate(va: VirtAddr) -> Result<(PhysAddr, VmFlags, usize), VmErr> {
 
 #[inline]
 unsafe fn table_mut(p: PhysAddr) -> &'static mut PageTable {
-    &mut *(VirtAddr::new(KERNEL_BASE + p.as_u64()).as_u64() as *mut PageTable)
+    &mut *(phys_va(p).as_u64() as *mut PageTable)
+}
+
+#[inline]
+fn phys_va(p: PhysAddr) -> VirtAddr {
+    VirtAddr::new(PHYS_WINDOW.load(Ordering::Acquire) + p.as_u64())
 }
 
 unsafe fn walk_l2_entry_mut<'a>(root: &'a mut PageTable, va: VirtAddr) -> Option<(&'a mut PageTable, usize)> {
@@ -395,8 +1002,11 @@ fn vmflags_from_ptf(p: PtF) -> VmFlags {
 // ───────────────────────────────────────────────────────────────────────────────
 
 /// Map a stack with a guard page below it: [guard][stack...]
+/// The pair is registered with `mapcheck`; with `nonos-nx-stack` the stack
+/// is always mapped NX.
 pub fn map_stack_with_guard(base: VirtAddr, size: usize, flags: VmFlags) -> Result<(), VmErr> {
     if size == 0 { return Err(VmErr::BadRange); }
+    let flags = if cfg!(feature = "nonos-nx-stack") { flags | VmFlags::NX } else { flags };
     let stack_pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
     // guard page unmapped at base - PAGE_SIZE
     // map stack starting at `base`
@@ -407,6 +1017,7 @@ pub fn map_stack_with_guard(base: VirtAddr, size: usize, flags: VmFlags) -> Resu
             flags
         )?;
     }
+    crate::memory::mapcheck::track_stack(Some(VirtAddr::new(base.as_u64() - PAGE_SIZE as u64)), base, stack_pages);
     Ok(())
 }
 
@@ -415,21 +1026,222 @@ pub fn map_stack_with_guard(base: VirtAddr, size: usize, flags: VmFlags) -> Resu
     VirtAddr::new(va + kaslr.slide)
 }
 
+/// Map physical [0, phys_hi) at `LAYOUT.directmap_lo` (randomized by KASLR)
+/// with 2 MiB pages and reach page tables through it from then on. Call once,
+/// after `kaslr::init`. The direct map is never split, so no split table is
+/// reserved for it. Returns the bytes mapped.
+pub fn map_directmap(phys_hi: u64) -> Result<u64, VmErr> {
+    let (lo, sz) = unsafe { (layout::LAYOUT.directmap_lo, layout::LAYOUT.directmap_sz) };
+    if !is_aligned_2m(lo) { return Err(VmErr::Misaligned); }
+    let hi = align_up(phys_hi.min(sz), HUGE_2M as u64);
+    let flags = VmFlags::RW | VmFlags::NX | VmFlags::GLOBAL;
+    let hw = to_ptf(flags)? | PtF::HUGE_PAGE;
+    let mut root = root_lock()?;
+    for pa in (0..hi).step_by(HUGE_2M) {
+        let page = Page::<Size2MiB>::containing_address(VirtAddr::new(lo + pa));
+        let frame = PhysFrame::<Size2MiB>::containing_address(PhysAddr::new(pa));
+        unsafe { root.map_to(page, frame, hw, &mut PhysAllocShim) }
+            .map(|f| f.flush())
+            .map_err(|_| VmErr::NoMemory)?;
+    }
+    drop(root);
+    PHYS_WINDOW.store(lo, Ordering::Release);
+    audit_map(lo, 0, hi, flags.bits());
+    Ok(hi)
+}
+
+/// Map `cpu`'s per-CPU area at `layout::percpu_base(cpu)` (stripe + KASLR
+/// offset) and fill it from the `.percpu` template. Returns its base, the
+/// value for GS.
+pub fn map_percpu(cpu: usize) -> Result<VirtAddr, VmErr> {
+    let (start, end) = unsafe {
+        (&layout::__percpu_start as *const u8 as u64, &layout::__percpu_end as *const u8 as u64)
+    };
+    let len = align_up((end - start).max(1), PAGE_SIZE as u64);
+    if len > layout::PERCPU_STRIDE - crate::memory::kaslr::percpu_offset(cpu) {
+        return Err(VmErr::BadRange);
+    }
+    let base = layout::percpu_base(cpu);
+    for off in (0..len).step_by(PAGE_SIZE) {
+        let frame = phys_alloc(crate::memory::phys::AllocFlags::ZERO).ok_or(VmErr::NoMemory)?;
+        map4k_at(VirtAddr::new(base + off), PhysAddr::new(frame.0), VmFlags::RW | VmFlags::NX | VmFlags::GLOBAL)?;
+    }
+    // SAFETY: [base, base+len) was just mapped RW; the template is kernel data
+    unsafe { ptr::copy_nonoverlapping(start as *const u8, base as *mut u8, (end - start) as usize) };
+    Ok(VirtAddr::new(base))
+}
+
+/// Move every page table (the root included) whose frame satisfies `owned`
+/// into a fresh frame with the same entries, then flush. The kernel starts
+/// on tables partly built by firmware in boot services memory; this runs
+/// before that memory is released. L4 entries aliasing the same L3 (the
+/// bootloader's phys window) keep sharing its copy. Returns the tables moved.
+pub fn detach_tables(owned: impl Fn(u64) -> bool) -> Result<usize, VmErr> {
+    let mut root = root_lock()?;
+    let mut moved = 0;
+    unsafe {
+        let mut before = [0u64; 512];
+        for i4 in 0..512 { before[i4] = root[i4].addr().as_u64(); }
+        for i4 in 0..512 {
+            if i4 == SELFREF_SLOT || root[i4].is_unused() { continue; }
+            match (0..i4).find(|&j| j != SELFREF_SLOT && !root[j].is_unused() && before[j] == before[i4]) {
+                Some(j) => { let (pa, f) = (root[j].addr(), root[i4].flags()); root[i4].set_addr(pa, f); }
+                None => detach_entry(&mut root[i4], 3, &owned, &mut moved)?,
+            }
+        }
+
+        let mut kspace = KSPACE.lock();
+        let old = kspace.as_ref().ok_or(VmErr::NotInitialized)?.root_phys();
+        if owned(old) {
+            let f = phys_alloc(crate::memory::phys::AllocFlags::empty()).ok_or(VmErr::NoMemory)?;
+            let copy = table_mut(PhysAddr::new(f.0));
+            ptr::copy_nonoverlapping(&*root as *const PageTable, copy as *mut PageTable, 1);
+            copy[SELFREF_SLOT].set_addr(PhysAddr::new(f.0), PtF::PRESENT | PtF::WRITABLE);
+            let aspace = AddressSpace::from_root(f.0)?;
+            aspace.install();
+            *kspace = Some(aspace);
+            *root.0 = Some(copy);
+            moved += 1;
+        }
+    }
+    pcid::flush_all_nonglobal();
+    Ok(moved)
+}
+
+/// Copy the table `e` points to if `owned`, then recurse into it; `level`
+/// is that table's (3 = L3 ... 1 = L1)
+unsafe fn detach_entry(e: &mut PageTableEntry, level: u8, owned: &impl Fn(u64) -> bool, moved: &mut usize) -> Result<(), VmErr> {
+    if e.is_unused() || e.flags().contains(PtF::HUGE_PAGE) { return Ok(()); }
+    if owned(e.addr().as_u64()) {
+        let f = phys_alloc(crate::memory::phys::AllocFlags::empty()).ok_or(VmErr::NoMemory)?;
+        ptr::copy_nonoverlapping(table_mut(e.addr()) as *const PageTable, table_mut(PhysAddr::new(f.0)) as *mut PageTable, 1);
+        let flags = e.flags();
+        e.set_addr(PhysAddr::new(f.0), flags);
+        *moved += 1;
+    }
+    if level > 1 {
+        let t = table_mut(e.addr());
+        for i in 0..512 { detach_entry(&mut t[i], level - 1, owned, moved)?; }
+    }
+    Ok(())
+}
+
 // ───────────────────────────────────────────────────────────────────────────────
 // Table GC & TLB shootdown (single-CPU stub now)
 // ───────────────────────────────────────────────────────────────────────────────
 
-/// Best-effort GC: attempt to free empty L1/L2/L3 tables after unmaps.
-/// Safe to call after large range unmaps.
-pub fn gc_tables() -> Result<(), VmErr> {
-    // For simplicity, skip a full walk here; you can implement a walker that
-    // checks child tables for emptiness and returns frames via phys_free().
-    // Hooks are here to call from unmap_range paths in the future.
-    Ok(())
+/// Best-effort GC: free empty L1/L2/L3 tables after unmaps.
+/// Runs under the root lock like every other table mutation. Call it after
+/// large range unmaps or from the pressure task's reclaim, never from the
+/// allocation path: it takes the root lock, which map/unmap hold while
+/// allocating. Returns the number of table frames released.
+///
+/// L3 tables are only collected in the lower half: kernel-half L4 entries
+/// are shared by every address space and must stay populated.
+pub fn gc_tables() -> Result<usize, VmErr> {
+    let mut root = root_lock()?;
+    let mut gone = GcBatch::new();
+
+    unsafe {
+        for i4 in 0..512 {
+            if i4 == SELFREF_SLOT || root[i4].is_unused() { continue; }
+            let l3 = table_mut(root[i4].addr());
+            for i3 in 0..512 {
+                if l3[i3].is_unused() || l3[i3].flags().contains(PtF::HUGE_PAGE) { continue; }
+                let l2 = table_mut(l3[i3].addr());
+                for i2 in 0..512 {
+                    if l2[i2].is_unused() || l2[i2].flags().contains(PtF::HUGE_PAGE) { continue; }
+                    if table_empty(table_mut(l2[i2].addr())) {
+                        gone.push(va_of(i4, i3, i2, 0), l2[i2].addr());
+                        l2[i2].set_unused();
+                    }
+                }
+                if table_empty(l2) {
+                    gone.push(va_of(i4, i3, 0, 0), l3[i3].addr());
+                    l3[i3].set_unused();
+                }
+            }
+            if i4 < 256 && table_empty(l3) {
+                gone.push(va_of(i4, 0, 0, 0), root[i4].addr());
+                root[i4].set_unused();
+            }
+        }
+    }
+
+    Ok(gone.release())
+}
+
+/// Tables unlinked by `gc_tables`. Paging-structure caches (of every PCID)
+/// may still point at them, so their frames go back to phys only after a
+/// shootdown of the VAs they covered, one batch at a time.
+struct GcBatch {
+    vas: [VirtAddr; SHOOTDOWN_MAX],
+    frames: [PhysAddr; SHOOTDOWN_MAX],
+    len: usize,
+    freed: usize,
+}
+
+impl GcBatch {
+    fn new() -> Self {
+        Self { vas: [VirtAddr::zero(); SHOOTDOWN_MAX], frames: [PhysAddr::zero(); SHOOTDOWN_MAX], len: 0, freed: 0 }
+    }
+
+    fn push(&mut self, va: VirtAddr, table: PhysAddr) {
+        if self.len == SHOOTDOWN_MAX {
+            self.release();
+        }
+        self.vas[self.len] = va;
+        self.frames[self.len] = table;
+        self.len += 1;
+    }
+
+    /// Shoot down and free what is pending; returns the total freed
+    fn release(&mut self) -> usize {
+        if self.len > 0 {
+            tlb_shootdown_local(&self.vas[..self.len]);
+            for pa in &self.frames[..self.len] {
+                phys_free(Frame(pa.as_u64()));
+            }
+            self.freed += self.len;
+            self.len = 0;
+        }
+        self.freed
+    }
 }
 
-/// Single-CPU local shootdown (used implicit invlpg in ops already).
-pub fn tlb_shootdown_local() { core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst); }
+#[inline]
+fn table_empty(t: &PageTable) -> bool {
+    t.iter().all(|e| e.is_unused())
+}
+
+/// Past this many pages one flush of the affected tags beats per-page INVLPG
+const SHOOTDOWN_MAX: usize = 32;
+
+/// Single-CPU shootdown after a kernel-root change at `vas` (single-page ops
+/// already invlpg). The kernel-half tables are shared, so the kernel space
+/// and whichever space runs here drop just those pages; with INVLPG/INVPCID
+/// that also drops the paging-structure caches above them. Every other tag
+/// is flushed when it next runs. More than `SHOOTDOWN_MAX` pages flush the
+/// two affected tags whole instead.
+pub fn tlb_shootdown_local(vas: &[VirtAddr]) {
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+    let kspace = KSPACE.lock();
+    let Some(ks) = kspace.as_ref() else {
+        pcid::flush_all_nonglobal();
+        return;
+    };
+    let running = pcid::current();
+    if vas.len() > SHOOTDOWN_MAX {
+        ks.flush();
+        if running != ks.tag() { pcid::flush(running); }
+    } else {
+        for va in vas {
+            ks.flush_page(*va);
+            if running != ks.tag() { pcid::flush_page(running, *va); }
+        }
+    }
+    pcid::mark_others_stale();
+}
 
 // ───────────────────────────────────────────────────────────────────────────────
 // Mapper for x86_64 crate (using our root)
@@ -437,7 +1249,7 @@ pub fn tlb_shootdown_local() { core::sync::atomic::fence(core::sync::atomic::Ord
 
 pub struct MapCtx;
 impl MapCtx {
-    #[inline] pub fn root() -> Result<&'static mut PageTable, VmErr> { root_mut() }
+    #[inline] pub fn root() -> Result<RootGuard, VmErr> { root_lock() }
 }
 
 // ───────────────────────────────────────────────────────────────────────────────
@@ -446,6 +1258,7 @@ impl MapCtx {
 
 /// Enforce W^X by walking a VA range and asserting no RW+X mappings exist.
 /// Intended for debug builds; cheap enough for boot-time check in release too.
+/// Whole-table checks (W^X, user/kernel, stacks, guards) live in `mapcheck`.
 pub fn assert_wx_exclusive(range_base: VirtAddr, len: usize) -> Result<(), VmErr> {
     let pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
     for p in 0..pages {
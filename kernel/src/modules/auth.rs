@@ -1,16 +1,134 @@
 //! NØNOS Module Admission Authority
 //!
-//! Verifies `.mod` manifests using a decentralized trust policy.
-//! This integrates zk-proof of signer identity, root key rotation,
-//! DAO-curated signer registry, and fine-grained capability scoping.
+//! Verifies `.mod` manifests against a threshold signer policy:
+//! - A manifest needs valid signatures from at least `k` of the `n` policy signers
+//! - Signer keys carry roles and validity windows
+//! - Root rotation is itself a signed statement chained from the previous root
+//! - Signer keys are algorithm-tagged (`crypto::sig::SigAlgo`). Only Ed25519
+//!   signatures count toward the threshold; the manifest's full signature set
+//!   (any scheme) is then checked as a hybrid block against the pinned signer
+//!   keys under the kernel's `ADMISSION_HYBRID` policy
+//! - Privileged capabilities are only issued when a `SYSTEM` signer co-signs
+//! - Every signature covers `ModuleManifest::signed_digest`: the code hash
+//!   plus name, version, signer chain, fault policy, importance, provides and
+//!   dependencies, so no metadata can be swapped under a valid signature
 
-use crate::crypto::vault::{verify_signature, get_root_pubkeys, verify_zk_attestation};
-use crate::modules::manifest::ModuleManifest;
+use crate::crypto::sig::{
+    sha3_digest, validate_signature_block, verify_ed25519_signature, HybridPolicy, SigAlgo,
+    SignatureBlock, SignatureEntry,
+};
+use crate::crypto::vault::get_root_pubkeys;
+use crate::modules::manifest::{AuthMethod, ModuleManifest};
 use crate::capabilities::{CapabilityToken, Capability};
 use crate::log::logger::{log_info, log_warn};
 
 use alloc::vec::Vec;
-use alloc::collections::BTreeSet;
+use spin::RwLock;
+
+/// Threshold used until the first signed rotation installs a policy
+pub const BOOT_THRESHOLD: usize = 1;
+
+/// Hybrid policy every manifest signature block is held to. Pinned here,
+/// never taken from the manifest. Without a PQ verifier every signature a
+/// manifest carries must come from a policy signer and verify; a kernel
+/// built with one instead requires a verified classical and a verified
+/// ML-DSA signature, both from pinned signers.
+#[cfg(not(feature = "nonos-crypto-pq"))]
+pub const ADMISSION_HYBRID: HybridPolicy = HybridPolicy::All;
+#[cfg(feature = "nonos-crypto-pq")]
+pub const ADMISSION_HYBRID: HybridPolicy = HybridPolicy::ClassicalAndPq;
+
+/// Capabilities that require a `SYSTEM` co-signer
+const PRIVILEGED_CAPS: &[Capability] = &[
+    Capability::SecureMem,
+    Capability::CryptoOps,
+    Capability::ModuleLoad,
+];
+
+bitflags::bitflags! {
+    /// What a signer key may attest to
+    pub struct SignerRoles: u32 {
+        /// Counts toward the manifest admission threshold
+        const ADMIT  = 1 << 0;
+        /// Unlocks privileged capabilities when co-signing
+        const SYSTEM = 1 << 1;
+    }
+}
+
+/// A policy signer key with its scheme, roles and validity window (seconds)
+#[derive(Debug, Clone)]
+pub struct SignerKey {
+    pub algo: SigAlgo,
+    pub pubkey: Vec<u8>,
+    pub roles: SignerRoles,
+    pub not_before: u64,
+    pub not_after: Option<u64>,
+}
+
+impl SignerKey {
+    /// Validity windows are wall-clock (UNIX seconds). Without a trusted
+    /// clock only keys with no window at all count.
+    pub fn valid_at(&self, now: Option<u64>) -> bool {
+        match now {
+            Some(now) => now >= self.not_before && self.not_after.map_or(true, |end| now <= end),
+            None => self.not_before == 0 && self.not_after.is_none(),
+        }
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.push(self.algo.id());
+        out.extend_from_slice(&(self.pubkey.len() as u16).to_le_bytes());
+        out.extend_from_slice(&self.pubkey);
+        out.extend_from_slice(&self.roles.bits().to_le_bytes());
+        out.extend_from_slice(&self.not_before.to_le_bytes());
+        out.extend_from_slice(&self.not_after.unwrap_or(u64::MAX).to_le_bytes());
+    }
+}
+
+/// Active admission policy
+#[derive(Debug, Clone)]
+pub struct SignerPolicy {
+    /// Key that must sign the next rotation and any signer approval
+    pub root: [u8; 32],
+    pub threshold: usize,
+    pub signers: Vec<SignerKey>,
+    /// Rotation sequence; 0 is the boot policy
+    pub sequence: u64,
+    /// Signer approvals applied since the last rotation; the next approval
+    /// must carry `approvals + 1`
+    pub approvals: u64,
+    /// Hash chain over every applied rotation and approval
+    pub chain_digest: [u8; 32],
+}
+
+/// Signed statement replacing the root, threshold and signer set.
+/// Must be signed by the current root and chain from the current digest.
+#[derive(Debug, Clone)]
+pub struct RootRotation {
+    pub sequence: u64,
+    pub prev_digest: [u8; 32],
+    pub new_root: [u8; 32],
+    pub threshold: usize,
+    pub signers: Vec<SignerKey>,
+    pub signature: [u8; 64],
+}
+
+impl RootRotation {
+    /// Canonical encoding covered by the root signature
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(96 + self.signers.len() * 92);
+        out.extend_from_slice(b"NONOS:ROOT-ROTATE:v1");
+        out.extend_from_slice(&self.sequence.to_le_bytes());
+        out.extend_from_slice(&self.prev_digest);
+        out.extend_from_slice(&self.new_root);
+        out.extend_from_slice(&(self.threshold as u32).to_le_bytes());
+        out.extend_from_slice(&(self.signers.len() as u32).to_le_bytes());
+        for key in &self.signers {
+            key.encode_into(&mut out);
+        }
+        out
+    }
+}
 
 /// Result of decentralized manifest verification
 pub enum AuthResult {
@@ -18,90 +136,209 @@ pub enum AuthResult {
     Rejected(&'static str),
 }
 
-/// DAO-governed trusted signer registry (RAM-loaded)
-static mut TRUSTED_SIGNERS: Option<BTreeSet<[u8; 32]>> = None;
+static POLICY: RwLock<Option<SignerPolicy>> = RwLock::new(None);
 
-/// Load the trusted root registry from vault
+/// Load the boot signer policy from the vault trust roots.
+/// The first root is the policy root; every root may admit and co-sign system modules.
 pub fn init_trusted_signers() {
     let keys = get_root_pubkeys();
-    unsafe {
-        TRUSTED_SIGNERS = Some(BTreeSet::from_iter(keys));
+    let root = match keys.first() {
+        Some(root) => *root,
+        None => {
+            log_warn("auth", "No trust roots provisioned; admission disabled");
+            return;
+        }
+    };
+
+    let signers = keys
+        .iter()
+        .map(|k| SignerKey {
+            algo: SigAlgo::Ed25519,
+            pubkey: k.to_vec(),
+            roles: SignerRoles::ADMIT | SignerRoles::SYSTEM,
+            not_before: 0,
+            not_after: None,
+        })
+        .collect();
+
+    *POLICY.write() = Some(SignerPolicy {
+        root,
+        threshold: BOOT_THRESHOLD,
+        signers,
+        sequence: 0,
+        approvals: 0,
+        chain_digest: sha3_digest(&root),
+    });
+    log_info("auth", &format!(
+        "Signer policy initialized: root={:x?} | {}-of-{}",
+        &root[..4], BOOT_THRESHOLD, keys.len()
+    ));
+}
+
+/// Apply a root rotation signed by the current root
+pub fn rotate_root(rotation: &RootRotation) -> Result<(), &'static str> {
+    let mut guard = POLICY.write();
+    let policy = guard.as_mut().ok_or("Signer policy not initialized")?;
+
+    if rotation.sequence != policy.sequence + 1 {
+        return Err("Rotation out of sequence");
+    }
+    if rotation.prev_digest != policy.chain_digest {
+        return Err("Rotation does not chain from current root");
     }
-    log_info("auth", "Trusted signer root initialized");
+    if rotation.threshold == 0 || rotation.threshold > rotation.signers.len() {
+        return Err("Rotation threshold unsatisfiable");
+    }
+
+    let encoded = rotation.encode();
+    if !verify_ed25519_signature(&policy.root, &encoded, &rotation.signature) {
+        return Err("Rotation not signed by current root");
+    }
+
+    let mut chain = Vec::with_capacity(32 + encoded.len());
+    chain.extend_from_slice(&policy.chain_digest);
+    chain.extend_from_slice(&encoded);
+
+    *policy = SignerPolicy {
+        root: rotation.new_root,
+        threshold: rotation.threshold,
+        signers: rotation.signers.clone(),
+        sequence: rotation.sequence,
+        approvals: 0,
+        chain_digest: sha3_digest(&chain),
+    };
+
+    log_info("auth", &format!(
+        "Root rotated to {:x?} | seq={} | {}-of-{}",
+        &rotation.new_root[..4], rotation.sequence, rotation.threshold, rotation.signers.len()
+    ));
+    Ok(())
 }
 
-/// Add a DAO-approved signer (zk-proven identity)
-pub fn approve_signer(pubkey: [u8; 32]) {
-    unsafe {
-        if let Some(registry) = TRUSTED_SIGNERS.as_mut() {
-            registry.insert(pubkey);
-        }
+/// Add a signer to the current policy; the approval must be signed by the
+/// root and carry the next approval sequence, so an old approval can never
+/// be replayed to restore a superseded key or window
+pub fn approve_signer(key: SignerKey, sequence: u64, root_signature: &[u8; 64]) -> Result<(), &'static str> {
+    let mut guard = POLICY.write();
+    let policy = guard.as_mut().ok_or("Signer policy not initialized")?;
+
+    if sequence != policy.approvals + 1 {
+        return Err("Signer approval out of sequence");
+    }
+
+    let mut stmt = Vec::with_capacity(112);
+    stmt.extend_from_slice(b"NONOS:SIGNER-APPROVE:v2");
+    stmt.extend_from_slice(&policy.chain_digest);
+    stmt.extend_from_slice(&policy.sequence.to_le_bytes());
+    stmt.extend_from_slice(&sequence.to_le_bytes());
+    key.encode_into(&mut stmt);
+
+    if !verify_ed25519_signature(&policy.root, &stmt, root_signature) {
+        return Err("Signer approval not signed by root");
     }
-    log_info("auth", &format!("Signer approved: {:x?}", &pubkey[..4]));
+
+    let mut chain = Vec::with_capacity(32 + stmt.len());
+    chain.extend_from_slice(&policy.chain_digest);
+    chain.extend_from_slice(&stmt);
+    policy.chain_digest = sha3_digest(&chain);
+    policy.approvals = sequence;
+
+    log_info("auth", &format!(
+        "Signer approved: {:?} {:x?} roles={:?}",
+        key.algo, &key.pubkey[..4.min(key.pubkey.len())], key.roles
+    ));
+    policy.signers.retain(|s| s.pubkey != key.pubkey);
+    policy.signers.push(key);
+    Ok(())
+}
+
+/// Snapshot of the active policy (CLI / attestation)
+pub fn current_policy() -> Option<SignerPolicy> {
+    POLICY.read().clone()
 }
 
 /// Core manifest authentication and scope filtering
 pub fn authenticate_manifest(manifest: &ModuleManifest) -> AuthResult {
-    let sig = manifest.signature.ok_or("Missing signature").unwrap();
-    let zk = manifest.zk_proof;
-
-    // Derive attested identity from zk proof
-    let signer_id = match zk {
-        Some(proof) => {
-            match verify_zk_attestation(proof) {
-                Some(id) => id,
-                None => return AuthResult::Rejected("zkProof identity invalid"),
-            }
-        }
-        None => manifest.signer_id,
-    };
-
-    if signer_id.is_none() {
-        return AuthResult::Rejected("No valid signer identity");
+    match manifest.auth_method {
+        AuthMethod::VaultSignature => {}
+        _ => return AuthResult::Rejected("Only signer signatures count toward admission"),
     }
 
-    let signer_key = signer_id.unwrap();
-
-    // Validate against DAO signer registry
-    let trusted = unsafe {
-        TRUSTED_SIGNERS
-            .as_ref()
-            .map(|set| set.contains(&signer_key))
-            .unwrap_or(false)
+    let guard = POLICY.read();
+    let policy = match guard.as_ref() {
+        Some(policy) => policy,
+        None => return AuthResult::Rejected("Signer policy not initialized"),
     };
+    let now = current_time();
+    let digest = manifest.signed_digest();
 
-    if !trusted {
-        return AuthResult::Rejected("Signer not in trusted DAO registry");
+    let primary = (SigAlgo::Ed25519, &manifest.signer.0[..], &manifest.signature[..]);
+    let cosigned = manifest.cosignatures.iter().map(|c| (c.algo, c.signer, c.signature));
+    let entries: Vec<SignatureEntry> = core::iter::once(primary)
+        .chain(cosigned)
+        .map(|(algo, pubkey, signature)| SignatureEntry { algo, pubkey, signature, signer: manifest.name })
+        .collect();
+
+    // Threshold: Ed25519 only
+    let mut counted: Vec<&[u8]> = Vec::new();
+    let mut system = false;
+    for entry in entries.iter().filter(|e| e.algo == SigAlgo::Ed25519) {
+        if counted.contains(&entry.pubkey) {
+            continue;
+        }
+        let key = match policy.signers.iter().find(|k| k.pubkey == entry.pubkey) {
+            Some(key) if key.algo == SigAlgo::Ed25519 && key.valid_at(now) && key.roles.contains(SignerRoles::ADMIT) => key,
+            _ => continue,
+        };
+        let (pk, signature) = match (<&[u8; 32]>::try_from(entry.pubkey), <&[u8; 64]>::try_from(entry.signature)) {
+            (Ok(pk), Ok(signature)) => (pk, signature),
+            _ => continue,
+        };
+        if !verify_ed25519_signature(pk, &digest, signature) {
+            continue;
+        }
+        system |= key.roles.contains(SignerRoles::SYSTEM);
+        counted.push(entry.pubkey);
     }
 
-    if !verify_signature(manifest.hash, sig, &signer_key) {
-        return AuthResult::Rejected("Signature mismatch");
+    if counted.len() < policy.threshold {
+        log_warn("auth", &format!(
+            "'{}' has {} of {} required signatures",
+            manifest.name, counted.len(), policy.threshold
+        ));
+        return AuthResult::Rejected("Signature threshold not met");
     }
 
-    // Issue scoped capabilities (future: filter by role or NFT)
-    let token = CapabilityToken {
-        owner_module: manifest.name,
-        permissions: manifest.required_caps,
+    // Hybrid block: every carried signature, any scheme, against pinned keys
+    let block = SignatureBlock { entries: &entries, payload_digest: sha3_digest(&digest) };
+    let pinned = |algo: SigAlgo, pubkey: &[u8]| {
+        policy.signers.iter().any(|k| k.algo == algo && k.pubkey == pubkey && k.valid_at(now))
     };
+    if !validate_signature_block(&block, &digest, ADMISSION_HYBRID, pinned) {
+        log_warn("auth", &format!("'{}' signature block fails {:?}", manifest.name, ADMISSION_HYBRID));
+        return AuthResult::Rejected("Manifest signature block rejected");
+    }
+
+    let mut token = CapabilityToken::new(manifest.name, manifest.required_caps.to_vec());
+    if !system {
+        let unprivileged: Vec<Capability> = token
+            .permissions
+            .iter()
+            .copied()
+            .filter(|c| !PRIVILEGED_CAPS.contains(c))
+            .collect();
+        token = token.restrict(&unprivileged);
+    }
 
     log_info("auth", &format!(
-        "Authenticated module '{}' by {:x?}, caps = {}",
-        manifest.name, &signer_key[..4], token.permissions.len()
+        "Authenticated module '{}' | {}-of-{} signers | caps = {}",
+        manifest.name, counted.len(), policy.threshold, token.permissions.len()
     ));
 
     AuthResult::Verified(token)
 }
 
-/// DAO-unsafe fallback (for local devnet only)
-pub fn unsafe_allow_all_caps(name: &'static str) -> CapabilityToken {
-    CapabilityToken {
-        owner_module: name,
-        permissions: &[
-            Capability::CoreExec,
-            Capability::IO,
-            Capability::IPC,
-            Capability::Crypto,
-            Capability::Storage,
-        ],
-    }
+/// Wall-clock UNIX seconds from the handoff RTC snapshot, not uptime
+fn current_time() -> Option<u64> {
+    crate::boot::handoff::unix_time()
 }
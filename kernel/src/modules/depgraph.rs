@@ -0,0 +1,276 @@
+//! NØNOS Module Dependency Graph
+//!
+//! Manifests declare dependencies on other modules or on named services,
+//! each with a version constraint. The loader builds a DAG over the queued
+//! and running capsules, rejects cycles, and admits in topological order.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+/// `major.minor.patch` module version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl Version {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self { major, minor, patch }
+    }
+
+    /// Parse `1`, `1.2` or `1.2.3`; missing components are zero
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.trim().splitn(3, '.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = match parts.next() {
+            Some(p) => p.parse().ok()?,
+            None => 0,
+        };
+        let patch = match parts.next() {
+            Some(p) => p.parse().ok()?,
+            None => 0,
+        };
+        Some(Self { major, minor, patch })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Version constraint attached to a dependency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionReq {
+    /// Any version
+    Any,
+    /// Exactly this version
+    Exact(Version),
+    /// This version or newer
+    AtLeast(Version),
+    /// Same major (same minor for 0.x), at least this version (`^`)
+    Compatible(Version),
+}
+
+impl VersionReq {
+    pub fn matches(&self, v: &Version) -> bool {
+        match *self {
+            VersionReq::Any => true,
+            VersionReq::Exact(req) => *v == req,
+            VersionReq::AtLeast(req) => *v >= req,
+            VersionReq::Compatible(req) => {
+                if *v < req || v.major != req.major {
+                    false
+                } else if req.major == 0 {
+                    v.minor == req.minor
+                } else {
+                    true
+                }
+            }
+        }
+    }
+}
+
+/// What a dependency points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepTarget {
+    /// A module by manifest name
+    Module(&'static str),
+    /// Any module whose manifest `provides` this service name
+    Service(&'static str),
+}
+
+/// One declared manifest dependency
+#[derive(Debug, Clone, Copy)]
+pub struct Dependency {
+    pub target: DepTarget,
+    pub version: VersionReq,
+}
+
+impl Dependency {
+    pub const fn module(name: &'static str, version: VersionReq) -> Self {
+        Self { target: DepTarget::Module(name), version }
+    }
+
+    pub const fn service(name: &'static str, version: VersionReq) -> Self {
+        Self { target: DepTarget::Service(name), version }
+    }
+
+    /// Returns true if a module with this identity satisfies the dependency
+    pub fn satisfied_by(&self, name: &str, version: &str, provides: &[&str]) -> bool {
+        let target_ok = match self.target {
+            DepTarget::Module(m) => m == name,
+            DepTarget::Service(s) => provides.iter().any(|p| *p == s),
+        };
+        target_ok && Version::parse(version).map_or(false, |v| self.version.matches(&v))
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            DepTarget::Module(m) => write!(f, "module '{}' {:?}", m, self.version),
+            DepTarget::Service(s) => write!(f, "service '{}' {:?}", s, self.version),
+        }
+    }
+}
+
+/// Graph node: a module identity plus its declared dependencies
+#[derive(Debug, Clone, Copy)]
+pub struct DepNode {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub provides: &'static [&'static str],
+    pub dependencies: &'static [Dependency],
+}
+
+/// A dependency cycle; `path` lists the modules that could not be ordered
+#[derive(Debug, Clone)]
+pub struct CycleError {
+    pub path: Vec<&'static str>,
+}
+
+/// Dependency DAG over a set of modules
+pub struct DepGraph {
+    nodes: Vec<DepNode>,
+}
+
+impl DepGraph {
+    pub fn new() -> Self {
+        Self { nodes: Vec::new() }
+    }
+
+    pub fn add(&mut self, node: DepNode) {
+        self.nodes.push(node);
+    }
+
+    pub fn nodes(&self) -> &[DepNode] {
+        &self.nodes
+    }
+
+    /// Indices of graph nodes that satisfy `dep`
+    fn providers(&self, dep: &Dependency) -> impl Iterator<Item = usize> + '_ {
+        let dep = *dep;
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(move |(_, n)| dep.satisfied_by(n.name, n.version, n.provides))
+            .map(|(i, _)| i)
+    }
+
+    /// Kahn's algorithm. Dependencies with no provider in the graph add no
+    /// edge; the loader holds those modules until the provider shows up.
+    pub fn topo_order(&self) -> Result<Vec<usize>, CycleError> {
+        let n = self.nodes.len();
+        let mut indegree = alloc::vec![0usize; n];
+        let mut edges: Vec<Vec<usize>> = alloc::vec![Vec::new(); n];
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            for dep in node.dependencies {
+                for p in self.providers(dep) {
+                    if p != i {
+                        edges[p].push(i);
+                        indegree[i] += 1;
+                    }
+                }
+            }
+        }
+
+        let mut ready: Vec<usize> = (0..n).filter(|&i| indegree[i] == 0).collect();
+        let mut order = Vec::with_capacity(n);
+        let mut cursor = 0;
+        while cursor < ready.len() {
+            let i = ready[cursor];
+            cursor += 1;
+            order.push(i);
+            for &d in &edges[i] {
+                indegree[d] -= 1;
+                if indegree[d] == 0 {
+                    ready.push(d);
+                }
+            }
+        }
+
+        if order.len() == n {
+            Ok(order)
+        } else {
+            Err(CycleError {
+                path: (0..n)
+                    .filter(|&i| indegree[i] > 0)
+                    .map(|i| self.nodes[i].name)
+                    .collect(),
+            })
+        }
+    }
+
+    /// Modules left without a provider once `name` stops, directly or
+    /// transitively. A dependency still satisfied by another node for which
+    /// `live` holds (and which is not itself stopping) keeps its dependent up.
+    pub fn orphaned_by(&self, name: &'static str, live: impl Fn(&DepNode) -> bool) -> Vec<&'static str> {
+        let mut stopped: Vec<&'static str> = alloc::vec![name];
+        loop {
+            let mut changed = false;
+            for node in &self.nodes {
+                if stopped.contains(&node.name) {
+                    continue;
+                }
+                let orphaned = node.dependencies.iter().any(|dep| {
+                    let lost = self
+                        .nodes
+                        .iter()
+                        .any(|p| stopped.contains(&p.name) && dep.satisfied_by(p.name, p.version, p.provides));
+                    let kept = self.nodes.iter().any(|p| {
+                        !stopped.contains(&p.name)
+                            && p.name != node.name
+                            && live(p)
+                            && dep.satisfied_by(p.name, p.version, p.provides)
+                    });
+                    lost && !kept
+                });
+                if orphaned {
+                    stopped.push(node.name);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        stopped.remove(0);
+        stopped
+    }
+
+    /// Every module that depends on `name`, directly or transitively
+    pub fn dependents_of(&self, name: &str) -> Vec<&'static str> {
+        let mut out: Vec<usize> = Vec::new();
+        let mut frontier: Vec<usize> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.name == name)
+            .map(|(i, _)| i)
+            .collect();
+
+        while let Some(p) = frontier.pop() {
+            let provider = self.nodes[p];
+            for (i, node) in self.nodes.iter().enumerate() {
+                if out.contains(&i) || node.name == name {
+                    continue;
+                }
+                let depends = node
+                    .dependencies
+                    .iter()
+                    .any(|d| d.satisfied_by(provider.name, provider.version, provider.provides));
+                if depends {
+                    out.push(i);
+                    frontier.push(i);
+                }
+            }
+        }
+
+        out.into_iter().map(|i| self.nodes[i].name).collect()
+    }
+}
@@ -5,10 +5,16 @@
 //! Used during loading, validation, and runtime sandbox enforcement.
 
 use crate::capabilities::Capability;
+use crate::crypto::hash::{domain, hash_tagged, HashAlgo};
+use crate::crypto::sig::SigAlgo;
 use crate::crypto::vault::{verify_signature, VaultPublicKey};
+use crate::modules::depgraph::{DepNode, DepTarget, Dependency, Version, VersionReq};
 use crate::modules::runtime::FaultPolicy;
 use alloc::vec::Vec;
 
+/// Manifest `importance` of capsules the OOM policy must never select
+pub const IMPORTANCE_CRITICAL: u8 = u8::MAX;
+
 #[derive(Debug, Clone)]
 pub enum AuthMethod {
     VaultSignature,
@@ -16,6 +22,15 @@ pub enum AuthMethod {
     HardwareRoot,
 }
 
+/// Additional signature over `ModuleManifest::signed_digest` (threshold admission).
+/// Carries its algorithm id so signers can migrate schemes without a kernel rebuild.
+#[derive(Debug, Clone, Copy)]
+pub struct ManifestSignature {
+    pub algo: SigAlgo,
+    pub signer: &'static [u8],
+    pub signature: &'static [u8],
+}
+
 #[derive(Debug)]
 pub struct ModuleManifest {
     pub name: &'static str,
@@ -29,6 +44,7 @@ pub struct ModuleManifest {
     // Auth
     pub signature: [u8; 64],
     pub signer: VaultPublicKey,
+    pub cosignatures: &'static [ManifestSignature],
     pub auth_chain_id: Option<[u8; 32]>,
     pub auth_method: AuthMethod,
     pub zk_attestation: Option<[u8; 64]>,
@@ -37,6 +53,17 @@ pub struct ModuleManifest {
     pub required_caps: &'static [Capability],
     pub fault_policy: Option<FaultPolicy>,
     pub memory_bytes: usize,
+    /// Read-only code prefix of `memory_bytes`, shared copy-on-write between
+    /// instances with the same `hash`
+    pub code_bytes: usize,
+    /// Code image the loader writes into that prefix; `hash` is its BLAKE3
+    pub code: &'static [u8],
+    /// OOM ranking: lower is killed first; `IMPORTANCE_CRITICAL` is never killed
+    pub importance: u8,
+
+    // Dependency contract
+    pub provides: &'static [&'static str],
+    pub dependencies: &'static [Dependency],
 
     // Runtime validation
     pub timestamp: u64,
@@ -44,11 +71,63 @@ pub struct ModuleManifest {
 }
 
 impl ModuleManifest {
+    /// Digest every manifest signature and proof covers:
+    /// `SHA3(MODULE_MANIFEST tag || hash || metadata)`. Besides the code
+    /// image it binds the fields admission, upgrade lineage and the OOM
+    /// policy act on, so none of them can be rewritten on a signed image.
+    pub fn signed_digest(&self) -> [u8; 32] {
+        let mut out = Vec::with_capacity(128);
+        out.extend_from_slice(&self.hash);
+        put_str(&mut out, self.name);
+        put_str(&mut out, self.version);
+        match self.auth_chain_id {
+            Some(chain) => {
+                out.push(1);
+                out.extend_from_slice(&chain);
+            }
+            None => out.push(0),
+        }
+        out.push(match self.fault_policy {
+            None => 0,
+            Some(FaultPolicy::Restart) => 1,
+            Some(FaultPolicy::Shutdown) => 2,
+            Some(FaultPolicy::Escalate) => 3,
+            Some(FaultPolicy::Suspend) => 4,
+        });
+        out.push(self.importance);
+        out.extend_from_slice(&(self.provides.len() as u16).to_le_bytes());
+        for service in self.provides {
+            put_str(&mut out, service);
+        }
+        out.extend_from_slice(&(self.dependencies.len() as u16).to_le_bytes());
+        for dep in self.dependencies {
+            let (kind, name) = match dep.target {
+                DepTarget::Module(name) => (0, name),
+                DepTarget::Service(name) => (1, name),
+            };
+            out.push(kind);
+            put_str(&mut out, name);
+            let (req, v) = match dep.version {
+                VersionReq::Any => (0, Version::new(0, 0, 0)),
+                VersionReq::Exact(v) => (1, v),
+                VersionReq::AtLeast(v) => (2, v),
+                VersionReq::Compatible(v) => (3, v),
+            };
+            out.push(req);
+            for part in [v.major, v.minor, v.patch] {
+                out.extend_from_slice(&part.to_le_bytes());
+            }
+        }
+        // SHA3 is always built in, so this never falls back
+        hash_tagged(HashAlgo::Sha3_256, domain::MODULE_MANIFEST, &out).map_or([0u8; 32], |d| d.bytes)
+    }
+
     /// Checks signature or proof based on declared method
     pub fn verify(&self) -> Result<(), &'static str> {
+        let digest = self.signed_digest();
         match self.auth_method {
             AuthMethod::VaultSignature => {
-                if verify_signature(&self.hash, &self.signature, &self.signer) {
+                if verify_signature(&digest, &self.signature, &self.signer) {
                     Ok(())
                 } else {
                     Err("Vault signature invalid")
@@ -56,8 +135,7 @@ impl ModuleManifest {
             },
             AuthMethod::ZkAttestation => {
                 if let Some(proof) = self.zk_attestation {
-                    // stub for future zk-verifier
-                    if crate::crypto::zk::verify_proof(&self.hash, &proof) {
+                    if crate::crypto::zk::verify_manifest_proof(&digest, &proof) {
                         Ok(())
                     } else {
                         Err("ZK proof invalid")
@@ -70,14 +148,33 @@ impl ModuleManifest {
         }
     }
 
+    /// Identity and dependency edges for the loader's DAG
+    pub fn dep_node(&'static self) -> DepNode {
+        DepNode {
+            name: self.name,
+            version: self.version,
+            provides: self.provides,
+            dependencies: self.dependencies,
+        }
+    }
+
     /// Check manifest bounds and expiration logic
     pub fn validate_constraints(&self, now: u64) -> Result<(), &'static str> {
         if self.memory_bytes == 0 || self.memory_bytes > 64 * 1024 * 1024 {
             return Err("Manifest requested memory outside policy bounds");
         }
+        if self.code_bytes > self.memory_bytes {
+            return Err("Manifest code segment exceeds its memory");
+        }
+        if self.code.len() != self.code_bytes {
+            return Err("Manifest code image does not match code_bytes");
+        }
         if self.name.len() > 32 {
             return Err("Module name too long");
         }
+        if crate::modules::depgraph::Version::parse(self.version).is_none() {
+            return Err("Manifest version is not major.minor.patch");
+        }
         if let Some(expiry) = self.expiry_seconds {
             if now > self.timestamp + expiry {
                 return Err("Manifest expired");
@@ -86,3 +183,9 @@ impl ModuleManifest {
         Ok(())
     }
 }
+
+/// `u16 len || bytes`
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u16).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
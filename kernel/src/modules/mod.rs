@@ -0,0 +1,24 @@
+//! NØNOS Module Subsystem
+//!
+//! Verified `.mod` capsules: manifests, admission authority, loader queue,
+//! sandbox construction, runtime lifecycle and supervision.
+
+pub mod auth;
+pub mod depgraph;
+pub mod manifest;
+pub mod mod_loader;
+pub mod mod_runner;
+pub mod registry;
+pub mod runtime;
+pub mod sandbox;
+pub mod supervisor;
+pub mod teardown;
+pub mod upgrade;
+pub mod vm;
+pub mod zkvm;
+
+pub use depgraph::{DepGraph, DepTarget, Dependency, Version, VersionReq};
+pub use runtime::{CapsuleState, FaultPolicy, RuntimeCapsule};
+pub use sandbox::SandboxContext;
+pub use teardown::{teardown, TerminationReason, TerminationRecord};
+pub use supervisor::{init_supervisor, supervise, SupervisorPolicy};
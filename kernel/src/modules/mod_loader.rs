@@ -5,9 +5,12 @@
 //! queueing, and secure capsule admission into the ZeroState VM.
 
 use crate::modules::auth::{authenticate_manifest, AuthResult};
+use crate::modules::depgraph::DepGraph;
 use crate::modules::manifest::ModuleManifest;
 use crate::modules::mod_runner::launch_module;
-use crate::modules::registry::register_module_instance;
+use crate::modules::registry::{self, register_module_instance};
+use crate::modules::runtime::CapsuleState;
+use crate::modules::teardown::{teardown, TerminationReason};
 use crate::capabilities::{CapabilityToken};
 use crate::log::logger::{log_info, log_warn};
 
@@ -24,6 +27,17 @@ struct VerifiedModule {
     timestamp: Duration,
 }
 
+/// Why `admit_next_module` launched nothing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdmitError {
+    /// No verified modules pending
+    Idle,
+    /// Every queued module still waits on a dependency that is not `Active`
+    Blocked,
+    /// Admission failed outright
+    Failed(&'static str),
+}
+
 /// Global loader state (mutex-protected)
 struct LoaderState {
     queue: Vec<VerifiedModule>,
@@ -48,6 +62,15 @@ pub fn verify_and_queue(manifest: &'static ModuleManifest) -> Result<(), &'stati
         return Err("Queue full — denial of service guard");
     }
 
+    if let Err(cycle) = graph_with(&state.queue, Some(manifest)).topo_order() {
+        state.rejected_count += 1;
+        log_warn("mod_loader", &format!(
+            "Rejected '{}': dependency cycle through {:?}",
+            manifest.name, cycle.path
+        ));
+        return Err("Dependency cycle");
+    }
+
     match authenticate_manifest(manifest) {
         AuthResult::Verified(token) => {
             let entry = VerifiedModule {
@@ -72,21 +95,131 @@ pub fn verify_and_queue(manifest: &'static ModuleManifest) -> Result<(), &'stati
     }
 }
 
-/// Attempt to launch oldest queued verified module
-pub fn admit_next_module() -> Result<(), &'static str> {
+/// Launch the first queued module, in dependency order, whose
+/// dependencies are all `Active`. Modules with pending dependencies stay
+/// queued.
+pub fn admit_next_module() -> Result<(), AdmitError> {
     let mut state = MODULE_LOADER.lock();
 
     if state.queue.is_empty() {
-        return Err("No verified modules pending");
+        return Err(AdmitError::Idle);
     }
 
-    let VerifiedModule { manifest, token, .. } = state.queue.remove(0);
-    let instance = launch_module(manifest, token.clone())?;
+    let order = graph_with(&state.queue, None)
+        .topo_order()
+        .map_err(|_| AdmitError::Failed("Dependency cycle in pending queue"))?;
+
+    let ready = order
+        .into_iter()
+        .find(|&i| dependencies_active(state.queue[i].manifest))
+        .ok_or(AdmitError::Blocked)?;
+
+    let VerifiedModule { manifest, token, .. } = state.queue.remove(ready);
+    drop(state);
+
+    let instance = launch_module(manifest, token.clone()).map_err(AdmitError::Failed)?;
 
     register_module_instance(manifest.name, &instance);
     Ok(())
 }
 
+/// Admit every module whose dependencies can be met; returns how many launched
+pub fn admit_ready_modules() -> usize {
+    let mut launched = 0;
+    loop {
+        match admit_next_module() {
+            Ok(()) => launched += 1,
+            Err(AdmitError::Idle) | Err(AdmitError::Blocked) => break,
+            Err(AdmitError::Failed(reason)) => {
+                log_warn("mod_loader", &format!("Admission halted: {}", reason));
+                break;
+            }
+        }
+    }
+    launched
+}
+
+/// A dependency faulted under `FaultPolicy::Shutdown`: stop every running
+/// dependent, direct or transitive, and drop queued dependents, unless
+/// another `Active` provider still satisfies what they need.
+pub fn propagate_shutdown(name: &'static str) {
+    let running = registry::list_capsules();
+
+    let mut graph = DepGraph::new();
+    for meta in &running {
+        graph.add(meta.manifest.dep_node());
+    }
+    {
+        let state = MODULE_LOADER.lock();
+        for entry in &state.queue {
+            graph.add(entry.manifest.dep_node());
+        }
+    }
+
+    let dependents = graph.orphaned_by(name, |node| {
+        running
+            .iter()
+            .any(|m| m.name == node.name && m.state == CapsuleState::Active)
+    });
+    if dependents.is_empty() {
+        return;
+    }
+
+    log_warn("mod_loader", &format!(
+        "'{}' shut down, stopping dependents {:?}",
+        name, dependents
+    ));
+
+    MODULE_LOADER
+        .lock()
+        .queue
+        .retain(|entry| !dependents.contains(&entry.manifest.name));
+
+    for dependent in dependents {
+        let meta = match running.iter().find(|m| m.name == dependent) {
+            Some(meta) => meta,
+            None => continue,
+        };
+        let supervised = crate::modules::supervisor::unsupervise(dependent);
+        let tracked = crate::runtime::zerostate::take_sandbox(&meta.exec_id);
+        match supervised.or(tracked) {
+            Some(mut context) => {
+                teardown(&mut context, &meta.uid, TerminationReason::DependencyStopped);
+            }
+            None => {
+                registry::unregister_module(&meta.uid);
+            }
+        }
+    }
+}
+
+/// Build the DAG over queued modules, all registered modules, and optionally
+/// a candidate about to be queued.
+fn graph_with(queue: &[VerifiedModule], candidate: Option<&'static ModuleManifest>) -> DepGraph {
+    let mut graph = DepGraph::new();
+    for meta in registry::list_capsules() {
+        graph.add(meta.manifest.dep_node());
+    }
+    for entry in queue {
+        graph.add(entry.manifest.dep_node());
+    }
+    if let Some(manifest) = candidate {
+        graph.add(manifest.dep_node());
+    }
+    graph
+}
+
+/// Returns true if every declared dependency has an `Active` provider
+fn dependencies_active(manifest: &ModuleManifest) -> bool {
+    let running = registry::list_capsules();
+    manifest.dependencies.iter().all(|dep| {
+        running.iter().any(|meta| {
+            meta.state == CapsuleState::Active
+                && dep.satisfied_by(meta.name, meta.manifest.version, meta.manifest.provides)
+        })
+    })
+}
+
 /// For CLI telemetry: get number of rejections so far
 pub fn rejected_count() -> usize {
     MODULE_LOADER.lock().rejected_count
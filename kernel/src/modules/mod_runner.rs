@@ -6,6 +6,8 @@
 use crate::modules::mod_loader::ModuleAdmission;
 use crate::modules::sandbox::SandboxContext;
 use crate::modules::registry::{register_module};
+use crate::modules::runtime::FaultPolicy;
+use crate::modules::supervisor::supervise;
 use crate::log::logger::{log_info, log_warn};
 use crate::runtime::zerostate::{track_active_sandbox};
 use crate::crypto::zk::{derive_exec_id, hash_capsule};
@@ -78,14 +80,21 @@ pub fn launch_module(admission: ModuleAdmission) -> LaunchResult {
         &capsule_hash[..6],
     ));
 
-    LaunchResult::Success(LaunchAudit {
+    let audit = LaunchAudit {
         module_name: context.name,
         exec_id,
         capsule_fingerprint: capsule_hash,
         token: context.token.clone(),
         memory_bytes: context.memory.size,
         attested: true,
-    })
+    };
+
+    // Step 5: Restartable capsules are owned by the supervisor from here on
+    if context.runtime().fault_policy() == FaultPolicy::Restart {
+        supervise(admission.uid, admission.manifest, context.token.clone(), context);
+    }
+
+    LaunchResult::Success(audit)
 }
 
 /// Perform a dry-run capsule load for audit or bootstrap (no execution)
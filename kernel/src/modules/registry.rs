@@ -63,6 +63,16 @@ pub fn register_module(
     ));
 }
 
+/// Mirror a live instance's runtime state into its entry, so dependency
+/// checks and telemetry see faults, suspensions and heartbeats
+pub fn update_state(exec_id: &[u8; 32], capsule: &RuntimeCapsule) {
+    if let Some(meta) = REGISTRY.write().values_mut().find(|m| m.exec_id == *exec_id) {
+        meta.state = capsule.state();
+        meta.heartbeat = capsule.last_seen();
+        meta.memory_usage = capsule.memory_bytes();
+    }
+}
+
 /// Remove a module entry by UID
 pub fn unregister_module(uid: &[u8; 32]) -> bool {
     let mut reg = REGISTRY.write();
@@ -8,11 +8,13 @@
 //! - Fully memory-aware and restart-compatible
 
 use crate::capabilities::CapabilityToken;
-use crate::crypto::zk::{AttestationProof, generate_snapshot_signature};
+use crate::crypto::signer::{self, SNAPSHOT_DOMAIN, SNAPSHOT_LABEL};
+use crate::crypto::zk::AttestationProof;
 use crate::log::logger::{log_info, log_warn};
 
 use core::time::{Duration, Instant};
 use alloc::string::String;
+use alloc::vec::Vec;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CapsuleState {
@@ -68,6 +70,11 @@ impl RuntimeCapsule {
         matches!(self.state, CapsuleState::Active)
     }
 
+    /// Return true if fault policy left this capsule waiting for a restart
+    pub fn needs_restart(&self) -> bool {
+        matches!(self.state, CapsuleState::Restarting)
+    }
+
     /// Lifecycle transition: mark capsule inactive
     pub fn mark_inactive(&mut self) {
         self.state = CapsuleState::Inactive;
@@ -142,9 +149,35 @@ impl RuntimeCapsule {
         self.policy
     }
 
-    /// Export cryptographic zkSnapshot (signed execution metadata)
+    /// Canonical snapshot statement covered by the snapshot signature
+    pub fn snapshot_statement(&self, exec_id: [u8; 32]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(96);
+        out.extend_from_slice(SNAPSHOT_DOMAIN);
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(self.name.as_bytes());
+        out.extend_from_slice(&exec_id);
+        out.extend_from_slice(&(self.memory_bytes as u64).to_le_bytes());
+        out.push(self.state as u8);
+        out.extend_from_slice(&self.uptime().as_secs().to_le_bytes());
+        out
+    }
+
+    /// Export cryptographic zkSnapshot (signed execution metadata).
+    /// Signed by the vault under the capsule's snapshot handle; all-zero if
+    /// no handle was granted or the handle's policy refused.
     pub fn generate_signed_snapshot(&self, exec_id: [u8; 32]) -> [u8; 64] {
-        generate_snapshot_signature(exec_id, &self.token, self.memory_bytes, self.state)
+        let statement = self.snapshot_statement(exec_id);
+        let signed = signer::handle_for(signer::Principal::Capsule(self.name), SNAPSHOT_LABEL)
+            .ok_or("No snapshot key granted")
+            .and_then(|handle| signer::sign(&self.token, handle, &statement));
+
+        match signed {
+            Ok(signature) => signature,
+            Err(e) => {
+                log_warn("runtime", &format!("Capsule '{}' snapshot unsigned: {}", self.name, e));
+                [0u8; 64]
+            }
+        }
     }
 
     /// Export high-level attestation proof (for zkRelay export)
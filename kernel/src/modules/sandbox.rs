@@ -10,17 +10,26 @@
 //! Every capsule is instantiated through this zero-trust boundary.
 
 use crate::capabilities::{CapabilityToken};
-use crate::crypto::zk::{AttestationProof, derive_exec_id, generate_snapshot_signature};
-use crate::memory::region::{MemoryRegion, allocate_region};
+use crate::crypto::signer::{self, SigningPolicy, SNAPSHOT_LABEL};
+use crate::crypto::vault::KeyUsage;
+use crate::crypto::zk::{AttestationProof, derive_exec_id};
+use crate::crypto::entropy::rand_u64;
+use crate::crypto::hash::blake3_hash;
+use crate::memory::region::{self, MemoryRegion, RegionStats, SharedCode};
 use crate::modules::manifest::ModuleManifest;
-use crate::modules::runtime::{RuntimeCapsule, FaultPolicy};
+use crate::modules::runtime::{CapsuleState, RuntimeCapsule, FaultPolicy};
+use crate::modules::teardown::{teardown, TerminationReason, TerminationRecord};
 use crate::log::logger::{log_info, log_warn};
 
+use alloc::vec::Vec;
+
 /// Core sandbox state encapsulation for a `.mod` capsule
 pub struct SandboxContext {
     pub name: &'static str,
     pub exec_id: [u8; 32],
     pub memory: MemoryRegion,
+    /// Regions granted on top of `memory` (e.g. an upgrade handoff area)
+    pub grants: Vec<MemoryRegion>,
     pub token: CapabilityToken,
     pub runtime: RuntimeCapsule,
 }
@@ -28,12 +37,25 @@ pub struct SandboxContext {
 impl SandboxContext {
     /// Construct a fully isolated sandbox from a manifest
     pub fn new(manifest: &'static ModuleManifest, token: &CapabilityToken) -> Result<Self, &'static str> {
+        if crate::crypto::selftest::restricted_mode() {
+            return Err("Crypto self-test failed; capsule admission disabled");
+        }
         if !manifest.is_valid() {
             return Err("Manifest integrity or policy check failed");
         }
 
-        let mem = allocate_region(manifest.memory_required as usize)
+        // Snapshot key is granted before any memory is committed
+        signer::grant(signer::Principal::Capsule(manifest.name), KeyUsage::ModuleIsolation, SNAPSHOT_LABEL, SigningPolicy::snapshots())?;
+
+        // Demand-paged: only what the capsule touches is committed, and its
+        // code is shared with other instances of the same manifest once sealed
+        let code = SharedCode { key: manifest.hash, bytes: manifest.code_bytes };
+        let mem = region::reserve_region(manifest.memory_bytes, Some(code))
             .ok_or("Sandbox memory allocation failed")?;
+        if let Err(reason) = load_code(&mem, manifest) {
+            region::free_region(&mem);
+            return Err(reason);
+        }
 
         let exec_id = derive_exec_id(manifest.name, token);
         let policy = manifest.fault_policy.unwrap_or(FaultPolicy::Restart);
@@ -51,22 +73,89 @@ impl SandboxContext {
             name: manifest.name,
             exec_id,
             memory: mem,
+            grants: Vec::new(),
             token: token.clone(),
             runtime,
         })
     }
 
-    /// Trigger a secure runtime halt
-    pub fn shutdown(&mut self) {
+    /// Re-create a sandbox from its original verified manifest.
+    ///
+    /// The capsule gets fresh memory and a fresh `exec_id` bound to the
+    /// restart `generation`, so attestations from a previous incarnation
+    /// can never be replayed for the new one.
+    pub fn respawn(
+        manifest: &'static ModuleManifest,
+        token: &CapabilityToken,
+        generation: u32,
+    ) -> Result<Self, &'static str> {
+        let mut ctx = Self::new(manifest, token)?;
+
+        let mut seed = [0u8; 48];
+        seed[..32].copy_from_slice(&ctx.exec_id);
+        seed[32..36].copy_from_slice(&generation.to_le_bytes());
+        seed[40..48].copy_from_slice(&rand_u64().to_le_bytes());
+        ctx.exec_id = blake3_hash(&seed);
+
+        log_info("sandbox", &format!(
+            "[~] Sandbox '{}' respawned | gen={} | exec_id={:x?}",
+            manifest.name,
+            generation,
+            &ctx.exec_id[..4]
+        ));
+
+        Ok(ctx)
+    }
+
+    /// Trigger a secure runtime halt and release everything the capsule held.
+    /// Goes through `modules::teardown`, the single capsule exit path.
+    pub fn shutdown(&mut self, uid: &[u8; 32]) -> TerminationRecord {
         log_warn("sandbox", &format!("Shutting down '{}'", self.name));
-        self.runtime.terminate();
-        // In a production scenario, memory wipe and zeroization should occur here
-        self.memory.zeroize();
+        teardown(self, uid, TerminationReason::Shutdown)
+    }
+
+
+    /// Make `region` part of this capsule's memory perimeter
+    pub fn grant(&mut self, region: MemoryRegion) {
+        log_info("sandbox", &format!(
+            "'{}' granted {} KB at {:p}",
+            self.name, region.size / 1024, region.base.as_ptr()
+        ));
+        self.grants.push(region);
+    }
+
+    /// Withdraw a grant; the caller still owns and frees the region
+    pub fn revoke(&mut self, region: &MemoryRegion) {
+        self.grants.retain(|g| g.base != region.base);
+    }
+
+    /// True if `[addr, addr+len)` lies wholly inside the capsule's own
+    /// memory or one of its grants
+    pub fn owns_range(&self, addr: usize, len: usize) -> bool {
+        let end = match addr.checked_add(len) {
+            Some(end) => end,
+            None => return false,
+        };
+        self.perimeter().iter().any(|&(base, size)| addr >= base && end <= base + size)
+    }
+
+    /// `(base, size)` of the capsule's own memory followed by its grants
+    pub fn perimeter(&self) -> Vec<(usize, usize)> {
+        core::iter::once(&self.memory)
+            .chain(self.grants.iter())
+            .map(|r| (r.base.as_ptr() as usize, r.size))
+            .collect()
+    }
+
+    /// Resident-set accounting for this capsule's memory
+    pub fn memory_stats(&self) -> RegionStats {
+        region::region_stats(&self.memory).unwrap_or_default()
     }
 
     /// Tick capsule runtime — invoked on IPC or CPU cycles
     pub fn tick(&mut self) {
         self.runtime.tick();
+        crate::modules::registry::update_state(&self.exec_id(), &self.runtime);
     }
 
     /// Check liveness
@@ -74,9 +163,14 @@ impl SandboxContext {
         self.runtime.is_active()
     }
 
-    /// Enforce fault policy immediately (used on traps)
-    pub fn enforce_fault(&mut self) {
+    /// Enforce fault policy immediately (used on traps) and return the state
+    /// it leaves the capsule in. A `Terminating` capsule must then be torn
+    /// down and its dependents stopped by the owner, once it holds no lock
+    /// on this sandbox (see `supervisor::fault`).
+    pub fn enforce_fault(&mut self) -> CapsuleState {
         self.runtime.fault();
+        crate::modules::registry::update_state(&self.exec_id(), &self.runtime);
+        self.runtime.state()
     }
 
     /// Immutable access to runtime telemetry
@@ -104,3 +198,25 @@ impl SandboxContext {
         self.name
     }
 }
+
+/// Write the manifest's code image into the region's code prefix, seal it
+/// read-only (shared with other instances of the same hash), then check the
+/// sealed pages against `manifest.hash` before anything can execute them
+fn load_code(mem: &MemoryRegion, manifest: &ModuleManifest) -> Result<(), &'static str> {
+    if manifest.code_bytes == 0 {
+        return Ok(());
+    }
+    let base = mem.base.as_ptr();
+    if !region::code_sealed(&manifest.hash) {
+        // Pages fault in private and writable until sealed
+        unsafe { core::ptr::copy_nonoverlapping(manifest.code.as_ptr(), base, manifest.code_bytes) };
+    }
+    region::seal_code(mem)?;
+
+    let sealed = unsafe { core::slice::from_raw_parts(base as *const u8, manifest.code_bytes) };
+    if blake3_hash(sealed) != manifest.hash {
+        log_warn("sandbox", &format!("'{}' sealed code does not match its manifest hash", manifest.name));
+        return Err("Sealed code does not match manifest hash");
+    }
+    Ok(())
+}
@@ -0,0 +1,607 @@
+//! NØNOS Capsule Supervisor
+//!
+//! Kernel task that acts on `FaultPolicy::Restart`. It owns the sandbox of
+//! every supervised capsule and:
+//! - Re-creates capsules left in `Restarting` from their original verified
+//!   manifest, with a fresh `exec_id` per restart generation
+//! - Applies exponential backoff between restarts
+//! - Declares a crash loop after `max_restarts` restarts inside
+//!   `restart_window` and leaves the capsule terminated
+//! - Sends IPC heartbeat probes and faults capsules whose `last_seen` goes stale
+//! - Probes unsupervised capsules (any other policy) the same way and faults
+//!   them through `fault`; those stay owned by `runtime::zerostate`
+//! - Selects and kills the OOM victim on request of `memory::pressure`
+
+use crate::capabilities::{self, CapabilityToken};
+use crate::ipc::message::{IpcEnvelope, MessageType, MsgFlags};
+use crate::ipc::send_envelope;
+use crate::log::logger::{log_info, log_warn};
+use crate::modules::manifest::{ModuleManifest, IMPORTANCE_CRITICAL};
+use crate::modules::registry::register_module;
+use crate::modules::runtime::{CapsuleState, FaultPolicy};
+use crate::modules::sandbox::SandboxContext;
+use crate::modules::teardown::{retire_instance, teardown, TerminationReason};
+use crate::runtime::zerostate::{take_sandbox, track_active_sandbox, tracked_names, with_tracked};
+
+use alloc::collections::{BTreeMap, VecDeque};
+use core::cmp::Reverse;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+use spin::{Mutex, RwLock};
+
+/// IPC endpoint the supervisor probes from; capsules answer here
+pub const SUPERVISOR_ENDPOINT: &str = "kernel.supervisor";
+/// Probe payload sent to capsules
+pub const PROBE_PING: &[u8] = b"nonos.ping";
+/// Expected probe reply payload
+pub const PROBE_PONG: &[u8] = b"nonos.pong";
+
+/// Restart and liveness tuning, mirrors `nonosctl` host runtime defaults
+#[derive(Debug, Clone, Copy)]
+pub struct SupervisorPolicy {
+    pub max_restarts: usize,
+    pub restart_window: Duration,
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+    pub probe_interval: Duration,
+    pub heartbeat_timeout: Duration,
+}
+
+impl SupervisorPolicy {
+    pub const fn default_policy() -> Self {
+        Self {
+            max_restarts: 5,
+            restart_window: Duration::from_secs(60),
+            backoff_base: Duration::from_millis(500),
+            backoff_max: Duration::from_secs(30),
+            probe_interval: Duration::from_secs(1),
+            heartbeat_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Delay before restart attempt `attempt` (1-based): base * 2^(attempt-1), capped
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let shift = attempt.saturating_sub(1).min(16) as u32;
+        let ns = (self.backoff_base.as_nanos() as u64).saturating_mul(1u64 << shift);
+        Duration::from_nanos(ns.min(self.backoff_max.as_nanos() as u64))
+    }
+}
+
+impl Default for SupervisorPolicy {
+    fn default() -> Self {
+        Self::default_policy()
+    }
+}
+
+/// Supervision state of a single capsule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupervisionState {
+    Running,
+    BackingOff { restart_at_ns: u64 },
+    CrashLoop,
+}
+
+/// Telemetry view of a supervised capsule (CLI / gui_bridge)
+#[derive(Debug, Clone)]
+pub struct SupervisorStatus {
+    pub name: &'static str,
+    pub exec_id: [u8; 32],
+    pub generation: u32,
+    pub restarts_in_window: usize,
+    pub state: SupervisionState,
+    pub last_seen: Duration,
+}
+
+/// Outcome of an OOM kill
+#[derive(Debug, Clone, Copy)]
+pub struct OomKill {
+    pub name: &'static str,
+    pub exec_id: [u8; 32],
+    pub importance: u8,
+    pub policy: FaultPolicy,
+    /// Pages reclaimed from the victim
+    pub pages: usize,
+}
+
+struct Supervised {
+    uid: [u8; 32],
+    manifest: &'static ModuleManifest,
+    token: CapabilityToken,
+    context: SandboxContext,
+    generation: u32,
+    restarts: VecDeque<u64>,
+    state: SupervisionState,
+}
+
+static SUPERVISED: Mutex<BTreeMap<&'static str, Supervised>> = Mutex::new(BTreeMap::new());
+static POLICY: RwLock<SupervisorPolicy> = RwLock::new(SupervisorPolicy::default_policy());
+static STARTED: AtomicBool = AtomicBool::new(false);
+static PROBE_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Spawn the supervisor kernel task
+pub fn init_supervisor() {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    use crate::sched::task;
+
+    extern "C" fn supervisor_thread(_: usize) -> ! {
+        loop {
+            let now = now_ns();
+            poll(now);
+            crate::modules::upgrade::poll_upgrades(now);
+            // Queued modules whose providers just became Active
+            crate::modules::mod_loader::admit_ready_modules();
+
+            let interval = POLICY.read().probe_interval;
+            crate::arch::x86_64::time::timer::sleep_ns(interval.as_nanos() as u64);
+        }
+    }
+
+    task::kspawn(
+        "modules.supervisor",
+        supervisor_thread,
+        0,
+        task::Priority::Normal,
+        task::Affinity::ANY,
+    );
+
+    log_info("supervisor", "Capsule supervisor online");
+}
+
+/// Replace the global restart/liveness policy
+pub fn set_policy(policy: SupervisorPolicy) {
+    *POLICY.write() = policy;
+}
+
+/// Current restart/liveness policy
+pub fn policy() -> SupervisorPolicy {
+    *POLICY.read()
+}
+
+/// Hand a freshly launched sandbox to the supervisor.
+///
+/// `manifest` must be the verified manifest the sandbox was admitted from;
+/// restarts are always rebuilt from it, never from runtime state.
+pub fn supervise(
+    uid: [u8; 32],
+    manifest: &'static ModuleManifest,
+    token: CapabilityToken,
+    context: SandboxContext,
+) {
+    log_info("supervisor", &format!(
+        "Supervising '{}' | exec_id={:x?}",
+        manifest.name,
+        &context.exec_id()[..4]
+    ));
+
+    SUPERVISED.lock().insert(manifest.name, Supervised {
+        uid,
+        manifest,
+        token,
+        context,
+        generation: 0,
+        restarts: VecDeque::new(),
+        state: SupervisionState::Running,
+    });
+}
+
+/// Stop supervising a capsule and return its sandbox to the caller
+pub fn unsupervise(name: &str) -> Option<SandboxContext> {
+    SUPERVISED.lock().remove(name).map(|entry| entry.context)
+}
+
+/// Token and memory perimeter of the running capsule `name`, for checking
+/// the pointers it passes to syscalls. A capsule mid-upgrade answers with
+/// whichever instance is currently serving; an unsupervised one with its
+/// ZeroState-tracked sandbox, while that is active.
+pub fn caller_perimeter(name: &str) -> Option<(CapabilityToken, Vec<(usize, usize)>)> {
+    if let Some(entry) = SUPERVISED.lock().get(name) {
+        return Some((entry.token.clone(), entry.context.perimeter()));
+    }
+    if let Some(serving) = crate::modules::upgrade::serving_perimeter(name) {
+        return Some(serving);
+    }
+    with_tracked(name, |ctx| ctx.is_active().then(|| (ctx.token.clone(), ctx.perimeter()))).flatten()
+}
+
+/// Record a probe reply (or any proof of life) from a capsule. Only the
+/// capsule holding `token` can vouch for itself.
+pub fn heartbeat(name: &str, token: &CapabilityToken) -> Result<(), &'static str> {
+    if name != token.owner_module {
+        return Err("heartbeat sender does not own token");
+    }
+    if let Some(entry) = SUPERVISED.lock().get_mut(name) {
+        entry.context.tick();
+        return Ok(());
+    }
+    if !crate::modules::upgrade::heartbeat(name) {
+        with_tracked(name, |ctx| ctx.tick());
+    }
+    Ok(())
+}
+
+/// Fault the running capsule `name` (a trap, a missed heartbeat) and act on
+/// its `FaultPolicy`, whichever of the supervisor or ZeroState owns it. The
+/// caller must hold no module lock. Capsules mid-upgrade are judged by
+/// `upgrade::poll_upgrades` instead.
+pub fn fault(name: &'static str) {
+    if upgrading(name) {
+        return;
+    }
+    if SUPERVISED.lock().contains_key(name) {
+        let policy = *POLICY.read();
+        fault_supervised(name, &policy, now_ns());
+    } else {
+        fault_tracked(name);
+    }
+}
+
+/// Returns true if the envelope is a probe reply addressed to the supervisor
+pub fn is_probe_reply(envelope: &IpcEnvelope) -> bool {
+    envelope.to == SUPERVISOR_ENDPOINT
+        && envelope.header.msg_type == MessageType::Signal
+        && envelope.data.as_slice() == PROBE_PONG
+}
+
+/// One supervision pass: probe, detect stale heartbeats, restart or give up.
+/// Stale capsules are faulted after the supervisor lock is dropped, since
+/// acting on a fault policy may take it again.
+pub fn poll(now: u64) {
+    let policy = *POLICY.read();
+    let mut stale = Vec::new();
+    let mut supervised = SUPERVISED.lock();
+    let watched: Vec<&'static str> = supervised.keys().copied().collect();
+
+    for entry in supervised.values_mut() {
+        match entry.state {
+            SupervisionState::CrashLoop => continue,
+            SupervisionState::BackingOff { restart_at_ns } => {
+                if now >= restart_at_ns {
+                    restart(entry, now);
+                }
+                continue;
+            }
+            SupervisionState::Running => {}
+        }
+
+        if entry.context.is_active() {
+            if entry.context.runtime().last_seen() > policy.heartbeat_timeout {
+                log_warn("supervisor", &format!(
+                    "'{}' missed heartbeat for {} ms, faulting",
+                    entry.manifest.name,
+                    entry.context.runtime().last_seen().as_millis()
+                ));
+                stale.push(entry.manifest.name);
+                continue;
+            }
+            send_probe(entry.manifest.name);
+        }
+
+        if entry.context.runtime().needs_restart() {
+            schedule_restart(entry, &policy, now);
+        }
+    }
+    drop(supervised);
+
+    // Capsules outside supervision get the same liveness check
+    for name in tracked_names() {
+        if watched.contains(&name) || upgrading(name) {
+            continue;
+        }
+        match with_tracked(name, |ctx| ctx.is_active().then(|| ctx.runtime().last_seen())) {
+            Some(Some(last_seen)) if last_seen > policy.heartbeat_timeout => {
+                log_warn("supervisor", &format!(
+                    "'{}' missed heartbeat for {} ms, faulting",
+                    name,
+                    last_seen.as_millis()
+                ));
+                stale.push(name);
+            }
+            Some(Some(_)) => send_probe(name),
+            _ => {}
+        }
+    }
+
+    for name in stale {
+        if watched.contains(&name) {
+            fault_supervised(name, &policy, now);
+        } else {
+            fault_tracked(name);
+        }
+    }
+}
+
+/// Snapshot of every supervised capsule
+pub fn status() -> Vec<SupervisorStatus> {
+    SUPERVISED
+        .lock()
+        .values()
+        .map(|entry| SupervisorStatus {
+            name: entry.manifest.name,
+            exec_id: entry.context.exec_id(),
+            generation: entry.generation,
+            restarts_in_window: entry.restarts.len(),
+            state: entry.state,
+            last_seen: entry.context.runtime().last_seen(),
+        })
+        .collect()
+}
+
+/// Kill the most expendable running capsule to relieve memory pressure.
+///
+/// Candidates are every running capsule, supervised or tracked by ZeroState.
+/// Victims are ranked by manifest `importance` (lowest first), then by
+/// fault-policy priority (capsules that come back on their own go first),
+/// then by private resident pages (largest first). `IMPORTANCE_CRITICAL`
+/// capsules are never selected; `importance` is covered by the manifest
+/// signature (`ModuleManifest::signed_digest`), so no capsule can claim it.
+///
+/// The victim is faulted so its `FaultPolicy` decides what follows, but its
+/// memory is reclaimed immediately whatever the policy: a `Restart` capsule
+/// respawns after the usual backoff, `Suspend`/`Escalate` leave it stopped,
+/// `Shutdown` tears it down and stops its dependents.
+pub fn oom_kill() -> Option<OomKill> {
+    let mut candidates: Vec<(u8, u8, Reverse<usize>, &'static str)> = Vec::new();
+    let watched: Vec<&'static str> = {
+        let supervised = SUPERVISED.lock();
+        candidates.extend(
+            supervised
+                .values()
+                .filter(|e| e.state == SupervisionState::Running && e.context.is_active())
+                .filter(|e| e.manifest.importance != IMPORTANCE_CRITICAL)
+                .map(|e| (
+                    e.manifest.importance,
+                    policy_rank(e.context.runtime().fault_policy()),
+                    Reverse(e.context.memory_stats().private_pages),
+                    e.manifest.name,
+                )),
+        );
+        supervised.keys().copied().collect()
+    };
+    for name in tracked_names() {
+        if watched.contains(&name) || upgrading(name) {
+            continue;
+        }
+        let importance = match crate::modules::registry::find_by_name(name) {
+            Some(meta) if meta.manifest.importance != IMPORTANCE_CRITICAL => meta.manifest.importance,
+            _ => continue,
+        };
+        let ranked = with_tracked(name, |ctx| {
+            ctx.is_active().then(|| (
+                importance,
+                policy_rank(ctx.runtime().fault_policy()),
+                Reverse(ctx.memory_stats().private_pages),
+                name,
+            ))
+        });
+        candidates.extend(ranked.flatten());
+    }
+
+    let (importance, _, _, name) = candidates.into_iter().min()?;
+    if watched.contains(&name) {
+        oom_kill_supervised(name)
+    } else {
+        oom_kill_tracked(name, importance)
+    }
+}
+
+// ===== Private Helpers =====
+
+fn oom_kill_supervised(name: &'static str) -> Option<OomKill> {
+    let policy = *POLICY.read();
+    let now = now_ns();
+    let mut supervised = SUPERVISED.lock();
+
+    let entry = supervised.get_mut(name)?;
+    let fault_policy = entry.context.runtime().fault_policy();
+    log_warn("supervisor", &format!(
+        "OOM: killing '{}' | importance={} | policy={:?} | resident={} KB",
+        name,
+        entry.manifest.importance,
+        fault_policy,
+        entry.context.memory_stats().resident_bytes() / 1024
+    ));
+
+    // Shutdown propagation needs the supervisor lock we hold, so it runs
+    // below once it is dropped
+    let after = entry.context.enforce_fault();
+    let exec_id = entry.context.exec_id();
+    let importance = entry.manifest.importance;
+
+    let record = if after == CapsuleState::Terminating {
+        let mut entry = supervised.remove(name)?;
+        drop(supervised);
+        let record = teardown(&mut entry.context, &entry.uid, TerminationReason::OutOfMemory);
+        crate::modules::mod_loader::propagate_shutdown(name);
+        record
+    } else {
+        let record = retire_instance(&mut entry.context, TerminationReason::OutOfMemory);
+        if after == CapsuleState::Restarting {
+            schedule_restart(entry, &policy, now);
+        }
+        record
+    };
+
+    Some(OomKill { name, exec_id, importance, policy: fault_policy, pages: record.pages_scrubbed })
+}
+
+/// A ZeroState-owned victim leaves ZeroState either way: torn down under
+/// `Shutdown`, otherwise retired and left stopped in the registry
+fn oom_kill_tracked(name: &'static str, importance: u8) -> Option<OomKill> {
+    let (exec_id, fault_policy, resident, after) = with_tracked(name, |ctx| {
+        let fault_policy = ctx.runtime().fault_policy();
+        let resident = ctx.memory_stats().resident_bytes();
+        (ctx.exec_id(), fault_policy, resident, ctx.enforce_fault())
+    })?;
+    log_warn("supervisor", &format!(
+        "OOM: killing '{}' | importance={} | policy={:?} | resident={} KB",
+        name,
+        importance,
+        fault_policy,
+        resident / 1024
+    ));
+
+    let mut context = take_sandbox(&exec_id)?;
+    let terminating = after == CapsuleState::Terminating;
+    let record = match crate::modules::registry::find_by_exec_id(&exec_id) {
+        Some(meta) if terminating => teardown(&mut context, &meta.uid, TerminationReason::OutOfMemory),
+        _ => retire_instance(&mut context, TerminationReason::OutOfMemory),
+    };
+    if terminating {
+        crate::modules::mod_loader::propagate_shutdown(name);
+    }
+
+    Some(OomKill { name, exec_id, importance, policy: fault_policy, pages: record.pages_scrubbed })
+}
+
+/// Fault a supervised capsule and act on its policy. The transition runs
+/// under the supervisor lock; a `Shutdown` teardown and the propagation to
+/// dependents run after it is dropped, since both take it again.
+fn fault_supervised(name: &'static str, policy: &SupervisorPolicy, now: u64) {
+    let mut supervised = SUPERVISED.lock();
+    let entry = match supervised.get_mut(name) {
+        Some(entry) if entry.state == SupervisionState::Running => entry,
+        _ => return,
+    };
+
+    match entry.context.enforce_fault() {
+        CapsuleState::Restarting => schedule_restart(entry, policy, now),
+        CapsuleState::Terminating => {
+            let mut entry = match supervised.remove(name) {
+                Some(entry) => entry,
+                None => return,
+            };
+            drop(supervised);
+            teardown(&mut entry.context, &entry.uid, TerminationReason::Fault);
+            crate::modules::mod_loader::propagate_shutdown(name);
+        }
+        _ => {}
+    }
+}
+
+/// Fault a capsule ZeroState owns. As with `fault_supervised`, a `Shutdown`
+/// teardown and its propagation run once the registry lock is released.
+fn fault_tracked(name: &'static str) {
+    let (exec_id, after) = match with_tracked(name, |ctx| (ctx.exec_id(), ctx.enforce_fault())) {
+        Some(faulted) => faulted,
+        None => return,
+    };
+    if after != CapsuleState::Terminating {
+        return;
+    }
+
+    if let Some(mut context) = take_sandbox(&exec_id) {
+        match crate::modules::registry::find_by_exec_id(&exec_id) {
+            Some(meta) => teardown(&mut context, &meta.uid, TerminationReason::Fault),
+            None => retire_instance(&mut context, TerminationReason::Fault),
+        };
+    }
+    crate::modules::mod_loader::propagate_shutdown(name);
+}
+
+/// True while `name` has an upgrade in flight; `upgrade` owns both instances
+fn upgrading(name: &str) -> bool {
+    crate::modules::upgrade::pending_upgrades().iter().any(|(n, _)| *n == name)
+}
+
+/// OOM preference among equally important capsules: lower goes first
+fn policy_rank(policy: FaultPolicy) -> u8 {
+    match policy {
+        FaultPolicy::Restart => 0,
+        FaultPolicy::Suspend => 1,
+        FaultPolicy::Shutdown => 2,
+        FaultPolicy::Escalate => 3,
+    }
+}
+
+fn schedule_restart(entry: &mut Supervised, policy: &SupervisorPolicy, now: u64) {
+    let window_ns = policy.restart_window.as_nanos() as u64;
+    while let Some(&oldest) = entry.restarts.front() {
+        if now.saturating_sub(oldest) > window_ns {
+            entry.restarts.pop_front();
+        } else {
+            break;
+        }
+    }
+
+    if entry.restarts.len() >= policy.max_restarts {
+        log_warn("supervisor", &format!(
+            "'{}' crash loop: {} restarts within {} s, giving up",
+            entry.manifest.name,
+            entry.restarts.len(),
+            policy.restart_window.as_secs()
+        ));
+        teardown(&mut entry.context, &entry.uid, TerminationReason::CrashLoop);
+        entry.state = SupervisionState::CrashLoop;
+        return;
+    }
+
+    entry.restarts.push_back(now);
+    let delay = policy.backoff(entry.restarts.len());
+    entry.state = SupervisionState::BackingOff {
+        restart_at_ns: now.saturating_add(delay.as_nanos() as u64),
+    };
+
+    log_info("supervisor", &format!(
+        "'{}' restart {}/{} in {} ms",
+        entry.manifest.name,
+        entry.restarts.len(),
+        policy.max_restarts,
+        delay.as_millis()
+    ));
+}
+
+fn restart(entry: &mut Supervised, now: u64) {
+    retire_instance(&mut entry.context, TerminationReason::Restart);
+
+    let generation = entry.generation.wrapping_add(1);
+    match SandboxContext::respawn(entry.manifest, &entry.token, generation) {
+        Ok(context) => {
+            track_active_sandbox(&context);
+            register_module(
+                entry.uid,
+                entry.manifest,
+                context.runtime(),
+                context.exec_id(),
+                Some(context.export_attestation()),
+            );
+            entry.context = context;
+            entry.generation = generation;
+            entry.state = SupervisionState::Running;
+        }
+        Err(reason) => {
+            // Count the failed respawn as a crash; the next pass backs off again.
+            log_warn("supervisor", &format!("'{}' respawn failed: {}", entry.manifest.name, reason));
+            entry.state = SupervisionState::Running;
+            schedule_restart(entry, &POLICY.read(), now);
+        }
+    }
+}
+
+pub(crate) fn send_probe(name: &'static str) {
+    let token = match capabilities::get("kernel") {
+        Some(token) => token,
+        None => return,
+    };
+
+    let envelope = IpcEnvelope::new(
+        MessageType::Signal,
+        SUPERVISOR_ENDPOINT,
+        name,
+        PROBE_PING,
+        PROBE_SEQ.fetch_add(1, Ordering::Relaxed),
+        MsgFlags::ACK_REQUIRED | MsgFlags::SYSTEM_ONLY,
+        1,
+        None,
+    );
+
+    // Capsules without a supervisor channel are judged on their own ticks.
+    let _ = send_envelope(envelope, &token);
+}
+
+fn now_ns() -> u64 {
+    crate::arch::x86_64::time::timer::now_ns()
+}
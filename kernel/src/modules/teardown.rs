@@ -0,0 +1,172 @@
+//! NØNOS Capsule Teardown
+//!
+//! The single exit path for a sandbox. Every capsule that leaves the system,
+//! whether by shutdown, crash loop, upgrade retirement or dependency
+//! propagation, goes through here so that:
+//! - Every page is scrubbed, unmapped and freed (`PhysFree` proof events)
+//! - IPC channels and streams touching the capsule are closed
+//! - Its capability token and vault signing handles are revoked
+//! - Its name is removed from `modules::registry` and `runtime::zerostate`
+//! - A termination record signed by the kernel vault key is emitted
+
+use crate::capabilities;
+use crate::crypto::hash::Digest;
+use crate::crypto::signer::{kernel_sign, release_handles};
+use crate::ipc::channel::IPC_BUS;
+use crate::ipc::transport::close_streams_for;
+use crate::log::logger::log_warn;
+use crate::memory::region::free_region;
+use crate::modules::registry::unregister_module;
+use crate::modules::runtime::CapsuleState;
+use crate::modules::sandbox::SandboxContext;
+use crate::runtime::zerostate::take_sandbox;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// Recent termination records kept for CLI / telemetry export
+const RECORD_HISTORY: usize = 64;
+
+/// Why a capsule left the system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TerminationReason {
+    Shutdown = 0x01,
+    Fault = 0x02,
+    CrashLoop = 0x03,
+    Restart = 0x04,
+    Upgraded = 0x05,
+    RolledBack = 0x06,
+    DependencyStopped = 0x07,
+    OutOfMemory = 0x08,
+}
+
+/// Signed record of a capsule teardown
+#[derive(Debug, Clone)]
+pub struct TerminationRecord {
+    pub name: &'static str,
+    pub exec_id: [u8; 32],
+    pub reason: TerminationReason,
+    pub pages_scrubbed: usize,
+    pub channels_closed: usize,
+    pub streams_closed: usize,
+    pub identity_released: bool,
+    pub timestamp_ns: u64,
+    /// Memory proof commitment at teardown, algorithm-tagged
+    pub proof_root: Digest,
+    /// Ed25519 over `encode()` under `signer::kernel_handle()`
+    pub signature: [u8; 64],
+}
+
+impl TerminationRecord {
+    /// Canonical little-endian encoding covered by the signature
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(128);
+        out.extend_from_slice(b"NONOS:TERM:v2");
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(self.name.as_bytes());
+        out.extend_from_slice(&self.exec_id);
+        out.push(self.reason as u8);
+        out.extend_from_slice(&(self.pages_scrubbed as u64).to_le_bytes());
+        out.extend_from_slice(&(self.channels_closed as u32).to_le_bytes());
+        out.extend_from_slice(&(self.streams_closed as u32).to_le_bytes());
+        out.push(self.identity_released as u8);
+        out.extend_from_slice(&self.timestamp_ns.to_le_bytes());
+        out.extend_from_slice(&self.proof_root.encode());
+        out
+    }
+}
+
+static RECORDS: Mutex<VecDeque<TerminationRecord>> = Mutex::new(VecDeque::new());
+
+/// Tear a capsule down completely: its instance and its identity.
+pub fn teardown(ctx: &mut SandboxContext, uid: &[u8; 32], reason: TerminationReason) -> TerminationRecord {
+    run(ctx, Some(uid), reason)
+}
+
+/// Retire one instance while its identity (name, token, registry uid) lives
+/// on in a successor: restarts, upgrades and rollbacks.
+pub fn retire_instance(ctx: &mut SandboxContext, reason: TerminationReason) -> TerminationRecord {
+    run(ctx, None, reason)
+}
+
+/// Recent termination records, oldest first
+pub fn recent_records() -> Vec<TerminationRecord> {
+    RECORDS.lock().iter().cloned().collect()
+}
+
+fn run(ctx: &mut SandboxContext, identity: Option<&[u8; 32]>, reason: TerminationReason) -> TerminationRecord {
+    // A retired instance may still need its identity released (crash loop
+    // after a failed respawn), but its region must never be freed twice.
+    let already_retired = ctx.runtime().state() == CapsuleState::Inactive;
+
+    if !already_retired {
+        ctx.runtime_mut().terminate();
+        // Stop ZeroState from tracking (and scrubbing) a region we are about to free
+        let _ = take_sandbox(&ctx.exec_id());
+    }
+
+    let (channels_closed, streams_closed) = match identity {
+        Some(uid) => {
+            let channels = IPC_BUS.close_channels_for(ctx.name);
+            let streams = close_streams_for(ctx.name);
+            capabilities::revoke(ctx.name);
+            release_handles(ctx.name);
+            unregister_module(uid);
+            (channels, streams)
+        }
+        None => (0, 0),
+    };
+
+    let pages = if already_retired { 0 } else { free_region(&ctx.memory) };
+    ctx.runtime_mut().mark_inactive();
+
+    seal(ctx, reason, pages, channels_closed, streams_closed, identity.is_some())
+}
+
+fn seal(
+    ctx: &SandboxContext,
+    reason: TerminationReason,
+    pages_scrubbed: usize,
+    channels_closed: usize,
+    streams_closed: usize,
+    identity_released: bool,
+) -> TerminationRecord {
+    let mut record = TerminationRecord {
+        name: ctx.name,
+        exec_id: ctx.exec_id(),
+        reason,
+        pages_scrubbed,
+        channels_closed,
+        streams_closed,
+        identity_released,
+        timestamp_ns: crate::arch::x86_64::time::timer::now_ns(),
+        proof_root: crate::memory::proof::root_digest(),
+        signature: [0; 64],
+    };
+
+    match kernel_sign(&record.encode()) {
+        Ok(signature) => record.signature = signature,
+        Err(e) => log_warn("teardown", &format!("'{}' record left unsigned: {}", record.name, e)),
+    }
+
+    log_warn("teardown", &format!(
+        "'{}' terminated ({:?}) | exec_id={:x?} | pages={} | ipc={}+{} | sig={:x?}",
+        record.name,
+        reason,
+        &record.exec_id[..4],
+        pages_scrubbed,
+        channels_closed,
+        streams_closed,
+        &record.signature[..4]
+    ));
+
+    let mut records = RECORDS.lock();
+    if records.len() >= RECORD_HISTORY {
+        records.pop_front();
+    }
+    records.push_back(record.clone());
+
+    record
+}
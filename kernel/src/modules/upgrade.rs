@@ -0,0 +1,404 @@
+//! NØNOS Live Capsule Upgrade
+//!
+//! Replaces a running capsule without losing its RAM state:
+//! 1. Verify the new manifest and require the same signer chain and a
+//!    strictly higher version
+//! 2. Start the new instance suspended
+//! 3. Hand state over through a dedicated IPC session or a granted region,
+//!    within `HANDOFF_TIMEOUT`; an upgrade still in handoff then rolls back
+//! 4. Switch the service name and routes to the new instance
+//! 5. Keep the old instance suspended for a probation period; roll back
+//!    automatically if the new one faults, otherwise retire it with zeroization
+
+use crate::capabilities::CapabilityToken;
+use crate::ipc::channel::IPC_BUS;
+use crate::log::logger::{log_info, log_warn};
+use crate::memory::region::{allocate_region, free_region, MemoryRegion};
+use crate::modules::teardown::{retire_instance, TerminationReason};
+use crate::modules::auth::{authenticate_manifest, AuthResult};
+use crate::modules::depgraph::Version;
+use crate::modules::manifest::ModuleManifest;
+use crate::modules::registry::{self, register_module};
+use crate::modules::runtime::{CapsuleState, FaultPolicy};
+use crate::modules::sandbox::SandboxContext;
+use crate::modules::supervisor;
+use crate::runtime::zerostate::{take_sandbox, track_active_sandbox};
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::format;
+use core::time::Duration;
+use spin::Mutex;
+
+/// Default time the new instance must stay healthy before the old one is retired
+pub const DEFAULT_PROBATION: Duration = Duration::from_secs(30);
+/// Longest the old instance may spend handing off, unsupervised
+pub const HANDOFF_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the old instance hands its state to the new one
+#[derive(Debug, Clone, Copy)]
+pub enum HandoffMode {
+    /// Dedicated IPC session between the old instance and a staging endpoint
+    IpcSession,
+    /// Zeroed memory region granted to both instances
+    SharedRegion { bytes: usize },
+}
+
+/// What the capsules get to perform the handoff
+#[derive(Debug, Clone, Copy)]
+pub enum HandoffGrant {
+    Session { from: &'static str, to: &'static str },
+    Region { base: u64, size: usize },
+}
+
+/// Upgrade lifecycle phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradePhase {
+    /// New instance suspended, old instance still serving and handing off
+    Handoff { until_ns: u64 },
+    /// New instance serving, old instance suspended as rollback target
+    Probation { until_ns: u64 },
+}
+
+struct PendingUpgrade {
+    uid: [u8; 32],
+    old_manifest: &'static ModuleManifest,
+    old: SandboxContext,
+    new_manifest: &'static ModuleManifest,
+    new_token: CapabilityToken,
+    new: SandboxContext,
+    grant: HandoffGrant,
+    region: Option<MemoryRegion>,
+    probation: Duration,
+    phase: UpgradePhase,
+}
+
+static UPGRADES: Mutex<BTreeMap<&'static str, PendingUpgrade>> = Mutex::new(BTreeMap::new());
+/// Staging endpoint names, one per capsule name for the kernel's lifetime
+static STAGING: Mutex<BTreeMap<&'static str, &'static str>> = Mutex::new(BTreeMap::new());
+
+/// Start upgrading the running capsule named by `manifest.name`.
+///
+/// On success the new instance exists but is suspended; the returned grant
+/// tells both sides where to exchange state. Call `complete_handoff` once the
+/// old instance has written its state.
+pub fn begin_upgrade(
+    manifest: &'static ModuleManifest,
+    mode: HandoffMode,
+    probation: Duration,
+) -> Result<HandoffGrant, &'static str> {
+    if UPGRADES.lock().contains_key(manifest.name) {
+        return Err("Upgrade already in progress");
+    }
+
+    let current = registry::find_by_name(manifest.name).ok_or("No running instance to upgrade")?;
+    if current.state != CapsuleState::Active {
+        return Err("Running instance is not active");
+    }
+
+    check_lineage(current.manifest, manifest)?;
+
+    let new_token = match authenticate_manifest(manifest) {
+        AuthResult::Verified(token) => token,
+        AuthResult::Rejected(reason) => return Err(reason),
+    };
+
+    // The old instance is owned by the supervisor or, failing that, by ZeroState.
+    // Always untrack it so a restore does not count its memory twice.
+    let supervised = supervisor::unsupervise(manifest.name);
+    let tracked = take_sandbox(&current.exec_id);
+    let mut old = supervised.or(tracked).ok_or("Running instance not owned by kernel")?;
+    let old_token = old.token.clone();
+
+    let mut new = match SandboxContext::new(manifest, &new_token) {
+        Ok(ctx) => ctx,
+        Err(reason) => {
+            restore(current.uid, current.manifest, old);
+            return Err(reason);
+        }
+    };
+    new.runtime_mut().suspend();
+
+    let (grant, region) = match mode {
+        HandoffMode::IpcSession => {
+            let staging = staging_endpoint(manifest.name);
+            if let Err(reason) = IPC_BUS.open_channel(manifest.name, staging, old_token.clone()) {
+                retire_instance(&mut new, TerminationReason::RolledBack);
+                restore(current.uid, current.manifest, old);
+                return Err(reason);
+            }
+            (HandoffGrant::Session { from: manifest.name, to: staging }, None)
+        }
+        HandoffMode::SharedRegion { bytes } => match allocate_region(bytes) {
+            Some(region) => {
+                old.grant(region);
+                new.grant(region);
+                (
+                    HandoffGrant::Region { base: region.base.as_ptr() as u64, size: region.size },
+                    Some(region),
+                )
+            }
+            None => {
+                retire_instance(&mut new, TerminationReason::RolledBack);
+                restore(current.uid, current.manifest, old);
+                return Err("Handoff region allocation failed");
+            }
+        },
+    };
+
+    log_info("upgrade", &format!(
+        "Upgrading '{}' {} → {} | handoff={:?}",
+        manifest.name, current.manifest.version, manifest.version, grant
+    ));
+
+    UPGRADES.lock().insert(manifest.name, PendingUpgrade {
+        uid: current.uid,
+        old_manifest: current.manifest,
+        old,
+        new_manifest: manifest,
+        new_token,
+        new,
+        grant,
+        region,
+        probation,
+        phase: UpgradePhase::Handoff {
+            until_ns: crate::arch::x86_64::time::timer::now_ns()
+                .saturating_add(HANDOFF_TIMEOUT.as_nanos() as u64),
+        },
+    });
+
+    Ok(grant)
+}
+
+/// The old instance has handed off its state: switch the service over to the
+/// new instance and start probation.
+pub fn complete_handoff(name: &str, now_ns: u64) -> Result<(), &'static str> {
+    let mut upgrades = UPGRADES.lock();
+    let up = upgrades.get_mut(name).ok_or("No upgrade in progress")?;
+    if !matches!(up.phase, UpgradePhase::Handoff { .. }) {
+        return Err("Handoff already completed");
+    }
+
+    if let HandoffGrant::Session { from, to } = up.grant {
+        IPC_BUS.close_channel(from, to);
+    }
+
+    up.old.runtime_mut().suspend();
+    up.new.runtime_mut().state = CapsuleState::Active;
+    up.new.tick();
+
+    // Service name and routes follow the registry entry; re-point it
+    track_active_sandbox(&up.new);
+    register_module(
+        up.uid,
+        up.new_manifest,
+        up.new.runtime(),
+        up.new.exec_id(),
+        Some(up.new.export_attestation()),
+    );
+
+    up.phase = UpgradePhase::Probation {
+        until_ns: now_ns.saturating_add(up.probation.as_nanos() as u64),
+    };
+
+    log_info("upgrade", &format!(
+        "'{}' switched to exec_id={:x?}, probation {} s",
+        name,
+        &up.new.exec_id()[..4],
+        up.probation.as_secs()
+    ));
+    Ok(())
+}
+
+/// Abandon an upgrade that has not finished its handoff
+pub fn abort_upgrade(name: &str) -> Result<(), &'static str> {
+    let mut upgrades = UPGRADES.lock();
+    match upgrades.get(name).map(|up| up.phase) {
+        Some(UpgradePhase::Handoff { .. }) => {}
+        Some(UpgradePhase::Probation { .. }) => return Err("Upgrade in probation; it rolls back on fault"),
+        None => return Err("No upgrade in progress"),
+    }
+    let up = upgrades.remove(name).ok_or("No upgrade in progress")?;
+    drop(upgrades);
+
+    log_warn("upgrade", &format!("Upgrade of '{}' aborted", name));
+    rollback(up);
+    Ok(())
+}
+
+/// Proof of life from the instance serving `name` mid-upgrade (neither is
+/// supervised): the old one during handoff, the new one in probation.
+/// Returns false if `name` has no upgrade in flight.
+pub fn heartbeat(name: &str) -> bool {
+    match UPGRADES.lock().get_mut(name) {
+        Some(up) => {
+            match up.phase {
+                UpgradePhase::Handoff { .. } => up.old.tick(),
+                UpgradePhase::Probation { .. } => up.new.tick(),
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Token and perimeter of the instance serving `name` mid-upgrade: the old
+/// one during handoff, the new one in probation
+pub fn serving_perimeter(name: &str) -> Option<(CapabilityToken, Vec<(usize, usize)>)> {
+    let upgrades = UPGRADES.lock();
+    let up = upgrades.get(name)?;
+    Some(match up.phase {
+        UpgradePhase::Handoff { .. } => (up.old.token.clone(), up.old.perimeter()),
+        UpgradePhase::Probation { .. } => (up.new_token.clone(), up.new.perimeter()),
+    })
+}
+
+/// Drive upgrades: roll back handoffs past their deadline, probe the new
+/// instance in probation the way the supervisor would, roll back faulted or
+/// silent upgrades, commit healthy ones whose probation elapsed. Called from
+/// the supervisor loop.
+pub fn poll_upgrades(now_ns: u64) {
+    let timeout = supervisor::policy().heartbeat_timeout;
+    let mut upgrades = UPGRADES.lock();
+    let mut finished: alloc::vec::Vec<(&'static str, bool)> = alloc::vec::Vec::new();
+
+    for (name, up) in upgrades.iter_mut() {
+        if let UpgradePhase::Handoff { until_ns } = up.phase {
+            if now_ns >= until_ns {
+                finished.push((*name, false));
+            }
+        }
+        if let UpgradePhase::Probation { until_ns } = up.phase {
+            if up.new.is_active() {
+                if up.new.runtime().last_seen() > timeout {
+                    log_warn("upgrade", &format!("'{}' missed heartbeat during probation", name));
+                    up.new.runtime_mut().fault();
+                } else {
+                    supervisor::send_probe(name);
+                }
+            }
+            let faulted = matches!(
+                up.new.runtime().state(),
+                CapsuleState::Faulted
+                    | CapsuleState::Restarting
+                    | CapsuleState::Terminating
+                    | CapsuleState::Suspended
+            );
+            if faulted {
+                finished.push((*name, false));
+            } else if now_ns >= until_ns {
+                finished.push((*name, true));
+            }
+        }
+    }
+
+    for (name, healthy) in finished {
+        if let Some(up) = upgrades.remove(name) {
+            if healthy {
+                commit(up);
+            } else if matches!(up.phase, UpgradePhase::Handoff { .. }) {
+                log_warn("upgrade", &format!(
+                    "'{}' handoff exceeded {} s, rolling back",
+                    name,
+                    HANDOFF_TIMEOUT.as_secs()
+                ));
+                rollback(up);
+            } else {
+                log_warn("upgrade", &format!("'{}' faulted during probation, rolling back", name));
+                rollback(up);
+            }
+        }
+    }
+}
+
+/// Names of capsules with an upgrade in flight
+pub fn pending_upgrades() -> alloc::vec::Vec<(&'static str, UpgradePhase)> {
+    UPGRADES.lock().iter().map(|(name, up)| (*name, up.phase)).collect()
+}
+
+// ===== Private Helpers =====
+
+/// Same signer chain and strictly higher version
+fn check_lineage(old: &ModuleManifest, new: &ModuleManifest) -> Result<(), &'static str> {
+    let same_chain = match (old.auth_chain_id, new.auth_chain_id) {
+        (Some(a), Some(b)) => a == b,
+        _ => old.signer == new.signer,
+    };
+    if !same_chain {
+        return Err("Upgrade signed by a different signer chain");
+    }
+
+    let old_v = Version::parse(old.version).ok_or("Running version unparseable")?;
+    let new_v = Version::parse(new.version).ok_or("Upgrade version unparseable")?;
+    if new_v <= old_v {
+        return Err("Upgrade version must be higher than running version");
+    }
+    Ok(())
+}
+
+/// New instance stays, old instance is scrubbed
+fn commit(mut up: PendingUpgrade) {
+    retire_instance(&mut up.old, TerminationReason::Upgraded);
+    release_handoff(&mut up);
+
+    log_info("upgrade", &format!(
+        "'{}' upgrade to {} committed, old instance retired",
+        up.new_manifest.name, up.new_manifest.version
+    ));
+
+    hand_to_supervisor(up.uid, up.new_manifest, up.new_token, up.new);
+}
+
+/// New instance is scrubbed, old instance resumes service
+fn rollback(mut up: PendingUpgrade) {
+    retire_instance(&mut up.new, TerminationReason::RolledBack);
+    release_handoff(&mut up);
+
+    restore(up.uid, up.old_manifest, up.old);
+}
+
+fn release_handoff(up: &mut PendingUpgrade) {
+    if let HandoffGrant::Session { from, to } = up.grant {
+        IPC_BUS.close_channel(from, to);
+    }
+    if let Some(region) = up.region.take() {
+        up.old.revoke(&region);
+        up.new.revoke(&region);
+        free_region(&region);
+    }
+}
+
+/// `<name>~next`, allocated once per capsule name and reused by later upgrades
+fn staging_endpoint(name: &'static str) -> &'static str {
+    *STAGING.lock().entry(name).or_insert_with(|| {
+        let mut s = String::from(name);
+        s.push_str("~next");
+        Box::leak(s.into_boxed_str())
+    })
+}
+
+/// Put an instance back into service under its registry uid
+fn restore(uid: [u8; 32], manifest: &'static ModuleManifest, mut ctx: SandboxContext) {
+    if ctx.runtime().state() == CapsuleState::Suspended {
+        ctx.runtime_mut().state = CapsuleState::Active;
+        ctx.tick();
+    }
+    track_active_sandbox(&ctx);
+    register_module(uid, manifest, ctx.runtime(), ctx.exec_id(), Some(ctx.export_attestation()));
+
+    let token = ctx.token.clone();
+    hand_to_supervisor(uid, manifest, token, ctx);
+}
+
+fn hand_to_supervisor(
+    uid: [u8; 32],
+    manifest: &'static ModuleManifest,
+    token: CapabilityToken,
+    ctx: SandboxContext,
+) {
+    if ctx.runtime().fault_policy() == FaultPolicy::Restart {
+        supervisor::supervise(uid, manifest, token, ctx);
+    }
+}
@@ -13,21 +13,29 @@
 //! - zkSNARK / zkSTARK extensibility
 
 use crate::modules::vm::VmInstance;
-use crate::crypto::hash::{sha3_256, keccak256};
+use crate::crypto::hash::{domain, hash_tagged, Digest, HashAlgo};
+#[cfg(feature = "nonos-zk")]
+use crate::crypto::{hkdf::scrub, vault::derive_subkey, zk::zk_sigma};
 use crate::log::logger::{log_info, log_warn};
 use core::time::Duration;
 use core::ptr::NonNull;
 use alloc::vec::Vec;
 use alloc::string::ToString;
 
-/// zk-proof artifact (compressed SNARK or STARK)
+/// zk-proof artifact: `Y || R || s` — a Schnorr proof of knowledge (sigma
+/// protocol, `nonos-zk`) by the kernel attestation key `Y` over the
+/// attestation statement
 #[derive(Debug, Clone, Copy)]
 pub struct ZkProof(pub [u8; 96]);
 
+/// Vault subkey label of the kernel's zkVM attestation key
+#[cfg(feature = "nonos-zk")]
+const ATTEST_KEY_LABEL: &[u8] = b"zkvm-attest";
+
 /// Capsule execution fingerprint + proof
 #[derive(Debug, Clone)]
 pub struct CapsuleAttestation {
-    pub capsule_hash: [u8; 32],     // unique ZK fingerprint
+    pub capsule_hash: Digest,       // unique ZK fingerprint (keccak256, EVM-verifiable)
     pub proof: ZkProof,             // compressed ZK proof
     pub capsule_id: [u8; 32],       // sealed exec hash
     pub runtime_cycles: u64,        // simulated or real cycles
@@ -35,7 +43,7 @@ pub struct CapsuleAttestation {
     pub exec_stack: usize,          // stack ptr
 }
 
-/// Generate zero-knowledge attestation for a capsule (stub logic)
+/// Generate zero-knowledge attestation for a capsule
 pub fn generate_proof(instance: &VmInstance) -> CapsuleAttestation {
     let capsule_id = instance.sealed_fingerprint();
     let stack_addr = instance.stack().as_ptr() as usize;
@@ -46,17 +54,19 @@ pub fn generate_proof(instance: &VmInstance) -> CapsuleAttestation {
     preimage.extend_from_slice(&stack_addr.to_le_bytes());
     preimage.extend_from_slice(&entry_addr.to_le_bytes());
 
-    let capsule_hash = keccak256(&preimage);
+    let capsule_hash = hash_tagged(HashAlgo::Keccak256, domain::ZKVM_CAPSULE, &preimage)
+        .expect("keccak256 is always built");
     let runtime_cycles = simulate_cycle_count(&capsule_id);
+    let proof = prove(&attestation_statement(&capsule_hash, &capsule_id, runtime_cycles));
 
     log_info("zkvm", &format!(
-        "[ZKVM] Attested capsule hash=0x{:x?}, entry=0x{:x}, stack=0x{:x}, cycles={}",
+        "[ZKVM] Attested capsule hash={}, entry=0x{:x}, stack=0x{:x}, cycles={}",
         capsule_hash, entry_addr, stack_addr, runtime_cycles
     ));
 
     CapsuleAttestation {
         capsule_hash,
-        proof: ZkProof([0xA5; 96]), // replace with real SNARK
+        proof,
         capsule_id,
         runtime_cycles,
         exec_entry: entry_addr,
@@ -73,12 +83,59 @@ fn simulate_cycle_count(seed: &[u8; 32]) -> u64 {
     acc.wrapping_mul(1337) ^ 0xDEADBEEF
 }
 
-/// Verify zk-proof remotely (stubbed)
+/// Bytes the attestation proof is bound to
+fn attestation_statement(capsule_hash: &Digest, capsule_id: &[u8; 32], cycles: u64) -> Vec<u8> {
+    let mut statement = Vec::with_capacity(33 + 32 + 8);
+    statement.extend_from_slice(&capsule_hash.encode());
+    statement.extend_from_slice(capsule_id);
+    statement.extend_from_slice(&cycles.to_le_bytes());
+    statement
+}
+
+/// Public half of the kernel zkVM attestation key, for remote verifiers
+#[cfg(feature = "nonos-zk")]
+pub fn attestation_key() -> Option<[u8; 32]> {
+    let mut secret = [0u8; 32];
+    derive_subkey(ATTEST_KEY_LABEL, b"", &mut secret).ok()?;
+    let key = zk_sigma::public_key(&secret);
+    scrub(&mut secret);
+    Some(key)
+}
+
+#[cfg(feature = "nonos-zk")]
+fn prove(statement: &[u8]) -> ZkProof {
+    let mut out = [0u8; 96];
+    let mut secret = [0u8; 32];
+    if derive_subkey(ATTEST_KEY_LABEL, b"", &mut secret).is_err() {
+        log_warn("zkvm", "vault unavailable; capsule attestation left unproven");
+        return ZkProof(out);
+    }
+    let key = [zk_sigma::public_key(&secret)];
+    let vk = zk_sigma::VerifyingKey { circuit: zk_sigma::Circuit::ModSig, keys: &key };
+    let proof = zk_sigma::prove_schnorr(&vk, &secret, statement);
+    scrub(&mut secret);
+
+    out[..32].copy_from_slice(&key[0]);
+    out[32..].copy_from_slice(&proof);
+    ZkProof(out)
+}
+
+#[cfg(not(feature = "nonos-zk"))]
+fn prove(_statement: &[u8]) -> ZkProof {
+    log_warn("zkvm", "kernel built without nonos-zk; capsule attestation left unproven");
+    ZkProof([0u8; 96])
+}
+
+/// Verify a capsule attestation: the proof must come from this kernel's
+/// attestation key and bind the hash, capsule id and cycle count
 pub fn verify_proof(attestation: &CapsuleAttestation) -> bool {
-    let valid_signature = attestation.proof.0[0] == 0xA5;
-    let valid_length = attestation.proof.0.len() == 96;
+    let statement = attestation_statement(
+        &attestation.capsule_hash,
+        &attestation.capsule_id,
+        attestation.runtime_cycles,
+    );
 
-    if !valid_signature || !valid_length {
+    if !check_proof(&attestation.proof, &statement) {
         log_warn("zkvm", "ZK proof signature check failed");
         return false;
     }
@@ -92,3 +149,19 @@ pub fn verify_proof(attestation: &CapsuleAttestation) -> bool {
 
     true
 }
+
+#[cfg(feature = "nonos-zk")]
+fn check_proof(proof: &ZkProof, statement: &[u8]) -> bool {
+    let mut key = [[0u8; 32]; 1];
+    key[0].copy_from_slice(&proof.0[..32]);
+    if attestation_key() != Some(key[0]) {
+        return false;
+    }
+    let vk = zk_sigma::VerifyingKey { circuit: zk_sigma::Circuit::ModSig, keys: &key };
+    zk_sigma::verify(&vk, statement, &proof.0[32..])
+}
+
+#[cfg(not(feature = "nonos-zk"))]
+fn check_proof(_proof: &ZkProof, _statement: &[u8]) -> bool {
+    false
+}
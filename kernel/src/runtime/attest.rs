@@ -0,0 +1,251 @@
+//! NØNOS Attestation Quotes
+//!
+//! A quote binds every public state commitment the kernel keeps to a
+//! verifier-chosen nonce, and is signed by a vault-held attestation key:
+//! - ZeroState snapshot (state hash, sandbox Merkle root, capsule/memory use)
+//! - `memory::proof` global audit root
+//! - physical allocator bitmap hash
+//! - KASLR entropy transcript hash (commits to the bootloader's image slide)
+//!   and which randomizations were actually applied
+//! - logger chain head
+//! - boot capsule identity from the `ZeroStateBootInfo` handoff
+//! - Merkle root of the loaded module set (`modules::registry`)
+//! - crypto self-test status
+//! - latest periodic page-table policy scan (`memory::mapcheck`) and its time;
+//!   quotes never walk the tables themselves
+//!
+//! Remote parties challenge a node with a fresh nonce through
+//! `Syscall::AttestQuote` or the `attest.quote` console command (answered
+//! as a `gui_bridge` JSON frame). The attestation key is distinct from the
+//! kernel's general signing key and may only sign `QUOTE_DOMAIN` messages.
+//! Each challenger gets its own quote budget, so one capsule polling quotes
+//! cannot starve the others; the key's signing policy caps the total.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::vec::Vec;
+use core::time::Duration;
+use spin::Mutex;
+
+use crate::crypto::hash::{domain, Digest, HashAlgo};
+use crate::crypto::merkle::MerkleTree;
+use crate::crypto::selftest::{self, PostStatus};
+use crate::crypto::sig::verify_ed25519_signature;
+use crate::crypto::signer::{self, KeyHandle, Principal, SigningPolicy};
+use crate::crypto::vault::{self, KeyUsage, VaultPublicKey};
+use crate::log::logger::{log_info, log_warn, try_get_logger};
+use crate::memory::{kaslr, mapcheck, phys, proof};
+use crate::modules::registry;
+use crate::runtime::zerostate;
+
+/// Domain tag every quote starts with (the only thing the key may sign)
+pub const QUOTE_DOMAIN: &[u8] = b"NONOS:QUOTE:v2";
+/// Label of the kernel's attestation key in the vault
+const ATTEST_LABEL: &str = "attestation";
+/// Quotes per second a single challenger can extract
+const QUOTE_RATE: u32 = 8;
+/// Quotes per second the attestation key signs across all challengers
+const QUOTE_RATE_TOTAL: u32 = 64;
+const QUOTE_WINDOW: Duration = Duration::from_secs(1);
+pub const NONCE_LEN: usize = 32;
+pub const SIGNATURE_LEN: usize = 64;
+
+/// Signed, epoch-stamped statement of kernel state
+#[derive(Debug, Clone)]
+pub struct Quote {
+    /// Verifier challenge, echoed verbatim
+    pub nonce: [u8; NONCE_LEN],
+    pub timestamp_ns: u64,
+    pub zerostate_epoch: u64,
+    pub vault_epoch: u64,
+    pub state_hash: Digest,
+    pub sandbox_root: Digest,
+    pub capsule_count: u32,
+    pub memory_used: u64,
+    pub memory_proof_root: Digest,
+    pub phys_bitmap: Digest,
+    pub kaslr_transcript: Digest,
+    /// `kaslr::APPLIED_*` bits (image slid, region bases randomized)
+    pub kaslr_applied: u8,
+    pub log_chain: Digest,
+    /// `None` when booted without a valid handoff block
+    pub boot_capsule: Option<Digest>,
+    pub module_count: u32,
+    pub modules_root: Digest,
+    pub selftest: PostStatus,
+    /// When the reported page-table scan ran (ns since boot)
+    pub mapcheck_scanned_ns: u64,
+    /// Leaf mappings covered by that scan
+    pub mapcheck_mappings: u64,
+    /// Policy violations found by that scan
+    pub mapcheck_violations: u32,
+    /// Scans and violations since boot
+    pub mapcheck_scans: u64,
+    pub mapcheck_violations_total: u64,
+    /// Public half of the attestation key (also covered by the signature)
+    pub signer: VaultPublicKey,
+    /// Ed25519 over `encode()`
+    pub signature: [u8; SIGNATURE_LEN],
+}
+
+impl Quote {
+    /// Canonical little-endian encoding covered by the signature
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(512);
+        out.extend_from_slice(QUOTE_DOMAIN);
+        out.extend_from_slice(&self.nonce);
+        out.extend_from_slice(&self.timestamp_ns.to_le_bytes());
+        out.extend_from_slice(&self.zerostate_epoch.to_le_bytes());
+        out.extend_from_slice(&self.vault_epoch.to_le_bytes());
+        out.extend_from_slice(&self.state_hash.encode());
+        out.extend_from_slice(&self.sandbox_root.encode());
+        out.extend_from_slice(&self.capsule_count.to_le_bytes());
+        out.extend_from_slice(&self.memory_used.to_le_bytes());
+        out.extend_from_slice(&self.memory_proof_root.encode());
+        out.extend_from_slice(&self.phys_bitmap.encode());
+        out.extend_from_slice(&self.kaslr_transcript.encode());
+        out.push(self.kaslr_applied);
+        out.extend_from_slice(&self.log_chain.encode());
+        match &self.boot_capsule {
+            Some(d) => {
+                out.push(1);
+                out.extend_from_slice(&d.encode());
+            }
+            None => out.push(0),
+        }
+        out.extend_from_slice(&self.module_count.to_le_bytes());
+        out.extend_from_slice(&self.modules_root.encode());
+        out.push(self.selftest.ran as u8);
+        out.push(self.selftest.passed as u8);
+        out.extend_from_slice(&self.selftest.failed.to_le_bytes());
+        out.extend_from_slice(&self.mapcheck_scanned_ns.to_le_bytes());
+        out.extend_from_slice(&self.mapcheck_mappings.to_le_bytes());
+        out.extend_from_slice(&self.mapcheck_violations.to_le_bytes());
+        out.extend_from_slice(&self.mapcheck_scans.to_le_bytes());
+        out.extend_from_slice(&self.mapcheck_violations_total.to_le_bytes());
+        out.extend_from_slice(self.signer.as_bytes());
+        out
+    }
+
+    /// Wire form handed to challengers: `encode() || signature`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.encode();
+        out.extend_from_slice(&self.signature);
+        out
+    }
+
+    /// Check the signature against the embedded signer key. Whether that key
+    /// is trusted is the verifier's decision.
+    pub fn verify(&self) -> bool {
+        verify_ed25519_signature(self.signer.as_bytes(), &self.encode(), &self.signature)
+    }
+}
+
+/// The attestation key handle, granted on first use
+pub fn attestation_handle() -> Result<KeyHandle, &'static str> {
+    signer::grant(
+        Principal::Kernel,
+        KeyUsage::KernelIntegrity,
+        ATTEST_LABEL,
+        SigningPolicy::new(&[QUOTE_DOMAIN], QUOTE_RATE_TOTAL, QUOTE_WINDOW),
+    )
+}
+
+/// Public attestation key, for verifiers to pin
+pub fn attestation_key() -> Option<VaultPublicKey> {
+    attestation_handle().ok().and_then(signer::public_key)
+}
+
+/// Merkle root over the loaded modules, in registry (uid) order.
+/// Leaf: `uid || exec_id || manifest hash || name`.
+pub fn module_set_root() -> (u32, [u8; 32]) {
+    let mut tree = MerkleTree::new();
+    for m in registry::list_capsules() {
+        let mut leaf = Vec::with_capacity(96 + m.name.len());
+        leaf.extend_from_slice(&m.uid);
+        leaf.extend_from_slice(&m.exec_id);
+        leaf.extend_from_slice(&m.manifest.hash);
+        leaf.extend_from_slice(m.name.as_bytes());
+        tree.append(domain::ATTEST_MODULE, &leaf);
+    }
+    (tree.len() as u32, tree.root())
+}
+
+/// Quote budget of one challenger in the current window
+struct QuoteWindow {
+    start_ns: u64,
+    used: u32,
+}
+
+static QUOTE_BUDGET: Mutex<BTreeMap<Principal, QuoteWindow>> = Mutex::new(BTreeMap::new());
+
+/// Charge one quote to `caller`; expired windows are dropped so the table
+/// only holds challengers seen in the last window
+fn charge(caller: Principal) -> Result<(), &'static str> {
+    let now = crate::arch::x86_64::time::timer::now_ns();
+    let window = QUOTE_WINDOW.as_nanos() as u64;
+    let mut budget = QUOTE_BUDGET.lock();
+    budget.retain(|_, w| now.saturating_sub(w.start_ns) < window);
+    let w = budget.entry(caller).or_insert(QuoteWindow { start_ns: now, used: 0 });
+    if w.used >= QUOTE_RATE {
+        return Err("Quote rate limit exceeded");
+    }
+    w.used += 1;
+    Ok(())
+}
+
+/// Produce a signed quote over current kernel state for `nonce`, charged to
+/// `caller`'s quote budget
+pub fn quote(caller: Principal, nonce: &[u8; NONCE_LEN]) -> Result<Quote, &'static str> {
+    charge(caller)?;
+    let handle = attestation_handle()?;
+    let signer_key = signer::public_key(handle).ok_or("Attestation key unavailable")?;
+
+    let snapshot = zerostate::snapshot_state();
+    let (module_count, modules_root) = module_set_root();
+    let log_chain = try_get_logger().map(|l| l.get_chain_hash()).unwrap_or([0; 32]);
+    let boot_capsule = crate::boot::handoff::boot_info()
+        .map(|zs| zs.capsule_digest());
+    let scans = mapcheck::stats();
+    let scan = scans.last.ok_or("No page-table scan yet")?;
+
+    let mut q = Quote {
+        nonce: *nonce,
+        timestamp_ns: crate::arch::x86_64::time::timer::now_ns(),
+        zerostate_epoch: zerostate::current_epoch(),
+        vault_epoch: vault::current_epoch(),
+        state_hash: Digest::new(HashAlgo::Blake3, snapshot.hash),
+        sandbox_root: Digest::new(HashAlgo::Blake3, snapshot.proof_root),
+        capsule_count: snapshot.capsule_count as u32,
+        memory_used: snapshot.memory_used as u64,
+        memory_proof_root: proof::root_digest(),
+        phys_bitmap: Digest::new(HashAlgo::Sha3_256, phys::bitmap_hash()),
+        kaslr_transcript: Digest::new(HashAlgo::Sha3_256, kaslr::transcript_hash()),
+        kaslr_applied: kaslr::applied(),
+        log_chain: Digest::new(HashAlgo::Blake3, log_chain),
+        boot_capsule,
+        module_count,
+        modules_root: Digest::new(HashAlgo::Blake3, modules_root),
+        selftest: selftest::status(),
+        mapcheck_scanned_ns: scan.timestamp_ns,
+        mapcheck_mappings: scan.mappings,
+        mapcheck_violations: scan.violations,
+        mapcheck_scans: scans.scans,
+        mapcheck_violations_total: scans.violations_total,
+        signer: signer_key,
+        signature: [0; SIGNATURE_LEN],
+    };
+
+    match signer::sign_as(Principal::Kernel, handle, &q.encode()) {
+        Ok(sig) => q.signature = sig,
+        Err(e) => {
+            log_warn("attest", &format!("quote not signed: {}", e));
+            return Err(e);
+        }
+    }
+    log_info("attest", &format!(
+        "quote issued | nonce={:x?} | epoch={} | modules={} | mem_root={} | wx_violations={}",
+        &nonce[..4], q.zerostate_epoch, q.module_count, q.memory_proof_root, q.mapcheck_violations
+    ));
+    Ok(q)
+}
@@ -5,6 +5,7 @@
 pub mod zerostate;
 pub mod capsule;
 pub mod isolation;
+pub mod attest;
 
 pub use zerostate::{init_zerostate, track_active_sandbox, ZeroStateConfig};
 pub use capsule::{CapsuleRuntime, CapsuleId};
@@ -5,12 +5,15 @@
 //! written to persistent storage.
 
 use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
 use core::sync::atomic::{AtomicU64, AtomicBool, Ordering};
 use spin::RwLock;
 
-use crate::crypto::hash::blake3_hash;
-use crate::memory::region::MemRegion;
+use crate::crypto::hash::{blake3_hash, domain};
+use crate::crypto::merkle::{self, MerkleTree};
+use crate::memory::region;
 use crate::modules::sandbox::SandboxContext;
+use crate::modules::teardown::{retire_instance, TerminationReason, TerminationRecord};
 
 /// Global ZeroState configuration
 #[derive(Clone, Copy)]
@@ -42,6 +45,10 @@ pub struct StateSnapshot {
     pub proof_root: [u8; 32],
 }
 
+/// Capsule memory is demand-paged, so reservations may exceed the
+/// ephemeral heap by this factor; resident pages never may
+const RESERVE_OVERCOMMIT: usize = 4;
+
 /// Active sandboxes registry
 struct SandboxRegistry {
     sandboxes: BTreeMap<[u8; 32], SandboxContext>,
@@ -101,16 +108,45 @@ pub fn track_active_sandbox(sandbox: &SandboxContext) {
     }
 }
 
-/// Remove sandbox from tracking
-pub fn untrack_sandbox(exec_id: &[u8; 32]) {
+/// Stop a tracked sandbox. Scrubbing and release go through
+/// `modules::teardown` like every other capsule exit; the supervisor's copy
+/// is taken too so the region is never freed twice.
+pub fn untrack_sandbox(exec_id: &[u8; 32]) -> Option<TerminationRecord> {
+    let meta = crate::modules::registry::find_by_exec_id(exec_id);
+    let supervised = meta
+        .as_ref()
+        .and_then(|m| crate::modules::supervisor::unsupervise(m.name));
+    let tracked = take_sandbox(exec_id);
+    let mut sandbox = supervised.or(tracked)?;
+    Some(match meta {
+        Some(meta) => sandbox.shutdown(&meta.uid),
+        None => retire_instance(&mut sandbox, TerminationReason::Shutdown),
+    })
+}
+
+/// Take ownership of a tracked sandbox without scrubbing it
+pub fn take_sandbox(exec_id: &[u8; 32]) -> Option<SandboxContext> {
     let mut reg = REGISTRY.write();
-    if let Some(registry) = reg.as_mut() {
-        if let Some(sandbox) = registry.sandboxes.remove(exec_id) {
-            registry.total_memory -= sandbox.memory.size;
-            
-            // Zero out memory before release
-            zero_memory_region(&sandbox.memory);
-        }
+    let registry = reg.as_mut()?;
+    let sandbox = registry.sandboxes.remove(exec_id)?;
+    registry.total_memory -= sandbox.memory.size;
+    Some(sandbox)
+}
+
+/// Run `f` on the tracked sandbox named `name`. The registry lock is held
+/// across `f`, so it must not tear the sandbox down or call back into
+/// ZeroState. Supervised capsules keep their live instance in the supervisor.
+pub fn with_tracked<R>(name: &str, f: impl FnOnce(&mut SandboxContext) -> R) -> Option<R> {
+    let mut reg = REGISTRY.write();
+    let sandbox = reg.as_mut()?.sandboxes.values_mut().find(|s| s.name == name)?;
+    Some(f(sandbox))
+}
+
+/// Names of every tracked sandbox
+pub fn tracked_names() -> Vec<&'static str> {
+    match REGISTRY.read().as_ref() {
+        Some(registry) => registry.sandboxes.values().map(|s| s.name).collect(),
+        None => Vec::new(),
     }
 }
 
@@ -119,12 +155,8 @@ pub fn snapshot_state() -> StateSnapshot {
     let reg = REGISTRY.read();
     let registry = reg.as_ref().expect("ZeroState not initialized");
     
-    // Compute merkle root of all active sandboxes
-    let mut hasher = blake3::Hasher::new();
-    for (exec_id, _) in &registry.sandboxes {
-        hasher.update(exec_id);
-    }
-    let proof_root = *hasher.finalize().as_bytes();
+    // Merkle root of all active sandboxes, in exec_id order
+    let proof_root = sandbox_tree(registry).root();
     
     // Compute overall state hash
     let mut state_data = Vec::new();
@@ -144,14 +176,66 @@ pub fn snapshot_state() -> StateSnapshot {
     }
 }
 
-/// Check if we're at capacity
+fn sandbox_tree(registry: &SandboxRegistry) -> MerkleTree {
+    let mut tree = MerkleTree::new();
+    for exec_id in registry.sandboxes.keys() {
+        tree.append(domain::ZEROSTATE_SANDBOX, exec_id);
+    }
+    tree
+}
+
+/// Inclusion proof of one live sandbox against `StateSnapshot::proof_root`
+#[derive(Debug, Clone)]
+pub struct SandboxProof {
+    pub exec_id: [u8; 32],
+    pub epoch: u64,
+    pub index: usize,
+    pub size: usize,
+    pub path: Vec<merkle::Hash>,
+    /// Sandbox root the path leads to (what a quote carries as `sandbox_root`)
+    pub root: merkle::Hash,
+}
+
+impl SandboxProof {
+    pub fn verify(&self) -> bool {
+        let leaf = merkle::leaf_hash(domain::ZEROSTATE_SANDBOX, &self.exec_id);
+        merkle::verify_inclusion(&leaf, self.index, self.size, &self.path, &self.root)
+    }
+}
+
+/// Prove a live sandbox; path and root come from one registry read
+pub fn sandbox_inclusion_proof(exec_id: &[u8; 32]) -> Option<SandboxProof> {
+    let reg = REGISTRY.read();
+    let registry = reg.as_ref()?;
+    let index = registry.sandboxes.keys().position(|id| id == exec_id)?;
+    let tree = sandbox_tree(registry);
+    let path = tree.inclusion_proof(index, tree.len())?;
+    Some(SandboxProof {
+        exec_id: *exec_id,
+        epoch: EPOCH.load(Ordering::Relaxed),
+        index,
+        size: tree.len(),
+        path,
+        root: tree.root(),
+    })
+}
+
+/// Check if we're at capacity (always false in crypto restricted mode or
+/// under critical memory pressure)
 pub fn can_admit_capsule(memory_required: usize) -> bool {
+    if crate::crypto::selftest::restricted_mode() {
+        return false;
+    }
+    if crate::memory::pressure::level() >= crate::memory::pressure::PressureLevel::Critical {
+        return false;
+    }
     let reg = REGISTRY.read();
     if let Some(registry) = reg.as_ref() {
         let config = CONFIG.read();
         
         registry.sandboxes.len() < config.max_capsules &&
-        registry.total_memory + memory_required <= config.ephemeral_heap_size
+        region::resident_bytes() < config.ephemeral_heap_size &&
+        registry.total_memory + memory_required <= config.ephemeral_heap_size * RESERVE_OVERCOMMIT
     } else {
         false
     }
@@ -167,17 +251,22 @@ pub fn rotate_epoch() {
     let new_epoch = generate_epoch();
     EPOCH.store(new_epoch, Ordering::SeqCst);
     
-    // Clear all sandboxes on epoch rotation
-    let mut reg = REGISTRY.write();
-    if let Some(registry) = reg.as_mut() {
-        for (_, sandbox) in registry.sandboxes.drain() {
-            zero_memory_region(&sandbox.memory);
-        }
-        registry.total_memory = 0;
+    // Clear all sandboxes on epoch rotation (teardown takes the registry
+    // lock itself, so collect first)
+    let live: Vec<[u8; 32]> = match REGISTRY.read().as_ref() {
+        Some(registry) => registry.sandboxes.keys().copied().collect(),
+        None => Vec::new(),
+    };
+    for exec_id in &live {
+        let _ = untrack_sandbox(exec_id);
+    }
+    if let Some(registry) = REGISTRY.write().as_mut() {
         registry.snapshot_counter = 0;
     }
+    // Epoch-scoped keys of the torn-down capsules go with them
+    let key_epoch = crate::crypto::vault::rotate_epoch();
     
-    log::warn!("[ZEROSTATE] Epoch rotated to {}, all state cleared", new_epoch);
+    log::warn!("[ZEROSTATE] Epoch rotated to {} (key epoch {}), all state cleared", new_epoch, key_epoch);
 }
 
 // ===== Private Helpers =====
@@ -187,10 +276,11 @@ fn init_ephemeral_pool() {
     let config = CONFIG.read();
     let pages = config.ephemeral_heap_size / 4096;
     
-    // This would allocate from phys and map to a dedicated VA range
-    // For now, we track virtually
+    // Sandboxes are demand-paged; the pool is the cap on frames they may
+    // hold resident, not a preallocated range
+    region::set_resident_limit(config.ephemeral_heap_size);
     
-    log::info!("[ZEROSTATE] Ephemeral pool: {} MB", config.ephemeral_heap_size / (1024 * 1024));
+    log::info!("[ZEROSTATE] Ephemeral pool: {} MB resident ({} pages)", config.ephemeral_heap_size / (1024 * 1024), pages);
 }
 
 fn start_attestation_service() {
@@ -208,10 +298,7 @@ fn start_attestation_service() {
             
             // Sleep until next checkpoint
             let interval_ms = CONFIG.read().checkpoint_interval_ms;
-            crate::arch::x86_64::time::timer::sleep_long_ns(
-                interval_ms * 1_000_000,
-                || {}
-            );
+            crate::arch::x86_64::time::timer::sleep_ns(interval_ms * 1_000_000);
         }
     }
     
@@ -238,16 +325,6 @@ fn generate_attestation(sandbox: &SandboxContext) {
     // Could publish to network or store in ring buffer
 }
 
-fn zero_memory_region(region: &MemRegion) {
-    unsafe {
-        core::ptr::write_bytes(
-            region.start.as_ptr() as *mut u8,
-            0,
-            region.size_bytes() as usize
-        );
-    }
-}
-
 fn generate_epoch() -> u64 {
     // Combine boot time with entropy
     let boot_ns = crate::arch::x86_64::time::timer::now_ns();
@@ -264,7 +341,10 @@ fn current_time() -> u64 {
 pub struct ZeroStateStats {
     pub epoch: u64,
     pub active_capsules: usize,
+    /// Bytes reserved by live sandboxes
     pub memory_used: usize,
+    /// Bytes actually backed by frames (shared code counted once)
+    pub memory_resident: usize,
     pub memory_available: usize,
     pub snapshots_taken: u64,
 }
@@ -278,7 +358,8 @@ pub fn get_stats() -> ZeroStateStats {
             epoch: current_epoch(),
             active_capsules: registry.sandboxes.len(),
             memory_used: registry.total_memory,
-            memory_available: config.ephemeral_heap_size - registry.total_memory,
+            memory_resident: region::resident_bytes(),
+            memory_available: config.ephemeral_heap_size.saturating_sub(region::resident_bytes()),
             snapshots_taken: registry.snapshot_counter,
         }
     } else {
@@ -286,6 +367,7 @@ pub fn get_stats() -> ZeroStateStats {
             epoch: 0,
             active_capsules: 0,
             memory_used: 0,
+            memory_resident: 0,
             memory_available: 0,
             snapshots_taken: 0,
         }
@@ -109,7 +109,7 @@ fn boot_canary_salt() -> u64 {
     *BOOT_CANARY_SALT.call_once(|| {
         // Derive from boot nonce using a small PRF so canaries differ per boot (public).
         let mut out = [0u8; 8];
-        crate::memory::kaslr::derive_subkey(b"STACK-CANARY", b"kernel", &mut out, &kaslr::init(Default::default()).transcript_hash);
+        crate::memory::kaslr::derive_subkey(b"STACK-CANARY", b"kernel", &mut out, &kaslr::transcript_hash());
         u64::from_le_bytes(out)
     })
 }
@@ -145,11 +145,17 @@ unsafe fn alloc_stack(pages: usize) -> Stack {
 
     let top = stack_va + (pages as u64) * PAGE_SIZE as u64;
     // Leave `guard_va` unmapped (faults on underflow)
+    crate::memory::mapcheck::track_stack(
+        Some(x86_64::VirtAddr::new(guard_va)),
+        x86_64::VirtAddr::new(stack_va),
+        pages,
+    );
 
     Stack { base: stack_va, top, pages }
 }
 
 unsafe fn free_stack(stk: &Stack) {
+    crate::memory::mapcheck::untrack(x86_64::VirtAddr::new(stk.base));
     for i in 0..stk.pages {
         let va = stk.base + (i as u64) * PAGE_SIZE as u64;
         if let Some(pa) = virt::unmap4k(x86_64::VirtAddr::new(va)) {
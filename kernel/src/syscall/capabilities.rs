@@ -79,6 +79,11 @@ pub fn verify_capability(required: Capability) -> bool {
     }
 }
 
+/// Module the current syscall context runs on behalf of
+pub fn current_owner() -> Option<&'static str> {
+    unsafe { CURRENT_TOKEN.as_ref().map(|tok| tok.owner_module) }
+}
+
 /// Returns full printable capability trace for diagnostics
 pub fn debug_token() -> String {
     unsafe {
@@ -6,8 +6,10 @@
 //! with zero-trust policies defined in `capabilities.rs`.
 
 pub mod capabilities;
+pub mod uaccess;
 
 use crate::syscall::capabilities::{Capability, verify_capability};
+use crate::syscall::uaccess::UserCaller;
 use crate::log::logger::try_get_logger;
 
 /// System call operation codes
@@ -21,8 +23,35 @@ pub enum Syscall {
     ReadEntropy = 0x05,
     IPCSend = 0x06,
     IPCReceive = 0x07,
+    VaultSign = 0x08,
+    VaultPublicKey = 0x09,
+    AttestQuote = 0x0A,
 }
 
+/// `VaultSign` argument block (arg1 points here; arg0 is the key handle)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VaultSignRequest {
+    pub msg_ptr: u64,
+    pub msg_len: u64,
+    /// Receives the 64-byte signature
+    pub sig_out: u64,
+}
+
+/// `AttestQuote` argument block (arg0 points here)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AttestQuoteRequest {
+    /// 32-byte verifier nonce
+    pub nonce_ptr: u64,
+    /// Receives the quote in wire form (`Quote::to_bytes`)
+    pub out_ptr: u64,
+    pub out_len: u64,
+}
+
+/// Largest message `VaultSign` accepts
+const VAULT_SIGN_MAX: usize = 4096;
+
 impl Syscall {
     pub fn from_raw(val: u64) -> Option<Self> {
         match val {
@@ -33,6 +62,9 @@ impl Syscall {
             0x05 => Some(Syscall::ReadEntropy),
             0x06 => Some(Syscall::IPCSend),
             0x07 => Some(Syscall::IPCReceive),
+            0x08 => Some(Syscall::VaultSign),
+            0x09 => Some(Syscall::VaultPublicKey),
+            0x0A => Some(Syscall::AttestQuote),
             _ => None,
         }
     }
@@ -81,12 +113,67 @@ pub fn handle_syscall(syscall_id: u64, arg0: u64, arg1: u64) -> u64 {
                 0
             })
         },
+        // Vault calls are gated by the signer's own capability check, the
+        // same one the `kernel.vault` IPC endpoint applies
+        Some(Syscall::VaultSign) => {
+            vault_sign(arg0, arg1).unwrap_or_else(deny)
+        },
+        Some(Syscall::VaultPublicKey) => {
+            vault_public_key(arg0, arg1).unwrap_or_else(deny)
+        },
+        Some(Syscall::AttestQuote) => {
+            enforce(Capability::Crypto, || attest_quote(arg0).unwrap_or_else(deny))
+        },
         None => {
             deny("Unknown syscall")
         },
     }
 }
 
+/// Sign a caller buffer under a granted vault key handle; only the
+/// signature is written back
+fn vault_sign(handle: u64, request: u64) -> Result<u64, &'static str> {
+    let caller = UserCaller::current()?;
+    crate::crypto::signer::authorize(&caller.token)?;
+    let req: VaultSignRequest = caller.read_struct(request)?;
+    if req.msg_len > VAULT_SIGN_MAX as u64 {
+        return Err("Vault sign message too long");
+    }
+    let message = caller.read_bytes(req.msg_ptr, req.msg_len)?;
+
+    let sig = crate::crypto::signer::sign(&caller.token, crate::crypto::signer::KeyHandle(handle as u32), &message)?;
+    caller.copy_to_user(req.sig_out, &sig)?;
+    Ok(0)
+}
+
+/// Write the 32-byte public key of a vault handle to `out`
+fn vault_public_key(handle: u64, out: u64) -> Result<u64, &'static str> {
+    let caller = UserCaller::current()?;
+    crate::crypto::signer::authorize(&caller.token)?;
+    let pk = crate::crypto::signer::public_key(crate::crypto::signer::KeyHandle(handle as u32))
+        .ok_or("Unknown key handle")?;
+    caller.copy_to_user(out, pk.as_bytes())?;
+    Ok(0)
+}
+
+/// Answer a verifier challenge with a signed quote; returns the number of
+/// bytes written
+fn attest_quote(request: u64) -> Result<u64, &'static str> {
+    let caller = UserCaller::current()?;
+    let req: AttestQuoteRequest = caller.read_struct(request)?;
+    let mut nonce = [0u8; crate::runtime::attest::NONCE_LEN];
+    caller.copy_from_user(&mut nonce, req.nonce_ptr)?;
+
+    let principal = crate::crypto::signer::Principal::Capsule(caller.token.owner_module);
+    let quote = crate::runtime::attest::quote(principal, &nonce)?;
+    let wire = quote.to_bytes();
+    if req.out_len < wire.len() as u64 {
+        return Err("Attest output buffer too small");
+    }
+    caller.copy_to_user(req.out_ptr, &wire)?;
+    Ok(wire.len() as u64)
+}
+
 /// Enforces a capability before executing syscall body
 fn enforce<F: FnOnce() -> u64>(required: Capability, op: F) -> u64 {
     if verify_capability(required) {
@@ -0,0 +1,76 @@
+//! NØNOS Syscall User Memory Access
+//!
+//! Capsules run against kernel virtual addresses, so a pointer handed to a
+//! syscall is only trusted once it lies wholly inside the calling capsule's
+//! memory perimeter (its region plus grants). Every syscall argument block
+//! and buffer goes through `UserCaller`; nothing dereferences a raw user
+//! pointer directly.
+
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::capabilities::CapabilityToken;
+use crate::syscall::capabilities::current_owner;
+
+/// The capsule behind the current syscall, resolved once per call
+pub struct UserCaller {
+    /// Admission token of the serving instance
+    pub token: CapabilityToken,
+    perimeter: Vec<(usize, usize)>,
+}
+
+impl UserCaller {
+    /// Resolve the calling capsule from the syscall context: supervised,
+    /// mid-upgrade, or tracked by ZeroState (policies other than `Restart`)
+    pub fn current() -> Result<Self, &'static str> {
+        let owner = current_owner().ok_or("No caller identity")?;
+        let (token, perimeter) = crate::modules::supervisor::caller_perimeter(owner)
+            .ok_or("Caller is not a running capsule")?;
+        if token.owner_module != owner {
+            return Err("Caller token mismatch");
+        }
+        Ok(Self { token, perimeter })
+    }
+
+    /// Validate `[addr, addr+len)` against the caller's perimeter
+    fn check(&self, addr: u64, len: u64) -> Result<usize, &'static str> {
+        if addr == 0 {
+            return Err("Null user pointer");
+        }
+        let end = addr.checked_add(len).ok_or("User range overflows")?;
+        let (addr, end) = (addr as usize, end as usize);
+        if self.perimeter.iter().any(|&(base, size)| addr >= base && end <= base + size) {
+            Ok(addr)
+        } else {
+            Err("User range outside caller memory")
+        }
+    }
+
+    /// Copy `dst.len()` bytes in from user address `src`
+    pub fn copy_from_user(&self, dst: &mut [u8], src: u64) -> Result<(), &'static str> {
+        let src = self.check(src, dst.len() as u64)?;
+        unsafe { core::ptr::copy_nonoverlapping(src as *const u8, dst.as_mut_ptr(), dst.len()) };
+        Ok(())
+    }
+
+    /// Copy `len` bytes in from user address `src` into a kernel buffer
+    pub fn read_bytes(&self, src: u64, len: u64) -> Result<Vec<u8>, &'static str> {
+        let src = self.check(src, len)?;
+        let mut buf = alloc::vec![0u8; len as usize];
+        unsafe { core::ptr::copy_nonoverlapping(src as *const u8, buf.as_mut_ptr(), buf.len()) };
+        Ok(buf)
+    }
+
+    /// Copy a `#[repr(C)]` argument block in from user address `src`
+    pub fn read_struct<T: Copy>(&self, src: u64) -> Result<T, &'static str> {
+        let src = self.check(src, mem::size_of::<T>() as u64)?;
+        Ok(unsafe { core::ptr::read_unaligned(src as *const T) })
+    }
+
+    /// Copy `src` out to user address `dst`
+    pub fn copy_to_user(&self, dst: u64, src: &[u8]) -> Result<(), &'static str> {
+        let dst = self.check(dst, src.len() as u64)?;
+        unsafe { core::ptr::copy_nonoverlapping(src.as_ptr(), dst as *mut u8, src.len()) };
+        Ok(())
+    }
+}
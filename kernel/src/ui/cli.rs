@@ -4,7 +4,7 @@
 // - Static registry: open-addressed table, lock-free reads
 // - Dual I/O: TUI + GUI bridge mirror; remote stdin preferred
 // - History + TAB completion; public suggest hook for TUI
-// - JSON telemetry frames to GUI (metrics, proof roots)
+// - JSON telemetry frames to GUI (metrics, proof roots/exports, attestation quotes)
 // - Event bus integration
 // - No heap on the hot path; fixed buffers
 //
@@ -25,6 +25,7 @@ use crate::sched::runqueue as rq;
 use crate::arch::x86_64::time::timer;
 use crate::arch::x86_64::interrupt::{apic, ioapic};
 use crate::memory::{self, proof};
+use crate::runtime::attest;
 
 const PROMPT: &str = "nonos# ";
 const MAX_LINE: usize = 256;
@@ -122,6 +123,11 @@ pub fn spawn() {
     reg_insert("sys.apic",             "show LAPIC id",                    cmd_sys_apic);
     reg_insert("sys.ioapic.route",     "route GSI: <gsi>",                 cmd_sys_ioapic_route);
 
+    // mem.*
+    reg_insert("mem.pressure",         "zone pressure + OOM: [reclaim]",   cmd_mem_pressure);
+    reg_insert("mem.wx",               "page-table W^X/guard scan: [last]", cmd_mem_wx);
+    reg_insert("mem.heap",             "heap counters + 2 MiB page usage", cmd_mem_heap);
+
     // rq.*
     reg_insert("rq.stats",             "runqueue counts",                  cmd_rq_stats);
 
@@ -133,6 +139,15 @@ pub fn spawn() {
 
     // proof.*
     reg_insert("proof.snapshot",       "emit proof root (GUI/event)",      cmd_proof_snapshot);
+    reg_insert("proof.export",         "export snapshot + batches (GUI)",  cmd_proof_export);
+    reg_insert("proof.prove",          "event inclusion proof: <cpu> <seq>", cmd_proof_prove);
+
+    // attest.*
+    reg_insert("attest.quote",         "signed state quote: [nonce-hex]",  cmd_attest_quote);
+
+    // zs.*
+    reg_insert("zs.rotate",            "new ZeroState + key epoch (stops all capsules)", cmd_zs_rotate);
+    reg_insert("zs.prove",             "sandbox inclusion proof: <capsule|exec-id-hex>", cmd_zs_prove);
 
     // net.*
     reg_insert("net.send.proof",       "publish proof root to mesh",       cmd_net_send_proof);
@@ -227,6 +242,57 @@ fn cmd_sys_ioapic_route(a: &[&str]) -> Result<(), &'static str> {
     Ok(())
 }
 
+fn cmd_mem_pressure(a: &[&str]) -> Result<(), &'static str> {
+    use memory::pressure;
+    if a.get(1).copied() == Some("reclaim") {
+        println(&format!("reclaimed {} pages", pressure::reclaim()));
+    }
+    let level = pressure::evaluate();
+    let st = pressure::stats();
+    let wm = pressure::watermarks();
+    println(&format!("pressure {:?} (low<{}‰ min<{}‰)", level, wm.low_permille, wm.min_permille));
+    for (kind, zs) in memory::phys::zone_stats().iter() {
+        println(&format!("  {:?}: {:?} free={}/{} reserved={}", kind, st.zones[kind.index()], zs.frames_free, zs.frames_total, zs.frames_reserved));
+    }
+    println(&format!("reclaimed={} alloc_failures={} oom_kills={}",
+        st.reclaimed_pages, st.alloc_failures, st.oom_kills));
+    Ok(())
+}
+
+fn cmd_mem_wx(a: &[&str]) -> Result<(), &'static str> {
+    use memory::mapcheck;
+    let report = if a.get(1).copied() == Some("last") {
+        mapcheck::stats().last.ok_or("no scan yet")?
+    } else {
+        mapcheck::scan(true).map_err(|_| "page tables not initialized")?
+    };
+    let st = mapcheck::stats();
+    println(&format!("wx scan: {} mappings, {} tracked, {} violations",
+        report.mappings, report.tracked, report.violations));
+    if !report.clean() {
+        let k = report.by_kind;
+        println(&format!("  rwx={} user_kernel_half={} user_exec={} exec_stack={} guard_mapped={}",
+            k[0], k[1], k[2], k[3], k[4]));
+        if let Some(v) = report.first {
+            println(&format!("  first: {:?} at {:#x} ({:?})", v.kind, v.va, v.flags));
+        }
+    }
+    println(&format!("scans={} violations_total={} untracked={}",
+        st.scans, st.violations_total, mapcheck::untracked()));
+    Ok(())
+}
+
+fn cmd_mem_heap(_a: &[&str]) -> Result<(), &'static str> {
+    let st = memory::alloc::stats();
+    let (quarantined, faults) = memory::alloc::hardening_stats();
+    println(&format!("small alloc={} free={}  large alloc={} free={}",
+        st.alloc_small, st.free_small, st.alloc_large, st.free_large));
+    println(&format!("huge live={} ({} MiB) heap_maps={} fallbacks={} splits={}",
+        st.huge_live, st.huge_live * 2, st.huge_maps, st.huge_fallbacks, st.huge_splits));
+    println(&format!("quarantined={} faults={}", quarantined, faults));
+    Ok(())
+}
+
 fn cmd_rq_stats(_a: &[&str]) -> Result<(), &'static str> {
     let c = rq::stats_counts();
     println(&format!("rq rt={} hi={} norm={} low={} idle={}", c[0], c[1], c[2], c[3], c[4]));
@@ -265,6 +331,89 @@ fn cmd_proof_snapshot(_a: &[&str]) -> Result<(), &'static str> {
     Ok(())
 }
 
+fn cmd_proof_export(_a: &[&str]) -> Result<(), &'static str> {
+    // Buffers are sized up front: nothing may allocate while the audit log is locked
+    let mut hdr = proof::SnapshotHeader::default();
+    proof::snapshot(&mut [], &mut hdr);
+    let cpus = hdr.cpu_count as usize;
+    let rec = proof::memaudit::BATCH_HDR_LEN + proof::BATCH_SIZE * proof::memaudit::EVENT_ENCODED_LEN;
+    let mut snap = alloc::vec![0u8; proof::memaudit::SNAPSHOT_HDR_LEN + cpus * 32];
+    let mut batches = alloc::vec![0u8; rec * proof::EXPORT_DEPTH * cpus];
+    let mut lens = alloc::vec![0usize; cpus];
+
+    // Snapshot and batches in one critical section, so every CPU's chain
+    // of exported batches ends at its snapshot root
+    let n = proof::export_all(&mut snap, &mut batches, &mut lens);
+    if n == 0 { return Err("proof snapshot export failed"); }
+    gui_json_proof_export("proof_snapshot", None, &snap[..n]);
+    let mut off = 0;
+    for (cpu, &len) in lens.iter().enumerate() {
+        if len > 0 { gui_json_proof_export("proof_batches", Some(cpu), &batches[off..off + len]); }
+        off += len;
+    }
+    println(&format!("exported snapshot ({} cpus) and {} batch bytes; {} batches lost",
+        cpus, off, proof::dropped_batches_total()));
+    Ok(())
+}
+
+fn cmd_proof_prove(a: &[&str]) -> Result<(), &'static str> {
+    let cpu = a.get(1).and_then(|x| x.parse::<usize>().ok()).ok_or("usage: proof.prove <cpu> <seq>")?;
+    let seq = a.get(2).and_then(|x| x.parse::<u64>().ok()).ok_or("usage: proof.prove <cpu> <seq>")?;
+    let (p, cpu_root) = proof::inclusion_proof(cpu, seq).ok_or("event not in a retained batch")?;
+    println(&format!("event seq {} kind {:#04x} leaf {}/{} batch {} (+{} later) ok={}",
+        p.event.seq, p.event.kind, p.leaf_index, p.batch.count, p.batch.index, p.link_count, p.verify(&cpu_root)));
+    println(&format!("cpu root {:02x?}", &cpu_root));
+    Ok(())
+}
+
+fn cmd_attest_quote(a: &[&str]) -> Result<(), &'static str> {
+    let mut nonce = [0u8; attest::NONCE_LEN];
+    match a.get(1) {
+        Some(h) => {
+            let h = h.trim_start_matches("0x");
+            if h.len() != nonce.len() * 2 { return Err("nonce must be 32 bytes hex"); }
+            for (i, b) in nonce.iter_mut().enumerate() {
+                *b = u8::from_str_radix(&h[i * 2..i * 2 + 2], 16).map_err(|_| "bad nonce hex")?;
+            }
+        }
+        None => crate::crypto::entropy::fill_bytes(&mut nonce),
+    }
+    let q = attest::quote(crate::crypto::signer::Principal::Kernel, &nonce)?;
+    println(&format!("quote epoch {} modules {} mem_root {} sig {:02x?}",
+        q.zerostate_epoch, q.module_count, q.memory_proof_root, &q.signature[..4]));
+    gui_json_quote(&q);
+    Ok(())
+}
+
+fn cmd_zs_rotate(_a: &[&str]) -> Result<(), &'static str> {
+    crate::runtime::zerostate::rotate_epoch();
+    println(&format!("zerostate epoch {:#x}, key epoch {}",
+        crate::runtime::zerostate::current_epoch(), crate::crypto::vault::current_epoch()));
+    Ok(())
+}
+
+fn cmd_zs_prove(a: &[&str]) -> Result<(), &'static str> {
+    let arg = a.get(1).ok_or("usage: zs.prove <capsule|exec-id-hex>")?;
+    let exec_id = match crate::modules::registry::find_by_name(arg) {
+        Some(meta) => meta.exec_id,
+        None => {
+            let h = arg.trim_start_matches("0x");
+            let mut id = [0u8; 32];
+            if h.len() != id.len() * 2 { return Err("no such capsule; exec id must be 32 bytes hex"); }
+            for (i, b) in id.iter_mut().enumerate() {
+                *b = u8::from_str_radix(&h[i * 2..i * 2 + 2], 16).map_err(|_| "bad exec id hex")?;
+            }
+            id
+        }
+    };
+    let p = crate::runtime::zerostate::sandbox_inclusion_proof(&exec_id).ok_or("sandbox not live")?;
+    println(&format!("sandbox leaf {}/{} epoch {:#x} path {} ok={}",
+        p.index, p.size, p.epoch, p.path.len(), p.verify()));
+    println(&format!("sandbox_root {:02x?}", &p.root));
+    gui_json_sandbox_proof(&p);
+    Ok(())
+}
+
 fn cmd_net_send_proof(_a: &[&str]) -> Result<(), &'static str> {
     let mut roots = [[0u8; 32]; 1];
     let mut hdr = proof::SnapshotHeader::default();
@@ -313,6 +462,61 @@ fn gui_json_proof(root: &[u8; 32], epoch: u64) {
     gui_bridge::send_json(&s);
 }
 
+fn gui_json_quote(q: &attest::Quote) {
+    fn hex(s: &mut alloc::string::String, bytes: &[u8]) {
+        for b in bytes { let _ = write!(s, "{:02x}", b); }
+    }
+    let mut s = alloc::string::String::with_capacity(2048);
+    let _ = write!(s, "{{\"type\":\"quote\",\"nonce\":\"");
+    hex(&mut s, &q.nonce);
+    let _ = write!(s, "\",\"ts\":{},\"epoch\":{},\"vault_epoch\":{},\"state\":\"{}\",\"sandboxes\":\"{}\",\"capsules\":{},\"mem_used\":{},\"mem_root\":\"{}\",\"phys_bitmap\":\"{}\",\"kaslr\":\"{}\",\"kaslr_applied\":{},\"log_chain\":\"{}\",",
+        q.timestamp_ns, q.zerostate_epoch, q.vault_epoch, q.state_hash, q.sandbox_root,
+        q.capsule_count, q.memory_used, q.memory_proof_root, q.phys_bitmap, q.kaslr_transcript, q.kaslr_applied, q.log_chain);
+    match &q.boot_capsule {
+        Some(d) => { let _ = write!(s, "\"boot_capsule\":\"{}\",", d); }
+        None => { let _ = write!(s, "\"boot_capsule\":null,"); }
+    }
+    let _ = write!(s, "\"modules\":{},\"modules_root\":\"{}\",\"selftest\":{{\"ran\":{},\"passed\":{},\"failed\":{}}},",
+        q.module_count, q.modules_root, q.selftest.ran, q.selftest.passed, q.selftest.failed);
+    let _ = write!(s, "\"mapcheck\":{{\"scanned_ns\":{},\"mappings\":{},\"violations\":{},\"scans\":{},\"violations_total\":{}}},\"signer\":\"",
+        q.mapcheck_scanned_ns, q.mapcheck_mappings, q.mapcheck_violations, q.mapcheck_scans, q.mapcheck_violations_total);
+    hex(&mut s, q.signer.as_bytes());
+    let _ = write!(s, "\",\"signature\":\"");
+    hex(&mut s, &q.signature);
+    let _ = write!(s, "\",\"wire\":\"");
+    hex(&mut s, &q.to_bytes());
+    let _ = write!(s, "\"}}");
+    gui_bridge::send_json(&s);
+}
+
+fn gui_json_sandbox_proof(p: &crate::runtime::zerostate::SandboxProof) {
+    fn hex(s: &mut alloc::string::String, bytes: &[u8]) {
+        for b in bytes { let _ = write!(s, "{:02x}", b); }
+    }
+    let mut s = alloc::string::String::with_capacity(256 + p.path.len() * 68);
+    let _ = write!(s, "{{\"type\":\"sandbox_proof\",\"exec_id\":\"");
+    hex(&mut s, &p.exec_id);
+    let _ = write!(s, "\",\"epoch\":{},\"index\":{},\"size\":{},\"root\":\"{}\",\"path\":[",
+        p.epoch, p.index, p.size, crate::crypto::hash::Digest::new(crate::crypto::hash::HashAlgo::Blake3, p.root));
+    for (i, h) in p.path.iter().enumerate() {
+        let _ = write!(s, "{}\"", if i == 0 { "" } else { "," });
+        hex(&mut s, h);
+        let _ = write!(s, "\"");
+    }
+    let _ = write!(s, "]}}");
+    gui_bridge::send_json(&s);
+}
+
+fn gui_json_proof_export(kind: &str, cpu: Option<usize>, data: &[u8]) {
+    let mut s = alloc::string::String::with_capacity(64 + data.len() * 2);
+    let _ = write!(s, "{{\"type\":\"{}\"", kind);
+    if let Some(cpu) = cpu { let _ = write!(s, ",\"cpu\":{}", cpu); }
+    let _ = write!(s, ",\"data\":\"");
+    for b in data { let _ = write!(s, "{:02x}", b); }
+    let _ = write!(s, "\"}}");
+    gui_bridge::send_json(&s);
+}
+
 // —————————————————— TAB completion hook (called by TUI) ——————————————————
 
 #[no_mangle]
@@ -23,6 +23,16 @@ pub enum Event {
     ProofRoot { root: [u8;32], epoch: u64 },
     SchedPick { tid: u64, prio: u8 },
     Log { lvl: u8, code: u32 },
+    /// Crypto power-on self-test outcome (`failed` = mask of `selftest::Kat`)
+    SelfTest { passed: bool, failed: u32 },
+    /// Zone pressure level change (`zone` = `phys::ZoneKind` index,
+    /// `level` = `memory::pressure::PressureLevel`)
+    MemPressure { zone: u8, level: u8, free_frames: u64 },
+    /// OOM policy killed a capsule (`importance` from its manifest)
+    OomKill { exec_id: [u8;32], importance: u8, pages: u64 },
+    /// Page-table scanner found a mapping that breaks policy
+    /// (`kind` = `memory::mapcheck::ViolationKind`, `flags` = effective `VmFlags`)
+    MapViolation { va: u64, kind: u8, flags: u64 },
 }
 
 struct Ring<const N: usize> {
@@ -1,41 +1,182 @@
-#![no_std]
+//! bootinfo.rs — NØNOS handoff extensions: physical memory map (ABI v1).
+//!
+//! Shared verbatim by the bootloader (`boot/src/memmap.rs`) and the kernel
+//! (`boot::handoff`), each including it with `#[path]`, so both sides agree
+//! byte-for-byte on the records that follow the 128-byte handoff header.
+//!
+//! Extension chain (little-endian, packed), starting `hdr_size` bytes after
+//! the `ZeroStateBootInfo` header and present only with `BootModeFlags::MEMMAP`:
+//!
+//!   0x00  u32 magic   = "ZSXT"
+//!   0x04  u16 kind    (ZS_EXT_MEMMAP)
+//!   0x06  u16 version (MEMMAP_VERSION)
+//!   0x08  u32 len     header + payload, bytes
+//!   0x0C  u16 count   number of records
+//!   0x0E  u16 flags   (MEMMAP_TRUNCATED, MEMMAP_SORTED)
+//!   0x10  MemoryRegion[count]   24 bytes each
+//!
+//! The kernel rejects an extension whose `kind`, `version` or `len` does not
+//! match; a newer record layout gets a new `version`, never a reinterpretation.
+//!
+//! no_std, alloc-free, no dependencies.
 
-/// Passed from bootloader ➞ kernel (always repr(C) ⇒ stable ABI).
-#[repr(C)]
-pub struct BootInfo<'a> {
-    /// Physical offset where the kernel *thinks* phys 0 lives in its virt space.
-    pub phys_memory_offset: u64,
+#![allow(dead_code)]
 
-    /// Slice covering the UEFI memory map copied into kernel space.
-    pub memory_map: &'a [MemoryRegion],
+use core::mem;
 
-    /// Root System-Description Pointer (ACPI 2+). None if firmware omitted it.
-    pub rsdp_addr: Option<u64>,
+/// Extension header magic "ZSXT" in LE
+pub const ZS_EXT_MAGIC: u32 = 0x5458_535A;
+/// Extension kind: physical memory map
+pub const ZS_EXT_MEMMAP: u16 = 1;
+/// Record layout version of the memory map extension
+pub const MEMMAP_VERSION: u16 = 1;
+/// Upper bound on records; firmware maps are a few hundred entries at most
+pub const MEMMAP_MAX_ENTRIES: usize = 512;
 
-    /// Raw command-line string given to the bootloader (e.g. from GRUB chainload).
-    pub cmdline: &'a str,
+/// The bootloader ran out of staging room and dropped trailing records
+pub const MEMMAP_TRUNCATED: u16 = 1 << 0;
+/// Records are sorted by `start` and do not overlap
+pub const MEMMAP_SORTED: u16 = 1 << 1;
 
-    /// Address of kernel ELF in physical memory (for kexec/debugging).
-    pub kernel_load_start: u64,
-    pub kernel_load_size:  u64,
+/// Region is backed by non-volatile memory (EFI_MEMORY_NV)
+pub const ATTR_NV: u32 = 1 << 0;
+/// Firmware runtime services need the region mapped (EFI_MEMORY_RUNTIME)
+pub const ATTR_RUNTIME: u32 = 1 << 1;
+/// Region supports write-back caching (EFI_MEMORY_WB)
+pub const ATTR_WB: u32 = 1 << 2;
+/// BootLoaded region that held firmware boot services code/data: free once
+/// the kernel runs on its own page tables and stack, never before
+pub const ATTR_BOOT_SERVICES: u32 = 1 << 3;
+
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct ZsExtHeader {
+    pub magic: u32,
+    pub kind: u16,
+    pub version: u16,
+    pub len: u32,
+    pub count: u16,
+    pub flags: u16,
 }
 
-/// Mirror of uefi::table::boot::MemoryDescriptor but firmware-agnostic.
-#[repr(C)]
-#[derive(Clone, Copy)]
+/// Firmware-agnostic memory map record
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
 pub struct MemoryRegion {
     pub start: u64,
-    pub len:   u64,
-    pub ty:    MemoryRegionType,
+    pub len: u64,
+    pub ty: u32,
+    pub attr: u32,
 }
 
+const _: () = {
+    assert!(mem::size_of::<ZsExtHeader>() == 16);
+    assert!(mem::size_of::<MemoryRegion>() == 24);
+};
+
 #[repr(u32)]
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MemoryRegionType {
     Usable          = 1,
     Reserved        = 2,
     AcpiReclaimable = 3,
     AcpiNvs         = 4,
     Mmio            = 5,
+    /// Bootloader code/data, the kernel capsule, this handoff itself and
+    /// (with ATTR_BOOT_SERVICES) firmware boot services memory
+    BootLoaded      = 6,
     BadMemory       = 0xFFFF_FFFF,
 }
+
+impl MemoryRegionType {
+    /// Unknown codes are treated as reserved, never as usable
+    pub fn from_raw(ty: u32) -> Self {
+        match ty {
+            1 => Self::Usable,
+            3 => Self::AcpiReclaimable,
+            4 => Self::AcpiNvs,
+            5 => Self::Mmio,
+            6 => Self::BootLoaded,
+            0xFFFF_FFFF => Self::BadMemory,
+            _ => Self::Reserved,
+        }
+    }
+
+    /// Classify a raw `EFI_MEMORY_TYPE`. Loader memory holds the kernel.
+    /// Boot services memory outlives `ExitBootServices` for us: the kernel
+    /// starts on the firmware's stack and page tables, so it is BootLoaded
+    /// too (tagged by `attr_for_efi`) until the kernel releases it.
+    pub fn from_efi(ty: u32) -> Self {
+        match ty {
+            1 | 2 => Self::BootLoaded,        // LoaderCode, LoaderData
+            3 | 4 => Self::BootLoaded,        // BootServicesCode/Data
+            7 => Self::Usable,                // Conventional
+            8 => Self::BadMemory,             // Unusable
+            9 => Self::AcpiReclaimable,
+            10 => Self::AcpiNvs,
+            11 | 12 => Self::Mmio,            // MemoryMappedIO, MemoryMappedIOPortSpace
+            _ => Self::Reserved,              // Reserved, RuntimeServices*, PalCode, Persistent
+        }
+    }
+}
+
+/// Map `EFI_MEMORY_*` attribute bits onto the `ATTR_*` subset we carry
+pub fn attr_from_efi(attr: u64) -> u32 {
+    let mut out = 0;
+    if attr & 0x8000 != 0 { out |= ATTR_NV; }
+    if attr & (1 << 63) != 0 { out |= ATTR_RUNTIME; }
+    if attr & 0x8 != 0 { out |= ATTR_WB; }
+    out
+}
+
+/// `ATTR_*` bits for a descriptor of `EFI_MEMORY_TYPE` `ty` with `EFI_MEMORY_*`
+/// attributes `attr`: `attr_from_efi` plus the boot services tag
+pub fn attr_for_efi(ty: u32, attr: u64) -> u32 {
+    let bs = if matches!(ty, 3 | 4) { ATTR_BOOT_SERVICES } else { 0 };
+    attr_from_efi(attr) | bs
+}
+
+impl MemoryRegion {
+    pub fn kind(&self) -> MemoryRegionType {
+        MemoryRegionType::from_raw(self.ty)
+    }
+
+    pub fn end(&self) -> u64 {
+        self.start.saturating_add(self.len)
+    }
+
+    /// Firmware boot services memory the kernel may release (ATTR_BOOT_SERVICES)
+    pub fn is_boot_services(&self) -> bool {
+        let attr = self.attr;
+        self.kind() == MemoryRegionType::BootLoaded && attr & ATTR_BOOT_SERVICES != 0
+    }
+}
+
+/// Bytes an extension of `count` records occupies
+pub const fn memmap_len(count: usize) -> usize {
+    mem::size_of::<ZsExtHeader>() + count * mem::size_of::<MemoryRegion>()
+}
+
+impl ZsExtHeader {
+    pub const fn memmap(count: u16, flags: u16) -> Self {
+        Self {
+            magic: ZS_EXT_MAGIC,
+            kind: ZS_EXT_MEMMAP,
+            version: MEMMAP_VERSION,
+            len: memmap_len(count as usize) as u32,
+            count,
+            flags,
+        }
+    }
+
+    /// Header invariants for a memory map extension of this ABI version
+    pub fn is_memmap(&self) -> bool {
+        let (magic, kind, version, len, count) =
+            (self.magic, self.kind, self.version, self.len, self.count);
+        magic == ZS_EXT_MAGIC
+            && kind == ZS_EXT_MEMMAP
+            && version == MEMMAP_VERSION
+            && (count as usize) <= MEMMAP_MAX_ENTRIES
+            && len as usize == memmap_len(count as usize)
+    }
+}
@@ -0,0 +1,150 @@
+//! digest.rs — NØNOS hash agility: algorithm ids, domain tags, canonical capsule identity.
+//!
+//! Shared verbatim by the kernel, the UEFI bootloader and `nonosctl`
+//! (each includes it with `#[path]`), so all three agree byte-for-byte on:
+//! - which algorithm produced a digest (`HashAlgo`, a one-byte wire id)
+//! - the domain-separation tag for each kind of commitment (`domain`)
+//! - the one canonical capsule identity (`capsule_identity`)
+//!
+//! no_std; needs only `blake3`. Other algorithms are computed by each crate's
+//! own hash layer and wrapped in `Digest` with their id.
+
+#![allow(dead_code)]
+
+use core::fmt;
+
+/// Hash algorithm identifiers. Wire values never change; new algorithms get new ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum HashAlgo {
+    Blake3 = 0x01,
+    Sha3_256 = 0x02,
+    Sha256 = 0x03,
+    Keccak256 = 0x04,
+}
+
+impl HashAlgo {
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0x01 => Some(HashAlgo::Blake3),
+            0x02 => Some(HashAlgo::Sha3_256),
+            0x03 => Some(HashAlgo::Sha256),
+            0x04 => Some(HashAlgo::Keccak256),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Lower-case name used in the text form (`blake3:<hex>`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgo::Blake3 => "blake3",
+            HashAlgo::Sha3_256 => "sha3-256",
+            HashAlgo::Sha256 => "sha256",
+            HashAlgo::Keccak256 => "keccak256",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "blake3" => Some(HashAlgo::Blake3),
+            "sha3-256" => Some(HashAlgo::Sha3_256),
+            "sha256" => Some(HashAlgo::Sha256),
+            "keccak256" => Some(HashAlgo::Keccak256),
+            _ => None,
+        }
+    }
+}
+
+/// Domain-separation tags. BLAKE3 uses them as `derive_key` contexts; the
+/// SHA families absorb `len(tag) as u8 || tag` before the data.
+pub mod domain {
+    pub const CAPSULE_COMMITMENT: &str = "NONOS:CAPSULE:COMMITMENT:v1";
+    pub const PROOF_EVENT: &str = "NONOS:PROOF:EVENT:v1";
+    pub const PROOF_ROOT: &str = "NONOS:PROOF:ROOT:v1";
+    pub const PROOF_BATCH: &str = "NONOS:PROOF:BATCH:v1";
+    pub const LOG_CHAIN: &str = "NONOS:LOG:CHAIN:v1";
+    pub const ZK_PROGRAM: &str = "NONOS:ZK:PROGRAM:v1";
+    pub const ZKVM_CAPSULE: &str = "NONOS:ZKVM:CAPSULE:v1";
+    /// Module manifest signatures (code hash + signed metadata)
+    pub const MODULE_MANIFEST: &str = "NONOS:MODULE:MANIFEST:v1";
+    /// Merkle leaf domains (`merkle::leaf_hash`)
+    pub const ZEROSTATE_SANDBOX: &str = "NONOS:ZEROSTATE:SANDBOX:v1";
+    pub const BEACON_STATE: &str = "NONOS:BEACON:STATE:v1";
+    pub const ATTEST_MODULE: &str = "NONOS:ATTEST:MODULE:v1";
+}
+
+/// Algorithm-tagged 256-bit digest.
+/// Binary form: `algo id || 32 bytes`; text form: `<name>:<hex>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Digest {
+    pub algo: HashAlgo,
+    pub bytes: [u8; 32],
+}
+
+pub const DIGEST_ENCODED_LEN: usize = 33;
+
+impl Digest {
+    pub const fn new(algo: HashAlgo, bytes: [u8; 32]) -> Self {
+        Self { algo, bytes }
+    }
+
+    pub fn encode(&self) -> [u8; DIGEST_ENCODED_LEN] {
+        let mut out = [0u8; DIGEST_ENCODED_LEN];
+        out[0] = self.algo.id();
+        out[1..].copy_from_slice(&self.bytes);
+        out
+    }
+
+    pub fn decode(raw: &[u8]) -> Option<Self> {
+        if raw.len() != DIGEST_ENCODED_LEN {
+            return None;
+        }
+        let algo = HashAlgo::from_id(raw[0])?;
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&raw[1..]);
+        Some(Self { algo, bytes })
+    }
+
+    /// Parse `<name>:<hex>`
+    pub fn parse(text: &str) -> Option<Self> {
+        let (name, hex) = text.split_once(':')?;
+        let algo = HashAlgo::from_name(name)?;
+        if hex.len() != 64 {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+        }
+        Some(Self { algo, bytes })
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.algo.name())?;
+        for b in self.bytes.iter() {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Domain-separated BLAKE3
+pub fn blake3_tagged(domain: &str, data: &[u8]) -> Digest {
+    let mut h = blake3::Hasher::new_derive_key(domain);
+    h.update(data);
+    Digest::new(HashAlgo::Blake3, *h.finalize().as_bytes())
+}
+
+/// The canonical capsule identity: domain-separated BLAKE3 over the payload
+/// bytes. The bootloader computes it and commits to it in
+/// `ZeroStateBootInfo.capsule_hash`, the kernel carries that commitment in
+/// attestation quotes (`boot_capsule`) and `nonosctl capsule verify` prints it.
+pub fn capsule_identity(payload: &[u8]) -> Digest {
+    blake3_tagged(domain::CAPSULE_COMMITMENT, payload)
+}
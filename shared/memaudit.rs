@@ -0,0 +1,477 @@
+//! memaudit.rs — NØNOS memory audit log: event encoding, commitments, export format.
+//!
+//! Shared verbatim by the kernel (`memory::proof`) and `nonosctl memaudit`
+//! (each includes it with `#[path]`, next to `digest.rs` and `merkle.rs`), so
+//! an auditor recomputes exactly what the kernel committed to.
+//!
+//! Commitment structure:
+//! - leaf:        `merkle::leaf_hash(PROOF_EVENT, event.encode())`
+//! - batch root:  Merkle root over the leaves of one folded batch
+//! - CPU root:    hash chain over batches,
+//!   `blake3_tagged(PROOF_BATCH, prev || index || count || batch_root)`
+//! - global root: `blake3_tagged(PROOF_ROOT, schema || boot_nonce || epoch || n || cpu roots)`
+//!
+//! Export stream (little-endian): a `SnapshotHeader` record followed by its
+//! CPU roots, and any number of batch records (`BatchHeader` + encoded
+//! events). Each batch carries the CPU root before and after it, so a
+//! contiguous run of batches verifies on its own and links to the snapshot.
+//!
+//! no_std and alloc-free; needs `blake3` plus the sibling `digest` and
+//! `merkle` modules in scope as `super::digest` / `super::merkle`.
+
+#![allow(dead_code)]
+
+use super::digest::{blake3_tagged, domain};
+use super::merkle::{self, Hash};
+
+pub const SCHEMA_VERSION: u32 = 2;
+/// Largest batch the kernel folds at once
+pub const MAX_BATCH: usize = 32;
+/// Depth of an in-batch audit path (log2 MAX_BATCH)
+pub const MAX_PATH: usize = 5;
+/// Later batches an `EventProof` can chain through to reach its CPU root
+pub const MAX_LINKS: usize = 8;
+
+pub const EVENT_ENCODED_LEN: usize = 76;
+pub const BATCH_MAGIC: [u8; 4] = *b"NMEB";
+pub const BATCH_HDR_LEN: usize = 4 + 4 + 4 + 4 + 8 + 32 + 32;
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"NMES";
+pub const SNAPSHOT_HDR_LEN: usize = 4 + 4 + 8 + 8 + 4 + 8 + 32;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Map4K        = 0x01,
+    Unmap4K      = 0x02,
+    Map2M        = 0x03,
+    Unmap2M      = 0x04,
+    PhysAlloc    = 0x10,
+    PhysFree     = 0x11,
+    Protect4K    = 0x20,
+    ProtectRange = 0x21,
+    /// Mapping policy violation found by the kernel's page-table scanner
+    /// (`flags` = violation code, `len` = page size)
+    Violation    = 0x30,
+}
+
+impl Kind {
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0x01 => Some(Kind::Map4K),
+            0x02 => Some(Kind::Unmap4K),
+            0x03 => Some(Kind::Map2M),
+            0x04 => Some(Kind::Unmap2M),
+            0x10 => Some(Kind::PhysAlloc),
+            0x11 => Some(Kind::PhysFree),
+            0x20 => Some(Kind::Protect4K),
+            0x21 => Some(Kind::ProtectRange),
+            0x30 => Some(Kind::Violation),
+            _ => None,
+        }
+    }
+}
+
+/// One memory state transition (canonical, versioned, zk-friendly)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Event {
+    pub ver:    u32,     // schema version
+    pub kind:   u8,      // Kind as u8
+    pub _pad:   [u8;3],  // reserved/padding (domain sep)
+    pub cpu:    u32,     // cpu id (apic id or logical id)
+    pub seq:    u64,     // per-cpu monotonic
+    pub tsc:    u64,     // rdtsc snapshot (best-effort)
+    pub epoch:  u64,     // logical epoch if you rotate keys/roots
+    pub vaddr:  u64,     // 0 for phys-only
+    pub paddr:  u64,     // physical base (if applicable)
+    pub len:    u64,     // byte length (page or range)
+    pub flags:  u64,     // mapping/arch flags (virt) or alloc flags (phys)
+    pub captag: u32,     // capability tags
+    pub _rsvd:  u32,     // reserved
+}
+
+impl Event {
+    pub const ZERO: Event = Event {
+        ver: 0, kind: 0, _pad: [0; 3], cpu: 0, seq: 0, tsc: 0, epoch: 0,
+        vaddr: 0, paddr: 0, len: 0, flags: 0, captag: 0, _rsvd: 0,
+    };
+
+    pub fn encode(&self) -> [u8; EVENT_ENCODED_LEN] {
+        let mut w = Writer::<EVENT_ENCODED_LEN>::new();
+        w.u32(self.ver);
+        w.bytes(&[self.kind]);
+        w.bytes(&self._pad);
+        w.u32(self.cpu);
+        w.u64(self.seq);
+        w.u64(self.tsc);
+        w.u64(self.epoch);
+        w.u64(self.vaddr);
+        w.u64(self.paddr);
+        w.u64(self.len);
+        w.u64(self.flags);
+        w.u32(self.captag);
+        w.u32(self._rsvd);
+        w.buf
+    }
+
+    pub fn decode(raw: &[u8]) -> Option<Self> {
+        if raw.len() != EVENT_ENCODED_LEN {
+            return None;
+        }
+        let mut r = Reader::new(raw);
+        Some(Event {
+            ver: r.u32(),
+            kind: r.u8(),
+            _pad: [r.u8(), r.u8(), r.u8()],
+            cpu: r.u32(),
+            seq: r.u64(),
+            tsc: r.u64(),
+            epoch: r.u64(),
+            vaddr: r.u64(),
+            paddr: r.u64(),
+            len: r.u64(),
+            flags: r.u64(),
+            captag: r.u32(),
+            _rsvd: r.u32(),
+        })
+    }
+
+    pub fn leaf(&self) -> Hash {
+        merkle::leaf_hash(domain::PROOF_EVENT, &self.encode())
+    }
+}
+
+/// Merkle root over one batch of events (at most `MAX_BATCH`)
+pub fn batch_root(events: &[Event]) -> Option<Hash> {
+    if events.is_empty() || events.len() > MAX_BATCH {
+        return None;
+    }
+    let mut leaves = [[0u8; 32]; MAX_BATCH];
+    for (leaf, ev) in leaves.iter_mut().zip(events) {
+        *leaf = ev.leaf();
+    }
+    Some(merkle::root_from_leaves(&leaves[..events.len()]))
+}
+
+/// Advance a CPU root by one batch
+pub fn chain(prev: &Hash, index: u64, count: u32, batch_root: &Hash) -> Hash {
+    let mut data = [0u8; 32 + 8 + 4 + 32];
+    data[..32].copy_from_slice(prev);
+    data[32..40].copy_from_slice(&index.to_le_bytes());
+    data[40..44].copy_from_slice(&count.to_le_bytes());
+    data[44..].copy_from_slice(batch_root);
+    blake3_tagged(domain::PROOF_BATCH, &data).bytes
+}
+
+/// Global commitment over all CPU roots (iterator, so the kernel can feed
+/// them straight from its CPU table)
+pub fn global_root<'a, I>(boot_nonce: u64, epoch: u64, cpu_roots: I) -> Hash
+where
+    I: ExactSizeIterator<Item = &'a Hash>,
+{
+    let mut h = blake3::Hasher::new_derive_key(domain::PROOF_ROOT);
+    h.update(&SCHEMA_VERSION.to_le_bytes());
+    h.update(&boot_nonce.to_le_bytes());
+    h.update(&epoch.to_le_bytes());
+    h.update(&(cpu_roots.len() as u32).to_le_bytes());
+    for root in cpu_roots {
+        h.update(root);
+    }
+    *h.finalize().as_bytes()
+}
+
+/* -------------------- export records -------------------- */
+
+/// Header of one exported batch; followed by `count` encoded events
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchHeader {
+    pub cpu: u32,
+    pub count: u32,
+    pub index: u64,
+    pub root_before: Hash,
+    pub root_after: Hash,
+}
+
+impl BatchHeader {
+    pub fn encode(&self) -> [u8; BATCH_HDR_LEN] {
+        let mut w = Writer::<BATCH_HDR_LEN>::new();
+        w.bytes(&BATCH_MAGIC);
+        w.u32(SCHEMA_VERSION);
+        w.u32(self.cpu);
+        w.u32(self.count);
+        w.u64(self.index);
+        w.bytes(&self.root_before);
+        w.bytes(&self.root_after);
+        w.buf
+    }
+
+    pub fn decode(raw: &[u8]) -> Option<Self> {
+        if raw.len() < BATCH_HDR_LEN || raw[..4] != BATCH_MAGIC {
+            return None;
+        }
+        let mut r = Reader::new(&raw[4..]);
+        if r.u32() != SCHEMA_VERSION {
+            return None;
+        }
+        Some(BatchHeader {
+            cpu: r.u32(),
+            count: r.u32(),
+            index: r.u64(),
+            root_before: r.hash(),
+            root_after: r.hash(),
+        })
+    }
+
+    /// Encoded size of the whole record
+    pub fn record_len(&self) -> usize {
+        BATCH_HDR_LEN + self.count as usize * EVENT_ENCODED_LEN
+    }
+
+    /// Recompute `root_after` from `root_before` and the batch's events
+    pub fn verify(&self, events: &[Event]) -> bool {
+        if events.len() != self.count as usize || events.iter().any(|e| e.cpu != self.cpu) {
+            return false;
+        }
+        match batch_root(events) {
+            Some(root) => chain(&self.root_before, self.index, self.count, &root) == self.root_after,
+            None => false,
+        }
+    }
+}
+
+/// Snapshot of the global commitment; followed by `cpu_count` CPU roots
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    pub schema: u32,
+    pub epoch:  u64,
+    pub boot_nonce: u64,
+    pub cpu_count: u32,
+    pub drop_total: u64,
+    pub root: [u8; 32], // global commitment at snapshot time
+}
+
+impl SnapshotHeader {
+    pub fn encode(&self) -> [u8; SNAPSHOT_HDR_LEN] {
+        let mut w = Writer::<SNAPSHOT_HDR_LEN>::new();
+        w.bytes(&SNAPSHOT_MAGIC);
+        w.u32(self.schema);
+        w.u64(self.epoch);
+        w.u64(self.boot_nonce);
+        w.u32(self.cpu_count);
+        w.u64(self.drop_total);
+        w.bytes(&self.root);
+        w.buf
+    }
+
+    pub fn decode(raw: &[u8]) -> Option<Self> {
+        if raw.len() < SNAPSHOT_HDR_LEN || raw[..4] != SNAPSHOT_MAGIC {
+            return None;
+        }
+        let mut r = Reader::new(&raw[4..]);
+        Some(SnapshotHeader {
+            schema: r.u32(),
+            epoch: r.u64(),
+            boot_nonce: r.u64(),
+            cpu_count: r.u32(),
+            drop_total: r.u64(),
+            root: r.hash(),
+        })
+    }
+
+    /// Check `root` against the CPU roots that follow the header
+    pub fn verify(&self, cpu_roots: &[Hash]) -> bool {
+        self.schema == SCHEMA_VERSION
+            && cpu_roots.len() == self.cpu_count as usize
+            && global_root(self.boot_nonce, self.epoch, cpu_roots.iter()) == self.root
+    }
+}
+
+/* -------------------- inclusion proofs -------------------- */
+
+/// One later batch of the same CPU, reduced to what `chain` needs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChainLink {
+    pub count: u32,
+    pub batch_root: Hash,
+}
+
+/// Proof that `event` is leaf `leaf_index` of batch `batch` on its CPU, and
+/// that the batch is committed by the CPU root: `links` carry the batches
+/// folded after it, so `batch.root_after` chains forward to that root.
+#[derive(Clone, Copy, Debug)]
+pub struct EventProof {
+    pub event: Event,
+    pub leaf_index: u32,
+    pub batch: BatchHeader,
+    pub path_len: u8,
+    pub path: [Hash; MAX_PATH],
+    pub link_count: u8,
+    pub links: [ChainLink; MAX_LINKS],
+}
+
+impl EventProof {
+    /// Build from a batch's events (kernel side, or host side from an export).
+    /// Add the batches that follow it with `push_link`, oldest first.
+    pub fn build(batch: &BatchHeader, events: &[Event], leaf_index: usize) -> Option<Self> {
+        if leaf_index >= events.len() || events.len() > MAX_BATCH {
+            return None;
+        }
+        let n = events.len();
+        let mut proof = EventProof {
+            event: events[leaf_index],
+            leaf_index: leaf_index as u32,
+            batch: *batch,
+            path_len: 0,
+            path: [[0u8; 32]; MAX_PATH],
+            link_count: 0,
+            links: [ChainLink::default(); MAX_LINKS],
+        };
+        let mut leaves = [[0u8; 32]; MAX_BATCH];
+        for (leaf, ev) in leaves.iter_mut().zip(events) {
+            *leaf = ev.leaf();
+        }
+        // RFC 9162 PATH over at most MAX_BATCH leaves, without allocation
+        let (mut lo, mut hi, m) = (0usize, n, leaf_index);
+        let mut rev = [[0u8; 32]; MAX_PATH];
+        let mut depth = 0;
+        while hi - lo > 1 {
+            let mut k = 1;
+            while k << 1 < hi - lo {
+                k <<= 1;
+            }
+            if m - lo < k {
+                rev[depth] = merkle::root_from_leaves(&leaves[lo + k..hi]);
+                hi = lo + k;
+            } else {
+                rev[depth] = merkle::root_from_leaves(&leaves[lo..lo + k]);
+                lo += k;
+            }
+            depth += 1;
+        }
+        // PATH lists siblings bottom-up
+        for i in 0..depth {
+            proof.path[i] = rev[depth - 1 - i];
+        }
+        proof.path_len = depth as u8;
+        Some(proof)
+    }
+
+    /// Append the next batch of the same CPU. False if it does not directly
+    /// follow the last one, or the link table is full.
+    pub fn push_link(&mut self, batch: &BatchHeader, events: &[Event]) -> bool {
+        let n = self.link_count as usize;
+        if n == MAX_LINKS || batch.cpu != self.batch.cpu || batch.index != self.batch.index + 1 + n as u64 {
+            return false;
+        }
+        let Some(batch_root) = batch_root(events) else { return false };
+        self.links[n] = ChainLink { count: batch.count, batch_root };
+        self.link_count += 1;
+        true
+    }
+
+    /// Recompute the batch root from the event and path, chain it into
+    /// `root_after`, then through `links` to the CPU root it implies
+    pub fn cpu_root(&self) -> Option<Hash> {
+        let path = &self.path[..(self.path_len as usize).min(MAX_PATH)];
+        let n = self.batch.count as usize;
+        if n == 0 || n > MAX_BATCH || self.event.cpu != self.batch.cpu {
+            return None;
+        }
+        // The batch root is not exported on its own: fold the path into the
+        // root it implies and check that it chains to `root_after`
+        let broot = fold_path(&self.event.leaf(), self.leaf_index as usize, n, path)?;
+        if chain(&self.batch.root_before, self.batch.index, self.batch.count, &broot) != self.batch.root_after {
+            return None;
+        }
+        let links = &self.links[..(self.link_count as usize).min(MAX_LINKS)];
+        let mut root = self.batch.root_after;
+        for (i, link) in links.iter().enumerate() {
+            root = chain(&root, self.batch.index + 1 + i as u64, link.count, &link.batch_root);
+        }
+        Some(root)
+    }
+
+    /// Check the proof against this CPU's root from a `SnapshotHeader`
+    /// (itself checked with `SnapshotHeader::verify`)
+    pub fn verify(&self, snapshot_cpu_root: &Hash) -> bool {
+        self.cpu_root() == Some(*snapshot_cpu_root)
+    }
+}
+
+/// Fold an audit path into the root it implies (RFC 9162 §2.1.3.2 without the final compare)
+fn fold_path(leaf: &Hash, index: usize, size: usize, path: &[Hash]) -> Option<Hash> {
+    if index >= size {
+        return None;
+    }
+    let (mut fnode, mut snode) = (index, size - 1);
+    let mut acc = *leaf;
+    for p in path {
+        if snode == 0 {
+            return None;
+        }
+        if fnode & 1 == 1 || fnode == snode {
+            acc = merkle::node_hash(p, &acc);
+            while fnode & 1 == 0 && fnode != 0 {
+                fnode >>= 1;
+                snode >>= 1;
+            }
+        } else {
+            acc = merkle::node_hash(&acc, p);
+        }
+        fnode >>= 1;
+        snode >>= 1;
+    }
+    if snode == 0 { Some(acc) } else { None }
+}
+
+/* -------------------- LE helpers -------------------- */
+
+struct Writer<const N: usize> {
+    buf: [u8; N],
+    pos: usize,
+}
+
+impl<const N: usize> Writer<N> {
+    fn new() -> Self {
+        Self { buf: [0; N], pos: 0 }
+    }
+    fn bytes(&mut self, b: &[u8]) {
+        self.buf[self.pos..self.pos + b.len()].copy_from_slice(b);
+        self.pos += b.len();
+    }
+    fn u32(&mut self, v: u32) {
+        self.bytes(&v.to_le_bytes());
+    }
+    fn u64(&mut self, v: u64) {
+        self.bytes(&v.to_le_bytes());
+    }
+}
+
+struct Reader<'a> {
+    raw: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(raw: &'a [u8]) -> Self {
+        Self { raw, pos: 0 }
+    }
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let mut out = [0u8; N];
+        out.copy_from_slice(&self.raw[self.pos..self.pos + N]);
+        self.pos += N;
+        out
+    }
+    fn u8(&mut self) -> u8 {
+        self.take::<1>()[0]
+    }
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+    fn hash(&mut self) -> Hash {
+        self.take()
+    }
+}
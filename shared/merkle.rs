@@ -0,0 +1,423 @@
+//! merkle.rs — NØNOS Merkle trees: commitments, inclusion and consistency proofs.
+//!
+//! Shared verbatim by the kernel (`crypto::merkle`) and `nonosctl` (each
+//! includes it with `#[path]`), so a proof produced on one side checks on
+//! the other. Tree shape follows RFC 9162 (Certificate Transparency v2): the
+//! left subtree is the largest power of two smaller than the tree size, so
+//! append-only logs get consistency proofs between any two sizes.
+//!
+//! Hashing is BLAKE3 in derive-key mode:
+//! - leaf: `derive_key(LEAF)(len(domain) as u8 || domain || data)` — every
+//!   leaf carries its caller's domain tag, so leaves of different structures
+//!   never collide with each other or with interior nodes;
+//! - node: `derive_key(NODE)(left || right)`;
+//! - empty tree: `derive_key(EMPTY)("")`.
+//!
+//! Verification is alloc-free; building trees and proofs needs `alloc`.
+
+#![allow(dead_code)]
+
+use alloc::vec::Vec;
+
+pub type Hash = [u8; 32];
+
+const TAG_LEAF: &str = "NONOS:MERKLE:LEAF:v1";
+const TAG_NODE: &str = "NONOS:MERKLE:NODE:v1";
+const TAG_EMPTY: &str = "NONOS:MERKLE:EMPTY:v1";
+
+/// Domain-separated leaf hash. `domain` is at most 255 bytes.
+pub fn leaf_hash(domain: &str, data: &[u8]) -> Hash {
+    let tag = domain.as_bytes();
+    let tag = &tag[..tag.len().min(255)];
+    let mut h = blake3::Hasher::new_derive_key(TAG_LEAF);
+    h.update(&[tag.len() as u8]);
+    h.update(tag);
+    h.update(data);
+    *h.finalize().as_bytes()
+}
+
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut h = blake3::Hasher::new_derive_key(TAG_NODE);
+    h.update(left);
+    h.update(right);
+    *h.finalize().as_bytes()
+}
+
+pub fn empty_root() -> Hash {
+    *blake3::Hasher::new_derive_key(TAG_EMPTY).finalize().as_bytes()
+}
+
+/// Largest power of two strictly below `n` (n >= 2)
+#[inline]
+fn split_point(n: usize) -> usize {
+    let mut k = 1;
+    while k << 1 < n {
+        k <<= 1;
+    }
+    k
+}
+
+/// Root over already-hashed leaves, without allocating (recursion depth log n)
+pub fn root_from_leaves(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => empty_root(),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&root_from_leaves(&leaves[..k]), &root_from_leaves(&leaves[k..]))
+        }
+    }
+}
+
+/// Append-only Merkle tree with O(log n) incremental root.
+///
+/// Keeps every leaf hash (needed for proofs) plus the frontier: the roots of
+/// the perfect subtrees that make up the current size, largest first.
+#[derive(Debug, Clone, Default)]
+pub struct MerkleTree {
+    leaves: Vec<Hash>,
+    frontier: Vec<(Hash, u32)>,
+}
+
+impl MerkleTree {
+    pub fn new() -> Self {
+        Self { leaves: Vec::new(), frontier: Vec::new() }
+    }
+
+    pub fn from_leaves(leaves: &[Hash]) -> Self {
+        let mut tree = Self::new();
+        for leaf in leaves {
+            tree.append_hash(*leaf);
+        }
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn leaf(&self, index: usize) -> Option<&Hash> {
+        self.leaves.get(index)
+    }
+
+    /// Hash `data` under `domain` and append it; returns the leaf index
+    pub fn append(&mut self, domain: &str, data: &[u8]) -> usize {
+        self.append_hash(leaf_hash(domain, data))
+    }
+
+    /// Append an already-computed leaf hash; returns the leaf index
+    pub fn append_hash(&mut self, leaf: Hash) -> usize {
+        self.leaves.push(leaf);
+        let mut node = (leaf, 0u32);
+        while let Some(&(left, height)) = self.frontier.last() {
+            if height != node.1 {
+                break;
+            }
+            self.frontier.pop();
+            node = (node_hash(&left, &node.0), height + 1);
+        }
+        self.frontier.push(node);
+        self.leaves.len() - 1
+    }
+
+    /// Root of the current tree
+    pub fn root(&self) -> Hash {
+        let mut iter = self.frontier.iter().rev();
+        let mut acc = match iter.next() {
+            Some(&(h, _)) => h,
+            None => return empty_root(),
+        };
+        for (left, _) in iter {
+            acc = node_hash(left, &acc);
+        }
+        acc
+    }
+
+    /// Root of the tree as it was at `size` leaves
+    pub fn root_at(&self, size: usize) -> Option<Hash> {
+        if size > self.leaves.len() {
+            return None;
+        }
+        Some(root_from_leaves(&self.leaves[..size]))
+    }
+
+    /// Audit path for leaf `index` in the tree of `size` leaves (RFC 9162 §2.1.3.1)
+    pub fn inclusion_proof(&self, index: usize, size: usize) -> Option<Vec<Hash>> {
+        if index >= size || size > self.leaves.len() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        self.path(index, 0, size, &mut proof);
+        Some(proof)
+    }
+
+    fn path(&self, index: usize, lo: usize, hi: usize, out: &mut Vec<Hash>) {
+        let n = hi - lo;
+        if n <= 1 {
+            return;
+        }
+        let k = split_point(n);
+        if index - lo < k {
+            self.path(index, lo, lo + k, out);
+            out.push(root_from_leaves(&self.leaves[lo + k..hi]));
+        } else {
+            self.path(index, lo + k, hi, out);
+            out.push(root_from_leaves(&self.leaves[lo..lo + k]));
+        }
+    }
+
+    /// Proof that the tree at `old_size` is a prefix of the tree at `new_size`
+    /// (RFC 9162 §2.1.4.1)
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Option<Vec<Hash>> {
+        if old_size > new_size || new_size > self.leaves.len() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        if old_size > 0 && old_size < new_size {
+            self.subproof(old_size, 0, new_size, true, &mut proof);
+        }
+        Some(proof)
+    }
+
+    fn subproof(&self, m: usize, lo: usize, hi: usize, complete: bool, out: &mut Vec<Hash>) {
+        let n = hi - lo;
+        if m == n {
+            if !complete {
+                out.push(root_from_leaves(&self.leaves[lo..hi]));
+            }
+            return;
+        }
+        let k = split_point(n);
+        if m <= k {
+            self.subproof(m, lo, lo + k, complete, out);
+            out.push(root_from_leaves(&self.leaves[lo + k..hi]));
+        } else {
+            self.subproof(m - k, lo + k, hi, false, out);
+            out.push(root_from_leaves(&self.leaves[lo..lo + k]));
+        }
+    }
+}
+
+/// Check that `leaf` sits at `index` in the tree of `size` leaves with `root`
+/// (RFC 9162 §2.1.3.2)
+pub fn verify_inclusion(leaf: &Hash, index: usize, size: usize, proof: &[Hash], root: &Hash) -> bool {
+    if index >= size {
+        return false;
+    }
+    let (mut fnode, mut snode) = (index, size - 1);
+    let mut acc = *leaf;
+    for p in proof {
+        if snode == 0 {
+            return false;
+        }
+        if fnode & 1 == 1 || fnode == snode {
+            acc = node_hash(p, &acc);
+            while fnode & 1 == 0 && fnode != 0 {
+                fnode >>= 1;
+                snode >>= 1;
+            }
+        } else {
+            acc = node_hash(&acc, p);
+        }
+        fnode >>= 1;
+        snode >>= 1;
+    }
+    snode == 0 && acc == *root
+}
+
+/// Check that the tree of `old_size` with `old_root` is a prefix of the tree
+/// of `new_size` with `new_root` (RFC 9162 §2.1.4.2)
+pub fn verify_consistency(
+    old_size: usize,
+    new_size: usize,
+    old_root: &Hash,
+    new_root: &Hash,
+    proof: &[Hash],
+) -> bool {
+    if old_size > new_size {
+        return false;
+    }
+    if old_size == new_size {
+        return proof.is_empty() && old_root == new_root;
+    }
+    if old_size == 0 {
+        return proof.is_empty();
+    }
+
+    // A power-of-two old tree is a complete left subtree; its root is implied
+    let (seed, rest) = if old_size.is_power_of_two() {
+        (*old_root, proof)
+    } else {
+        match proof.split_first() {
+            Some((first, rest)) => (*first, rest),
+            None => return false,
+        }
+    };
+
+    let (mut fnode, mut snode) = (old_size - 1, new_size - 1);
+    while fnode & 1 == 1 {
+        fnode >>= 1;
+        snode >>= 1;
+    }
+
+    let (mut fr, mut sr) = (seed, seed);
+    for c in rest {
+        if snode == 0 {
+            return false;
+        }
+        if fnode & 1 == 1 || fnode == snode {
+            fr = node_hash(c, &fr);
+            sr = node_hash(c, &sr);
+            while fnode & 1 == 0 && fnode != 0 {
+                fnode >>= 1;
+                snode >>= 1;
+            }
+        } else {
+            sr = node_hash(&sr, c);
+        }
+        fnode >>= 1;
+        snode >>= 1;
+    }
+    snode == 0 && fr == *old_root && sr == *new_root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const DOMAIN: &str = "NONOS:MERKLE:TEST:v1";
+
+    /// The seven inputs of the RFC 9162 §2.1.5 example tree (RFC 6962 test data)
+    const INPUTS: [&[u8]; 7] = [
+        b"",
+        &[0x00],
+        &[0x10],
+        &[0x20, 0x21],
+        &[0x30, 0x31],
+        &[0x40, 0x41, 0x42, 0x43],
+        &[0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57],
+    ];
+
+    /// Nodes named as in RFC 9162 §2.1.5:
+    ///
+    /// ```text
+    ///             hash
+    ///            /    \
+    ///           k      l
+    ///          / \    / \
+    ///         g   h  i   j
+    ///        / \ / \ / \ |
+    ///        a b c d e f d6
+    /// ```
+    struct Example {
+        leaf: [Hash; 7],
+        g: Hash,
+        h: Hash,
+        i: Hash,
+        k: Hash,
+        l: Hash,
+        root: Hash,
+    }
+
+    fn example() -> (MerkleTree, Example) {
+        let mut tree = MerkleTree::new();
+        let mut leaf = [[0u8; 32]; 7];
+        for (n, data) in INPUTS.iter().enumerate() {
+            assert_eq!(tree.append(DOMAIN, data), n);
+            leaf[n] = leaf_hash(DOMAIN, data);
+        }
+        let g = node_hash(&leaf[0], &leaf[1]);
+        let h = node_hash(&leaf[2], &leaf[3]);
+        let i = node_hash(&leaf[4], &leaf[5]);
+        let k = node_hash(&g, &h);
+        let l = node_hash(&i, &leaf[6]);
+        let root = node_hash(&k, &l);
+        (tree, Example { leaf, g, h, i, k, l, root })
+    }
+
+    #[test]
+    fn example_tree_roots() {
+        let (tree, x) = example();
+        assert_eq!(tree.root(), x.root);
+        assert_eq!(root_from_leaves(&x.leaf), x.root);
+        assert_eq!(tree.root_at(0), Some(empty_root()));
+        assert_eq!(tree.root_at(1), Some(x.leaf[0]));
+        assert_eq!(tree.root_at(3), Some(node_hash(&x.g, &x.leaf[2])));
+        assert_eq!(tree.root_at(4), Some(x.k));
+        assert_eq!(tree.root_at(6), Some(node_hash(&x.k, &x.i)));
+        assert_eq!(tree.root_at(8), None);
+    }
+
+    #[test]
+    fn example_inclusion_proofs() {
+        let (tree, x) = example();
+        let (a, b, c, f, j) = (x.leaf[0], x.leaf[1], x.leaf[2], x.leaf[5], x.leaf[6]);
+        let cases = [
+            (0, vec![b, x.h, x.l]),
+            (3, vec![c, x.g, x.l]),
+            (4, vec![f, j, x.k]),
+            (6, vec![x.i, x.k]),
+        ];
+        for (index, expected) in cases {
+            let proof = tree.inclusion_proof(index, 7).unwrap();
+            assert_eq!(proof, expected, "PATH({}, D[7])", index);
+            assert!(verify_inclusion(&x.leaf[index], index, 7, &proof, &x.root));
+        }
+        // PATH(0, D[1]) is empty and the root is the leaf itself
+        assert_eq!(tree.inclusion_proof(0, 1).unwrap(), Vec::<Hash>::new());
+        assert!(verify_inclusion(&a, 0, 1, &[], &a));
+        assert!(tree.inclusion_proof(7, 7).is_none());
+    }
+
+    #[test]
+    fn example_consistency_proofs() {
+        let (tree, x) = example();
+        let (c, d, j) = (x.leaf[2], x.leaf[3], x.leaf[6]);
+        let cases = [
+            (3, vec![c, d, x.g, x.l]),
+            (4, vec![x.l]),
+            (6, vec![x.i, j, x.k]),
+        ];
+        for (old, expected) in cases {
+            let proof = tree.consistency_proof(old, 7).unwrap();
+            assert_eq!(proof, expected, "PROOF({}, D[7])", old);
+            let old_root = tree.root_at(old).unwrap();
+            assert!(verify_consistency(old, 7, &old_root, &x.root, &proof));
+        }
+        for old in 0..=7 {
+            let proof = tree.consistency_proof(old, 7).unwrap();
+            assert!(verify_consistency(old, 7, &tree.root_at(old).unwrap(), &x.root, &proof));
+        }
+    }
+
+    #[test]
+    fn tampered_proofs_rejected() {
+        let (tree, x) = example();
+        let mut proof = tree.inclusion_proof(3, 7).unwrap();
+        assert!(!verify_inclusion(&x.leaf[3], 2, 7, &proof, &x.root));
+        assert!(!verify_inclusion(&x.leaf[3], 3, 4, &proof, &x.root));
+        assert!(!verify_inclusion(&x.leaf[2], 3, 7, &proof, &x.root));
+        proof.push(x.k);
+        assert!(!verify_inclusion(&x.leaf[3], 3, 7, &proof, &x.root));
+
+        let mut proof = tree.consistency_proof(3, 7).unwrap();
+        assert!(!verify_consistency(3, 7, &tree.root_at(4).unwrap(), &x.root, &proof));
+        proof[1][0] ^= 1;
+        assert!(!verify_consistency(3, 7, &tree.root_at(3).unwrap(), &x.root, &proof));
+    }
+
+    #[test]
+    fn leaves_are_domain_separated() {
+        assert_ne!(leaf_hash(DOMAIN, b"x"), leaf_hash("NONOS:MERKLE:OTHER:v1", b"x"));
+        let (a, b) = (leaf_hash(DOMAIN, b"a"), leaf_hash(DOMAIN, b"b"));
+        let mut joined = Vec::new();
+        joined.extend_from_slice(&a);
+        joined.extend_from_slice(&b);
+        assert_ne!(leaf_hash(DOMAIN, &joined), node_hash(&a, &b));
+    }
+}
@@ -0,0 +1,334 @@
+//! zk_sigma.rs — NØNOS sigma-protocol zero-knowledge verifier (Ristretto255).
+//!
+//! Shared verbatim by the kernel (`crypto::zk`, behind `nonos-zk`) and the
+//! bootloader (`zkverify`, behind `zk-sigma`). Two Fiat–Shamir proof systems:
+//!
+//! - `Circuit::ModSig`: Schnorr proof of knowledge of `x` with `Y = x·G`,
+//!   bound to a 32-byte statement. Proof: `R || s` (64 bytes).
+//! - `Circuit::AnonAuth`: Cramer–Damgård–Schoenmakers OR-proof that the
+//!   prover knows the secret of *one* key in a fixed ring, without revealing
+//!   which. Proof: `c_1..c_n || s_1..s_n` (64·n bytes).
+//!
+//! Public inputs are encoded canonically by `absorb_public_inputs`; the challenge is
+//! BLAKE3 (derive-key mode, 64-byte XOF) reduced mod ℓ. Verifying keys are
+//! fixed per circuit in `keys`; `vectors` holds known-answer proofs.
+//!
+//! no_std, alloc-free; needs `curve25519-dalek` (3.x) and `blake3`.
+
+#![allow(dead_code)]
+
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+
+/// Fiat–Shamir challenge domain
+const DS_CHALLENGE: &str = "NONOS:ZK:SIGMA:CHALLENGE:v1";
+/// Public-input encoding tag
+const PI_TAG: &[u8] = b"NONOS:ZK:SIGMA:PI:v1";
+/// Deterministic nonce derivation (prover side)
+const DS_NONCE: &str = "NONOS:ZK:SIGMA:NONCE:v1";
+
+/// Largest ring `AnonAuth` accepts
+pub const MAX_RING: usize = 16;
+pub const SCHNORR_PROOF_LEN: usize = 64;
+
+/// Circuit identifiers (wire values never change)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Circuit {
+    ModSig = 0x01,
+    AnonAuth = 0x02,
+}
+
+/// Fixed verifying key of a circuit: the public key(s) proofs are checked against
+#[derive(Debug, Clone, Copy)]
+pub struct VerifyingKey<'a> {
+    pub circuit: Circuit,
+    pub keys: &'a [[u8; 32]],
+}
+
+/// Canonical public-input encoding, absorbed into every challenge:
+/// `PI_TAG || circuit || n || key_1..key_n || len(statement) as u32 || statement`
+fn absorb_public_inputs(h: &mut blake3::Hasher, vk: &VerifyingKey, statement: &[u8]) {
+    h.update(PI_TAG);
+    h.update(&[vk.circuit as u8, vk.keys.len() as u8]);
+    for key in vk.keys {
+        h.update(key);
+    }
+    h.update(&(statement.len() as u32).to_le_bytes());
+    h.update(statement);
+}
+
+fn challenge(vk: &VerifyingKey, statement: &[u8], commitments: &[RistrettoPoint]) -> Scalar {
+    let mut h = blake3::Hasher::new_derive_key(DS_CHALLENGE);
+    absorb_public_inputs(&mut h, vk, statement);
+    for r in commitments {
+        h.update(r.compress().as_bytes());
+    }
+    let mut wide = [0u8; 64];
+    h.finalize_xof().fill(&mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+fn point(bytes: &[u8; 32]) -> Option<RistrettoPoint> {
+    CompressedRistretto(*bytes).decompress()
+}
+
+fn scalar(bytes: &[u8]) -> Option<Scalar> {
+    let mut b = [0u8; 32];
+    b.copy_from_slice(bytes);
+    Scalar::from_canonical_bytes(b)
+}
+
+/// Verify a proof for `vk.circuit` over `statement`
+pub fn verify(vk: &VerifyingKey, statement: &[u8], proof: &[u8]) -> bool {
+    match vk.circuit {
+        Circuit::ModSig => vk.keys.len() == 1 && verify_schnorr(vk, statement, proof),
+        Circuit::AnonAuth => verify_ring(vk, statement, proof),
+    }
+}
+
+/// Schnorr: accept iff `s·G == R + c·Y`, `c = H(pi, R)`
+fn verify_schnorr(vk: &VerifyingKey, statement: &[u8], proof: &[u8]) -> bool {
+    if proof.len() != SCHNORR_PROOF_LEN {
+        return false;
+    }
+    let mut r_bytes = [0u8; 32];
+    r_bytes.copy_from_slice(&proof[..32]);
+    let (y, r, s) = match (point(&vk.keys[0]), point(&r_bytes), scalar(&proof[32..])) {
+        (Some(y), Some(r), Some(s)) => (y, r, s),
+        _ => return false,
+    };
+    let c = challenge(vk, statement, &[r]);
+    RistrettoPoint::vartime_double_scalar_mul_basepoint(&(-c), &y, &s) == r
+}
+
+/// CDS OR-proof: recompute `R_i = s_i·G − c_i·Y_i`, accept iff `Σ c_i == H(pi, R_1..R_n)`
+fn verify_ring(vk: &VerifyingKey, statement: &[u8], proof: &[u8]) -> bool {
+    let n = vk.keys.len();
+    if n == 0 || n > MAX_RING || proof.len() != 64 * n {
+        return false;
+    }
+
+    let mut commitments = [RistrettoPoint::default(); MAX_RING];
+    let mut sum = Scalar::zero();
+    for i in 0..n {
+        let (y, c, s) = match (
+            point(&vk.keys[i]),
+            scalar(&proof[32 * i..32 * i + 32]),
+            scalar(&proof[32 * (n + i)..32 * (n + i) + 32]),
+        ) {
+            (Some(y), Some(c), Some(s)) => (y, c, s),
+            _ => return false,
+        };
+        commitments[i] = RistrettoPoint::vartime_double_scalar_mul_basepoint(&(-c), &y, &s);
+        sum += c;
+    }
+
+    challenge(vk, statement, &commitments[..n]) == sum
+}
+
+/// Public key for a 32-byte secret seed (seed reduced mod ℓ)
+pub fn public_key(secret: &[u8; 32]) -> [u8; 32] {
+    (Scalar::from_bytes_mod_order(*secret) * RISTRETTO_BASEPOINT_POINT).compress().to_bytes()
+}
+
+/// Schnorr prover with a deterministic nonce (`ModSig`). The verifying key
+/// must be `public_key(secret)`.
+pub fn prove_schnorr(vk: &VerifyingKey, secret: &[u8; 32], statement: &[u8]) -> [u8; SCHNORR_PROOF_LEN] {
+    let x = Scalar::from_bytes_mod_order(*secret);
+
+    let mut h = blake3::Hasher::new_derive_key(DS_NONCE);
+    h.update(secret);
+    absorb_public_inputs(&mut h, vk, statement);
+    let mut wide = [0u8; 64];
+    h.finalize_xof().fill(&mut wide);
+    let k = Scalar::from_bytes_mod_order_wide(&wide);
+    wide.iter_mut().for_each(|b| unsafe { core::ptr::write_volatile(b, 0) });
+
+    let r = k * RISTRETTO_BASEPOINT_POINT;
+    let c = challenge(vk, statement, &[r]);
+    let s = k + c * x;
+
+    let mut out = [0u8; SCHNORR_PROOF_LEN];
+    out[..32].copy_from_slice(r.compress().as_bytes());
+    out[32..].copy_from_slice(s.as_bytes());
+    out
+}
+
+/// Fixed verifying keys. The secrets were generated and kept offline; only
+/// the public points ship in the kernel and bootloader.
+pub mod keys {
+    use super::{Circuit, VerifyingKey};
+
+    /// Module-signature authority (`ZkCircuitType::ModSig`)
+    pub const MODSIG: VerifyingKey<'static> = VerifyingKey {
+        circuit: Circuit::ModSig,
+        keys: &[MODSIG_KEY],
+    };
+
+    /// Anonymous-signer ring (`ZkCircuitType::AnonAuth`, boot capsule proofs)
+    pub const ANON_AUTH: VerifyingKey<'static> = VerifyingKey {
+        circuit: Circuit::AnonAuth,
+        keys: &ANON_AUTH_RING,
+    };
+
+    pub const MODSIG_KEY: [u8; 32] = [
+        0x7e, 0x81, 0x25, 0x31, 0xbb, 0x48, 0x9a, 0xb8,
+        0x49, 0xd9, 0xd9, 0x30, 0x15, 0x4c, 0x56, 0xa1,
+        0x1d, 0x91, 0xde, 0x26, 0xa7, 0xdb, 0xc3, 0x64,
+        0x9d, 0x10, 0x5a, 0xe1, 0x10, 0xa5, 0x08, 0x0e,
+    ];
+    pub const ANON_AUTH_RING: [[u8; 32]; 4] = [
+        [
+            0x8a, 0x99, 0x68, 0x7b, 0x05, 0x67, 0xcd, 0x9b,
+            0xef, 0xc6, 0x79, 0xce, 0x06, 0x5c, 0x55, 0x52,
+            0x9a, 0x05, 0x5a, 0x84, 0x3e, 0x5f, 0x17, 0x54,
+            0x1d, 0x77, 0x13, 0xd3, 0xb2, 0x71, 0x33, 0x0c,
+        ],
+        [
+            0x2a, 0xf8, 0x4d, 0xcb, 0x23, 0x01, 0xd6, 0x41,
+            0x06, 0xb9, 0x41, 0xc1, 0x41, 0x21, 0xef, 0x90,
+            0x61, 0xd6, 0xaf, 0xad, 0x99, 0x8f, 0x96, 0xc0,
+            0xe1, 0xcd, 0xd4, 0xba, 0x17, 0xfd, 0x4d, 0x5d,
+        ],
+        [
+            0x3c, 0x6f, 0x0e, 0x54, 0xa9, 0x64, 0xe7, 0xe6,
+            0x25, 0x36, 0xb0, 0x45, 0x64, 0x0d, 0x81, 0xee,
+            0x99, 0x7b, 0x68, 0xb9, 0x72, 0xf4, 0xe2, 0x03,
+            0xb8, 0x7b, 0x98, 0x19, 0x96, 0xec, 0xdb, 0x02,
+        ],
+        [
+            0x22, 0x1c, 0xfb, 0xae, 0xf7, 0xad, 0x90, 0x4d,
+            0x87, 0x00, 0xe7, 0x75, 0x54, 0xef, 0x00, 0xe6,
+            0xbe, 0xb8, 0xa9, 0xdd, 0xef, 0xd7, 0x58, 0x53,
+            0x09, 0xbf, 0xab, 0xd4, 0x4a, 0x68, 0xec, 0x0d,
+        ],
+    ];
+}
+
+/// Known-answer vectors: valid proofs against `keys`
+pub mod vectors {
+    /// Statement and proof for `keys::MODSIG`
+    pub const MODSIG_STATEMENT: [u8; 32] = [
+        0xad, 0x7c, 0xc4, 0xe8, 0xca, 0x75, 0xdf, 0xad,
+        0xe2, 0x6a, 0x78, 0x8b, 0xa9, 0x26, 0xd1, 0xcf,
+        0x0f, 0xf0, 0x60, 0x86, 0xb7, 0x8b, 0x60, 0xfb,
+        0x15, 0xea, 0x91, 0x92, 0x4e, 0x24, 0x21, 0x76,
+    ];
+    pub const MODSIG_PROOF: [u8; 64] = [
+        0xb2, 0x75, 0xdc, 0xe9, 0x42, 0x80, 0x87, 0xab,
+        0x75, 0xf8, 0x79, 0xac, 0x46, 0xb6, 0x71, 0x8c,
+        0xec, 0xad, 0x83, 0x1c, 0x1d, 0x02, 0x40, 0x98,
+        0x3a, 0xc3, 0x70, 0x7e, 0xe8, 0xe1, 0x5d, 0x39,
+        0x7e, 0x85, 0x74, 0xe3, 0xb3, 0xb6, 0x59, 0x26,
+        0xf6, 0xbc, 0x6f, 0x91, 0x3e, 0xec, 0xaa, 0x68,
+        0xb7, 0x82, 0x5d, 0xd4, 0xb1, 0x76, 0xac, 0x0d,
+        0xc7, 0x9b, 0x5b, 0xd1, 0x66, 0x6e, 0xa1, 0x0c,
+    ];
+
+    /// Statement and proof for `keys::ANON_AUTH` (signer index not disclosed)
+    pub const ANON_AUTH_STATEMENT: [u8; 32] = [
+        0x12, 0x2e, 0x84, 0x35, 0x54, 0x0a, 0xaf, 0x64,
+        0x2e, 0x18, 0x43, 0xd5, 0x2d, 0x24, 0xa2, 0x51,
+        0x1d, 0xd4, 0x08, 0x27, 0x23, 0x43, 0x75, 0x3f,
+        0xe3, 0xab, 0x46, 0xc3, 0xd5, 0xd1, 0x84, 0x73,
+    ];
+    pub const ANON_AUTH_PROOF: [u8; 256] = [
+        0x8d, 0x7f, 0x37, 0xe8, 0x4c, 0xdb, 0x02, 0xf8,
+        0x53, 0x00, 0xe2, 0xbb, 0xa0, 0x7c, 0x53, 0x25,
+        0x88, 0x9f, 0x5b, 0xab, 0x1c, 0xa8, 0xc0, 0x01,
+        0xf3, 0x35, 0x61, 0x7f, 0x9b, 0x7b, 0x9f, 0x06,
+        0xea, 0x9c, 0xb6, 0x75, 0x13, 0x1b, 0xc2, 0x26,
+        0xf8, 0xae, 0x87, 0xbc, 0x31, 0x04, 0x64, 0x54,
+        0x5f, 0xff, 0xa7, 0xde, 0x1b, 0x78, 0x56, 0x36,
+        0x29, 0x78, 0xc4, 0xd8, 0x17, 0xa7, 0xa1, 0x02,
+        0x8d, 0xd0, 0x12, 0xf0, 0xf1, 0x4e, 0x55, 0xf3,
+        0x2d, 0xce, 0x85, 0x3a, 0x50, 0x59, 0x49, 0x13,
+        0x8d, 0x67, 0x1e, 0x74, 0xcf, 0xb0, 0x29, 0x0d,
+        0x74, 0xd2, 0xcb, 0xb0, 0x5f, 0xdf, 0x76, 0x0f,
+        0x93, 0x86, 0xed, 0x3e, 0x4d, 0x2f, 0x0f, 0xa5,
+        0xf4, 0x92, 0xb1, 0xcb, 0x7d, 0x97, 0xb4, 0x7b,
+        0xbe, 0xc1, 0x38, 0x25, 0x67, 0x6c, 0xa7, 0x96,
+        0x51, 0x77, 0x35, 0x8f, 0xac, 0x94, 0x95, 0x0c,
+        0x72, 0x9f, 0xd6, 0x95, 0xae, 0x89, 0x18, 0x90,
+        0x4e, 0x81, 0x5a, 0x45, 0xb6, 0x26, 0xa9, 0x32,
+        0xe3, 0xf5, 0xbe, 0xa9, 0x91, 0xea, 0xeb, 0x84,
+        0x8d, 0xc8, 0xf5, 0x20, 0xfc, 0x05, 0xf4, 0x0a,
+        0xb6, 0x2f, 0x7f, 0x5c, 0xd5, 0x95, 0x8a, 0x2a,
+        0x87, 0xcc, 0x96, 0x1d, 0xfc, 0x93, 0x84, 0xa7,
+        0x98, 0xb4, 0x8a, 0xa2, 0x2c, 0x9a, 0xa5, 0xcd,
+        0x45, 0x31, 0x91, 0x95, 0xee, 0xc2, 0xb9, 0x00,
+        0xab, 0x9b, 0x0c, 0x6f, 0xf3, 0xb1, 0x3f, 0x11,
+        0x99, 0x99, 0xba, 0xa3, 0x16, 0x65, 0x88, 0x02,
+        0x80, 0x3f, 0x0f, 0x08, 0x8c, 0xcb, 0x5f, 0xe4,
+        0xed, 0x5b, 0x35, 0xb2, 0x60, 0x2b, 0xb4, 0x0f,
+        0x4b, 0x21, 0xca, 0x9f, 0x84, 0xc5, 0xbf, 0xb1,
+        0xc1, 0x4d, 0x30, 0x1e, 0x69, 0xef, 0x8f, 0xd6,
+        0x6f, 0xc9, 0x95, 0x85, 0x07, 0xf1, 0xa3, 0xaa,
+        0xed, 0x7a, 0x6c, 0xe9, 0xf0, 0xe6, 0xfb, 0x0b,
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modsig_vector_verifies() {
+        assert!(verify(&keys::MODSIG, &vectors::MODSIG_STATEMENT, &vectors::MODSIG_PROOF));
+    }
+
+    #[test]
+    fn anon_auth_vector_verifies() {
+        assert!(verify(&keys::ANON_AUTH, &vectors::ANON_AUTH_STATEMENT, &vectors::ANON_AUTH_PROOF));
+    }
+
+    #[test]
+    fn tampered_vectors_rejected() {
+        for i in 0..vectors::MODSIG_PROOF.len() {
+            let mut proof = vectors::MODSIG_PROOF;
+            proof[i] ^= 0x01;
+            assert!(!verify(&keys::MODSIG, &vectors::MODSIG_STATEMENT, &proof), "modsig byte {}", i);
+        }
+        for i in 0..vectors::ANON_AUTH_PROOF.len() {
+            let mut proof = vectors::ANON_AUTH_PROOF;
+            proof[i] ^= 0x01;
+            assert!(!verify(&keys::ANON_AUTH, &vectors::ANON_AUTH_STATEMENT, &proof), "ring byte {}", i);
+        }
+
+        let mut statement = vectors::MODSIG_STATEMENT;
+        statement[0] ^= 0x01;
+        assert!(!verify(&keys::MODSIG, &statement, &vectors::MODSIG_PROOF));
+        let mut statement = vectors::ANON_AUTH_STATEMENT;
+        statement[31] ^= 0x01;
+        assert!(!verify(&keys::ANON_AUTH, &statement, &vectors::ANON_AUTH_PROOF));
+    }
+
+    #[test]
+    fn proofs_bound_to_their_circuit_and_keys() {
+        // Same proof, other circuit or a smaller ring
+        assert!(!verify(&keys::ANON_AUTH, &vectors::MODSIG_STATEMENT, &vectors::MODSIG_PROOF));
+        let ring = VerifyingKey { circuit: Circuit::AnonAuth, keys: &keys::ANON_AUTH_RING[..3] };
+        assert!(!verify(&ring, &vectors::ANON_AUTH_STATEMENT, &vectors::ANON_AUTH_PROOF[..192]));
+        assert!(!verify(&keys::MODSIG, &vectors::MODSIG_STATEMENT, &vectors::MODSIG_PROOF[..63]));
+    }
+
+    #[test]
+    fn prove_schnorr_round_trip() {
+        let secret = [0x42u8; 32];
+        let pk = [public_key(&secret)];
+        let vk = VerifyingKey { circuit: Circuit::ModSig, keys: &pk };
+        let statement = b"nonos schnorr round trip";
+
+        let proof = prove_schnorr(&vk, &secret, statement);
+        assert!(verify(&vk, statement, &proof));
+        // Deterministic nonce: same inputs, same proof
+        assert_eq!(proof, prove_schnorr(&vk, &secret, statement));
+
+        assert!(!verify(&vk, b"nonos schnorr round trip!", &proof));
+        let other = [public_key(&[0x43u8; 32])];
+        let wrong = VerifyingKey { circuit: Circuit::ModSig, keys: &other };
+        assert!(!verify(&wrong, statement, &proof));
+    }
+}